@@ -0,0 +1,341 @@
+//! Admin tokens authorising changes to execution counters.
+//!
+//! Administrative commands (deleting a counter, setting its count or its
+//! limit) must be authorised by a COSE_Sign1 token (see [`CoseSign1`]) signed
+//! by the admin key and bound to a nonce previously issued by the TA:
+//!
+//! ```text
+//! AdminClaims = {
+//!     "nonce"   => bstr,                          ; from issue_nonce()
+//!     "action"  => "reset" / "set_count" / "set_max",
+//!     "counter" => bstr,
+//!     ? "value" => uint,                          ; set_count and set_max
+//! }
+//! ```
+//!
+//! Nonces are kept in secure storage so the token can be signed offline. Up
+//! to [`MAX_NONCES`] are pending at a time, so requesting a nonce does not
+//! invalidate the one an admin is signing, and a nonce is only consumed by a
+//! token that has fully validated.
+
+use optee_utee::{
+    trace_println, DataFlag, Error, ErrorKind, ObjectStorageConstants, PersistentObject, Random,
+    Result,
+};
+
+use crate::cbor::{Decoder, Key};
+use crate::cose::CoseSign1;
+use crate::token::IssuerKey;
+use crate::{CounterStorage, ExecutionCounter};
+
+/// Size in bytes of an admin nonce.
+pub const NONCE_LEN: usize = 16;
+/// Maximum number of pending nonces; issuing another one drops the oldest.
+pub const MAX_NONCES: usize = 8;
+
+/// Claim name of the nonce.
+pub const CLAIM_NONCE: &str = "nonce";
+/// Claim name of the action.
+pub const CLAIM_ACTION: &str = "action";
+/// Claim name of the counter key.
+pub const CLAIM_COUNTER: &str = "counter";
+/// Claim name of the value for `set_count` and `set_max`.
+pub const CLAIM_VALUE: &str = "value";
+
+/// An administrative action on a counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdminAction {
+    /// Deletes the counter.
+    Reset,
+    /// Sets the number of executions performed.
+    SetCount(u32),
+    /// Sets the limit, discarding earlier top-ups.
+    SetMax(u32),
+}
+
+impl AdminAction {
+    /// Returns the name of the action as it appears in the claims.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminAction::Reset => "reset",
+            AdminAction::SetCount(_) => "set_count",
+            AdminAction::SetMax(_) => "set_max",
+        }
+    }
+}
+
+/// The claims of a verified admin token.
+#[derive(Clone, Copy, Debug)]
+pub struct AdminToken<'a> {
+    /// Nonce the token is bound to.
+    pub nonce: &'a [u8],
+    /// Storage key of the counter to act on.
+    pub counter: &'a [u8],
+    /// Action to perform.
+    pub action: AdminAction,
+}
+
+impl<'a> AdminToken<'a> {
+    /// Verifies an admin token signed by `key` and decodes its claims.
+    ///
+    /// The nonce is not checked; see [`consume_nonce`].
+    pub fn verify(bytes: &'a [u8], key: &IssuerKey) -> Result<Self> {
+        let payload = CoseSign1::decode(bytes)?.verify_payload(key)?;
+        Self::decode(payload)
+    }
+
+    /// Decodes a CBOR admin claims map.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut d = Decoder::new(bytes);
+        let (mut nonce, mut action, mut counter, mut value) = (None, None, None, None);
+        for _ in 0..d.map()? {
+            let duplicate = match d.key()? {
+                Key::Text(CLAIM_NONCE) => nonce.replace(d.bytes()?).is_some(),
+                Key::Text(CLAIM_ACTION) => action.replace(d.text()?).is_some(),
+                Key::Text(CLAIM_COUNTER) => counter.replace(d.bytes()?).is_some(),
+                Key::Text(CLAIM_VALUE) => value.replace(d.u32()?).is_some(),
+                _ => d.skip().map(|_| false)?,
+            };
+            if duplicate {
+                return Err(Error::new(ErrorKind::BadFormat));
+            }
+        }
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+
+        let action = match (action, value) {
+            (Some("reset"), None) => AdminAction::Reset,
+            (Some("set_count"), Some(value)) => AdminAction::SetCount(value),
+            (Some("set_max"), Some(value)) => AdminAction::SetMax(value),
+            _ => return Err(Error::new(ErrorKind::BadFormat)),
+        };
+        Ok(Self {
+            nonce: nonce.ok_or(Error::new(ErrorKind::BadFormat))?,
+            counter: counter.ok_or(Error::new(ErrorKind::BadFormat))?,
+            action,
+        })
+    }
+
+    /// Applies the action to the named counter, kept in `storage`.
+    ///
+    /// The nonce is not checked; call [`consume_nonce`] first, once the
+    /// action is known to be the expected one.
+    pub fn apply(&self, storage: CounterStorage) -> Result<()> {
+        // The base limit is only known to the TA using the counter, and none
+        // of the admin actions depend on it.
+        let counter = ExecutionCounter::new(self.counter, 0).with_storage(storage);
+        match self.action {
+            AdminAction::Reset => counter.delete(),
+            AdminAction::SetCount(count) => counter.set_count(count),
+            AdminAction::SetMax(max) => counter.set_max(max),
+        }
+    }
+}
+
+/// Reads the pending nonces stored under `id` into `nonces` and returns their
+/// number.
+fn read_nonces(id: &[u8], nonces: &mut [u8; MAX_NONCES * NONCE_LEN]) -> Result<usize> {
+    let object = match PersistentObject::open(
+        ObjectStorageConstants::Private,
+        id,
+        DataFlag::ACCESS_READ,
+    ) {
+        Ok(object) => object,
+        Err(e) if e.kind() == ErrorKind::ItemNotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let len = object.read(nonces)? as usize;
+    Ok(len / NONCE_LEN)
+}
+
+/// Stores `nonces` under `id`, replacing the pending ones.
+fn write_nonces(id: &[u8], nonces: &[u8]) -> Result<()> {
+    PersistentObject::create(
+        ObjectStorageConstants::Private,
+        id,
+        DataFlag::ACCESS_READ
+            | DataFlag::ACCESS_WRITE
+            | DataFlag::ACCESS_WRITE_META
+            | DataFlag::OVERWRITE,
+        None,
+        nonces,
+    )?;
+    Ok(())
+}
+
+/// Generates a fresh nonce and adds it to the nonces pending under `id`,
+/// dropping the oldest one if [`MAX_NONCES`] are already pending.
+pub fn issue_nonce(id: &[u8]) -> Result<[u8; NONCE_LEN]> {
+    let mut nonces = [0u8; MAX_NONCES * NONCE_LEN];
+    let mut count = read_nonces(id, &mut nonces)?;
+    if count == MAX_NONCES {
+        nonces.copy_within(NONCE_LEN.., 0);
+        count -= 1;
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    Random::generate(&mut nonce);
+    nonces[count * NONCE_LEN..(count + 1) * NONCE_LEN].copy_from_slice(&nonce);
+    write_nonces(id, &nonces[..(count + 1) * NONCE_LEN])?;
+    Ok(nonce)
+}
+
+/// Consumes `nonce` if it is pending under `id`.
+///
+/// Call this only once the token carrying `nonce` has been fully validated:
+/// the nonce is removed so the token cannot be replayed. Returns
+/// `AccessDenied` if the nonce is not pending, in which case the pending
+/// nonces are left untouched.
+pub fn consume_nonce(id: &[u8], nonce: &[u8]) -> Result<()> {
+    let mut nonces = [0u8; MAX_NONCES * NONCE_LEN];
+    let count = read_nonces(id, &mut nonces)?;
+    // Compare every pending nonce without an early exit, to avoid leaking
+    // the matching prefix.
+    let mut found = None;
+    for (i, stored) in nonces[..count * NONCE_LEN].chunks(NONCE_LEN).enumerate() {
+        let diff = stored
+            .iter()
+            .zip(nonce.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff == 0 && nonce.len() == NONCE_LEN {
+            found = Some(i);
+        }
+    }
+    let index = match found {
+        Some(index) => index,
+        None => {
+            trace_println!("[!] Admin nonce is not pending");
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+    };
+    nonces.copy_within((index + 1) * NONCE_LEN..count * NONCE_LEN, index * NONCE_LEN);
+    write_nonces(id, &nonces[..(count - 1) * NONCE_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+    use crate::testing::{self, KEY_A, KEY_B};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    const NONCE_KEY: &[u8] = b"admin_nonce\0";
+
+    fn claims(action: &str, value: Option<u64>) -> Vec<u8> {
+        claims_with_nonce(&[7; NONCE_LEN], action, value)
+    }
+
+    fn claims_with_nonce(nonce: &[u8], action: &str, value: Option<u64>) -> Vec<u8> {
+        let mut out = vec![];
+        cbor::write_header(&mut out, cbor::MAJOR_MAP, 3 + value.is_some() as u64);
+        cbor::write_text(&mut out, CLAIM_NONCE);
+        cbor::write_bytes(&mut out, nonce);
+        cbor::write_text(&mut out, CLAIM_ACTION);
+        cbor::write_text(&mut out, action);
+        cbor::write_text(&mut out, CLAIM_COUNTER);
+        cbor::write_bytes(&mut out, b"counter\0");
+        if let Some(value) = value {
+            cbor::write_text(&mut out, CLAIM_VALUE);
+            cbor::write_header(&mut out, cbor::MAJOR_UINT, value);
+        }
+        out
+    }
+
+    #[test]
+    fn test_decode_admin() {
+        let reset = claims("reset", None);
+        let token = AdminToken::decode(&reset).unwrap();
+        assert_eq!(token.action, AdminAction::Reset);
+        assert_eq!(token.nonce, &[7; NONCE_LEN]);
+        assert_eq!(token.counter, b"counter\0");
+
+        let set_max = claims("set_max", Some(5));
+        assert_eq!(
+            AdminToken::decode(&set_max).unwrap().action,
+            AdminAction::SetMax(5)
+        );
+
+        // set_count needs a value and reset must not carry one
+        assert!(AdminToken::decode(&claims("set_count", None)).is_err());
+        assert!(AdminToken::decode(&claims("reset", Some(1))).is_err());
+        assert!(AdminToken::decode(&claims("wipe", None)).is_err());
+    }
+
+    #[test]
+    fn test_nonces() {
+        let first = issue_nonce(NONCE_KEY).unwrap();
+        let second = issue_nonce(NONCE_KEY).unwrap();
+        assert_ne!(first, second);
+
+        // Issuing a nonce leaves the earlier ones pending, and a nonce that
+        // is not pending consumes nothing.
+        for nonce in [&[0; NONCE_LEN][..], &second[..8]] {
+            let err = consume_nonce(NONCE_KEY, nonce).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        }
+        consume_nonce(NONCE_KEY, &first).unwrap();
+        let err = consume_nonce(NONCE_KEY, &first).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        consume_nonce(NONCE_KEY, &second).unwrap();
+
+        // Only the last MAX_NONCES stay pending.
+        let nonces: Vec<_> = (0..=MAX_NONCES).map(|_| issue_nonce(NONCE_KEY).unwrap()).collect();
+        let err = consume_nonce(NONCE_KEY, &nonces[0]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        for nonce in nonces[1..].iter().rev() {
+            consume_nonce(NONCE_KEY, nonce).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_signed_admin_token() {
+        const COUNTER: &[u8] = b"counter\0";
+        let counter = ExecutionCounter::new(COUNTER, 1).with_storage(CounterStorage::Ree);
+        counter.check_and_increment().unwrap();
+        let key = KEY_A.public(b"admin");
+        let sign = |nonce: &[u8], action, value| {
+            testing::sign1(&KEY_A, b"admin", None, &claims_with_nonce(nonce, action, value))
+        };
+        let apply = |bytes: &[u8]| {
+            let token = AdminToken::verify(bytes, &key)?;
+            consume_nonce(NONCE_KEY, token.nonce)?;
+            token.apply(CounterStorage::Ree)
+        };
+
+        let nonce = issue_nonce(NONCE_KEY).unwrap();
+        let set_count = sign(&nonce, "set_count", Some(0));
+        let token = AdminToken::verify(&set_count, &key).unwrap();
+        assert_eq!(token.action, AdminAction::SetCount(0));
+        assert_eq!(token.action.name(), "set_count");
+        assert_eq!((token.nonce, token.counter), (&nonce[..], COUNTER));
+        apply(&set_count).unwrap();
+        assert_eq!(counter.count().unwrap(), 0);
+
+        // replay
+        counter.check_and_increment().unwrap();
+        assert_eq!(apply(&set_count).unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert_eq!(counter.count().unwrap(), 1);
+
+        // A token with an unknown action or from another key is rejected
+        // without consuming its nonce.
+        let nonce = issue_nonce(NONCE_KEY).unwrap();
+        let wipe = sign(&nonce, "wipe", None);
+        assert_eq!(apply(&wipe).unwrap_err().kind(), ErrorKind::BadFormat);
+        let reset = claims_with_nonce(&nonce, "reset", None);
+        let forged = testing::sign1(&KEY_B, b"admin", None, &reset);
+        assert_eq!(apply(&forged).unwrap_err().kind(), ErrorKind::SignatureInvalid);
+        let reset = sign(&nonce, "reset", None);
+
+        // stale nonce
+        for _ in 0..MAX_NONCES {
+            issue_nonce(NONCE_KEY).unwrap();
+        }
+        assert_eq!(apply(&reset).unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert_eq!(counter.count().unwrap(), 1);
+
+        let reset = sign(&issue_nonce(NONCE_KEY).unwrap(), "reset", None);
+        apply(&reset).unwrap();
+        assert_eq!(counter.count().unwrap(), 0);
+    }
+}
@@ -0,0 +1,293 @@
+//! A minimal CBOR (RFC 8949) reader and writer.
+//!
+//! Only the subset needed for COSE tokens is supported: unsigned and negative
+//! integers, byte and text strings, arrays, maps and tags, all with definite
+//! lengths. Anything else is rejected with `BadFormat`.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use optee_utee::{Error, ErrorKind, Result};
+
+/// Major type 0: unsigned integer.
+pub const MAJOR_UINT: u8 = 0;
+/// Major type 1: negative integer.
+pub const MAJOR_NINT: u8 = 1;
+/// Major type 2: byte string.
+pub const MAJOR_BYTES: u8 = 2;
+/// Major type 3: UTF-8 text string.
+pub const MAJOR_TEXT: u8 = 3;
+/// Major type 4: array of data items.
+pub const MAJOR_ARRAY: u8 = 4;
+/// Major type 5: map of pairs of data items.
+pub const MAJOR_MAP: u8 = 5;
+/// Major type 6: tagged data item.
+pub const MAJOR_TAG: u8 = 6;
+
+/// Maximum nesting depth accepted by [`Decoder::skip`].
+const MAX_DEPTH: usize = 8;
+
+fn bad_format() -> Error {
+    Error::new(ErrorKind::BadFormat)
+}
+
+/// A map key: either an integer label or a text label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key<'a> {
+    Int(i64),
+    Text(&'a str),
+}
+
+/// A cursor over a CBOR-encoded buffer.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns `true` once the whole buffer has been consumed.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    /// Returns the major type of the next item without consuming it.
+    pub fn peek_major(&self) -> Result<u8> {
+        self.buf.get(self.pos).map(|b| b >> 5).ok_or_else(bad_format)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).ok_or_else(bad_format)?;
+        let bytes = self.buf.get(self.pos..end).ok_or_else(bad_format)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Reads an item header and returns its major type and argument.
+    pub fn header(&mut self) -> Result<(u8, u64)> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let arg = match initial & 0x1f {
+            info @ 0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => {
+                let b = self.take(2)?;
+                u16::from_be_bytes([b[0], b[1]]) as u64
+            }
+            26 => {
+                let b = self.take(4)?;
+                u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u64
+            }
+            27 => {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(self.take(8)?);
+                u64::from_be_bytes(b)
+            }
+            // Reserved values and indefinite lengths are not supported.
+            _ => return Err(bad_format()),
+        };
+        Ok((major, arg))
+    }
+
+    fn expect(&mut self, major: u8) -> Result<u64> {
+        match self.header()? {
+            (m, arg) if m == major => Ok(arg),
+            _ => Err(bad_format()),
+        }
+    }
+
+    fn length(&mut self, major: u8) -> Result<usize> {
+        let len = self.expect(major)?;
+        // Every element takes at least one byte, so anything longer than the
+        // remaining input is malformed.
+        if len > (self.buf.len() - self.pos) as u64 {
+            return Err(bad_format());
+        }
+        Ok(len as usize)
+    }
+
+    pub fn uint(&mut self) -> Result<u64> {
+        self.expect(MAJOR_UINT)
+    }
+
+    /// Reads an unsigned integer which must fit in a `u32`.
+    pub fn u32(&mut self) -> Result<u32> {
+        let value = self.uint()?;
+        u32::try_from(value).map_err(|_| bad_format())
+    }
+
+    /// Reads a signed integer (major type 0 or 1).
+    pub fn int(&mut self) -> Result<i64> {
+        match self.header()? {
+            (MAJOR_UINT, arg) => i64::try_from(arg).map_err(|_| bad_format()),
+            (MAJOR_NINT, arg) => {
+                let arg = i64::try_from(arg).map_err(|_| bad_format())?;
+                Ok(-1 - arg)
+            }
+            _ => Err(bad_format()),
+        }
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.length(MAJOR_BYTES)?;
+        self.take(len)
+    }
+
+    pub fn text(&mut self) -> Result<&'a str> {
+        let len = self.length(MAJOR_TEXT)?;
+        core::str::from_utf8(self.take(len)?).map_err(|_| bad_format())
+    }
+
+    /// Reads an array header and returns the number of elements.
+    pub fn array(&mut self) -> Result<usize> {
+        self.length(MAJOR_ARRAY)
+    }
+
+    /// Reads a map header and returns the number of key/value pairs.
+    pub fn map(&mut self) -> Result<usize> {
+        self.length(MAJOR_MAP)
+    }
+
+    pub fn tag(&mut self) -> Result<u64> {
+        self.expect(MAJOR_TAG)
+    }
+
+    /// Reads a map key, which must be an integer or a text string.
+    pub fn key(&mut self) -> Result<Key<'a>> {
+        match self.peek_major()? {
+            MAJOR_UINT | MAJOR_NINT => self.int().map(Key::Int),
+            MAJOR_TEXT => self.text().map(Key::Text),
+            _ => Err(bad_format()),
+        }
+    }
+
+    /// Skips over the next data item, including any nested items.
+    pub fn skip(&mut self) -> Result<()> {
+        self.skip_nested(0)
+    }
+
+    /// Skips over the next data item and returns its encoded bytes.
+    pub fn raw(&mut self) -> Result<&'a [u8]> {
+        let start = self.pos;
+        self.skip()?;
+        Ok(&self.buf[start..self.pos])
+    }
+
+    fn skip_nested(&mut self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(bad_format());
+        }
+        match self.peek_major()? {
+            MAJOR_UINT | MAJOR_NINT => self.header().map(|_| ()),
+            MAJOR_BYTES => self.bytes().map(|_| ()),
+            MAJOR_TEXT => self.text().map(|_| ()),
+            MAJOR_ARRAY => {
+                for _ in 0..self.array()? {
+                    self.skip_nested(depth + 1)?;
+                }
+                Ok(())
+            }
+            MAJOR_MAP => {
+                for _ in 0..self.map()? {
+                    self.skip_nested(depth + 1)?;
+                    self.skip_nested(depth + 1)?;
+                }
+                Ok(())
+            }
+            MAJOR_TAG => {
+                self.tag()?;
+                self.skip_nested(depth + 1)
+            }
+            _ => Err(bad_format()),
+        }
+    }
+}
+
+/// Appends an item header with the shortest encoding of `arg`.
+pub fn write_header(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+pub fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_header(out, MAJOR_BYTES, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+pub fn write_text(out: &mut Vec<u8>, text: &str) {
+    write_header(out, MAJOR_TEXT, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_decode_integers() {
+        let buf = [0x17, 0x18, 0x18, 0x19, 0x01, 0x00, 0x20, 0x39, 0x01, 0x00];
+        let mut d = Decoder::new(&buf);
+        assert_eq!(d.uint().unwrap(), 23);
+        assert_eq!(d.uint().unwrap(), 24);
+        assert_eq!(d.uint().unwrap(), 256);
+        assert_eq!(d.int().unwrap(), -1);
+        assert_eq!(d.int().unwrap(), -257);
+        assert!(d.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_strings() {
+        let mut buf = vec![];
+        write_bytes(&mut buf, &[0xaa; 300]);
+        write_text(&mut buf, "Signature1");
+        let mut d = Decoder::new(&buf);
+        assert_eq!(d.bytes().unwrap(), &[0xaa; 300][..]);
+        assert_eq!(d.text().unwrap(), "Signature1");
+        assert!(d.is_empty());
+    }
+
+    #[test]
+    fn test_skip_nested() {
+        // {1: [h'01', "a"], "k": 6(-2)}, 7
+        let buf = [
+            0xa2, 0x01, 0x82, 0x41, 0x01, 0x61, 0x61, 0x61, 0x6b, 0xc6, 0x21, 0x07,
+        ];
+        let mut d = Decoder::new(&buf);
+        d.skip().unwrap();
+        assert_eq!(d.uint().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_reject_malformed() {
+        // byte string claiming more bytes than available
+        assert!(Decoder::new(&[0x45, 0x00]).bytes().is_err());
+        // indefinite-length array
+        assert!(Decoder::new(&[0x9f, 0xff]).array().is_err());
+        // wrong major type
+        assert!(Decoder::new(&[0x61, 0x61]).bytes().is_err());
+        // value does not fit in u32
+        assert!(Decoder::new(&[0x1b, 0, 0, 0, 1, 0, 0, 0, 0]).u32().is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! Issuer certificate chains rooted at a pinned key.
+//!
+//! Instead of embedding every issuer key, a TA pins a single root key in a
+//! [`TrustAnchor`]. COSE tokens then carry a short chain of compact CBOR
+//! certificates in the [`HEADER_CHAIN`](crate::cose::HEADER_CHAIN) header:
+//!
+//! ```text
+//! Chain       = [ + Certificate ]          ; first signed by root, last signs token
+//! Certificate = [
+//!     body:      bstr .cbor { "kid" => bstr, "n" => bstr, "e" => bstr,
+//!                             "nbf" => uint, "exp" => uint, "usage" => uint },
+//!     signature: bstr,                     ; RS256 over body by the previous key
+//! ]
+//! ```
+//!
+//! Every certificate must be valid at the time supplied by the caller. All but
+//! the last must allow [`USAGE_SIGN_CERTS`], and the last must allow
+//! [`USAGE_SIGN_TOKENS`].
+
+use optee_utee::{Error, ErrorKind, Result};
+
+use crate::cbor::{Decoder, Key};
+use crate::cose::CoseSign1;
+use crate::token::{IssuerKey, TokenClaims};
+
+/// The certified key may sign further certificates.
+pub const USAGE_SIGN_CERTS: u32 = 1 << 0;
+/// The certified key may sign tokens.
+pub const USAGE_SIGN_TOKENS: u32 = 1 << 1;
+
+/// Maximum number of certificates accepted in a chain.
+pub const MAX_CHAIN_LEN: usize = 4;
+
+/// A decoded, not yet verified, issuer certificate.
+pub struct Certificate<'a> {
+    body: &'a [u8],
+    signature: &'a [u8],
+    /// Key ID of the certified key.
+    pub kid: &'a [u8],
+    /// Big-endian RSA modulus of the certified key.
+    pub modulus: &'a [u8],
+    /// Big-endian RSA public exponent of the certified key.
+    pub exponent: &'a [u8],
+    /// Start of the validity period, in seconds.
+    pub not_before: u64,
+    /// End of the validity period (exclusive), in seconds.
+    pub not_after: u64,
+    /// Bitmask of `USAGE_*` flags.
+    pub usage: u32,
+}
+
+impl<'a> Certificate<'a> {
+    /// Reads one certificate from `d`.
+    pub fn decode(d: &mut Decoder<'a>) -> Result<Self> {
+        if d.array()? != 2 {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let body = d.bytes()?;
+        let signature = d.bytes()?;
+
+        let mut b = Decoder::new(body);
+        let (mut kid, mut modulus, mut exponent) = (None, None, None);
+        let (mut not_before, mut not_after, mut usage) = (None, None, None);
+        for _ in 0..b.map()? {
+            let duplicate = match b.key()? {
+                Key::Text("kid") => kid.replace(b.bytes()?).is_some(),
+                Key::Text("n") => modulus.replace(b.bytes()?).is_some(),
+                Key::Text("e") => exponent.replace(b.bytes()?).is_some(),
+                Key::Text("nbf") => not_before.replace(b.uint()?).is_some(),
+                Key::Text("exp") => not_after.replace(b.uint()?).is_some(),
+                Key::Text("usage") => usage.replace(b.u32()?).is_some(),
+                _ => b.skip().map(|_| false)?,
+            };
+            if duplicate {
+                return Err(Error::new(ErrorKind::BadFormat));
+            }
+        }
+        if !b.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+
+        let missing = || Error::new(ErrorKind::BadFormat);
+        Ok(Self {
+            body,
+            signature,
+            kid: kid.ok_or_else(missing)?,
+            modulus: modulus.ok_or_else(missing)?,
+            exponent: exponent.ok_or_else(missing)?,
+            not_before: not_before.ok_or_else(missing)?,
+            not_after: not_after.ok_or_else(missing)?,
+            usage: usage.ok_or_else(missing)?,
+        })
+    }
+
+    /// Returns `AccessDenied` unless `not_before <= now < not_after`.
+    pub fn check_validity(&self, now: u64) -> Result<()> {
+        if now < self.not_before || now >= self.not_after {
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+        Ok(())
+    }
+
+    /// Returns `AccessDenied` unless every flag in `usage` is allowed.
+    pub fn check_usage(&self, usage: u32) -> Result<()> {
+        if self.usage & usage != usage {
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+        Ok(())
+    }
+
+    /// Verifies that `issuer` signed this certificate.
+    pub fn verify(&self, issuer: &IssuerKey) -> Result<()> {
+        issuer.verify(self.body, self.signature)
+    }
+
+    /// Builds the certified key.
+    pub fn public_key(&self) -> Result<IssuerKey> {
+        IssuerKey::rsa(self.kid, self.modulus, self.exponent)
+    }
+}
+
+/// A pinned root key from which issuer chains are verified.
+pub struct TrustAnchor {
+    root: IssuerKey,
+}
+
+impl TrustAnchor {
+    pub fn new(root: IssuerKey) -> Self {
+        Self { root }
+    }
+
+    /// Verifies an encoded chain against the root and returns the key of the
+    /// last certificate.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: the chain is malformed, empty or longer than
+    ///    [`MAX_CHAIN_LEN`].
+    /// 2) `SignatureInvalid`: a certificate is not signed by its predecessor.
+    /// 3) `AccessDenied`: a certificate is outside its validity period or
+    ///    lacks the required usage.
+    pub fn verify_chain(&self, chain: &[u8], now: u64) -> Result<IssuerKey> {
+        let mut d = Decoder::new(chain);
+        let len = d.array()?;
+        if len == 0 || len > MAX_CHAIN_LEN {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let mut key: Option<IssuerKey> = None;
+        for i in 0..len {
+            let cert = Certificate::decode(&mut d)?;
+            cert.verify(key.as_ref().unwrap_or(&self.root))?;
+            cert.check_validity(now)?;
+            cert.check_usage(if i + 1 == len {
+                USAGE_SIGN_TOKENS
+            } else {
+                USAGE_SIGN_CERTS
+            })?;
+            key = Some(cert.public_key()?);
+        }
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        key.ok_or_else(|| Error::new(ErrorKind::BadFormat))
+    }
+
+    /// Verifies a COSE token and the chain it carries, checking both the
+    /// certificates' validity and the token's own expiry against `now`.
+    ///
+    /// Tokens without a chain must be signed by the root key itself.
+    pub fn verify_token<'a>(&self, bytes: &'a [u8], now: u64) -> Result<TokenClaims<'a>> {
+        let sign1 = CoseSign1::decode(bytes)?;
+        match sign1.chain() {
+            Some(chain) => sign1.verify(&self.verify_chain(chain, now)?, now),
+            None => sign1.verify(&self.root, now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+    use crate::testing::{self, certificate, TestKey, KEY_A, KEY_B, KEY_C, KEY_D};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn encode_cert(usage: u32) -> Vec<u8> {
+        let mut body = vec![];
+        cbor::write_header(&mut body, cbor::MAJOR_MAP, 6);
+        cbor::write_text(&mut body, "kid");
+        cbor::write_bytes(&mut body, b"int-1");
+        cbor::write_text(&mut body, "n");
+        cbor::write_bytes(&mut body, &[0xc3; 256]);
+        cbor::write_text(&mut body, "e");
+        cbor::write_bytes(&mut body, &[1, 0, 1]);
+        cbor::write_text(&mut body, "nbf");
+        cbor::write_header(&mut body, cbor::MAJOR_UINT, 100);
+        cbor::write_text(&mut body, "exp");
+        cbor::write_header(&mut body, cbor::MAJOR_UINT, 200);
+        cbor::write_text(&mut body, "usage");
+        cbor::write_header(&mut body, cbor::MAJOR_UINT, usage as u64);
+
+        let mut out = vec![];
+        cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 2);
+        cbor::write_bytes(&mut out, &body);
+        cbor::write_bytes(&mut out, &[0x55; 256]);
+        out
+    }
+
+    #[test]
+    fn test_decode_certificate() {
+        let buf = encode_cert(USAGE_SIGN_TOKENS);
+        let mut d = Decoder::new(&buf);
+        let cert = Certificate::decode(&mut d).unwrap();
+        assert!(d.is_empty());
+        assert_eq!(cert.kid, b"int-1");
+        assert_eq!(cert.modulus.len(), 256);
+        assert_eq!(cert.exponent, &[1, 0, 1]);
+
+        assert!(cert.check_validity(99).is_err());
+        assert!(cert.check_validity(100).is_ok());
+        assert!(cert.check_validity(200).is_err());
+        assert!(cert.check_usage(USAGE_SIGN_TOKENS).is_ok());
+        assert!(cert.check_usage(USAGE_SIGN_CERTS).is_err());
+    }
+
+    const VALIDITY: (u64, u64) = (100, 200);
+
+    /// Signs a token with `key` carrying `chain`, and verifies it against
+    /// [`KEY_A`] as root.
+    fn verify(key: &TestKey, kid: &[u8], chain: &[Vec<u8>], now: u64) -> Result<u32> {
+        let chain = testing::chain(chain);
+        let token = testing::sign1(key, kid, Some(&chain), &testing::claims(7, 1, None));
+        let anchor = TrustAnchor::new(KEY_A.public(b"root"));
+        anchor.verify_token(&token, now).map(|claims| claims.seq)
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let intermediate = certificate(&KEY_A, &KEY_B, b"int", VALIDITY, USAGE_SIGN_CERTS);
+        let leaf = certificate(&KEY_B, &KEY_C, b"leaf", VALIDITY, USAGE_SIGN_TOKENS);
+        let chain = [intermediate.clone(), leaf];
+        assert_eq!(verify(&KEY_C, b"leaf", &chain, 150).unwrap(), 7);
+        let both = USAGE_SIGN_CERTS | USAGE_SIGN_TOKENS;
+        let leaf = certificate(&KEY_A, &KEY_C, b"leaf", VALIDITY, both);
+        assert_eq!(verify(&KEY_C, b"leaf", &[leaf], 150).unwrap(), 7);
+
+        // Tokens without a chain are signed by the root itself.
+        let anchor = TrustAnchor::new(KEY_A.public(b"root"));
+        let token = testing::sign1(&KEY_A, b"root", None, &testing::claims(7, 1, Some(150)));
+        assert_eq!(anchor.verify_token(&token, 149).unwrap().seq, 7);
+        let err = anchor.verify_token(&token, 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+
+        // The token must be signed by the leaf key, under its key ID.
+        let leaf = certificate(&KEY_B, &KEY_C, b"leaf", VALIDITY, USAGE_SIGN_TOKENS);
+        let chain = [intermediate, leaf];
+        let err = verify(&KEY_B, b"leaf", &chain, 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+        let err = verify(&KEY_C, b"int", &chain, 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    }
+
+    #[test]
+    fn test_verify_chain_wrong_parent() {
+        let intermediate = certificate(&KEY_A, &KEY_B, b"int", VALIDITY, USAGE_SIGN_CERTS);
+        let leaf = certificate(&KEY_D, &KEY_C, b"leaf", VALIDITY, USAGE_SIGN_TOKENS);
+        let err = verify(&KEY_C, b"leaf", &[intermediate, leaf.clone()], 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+
+        // The first certificate must be signed by the root.
+        let err = verify(&KEY_C, b"leaf", &[leaf], 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+    }
+
+    #[test]
+    fn test_verify_chain_validity() {
+        let intermediate = certificate(&KEY_A, &KEY_B, b"int", VALIDITY, USAGE_SIGN_CERTS);
+        let leaf = certificate(&KEY_B, &KEY_C, b"leaf", (150, 300), USAGE_SIGN_TOKENS);
+        let chain = [intermediate, leaf];
+        assert!(verify(&KEY_C, b"leaf", &chain, 150).is_ok());
+        assert!(verify(&KEY_C, b"leaf", &chain, 199).is_ok());
+        // not yet valid, then expired leaf, then expired intermediate
+        for now in [149, 300, 200] {
+            let err = verify(&KEY_C, b"leaf", &chain, now).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        }
+    }
+
+    #[test]
+    fn test_verify_chain_usage() {
+        let intermediate = certificate(&KEY_A, &KEY_B, b"int", VALIDITY, USAGE_SIGN_CERTS);
+        let leaf = certificate(&KEY_B, &KEY_C, b"leaf", VALIDITY, USAGE_SIGN_CERTS);
+        let err = verify(&KEY_C, b"leaf", &[intermediate, leaf], 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+
+        let intermediate = certificate(&KEY_A, &KEY_B, b"int", VALIDITY, USAGE_SIGN_TOKENS);
+        let leaf = certificate(&KEY_B, &KEY_C, b"leaf", VALIDITY, USAGE_SIGN_TOKENS);
+        let err = verify(&KEY_C, b"leaf", &[intermediate, leaf], 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    }
+
+    #[test]
+    fn test_verify_chain_length() {
+        let keys = [&KEY_A, &KEY_B, &KEY_C, &KEY_D, &KEY_B, &KEY_C];
+        let chain = |len: usize| -> Vec<Vec<u8>> {
+            (0..len)
+                .map(|i| {
+                    let usage = if i + 1 == len { USAGE_SIGN_TOKENS } else { USAGE_SIGN_CERTS };
+                    certificate(keys[i], keys[i + 1], b"", VALIDITY, usage)
+                })
+                .collect()
+        };
+        assert!(verify(keys[MAX_CHAIN_LEN], b"", &chain(MAX_CHAIN_LEN), 150).is_ok());
+        let len = MAX_CHAIN_LEN + 1;
+        let err = verify(keys[len], b"", &chain(len), 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadFormat);
+        let err = verify(&KEY_A, b"", &[], 150).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadFormat);
+    }
+}
@@ -0,0 +1,443 @@
+//! COSE_Sign1 (RFC 9052) token decoding and verification.
+//!
+//! The token is a COSE_Sign1 structure, optionally wrapped in CBOR tag 18:
+//!
+//! ```text
+//! COSE_Sign1 = [
+//!     protected:   bstr .cbor { 1 => alg, 4 => kid },
+//!     unprotected: { },
+//!     payload:     bstr .cbor { "seq" => uint, "limit" => uint,
+//!                               ? "exp" => uint, ? "payload" => bstr },
+//!     signature:   bstr,
+//! ]
+//! ```
+//!
+//! Tokens requiring several issuers use COSE_Sign (tag 98) instead, with the
+//! same claims payload and one `[protected, unprotected, signature]` entry per
+//! signer; see [`CoseSign`] and the [`multisig`](crate::multisig) module.
+//!
+//! Only `RS256` (RSASSA-PKCS1-v1_5 with SHA-256) is accepted, matching the keys
+//! used for the legacy raw format.
+
+use alloc::vec::Vec;
+use optee_utee::{Error, ErrorKind, Result};
+
+use crate::cbor::{self, Decoder, Key};
+use crate::token::{IssuerKey, TokenClaims};
+
+/// CBOR tag identifying a COSE_Sign1 message.
+pub const COSE_SIGN1_TAG: u64 = 18;
+/// CBOR tag identifying a COSE_Sign message.
+pub const COSE_SIGN_TAG: u64 = 98;
+/// Maximum number of signatures accepted in a COSE_Sign message.
+pub const MAX_SIGNATURES: usize = 8;
+/// COSE header label for the algorithm.
+pub const HEADER_ALG: i64 = 1;
+/// COSE header label for the key ID.
+pub const HEADER_KID: i64 = 4;
+/// Private-use COSE header label carrying an issuer certificate chain, see
+/// the [`chain`](crate::chain) module.
+pub const HEADER_CHAIN: i64 = -65537;
+/// COSE algorithm ID for RSASSA-PKCS1-v1_5 with SHA-256.
+pub const ALG_RS256: i64 = -257;
+
+/// Claim name of the sequence number.
+pub const CLAIM_SEQ: &str = "seq";
+/// Claim name of the usage limit.
+pub const CLAIM_LIMIT: &str = "limit";
+/// Claim name of the expiry time.
+pub const CLAIM_EXP: &str = "exp";
+/// Claim name of the operation payload.
+pub const CLAIM_PAYLOAD: &str = "payload";
+
+/// Context string of the signature structure for COSE_Sign1.
+const SIGNATURE1_CONTEXT: &str = "Signature1";
+/// Context string of the signature structure for COSE_Sign.
+const SIGNATURE_CONTEXT: &str = "Signature";
+
+/// A decoded, not yet verified, COSE_Sign1 message.
+pub struct CoseSign1<'a> {
+    protected: &'a [u8],
+    alg: Option<i64>,
+    kid: &'a [u8],
+    chain: Option<&'a [u8]>,
+    payload: &'a [u8],
+    signature: &'a [u8],
+}
+
+#[derive(Default)]
+struct Headers<'a> {
+    alg: Option<i64>,
+    kid: Option<&'a [u8]>,
+    chain: Option<&'a [u8]>,
+}
+
+impl<'a> Headers<'a> {
+    /// Decodes a protected header map from its bstr wrapping.
+    fn decode_protected(protected: &'a [u8]) -> Result<Self> {
+        if protected.is_empty() {
+            return Ok(Headers::default());
+        }
+        let mut d = Decoder::new(protected);
+        let headers = Headers::decode(&mut d)?;
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        Ok(headers)
+    }
+
+    fn decode(d: &mut Decoder<'a>) -> Result<Self> {
+        let mut headers = Headers::default();
+        for _ in 0..d.map()? {
+            match d.key()? {
+                Key::Int(HEADER_ALG) if headers.alg.is_none() => headers.alg = Some(d.int()?),
+                Key::Int(HEADER_KID) if headers.kid.is_none() => headers.kid = Some(d.bytes()?),
+                Key::Int(HEADER_CHAIN) if headers.chain.is_none() => {
+                    headers.chain = Some(d.raw()?)
+                }
+                Key::Int(HEADER_ALG) | Key::Int(HEADER_KID) | Key::Int(HEADER_CHAIN) => {
+                    return Err(Error::new(ErrorKind::BadFormat))
+                }
+                _ => d.skip()?,
+            }
+        }
+        Ok(headers)
+    }
+}
+
+impl<'a> CoseSign1<'a> {
+    /// Parses a tagged or untagged COSE_Sign1 message.
+    ///
+    /// Returns `BadFormat` if `bytes` is not a well-formed COSE_Sign1 message
+    /// with an attached payload, or if trailing data follows it.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut d = Decoder::new(bytes);
+        if d.peek_major()? == cbor::MAJOR_TAG && d.tag()? != COSE_SIGN1_TAG {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        if d.array()? != 4 {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+
+        let protected = d.bytes()?;
+        let protected_headers = Headers::decode_protected(protected)?;
+        let unprotected_headers = Headers::decode(&mut d)?;
+        let payload = d.bytes()?;
+        let signature = d.bytes()?;
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+
+        Ok(Self {
+            protected,
+            // The algorithm must be integrity protected.
+            alg: protected_headers.alg,
+            kid: protected_headers
+                .kid
+                .or(unprotected_headers.kid)
+                .unwrap_or(&[]),
+            chain: protected_headers.chain.or(unprotected_headers.chain),
+            payload,
+            signature,
+        })
+    }
+
+    /// Returns the key ID from the headers, or an empty slice if absent.
+    pub fn kid(&self) -> &'a [u8] {
+        self.kid
+    }
+
+    /// Returns the algorithm from the protected header, if present.
+    pub fn alg(&self) -> Option<i64> {
+        self.alg
+    }
+
+    /// Returns the encoded certificate chain from the headers, if present.
+    pub fn chain(&self) -> Option<&'a [u8]> {
+        self.chain
+    }
+
+    /// Returns the raw signature bytes.
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature
+    }
+
+    /// Builds the `Sig_structure` that the signature is computed over.
+    pub fn to_be_signed(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.protected.len() + self.payload.len() + 24);
+        cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 4);
+        cbor::write_text(&mut out, SIGNATURE1_CONTEXT);
+        cbor::write_bytes(&mut out, self.protected);
+        cbor::write_bytes(&mut out, &[]);
+        cbor::write_bytes(&mut out, self.payload);
+        out
+    }
+
+    /// Verifies the signature with `key` and returns the authenticated payload.
+    ///
+    /// Returns `NotSupported` for algorithms other than `RS256`,
+    /// `AccessDenied` if `key` has a key ID that differs from the token's, and
+    /// `SignatureInvalid` if the signature does not verify.
+    pub fn verify_payload(&self, key: &IssuerKey) -> Result<&'a [u8]> {
+        if self.alg != Some(ALG_RS256) {
+            return Err(Error::new(ErrorKind::NotSupported));
+        }
+        if !key.kid().is_empty() && key.kid() != self.kid {
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+        key.verify(&self.to_be_signed(), self.signature)?;
+        Ok(self.payload)
+    }
+
+    /// Verifies the signature with `key`, decodes the claims and checks their
+    /// expiry against `now`.
+    ///
+    /// Fails as [`verify_payload`](Self::verify_payload) does, with
+    /// `BadFormat` if the payload is not a claims map, or with `AccessDenied`
+    /// if the token has expired, see [`TokenClaims::check_expiry`].
+    pub fn verify(&self, key: &IssuerKey, now: u64) -> Result<TokenClaims<'a>> {
+        let claims = decode_claims(self.verify_payload(key)?)?;
+        claims.check_expiry(now)?;
+        Ok(claims)
+    }
+}
+
+/// One signer's entry in a COSE_Sign message.
+pub struct CoseSignature<'a> {
+    protected: &'a [u8],
+    alg: Option<i64>,
+    kid: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> CoseSignature<'a> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self> {
+        if d.array()? != 3 {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let protected = d.bytes()?;
+        let protected_headers = Headers::decode_protected(protected)?;
+        let unprotected_headers = Headers::decode(d)?;
+        let signature = d.bytes()?;
+        Ok(Self {
+            protected,
+            alg: protected_headers.alg,
+            kid: protected_headers
+                .kid
+                .or(unprotected_headers.kid)
+                .unwrap_or(&[]),
+            signature,
+        })
+    }
+
+    /// Returns the signer's key ID, or an empty slice if absent.
+    pub fn kid(&self) -> &'a [u8] {
+        self.kid
+    }
+
+    /// Returns the algorithm from the signer's protected header, if present.
+    pub fn alg(&self) -> Option<i64> {
+        self.alg
+    }
+
+    /// Returns the raw signature bytes.
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature
+    }
+}
+
+/// A decoded, not yet verified, COSE_Sign message with one or more signers.
+pub struct CoseSign<'a> {
+    protected: &'a [u8],
+    payload: &'a [u8],
+    signatures: Vec<CoseSignature<'a>>,
+}
+
+impl<'a> CoseSign<'a> {
+    /// Parses a tagged or untagged COSE_Sign message.
+    ///
+    /// Returns `BadFormat` if `bytes` is not a well-formed COSE_Sign message
+    /// with an attached payload and between one and [`MAX_SIGNATURES`]
+    /// signatures, or if trailing data follows it.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut d = Decoder::new(bytes);
+        if d.peek_major()? == cbor::MAJOR_TAG && d.tag()? != COSE_SIGN_TAG {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        if d.array()? != 4 {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let protected = d.bytes()?;
+        Headers::decode_protected(protected)?;
+        Headers::decode(&mut d)?;
+        let payload = d.bytes()?;
+
+        let count = d.array()?;
+        if count == 0 || count > MAX_SIGNATURES {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let mut signatures = Vec::with_capacity(count);
+        for _ in 0..count {
+            signatures.push(CoseSignature::decode(&mut d)?);
+        }
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        Ok(Self {
+            protected,
+            payload,
+            signatures,
+        })
+    }
+
+    /// Returns the signer entries in message order.
+    pub fn signatures(&self) -> &[CoseSignature<'a>] {
+        &self.signatures
+    }
+
+    /// Returns the raw claims payload. It is not authenticated until enough
+    /// signatures have been verified.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Builds the `Sig_structure` that `signature` is computed over.
+    pub fn to_be_signed(&self, signature: &CoseSignature) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            self.protected.len() + signature.protected.len() + self.payload.len() + 24,
+        );
+        cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 5);
+        cbor::write_text(&mut out, SIGNATURE_CONTEXT);
+        cbor::write_bytes(&mut out, self.protected);
+        cbor::write_bytes(&mut out, signature.protected);
+        cbor::write_bytes(&mut out, &[]);
+        cbor::write_bytes(&mut out, self.payload);
+        out
+    }
+}
+
+/// Decodes a CBOR claims map.
+pub fn decode_claims(bytes: &[u8]) -> Result<TokenClaims<'_>> {
+    let mut d = Decoder::new(bytes);
+    let (mut seq, mut limit, mut exp, mut payload) = (None, None, None, None);
+    for _ in 0..d.map()? {
+        let duplicate = match d.key()? {
+            Key::Text(CLAIM_SEQ) => seq.replace(d.u32()?).is_some(),
+            Key::Text(CLAIM_LIMIT) => limit.replace(d.u32()?).is_some(),
+            Key::Text(CLAIM_EXP) => exp.replace(d.uint()?).is_some(),
+            Key::Text(CLAIM_PAYLOAD) => payload.replace(d.bytes()?).is_some(),
+            _ => d.skip().map(|_| false)?,
+        };
+        if duplicate {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+    }
+    if !d.is_empty() {
+        return Err(Error::new(ErrorKind::BadFormat));
+    }
+    Ok(TokenClaims {
+        seq: seq.ok_or(Error::new(ErrorKind::BadFormat))?,
+        limit: limit.ok_or(Error::new(ErrorKind::BadFormat))?,
+        exp,
+        payload: payload.unwrap_or(&[]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn claims_map() -> Vec<u8> {
+        let mut out = vec![];
+        cbor::write_header(&mut out, cbor::MAJOR_MAP, 3);
+        cbor::write_text(&mut out, CLAIM_SEQ);
+        cbor::write_header(&mut out, cbor::MAJOR_UINT, 7);
+        cbor::write_text(&mut out, CLAIM_LIMIT);
+        cbor::write_header(&mut out, cbor::MAJOR_UINT, 1);
+        cbor::write_text(&mut out, CLAIM_PAYLOAD);
+        cbor::write_bytes(&mut out, &[9, 0, 0, 0]);
+        out
+    }
+
+    #[test]
+    fn test_decode_sign1() {
+        // protected = {1: -257, 4: h'6b31'}
+        let protected = [0xa2, 0x01, 0x39, 0x01, 0x00, 0x04, 0x42, 0x6b, 0x31];
+        let payload = claims_map();
+        let mut msg = vec![0xd2];
+        cbor::write_header(&mut msg, cbor::MAJOR_ARRAY, 4);
+        cbor::write_bytes(&mut msg, &protected);
+        cbor::write_header(&mut msg, cbor::MAJOR_MAP, 0);
+        cbor::write_bytes(&mut msg, &payload);
+        cbor::write_bytes(&mut msg, &[0x55; 4]);
+
+        let sign1 = CoseSign1::decode(&msg).unwrap();
+        assert_eq!(sign1.alg(), Some(ALG_RS256));
+        assert_eq!(sign1.kid(), b"k1");
+        assert_eq!(sign1.signature(), &[0x55; 4]);
+
+        let tbs = sign1.to_be_signed();
+        let mut d = Decoder::new(&tbs);
+        assert_eq!(d.array().unwrap(), 4);
+        assert_eq!(d.text().unwrap(), SIGNATURE1_CONTEXT);
+        assert_eq!(d.bytes().unwrap(), &protected);
+        assert_eq!(d.bytes().unwrap(), &[] as &[u8]);
+        assert_eq!(d.bytes().unwrap(), payload.as_slice());
+
+        msg.push(0x00);
+        assert!(CoseSign1::decode(&msg).is_err());
+    }
+
+    #[test]
+    fn test_decode_sign() {
+        let payload = claims_map();
+        let mut msg = vec![0xd8, 0x62];
+        cbor::write_header(&mut msg, cbor::MAJOR_ARRAY, 4);
+        cbor::write_bytes(&mut msg, &[]);
+        cbor::write_header(&mut msg, cbor::MAJOR_MAP, 0);
+        cbor::write_bytes(&mut msg, &payload);
+        cbor::write_header(&mut msg, cbor::MAJOR_ARRAY, 2);
+        for kid in [b"k1", b"k2"].iter() {
+            // [<< {1: -257, 4: kid} >>, {}, h'55555555']
+            let protected = [0xa2, 0x01, 0x39, 0x01, 0x00, 0x04, 0x42, kid[0], kid[1]];
+            cbor::write_header(&mut msg, cbor::MAJOR_ARRAY, 3);
+            cbor::write_bytes(&mut msg, &protected);
+            cbor::write_header(&mut msg, cbor::MAJOR_MAP, 0);
+            cbor::write_bytes(&mut msg, &[0x55; 4]);
+        }
+
+        let sign = CoseSign::decode(&msg).unwrap();
+        assert_eq!(sign.payload(), payload.as_slice());
+        assert_eq!(sign.signatures().len(), 2);
+        assert_eq!(sign.signatures()[1].kid(), b"k2");
+        assert_eq!(sign.signatures()[1].alg(), Some(ALG_RS256));
+
+        let tbs = sign.to_be_signed(&sign.signatures()[0]);
+        let mut d = Decoder::new(&tbs);
+        assert_eq!(d.array().unwrap(), 5);
+        assert_eq!(d.text().unwrap(), SIGNATURE_CONTEXT);
+
+        // a COSE_Sign1 tag is rejected
+        msg.splice(0..2, [0xd2].iter().cloned());
+        assert!(CoseSign::decode(&msg).is_err());
+    }
+
+    #[test]
+    fn test_decode_claims() {
+        let claims = claims_map();
+        let decoded = decode_claims(&claims).unwrap();
+        assert_eq!(decoded.seq, 7);
+        assert_eq!(decoded.limit, 1);
+        assert_eq!(decoded.exp, None);
+        assert_eq!(decoded.payload, &[9, 0, 0, 0]);
+        assert!(decoded.check_expiry(u64::MAX).is_ok());
+
+        // missing "limit"
+        let mut out = vec![];
+        cbor::write_header(&mut out, cbor::MAJOR_MAP, 1);
+        cbor::write_text(&mut out, CLAIM_SEQ);
+        cbor::write_header(&mut out, cbor::MAJOR_UINT, 7);
+        assert!(decode_claims(&out).is_err());
+    }
+}
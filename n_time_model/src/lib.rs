@@ -10,7 +10,7 @@
 //! ## Example
 //!
 //! ```no_run
-//! use execution_counter::ExecutionCounter;
+//! use n_time_model::ExecutionCounter;
 //!
 //! const EXECUTION_KEY: &[u8] = b"my_exec_counter\0";
 //! const MAX_EXECUTIONS: u32 = 1;
@@ -21,13 +21,108 @@
 //! // proceed with sensitive operation...
 //! # Ok::<(), optee_utee::Error>(())
 //! ```
+//!
+//! Issuer-signed tokens granting executions can be verified with the
+//! [`token`] module, either in the legacy raw layout or as COSE_Sign1
+//! messages (see [`cose`]). Issuer keys can also be delegated from a pinned
+//! root through certificate chains, see [`chain`], and tokens can require
+//! several issuers to co-sign, see [`multisig`]. Signed top-ups raise the
+//! limit of an existing counter, see [`topup`], and admin tokens authorise
+//! resetting or overriding it, see [`admin`]. TAs can also delegate their
+//! counters to the quota-service TA, see [`quota`].
+//!
+//! The state of a counter can be inspected with
+//! [`status`](ExecutionCounter::status), and the counters kept by a TA
+//! enumerated with [`list`](ExecutionCounter::list). Counters are stored
+//! under their key prefixed with [`COUNTER_ID_PREFIX`], so a TA can keep other
+//! objects next to them as long as their IDs do not start with it.
+//!
+//! Counters are kept in the TA's private storage unless another
+//! [`CounterStorage`] is chosen with
+//! [`with_storage`](ExecutionCounter::with_storage). Keeping them in RPMB
+//! prevents the REE from rolling them back:
+//!
+//! ```no_run
+//! use n_time_model::{CounterStorage, ExecutionCounter};
+//!
+//! const COUNTER: ExecutionCounter =
+//!     ExecutionCounter::new(b"my_exec_counter\0", 1).with_storage(CounterStorage::RpmbOrRee);
+//!
+//! COUNTER.check_and_increment()?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
 
+extern crate alloc;
 extern crate optee_utee;
 
+pub mod admin;
+pub mod cbor;
+pub mod chain;
+pub mod cose;
+pub mod multisig;
+pub mod quota;
+pub mod token;
+pub mod topup;
+
+#[cfg(test)]
+mod testing;
+
+use alloc::vec::Vec;
 use optee_utee::{
-    trace_println, DataFlag, ObjectStorageConstants, PersistentObject,
-    Result, Error, ErrorKind,
+    trace_println, DataFlag, ObjectStorageConstants, PersistentObject, Result, Error, ErrorKind,
 };
+use ObjectStorageConstants::{Private, PrivateRee, PrivateRpmb};
+
+/// Prefix of the object IDs of counters, reserved for them in the storages
+/// they are kept in.
+pub const COUNTER_ID_PREFIX: &[u8] = b"counter:";
+
+/// Longest counter key, as object IDs are limited to 64 bytes.
+pub const MAX_KEY_LEN: usize = 64 - COUNTER_ID_PREFIX.len();
+
+/// The state of a counter as returned by [`ExecutionCounter::status`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterStatus {
+    /// Number of executions performed.
+    pub count: u32,
+    /// Effective limit, including top-ups and any limit set by an admin.
+    pub max: u32,
+    /// Number of executions still allowed.
+    pub remaining: u32,
+}
+
+/// The trusted storage an [`ExecutionCounter`] is kept in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterStorage {
+    /// The TA's private storage, whichever storage OP-TEE is configured to
+    /// use by default.
+    Private,
+    /// The REE file system. The REE cannot read or modify a counter kept
+    /// there, but it can roll it back to an earlier state.
+    Ree,
+    /// The Replay Protected Memory Block. Counters cannot be stored if the
+    /// TEE has no RPMB storage.
+    Rpmb,
+    /// RPMB if the TEE has an RPMB storage, the REE file system otherwise.
+    ///
+    /// A counter stays in the storage it was created in. The REE file system
+    /// is only used when OP-TEE reports that there is no RPMB storage at all;
+    /// other errors, such as RPMB being temporarily unavailable, are returned
+    /// so that a counter kept in RPMB is never mistaken for a new one.
+    RpmbOrRee,
+}
+
+impl CounterStorage {
+    /// Returns the storages a counter may be kept in, in order of preference.
+    fn storages(self) -> &'static [ObjectStorageConstants] {
+        match self {
+            CounterStorage::Private => &[Private],
+            CounterStorage::Ree => &[PrivateRee],
+            CounterStorage::Rpmb => &[PrivateRpmb],
+            CounterStorage::RpmbOrRee => &[PrivateRpmb, PrivateRee],
+        }
+    }
+}
 
 /// A utility for enforcing a fixed number of allowed executions of a TA operation.
 ///
@@ -43,6 +138,8 @@ pub struct ExecutionCounter<'a> {
     key: &'a [u8],
     /// Maximum allowed number of executions.
     max: u32,
+    /// Storage the counter is kept in.
+    storage: CounterStorage,
 }
 
 impl<'a> ExecutionCounter<'a> {
@@ -50,19 +147,35 @@ impl<'a> ExecutionCounter<'a> {
     ///
     /// # Arguments
     ///
-    /// * `key` - A null-terminated byte slice used as the key in secure storage.
+    /// * `key` - A null-terminated byte slice used as the key in secure
+    ///   storage, of at most [`MAX_KEY_LEN`] bytes.
     /// * `max` - The maximum number of allowed executions.
     ///
     /// # Returns
     ///
-    /// A new instance of `ExecutionCounter`.
+    /// A new instance of `ExecutionCounter`, kept in the TA's private
+    /// storage.
     pub const fn new(key: &'a [u8], max: u32) -> Self {
-        Self { key, max }
+        Self {
+            key,
+            max,
+            storage: CounterStorage::Private,
+        }
+    }
+
+    /// Keeps the counter in `storage` instead of the TA's private storage.
+    ///
+    /// All the operations on the counter, including top-ups and admin
+    /// actions, must use the same storage.
+    pub const fn with_storage(self, storage: CounterStorage) -> Self {
+        Self { storage, ..self }
     }
 
     /// Checks the current execution count and increments it if under the allowed limit.
     ///
-    /// If the limit has been reached or exceeded, returns an `AccessDenied` error.
+    /// The limit is `max` plus any executions granted through
+    /// [`top_up`](Self::top_up). If it has been reached or exceeded, returns an
+    /// `AccessDenied` error.
     ///
     /// # Returns
     ///
@@ -70,68 +183,253 @@ impl<'a> ExecutionCounter<'a> {
     ///
     /// `Err(Error { code: AccessDenied, .. })` if the execution limit has been reached.
     pub fn check_and_increment(&self) -> Result<()> {
-        let current = self.get()?;
+        let mut state = self.get()?.unwrap_or_default();
+        let limit = state.limit(self.max);
 
-        if current >= self.max {
+        if state.count >= limit {
             trace_println!(
                 "[+] Execution limit reached: {} of {}",
-                current,
-                self.max
+                state.count,
+                limit
             );
             return Err(Error::new(ErrorKind::AccessDenied));
         }
 
         trace_println!(
             "[+] Current count {} of {}, proceeding",
-            current,
-            self.max
+            state.count,
+            limit
         );
 
-        self.set(current + 1)
+        state.count += 1;
+        self.set(&state)
     }
 
-    /// Retrieves the current value of the execution counter from secure storage.
+    /// Raises the limit of an existing counter by `increment` executions.
     ///
-    /// If the counter object does not exist yet, returns 0 (first execution).
-    fn get(&self) -> Result<u32> {
-        match PersistentObject::open(
-            ObjectStorageConstants::Private,
-            self.key,
-            DataFlag::ACCESS_READ,
-        ) {
-            Ok(object) => {
-                let mut buf = [0u8; 4];
-                object.read(&mut buf)?;
-                Ok(u32::from_ne_bytes(buf))
+    /// `seq` is the sequence number of the top-up and must be greater than
+    /// that of any top-up applied before, so the same top-up cannot be
+    /// replayed. The new allowance and sequence number are stored in a single
+    /// write together with the count.
+    ///
+    /// # Errors
+    ///
+    /// 1) `ItemNotFound`: the counter has never been used.
+    /// 2) `AccessDenied`: `seq` is not newer than the last applied top-up.
+    /// 3) `Overflow`: the total allowance would not fit in a `u32`.
+    pub fn top_up(&self, seq: u32, increment: u32) -> Result<()> {
+        let mut state = self.get()?.ok_or(Error::new(ErrorKind::ItemNotFound))?;
+        if seq <= state.topup_seq {
+            trace_println!(
+                "[!] Rejecting top-up {}, last applied was {}",
+                seq,
+                state.topup_seq
+            );
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+        state.extra = state
+            .extra
+            .checked_add(increment)
+            .ok_or(Error::new(ErrorKind::Overflow))?;
+        state.topup_seq = seq;
+        trace_println!(
+            "[+] Top-up {} grants {} more executions",
+            seq,
+            increment
+        );
+        self.set(&state)
+    }
+
+    /// Returns the number of executions performed so far.
+    pub fn count(&self) -> Result<u32> {
+        Ok(self.get()?.unwrap_or_default().count)
+    }
+
+    /// Returns the effective limit of the counter, including top-ups and any
+    /// limit set by an admin.
+    pub fn limit(&self) -> Result<u32> {
+        Ok(self.get()?.unwrap_or_default().limit(self.max))
+    }
+
+    /// Returns the count, limit and remaining executions of the counter.
+    ///
+    /// A counter that has never been used reports a count of zero.
+    pub fn status(&self) -> Result<CounterStatus> {
+        let state = self.get()?.unwrap_or_default();
+        let max = state.limit(self.max);
+        Ok(CounterStatus {
+            count: state.count,
+            max,
+            remaining: max.saturating_sub(state.count),
+        })
+    }
+
+    /// Returns the keys of the counters in the TA's private storage, that is
+    /// the IDs starting with [`COUNTER_ID_PREFIX`] without the prefix.
+    ///
+    /// Counters written under their bare key by earlier versions are only
+    /// listed once they have been updated.
+    pub fn list() -> Result<Vec<Vec<u8>>> {
+        Self::list_in(CounterStorage::Private)
+    }
+
+    /// Returns the keys of the counters in `storage`, as
+    /// [`list`](Self::list) does for the TA's private storage.
+    pub fn list_in(storage: CounterStorage) -> Result<Vec<Vec<u8>>> {
+        let mut keys = Vec::new();
+        for &storage in storage.storages() {
+            Self::list_storage(storage, &mut keys)?;
+        }
+        Ok(keys)
+    }
+
+    /// Appends the keys of the counters in `storage` to `keys`.
+    fn list_storage(storage: ObjectStorageConstants, keys: &mut Vec<Vec<u8>>) -> Result<()> {
+        for object in PersistentObject::list(storage) {
+            let (id, _) = object?;
+            if let Some(key) = id.strip_prefix(COUNTER_ID_PREFIX) {
+                keys.push(key.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the number of executions performed, creating the counter if needed.
+    pub fn set_count(&self, count: u32) -> Result<()> {
+        let mut state = self.get()?.unwrap_or_default();
+        state.count = count;
+        self.set(&state)
+    }
+
+    /// Overrides the limit of the counter with `max`, creating the counter if
+    /// needed.
+    ///
+    /// Executions granted by earlier top-ups are discarded, but the last
+    /// top-up sequence number is kept so they cannot be replayed.
+    pub fn set_max(&self, max: u32) -> Result<()> {
+        let mut state = self.get()?.unwrap_or_default();
+        state.max = Some(max);
+        state.extra = 0;
+        self.set(&state)
+    }
+
+    /// Deletes the counter from secure storage.
+    ///
+    /// Returns `ItemNotFound` if the counter does not exist.
+    pub fn delete(&self) -> Result<()> {
+        let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE | DataFlag::ACCESS_WRITE_META;
+        let mut found = false;
+        // A counter whose move under the prefix was interrupted is kept under
+        // both IDs, so both are deleted.
+        while let Some(mut found_object) = self.open(flags)? {
+            found_object.object.close_and_delete()?;
+            found = true;
+        }
+        if !found {
+            return Err(Error::new(ErrorKind::ItemNotFound));
+        }
+        trace_println!("[+] Execution counter deleted");
+        Ok(())
+    }
+
+    /// Returns the object ID of the counter.
+    fn id(&self) -> Vec<u8> {
+        let mut id = Vec::with_capacity(COUNTER_ID_PREFIX.len() + self.key.len());
+        id.extend_from_slice(COUNTER_ID_PREFIX);
+        id.extend_from_slice(self.key);
+        id
+    }
+
+    /// Opens the counter object with `flags`, or returns `None` if no storage
+    /// holds it.
+    ///
+    /// Counters written before IDs were prefixed are found under their bare
+    /// key, if the object there has the size of a counter state.
+    fn open(&self, flags: DataFlag) -> Result<Option<FoundCounter>> {
+        let id = self.id();
+        for &storage in self.storage.storages() {
+            for legacy in [false, true] {
+                let object_id = if legacy { self.key } else { &id };
+                let object = match PersistentObject::open(storage, object_id, flags) {
+                    Ok(object) => object,
+                    Err(e) if e.kind() == ErrorKind::ItemNotFound => continue,
+                    Err(e) => return Err(e),
+                };
+                if legacy && !CounterState::is_valid_size(object.info()?.data_size()) {
+                    continue;
+                }
+                return Ok(Some(FoundCounter {
+                    object,
+                    storage,
+                    legacy,
+                }));
             }
-            Err(_) => {
+        }
+        Ok(None)
+    }
+
+    /// Retrieves the state of the execution counter from secure storage.
+    ///
+    /// If the counter object does not exist yet, returns `None` (first execution).
+    /// Any other error opening it is returned, so that a counter which is
+    /// only unreadable for now does not start over.
+    fn get(&self) -> Result<Option<CounterState>> {
+        match self.open(DataFlag::ACCESS_READ)? {
+            Some(found) => {
+                let mut buf = [0u8; CounterState::MAX_SIZE];
+                let len = found.object.read(&mut buf)? as usize;
+                CounterState::decode(&buf[..len]).map(Some)
+            }
+            None => {
                 trace_println!("[+] No counter found, assuming first use");
-                Ok(0)
+                Ok(None)
             }
         }
     }
 
-    /// Stores the updated execution count in secure persistent storage.
+    /// Stores the updated counter state in secure persistent storage.
     ///
-    /// Overwrites the existing object if it already exists.
-    fn set(&self, value: u32) -> Result<()> {
+    /// Overwrites the existing object if it already exists. A counter found
+    /// under its bare key is written under the prefixed ID, then deleted.
+    fn set(&self, state: &CounterState) -> Result<()> {
         let data_flag = DataFlag::ACCESS_READ
             | DataFlag::ACCESS_WRITE
             | DataFlag::ACCESS_WRITE_META
             | DataFlag::OVERWRITE;
 
-        let mut bytes = value.to_ne_bytes();
+        let mut bytes = [0u8; CounterState::MAX_SIZE];
+        let len = state.encode(&mut bytes);
+
+        // An existing counter is overwritten where it is, new ones go to the
+        // first storage that exists.
+        let existing = self
+            .open(DataFlag::ACCESS_READ)?
+            .map(|found| (found.storage, found.legacy));
+        let id = self.id();
+        let mut result = Err(Error::new(ErrorKind::ItemNotFound));
+        for &storage in self.storage.storages() {
+            if existing.is_some_and(|(existing, _)| existing != storage) {
+                continue;
+            }
+            result = PersistentObject::create(storage, &id, data_flag, None, &bytes[..len]);
+            match &result {
+                // Creating an object only fails with ItemNotFound if there is
+                // no such storage.
+                Err(e) if e.kind() == ErrorKind::ItemNotFound => {
+                    trace_println!("[!] No {:?} storage", storage);
+                }
+                _ => break,
+            }
+        }
 
-        match PersistentObject::create(
-            ObjectStorageConstants::Private,
-            self.key,
-            data_flag,
-            None,
-            &mut bytes,
-        ) {
+        match result {
             Ok(object) => {
                 drop(object);
-                trace_println!("[+] Execution counter updated to {}", value);
+                if let Some((storage, true)) = existing {
+                    PersistentObject::open(storage, self.key, DataFlag::ACCESS_WRITE_META)?
+                        .close_and_delete()?;
+                }
+                trace_println!("[+] Execution counter updated to {}", state.count);
                 Ok(())
             }
             Err(e) => {
@@ -141,3 +439,191 @@ impl<'a> ExecutionCounter<'a> {
         }
     }
 }
+
+/// A counter object opened by [`ExecutionCounter::open`].
+struct FoundCounter {
+    object: PersistentObject,
+    /// Storage the object was found in.
+    storage: ObjectStorageConstants,
+    /// Whether the object is stored under the bare key.
+    legacy: bool,
+}
+
+/// Persistent state of an [`ExecutionCounter`].
+///
+/// The state is stored as native-endian `u32` words: the count, then the
+/// top-up allowance and sequence number, then the limit override if one was
+/// set. Counters written before top-ups existed hold only the count.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct CounterState {
+    /// Number of executions performed.
+    count: u32,
+    /// Executions granted on top of the limit by top-ups.
+    extra: u32,
+    /// Sequence number of the last applied top-up.
+    topup_seq: u32,
+    /// Limit set by an admin, replacing the one the counter was created with.
+    max: Option<u32>,
+}
+
+impl CounterState {
+    const MAX_SIZE: usize = 16;
+
+    /// Returns the effective limit for a counter created with `max`.
+    fn limit(&self, max: u32) -> u32 {
+        self.max.unwrap_or(max).saturating_add(self.extra)
+    }
+
+    /// Encodes the state into `bytes` and returns the encoded length.
+    fn encode(&self, bytes: &mut [u8; Self::MAX_SIZE]) -> usize {
+        bytes[0..4].copy_from_slice(&self.count.to_ne_bytes());
+        bytes[4..8].copy_from_slice(&self.extra.to_ne_bytes());
+        bytes[8..12].copy_from_slice(&self.topup_seq.to_ne_bytes());
+        match self.max {
+            Some(max) => {
+                bytes[12..16].copy_from_slice(&max.to_ne_bytes());
+                16
+            }
+            None => 12,
+        }
+    }
+
+    /// Returns whether `len` is the length of an encoded state.
+    fn is_valid_size(len: usize) -> bool {
+        matches!(len, 4 | 12 | 16)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let word = |i: usize| u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        match bytes.len() {
+            len if Self::is_valid_size(len) => Ok(Self {
+                count: word(0),
+                extra: if bytes.len() > 4 { word(4) } else { 0 },
+                topup_seq: if bytes.len() > 4 { word(8) } else { 0 },
+                max: if bytes.len() > 12 { Some(word(12)) } else { None },
+            }),
+            _ => Err(Error::new(ErrorKind::BadFormat)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_state_encoding() {
+        let mut bytes = [0u8; CounterState::MAX_SIZE];
+        let mut state = CounterState {
+            count: 3,
+            extra: 5,
+            topup_seq: 7,
+            max: None,
+        };
+        let len = state.encode(&mut bytes);
+        assert_eq!(len, 12);
+        assert_eq!(CounterState::decode(&bytes[..len]).unwrap(), state);
+        assert_eq!(state.limit(1), 6);
+
+        state.max = Some(10);
+        let len = state.encode(&mut bytes);
+        assert_eq!(CounterState::decode(&bytes[..len]).unwrap(), state);
+        assert_eq!(state.limit(1), 15);
+
+        let legacy = CounterState::decode(&9u32.to_ne_bytes()).unwrap();
+        assert_eq!(legacy, CounterState { count: 9, ..CounterState::default() });
+        assert!(CounterState::decode(&[0; 5]).is_err());
+    }
+
+    #[test]
+    fn test_check_and_increment() {
+        let counter = ExecutionCounter::new(b"counter\0", 2);
+        assert_eq!(counter.count().unwrap(), 0);
+        counter.check_and_increment().unwrap();
+        counter.check_and_increment().unwrap();
+        let err = counter.check_and_increment().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        assert_eq!(counter.count().unwrap(), 2);
+
+        counter.top_up(1, 3).unwrap();
+        assert_eq!(counter.top_up(1, 3).unwrap_err().kind(), ErrorKind::AccessDenied);
+        counter.check_and_increment().unwrap();
+        assert_eq!(
+            counter.status().unwrap(),
+            CounterStatus {
+                count: 3,
+                max: 5,
+                remaining: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_rpmb_fallback() {
+        let counter = ExecutionCounter::new(b"counter", 1).with_storage(CounterStorage::RpmbOrRee);
+        optee_utee::sim::set_rpmb_available(false);
+        counter.check_and_increment().unwrap();
+        assert!(ExecutionCounter::list_in(CounterStorage::Rpmb).unwrap().is_empty());
+        assert_eq!(ExecutionCounter::list_in(CounterStorage::Ree).unwrap(), [b"counter".to_vec()]);
+
+        // The counter stays in the REE file system once RPMB is available.
+        optee_utee::sim::set_rpmb_available(true);
+        counter.top_up(1, 1).unwrap();
+        counter.check_and_increment().unwrap();
+        assert_eq!(counter.check_and_increment().unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert!(ExecutionCounter::list_in(CounterStorage::Rpmb).unwrap().is_empty());
+
+        let other = ExecutionCounter::new(b"other", 1).with_storage(CounterStorage::RpmbOrRee);
+        other.check_and_increment().unwrap();
+        assert_eq!(ExecutionCounter::list_in(CounterStorage::Rpmb).unwrap(), [b"other".to_vec()]);
+        assert_eq!(
+            ExecutionCounter::list_in(CounterStorage::RpmbOrRee).unwrap(),
+            [b"other".to_vec(), b"counter".to_vec()]
+        );
+        assert_eq!(ExecutionCounter::new(b"other", 1).count().unwrap(), 0);
+
+        optee_utee::sim::set_rpmb_available(false);
+        let rpmb = ExecutionCounter::new(b"rpmb", 1).with_storage(CounterStorage::Rpmb);
+        assert_eq!(rpmb.check_and_increment().unwrap_err().kind(), ErrorKind::ItemNotFound);
+        assert_eq!(other.delete().unwrap_err().kind(), ErrorKind::ItemNotFound);
+    }
+
+    #[test]
+    fn test_list_and_delete() {
+        assert!(ExecutionCounter::list().unwrap().is_empty());
+        let first = ExecutionCounter::new(b"first", 1);
+        let second = ExecutionCounter::new(b"second", 1);
+        first.check_and_increment().unwrap();
+        second.set_max(10).unwrap();
+        // objects of the size of a counter state are not taken for counters
+        PersistentObject::create(Private, b"nonce", DataFlag::ACCESS_READ, None, &[0; 16])
+            .unwrap();
+        assert_eq!(ExecutionCounter::list().unwrap(), [b"first".to_vec(), b"second".to_vec()]);
+
+        first.delete().unwrap();
+        assert_eq!(first.delete().unwrap_err().kind(), ErrorKind::ItemNotFound);
+        assert_eq!(first.count().unwrap(), 0);
+        assert_eq!(ExecutionCounter::list().unwrap(), [b"second".to_vec()]);
+    }
+
+    #[test]
+    fn test_legacy_counter() {
+        let flags = DataFlag::ACCESS_READ;
+        PersistentObject::create(Private, b"legacy\0", flags, None, &1u32.to_ne_bytes()).unwrap();
+        PersistentObject::create(Private, b"other\0", flags, None, &[0; 5]).unwrap();
+        let counter = ExecutionCounter::new(b"legacy\0", 2);
+        assert_eq!(counter.count().unwrap(), 1);
+        assert_eq!(ExecutionCounter::new(b"other\0", 2).count().unwrap(), 0);
+        assert!(ExecutionCounter::list().unwrap().is_empty());
+
+        // the counter is moved under the prefix when it is updated
+        counter.check_and_increment().unwrap();
+        assert_eq!(counter.check_and_increment().unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert_eq!(ExecutionCounter::list().unwrap(), [b"legacy\0".to_vec()]);
+        let err = PersistentObject::open(Private, b"legacy\0", flags).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+
+        counter.delete().unwrap();
+        assert_eq!(counter.count().unwrap(), 0);
+    }
+}
@@ -0,0 +1,196 @@
+//! M-of-N multi-signature tokens.
+//!
+//! High-value operations can require several issuers to authorise the same
+//! token. Such tokens are COSE_Sign messages (see [`CoseSign`]) carrying one
+//! signature per issuer, and the TA accepts them once a [`Threshold`] of
+//! distinct trusted keys has signed.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use n_time_model::multisig::Threshold;
+//! use n_time_model::token::IssuerKey;
+//!
+//! # let (n, e, bytes) = (&[0u8; 256], &[1u8, 0, 1], &[0u8; 0]);
+//! // "2 of these 3 keys"
+//! let policy = Threshold::new(
+//!     2,
+//!     vec![
+//!         IssuerKey::rsa(b"issuer-a", n, e)?,
+//!         IssuerKey::rsa(b"issuer-b", n, e)?,
+//!         IssuerKey::rsa(b"issuer-c", n, e)?,
+//!     ],
+//! )?;
+//! # let now = 0;
+//! let claims = policy.verify(bytes, now)?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use alloc::vec::Vec;
+use optee_utee::{Error, ErrorKind, Result};
+
+use crate::cose::{decode_claims, CoseSign, ALG_RS256};
+use crate::token::{IssuerKey, TokenClaims};
+
+/// A policy requiring signatures from at least `required` of a set of keys.
+pub struct Threshold {
+    required: usize,
+    keys: Vec<IssuerKey>,
+}
+
+impl Threshold {
+    /// Creates a policy accepting tokens signed by `required` of `keys`.
+    ///
+    /// # Errors
+    ///
+    /// `BadParameters` if `required` is zero or larger than the number of
+    /// keys, if the keys do not all have distinct, non-empty key IDs, or if
+    /// the same key is registered twice: one issuer holding two key IDs must
+    /// not count as two signers.
+    pub fn new(required: usize, keys: Vec<IssuerKey>) -> Result<Self> {
+        if required == 0 || required > keys.len() {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let mut moduli: Vec<Vec<u8>> = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            if key.kid().is_empty() || keys[..i].iter().any(|k| k.kid() == key.kid()) {
+                return Err(Error::new(ErrorKind::BadParameters));
+            }
+            let modulus = key.modulus()?;
+            if moduli.contains(&modulus) {
+                return Err(Error::new(ErrorKind::BadParameters));
+            }
+            moduli.push(modulus);
+        }
+        Ok(Self { required, keys })
+    }
+
+    /// Returns the number of distinct signers required.
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    /// Verifies a COSE_Sign token against the policy, decodes its claims and
+    /// checks their expiry against `now`.
+    ///
+    /// Signatures from unknown key IDs, with an algorithm other than `RS256`,
+    /// or repeating an already counted key ID are ignored.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: the token is malformed.
+    /// 2) `SignatureInvalid`: fewer than [`required`](Self::required) distinct
+    ///    trusted keys produced a valid signature.
+    /// 3) `AccessDenied`: the token has expired, see
+    ///    [`TokenClaims::check_expiry`].
+    pub fn verify<'a>(&self, bytes: &'a [u8], now: u64) -> Result<TokenClaims<'a>> {
+        let sign = CoseSign::decode(bytes)?;
+        let mut verified: Vec<usize> = Vec::with_capacity(self.required);
+        for signature in sign.signatures() {
+            if signature.alg() != Some(ALG_RS256) {
+                continue;
+            }
+            let index = match self.keys.iter().position(|k| k.kid() == signature.kid()) {
+                Some(index) if !verified.contains(&index) => index,
+                _ => continue,
+            };
+            let message = sign.to_be_signed(signature);
+            match self.keys[index].verify(&message, signature.signature()) {
+                Ok(()) => verified.push(index),
+                Err(e) if e.kind() == ErrorKind::SignatureInvalid => continue,
+                Err(e) => return Err(e),
+            }
+            if verified.len() == self.required {
+                let claims = decode_claims(sign.payload())?;
+                claims.check_expiry(now)?;
+                return Ok(claims);
+            }
+        }
+        Err(Error::new(ErrorKind::SignatureInvalid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, KEY_A, KEY_B, KEY_C, KEY_D};
+    use alloc::vec;
+
+    fn policy() -> Threshold {
+        let keys = vec![KEY_A.public(b"a"), KEY_B.public(b"b"), KEY_C.public(b"c")];
+        Threshold::new(2, keys).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        let err = Threshold::new(0, vec![KEY_A.public(b"a")]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        let err = Threshold::new(2, vec![KEY_A.public(b"a")]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        let err = Threshold::new(1, vec![KEY_A.public(b"")]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        let keys = vec![KEY_A.public(b"a"), KEY_B.public(b"a")];
+        assert_eq!(Threshold::new(2, keys).err().unwrap().kind(), ErrorKind::BadParameters);
+        // the same key under two key IDs
+        let keys = vec![KEY_A.public(b"a"), KEY_B.public(b"b"), KEY_A.public(b"c")];
+        assert_eq!(Threshold::new(2, keys).err().unwrap().kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_verify_threshold() {
+        let policy = policy();
+        let claims = testing::claims(7, 1, None);
+        let a: (&_, &[u8]) = (&KEY_A, b"a");
+        let c: (&_, &[u8]) = (&KEY_C, b"c");
+
+        let token = testing::cose_sign(&[a, c], &claims);
+        assert_eq!(policy.verify(&token, 0).unwrap().seq, 7);
+        let token = testing::cose_sign(&[(&KEY_B, b"b"), a, c], &claims);
+        assert_eq!(policy.verify(&token, 0).unwrap().seq, 7);
+
+        let token = testing::cose_sign(&[c], &claims);
+        assert_eq!(policy.verify(&token, 0).err().unwrap().kind(), ErrorKind::SignatureInvalid);
+    }
+
+    #[test]
+    fn test_verify_expiry() {
+        let policy = policy();
+        let claims = testing::claims(7, 1, Some(1000));
+        let token = testing::cose_sign(&[(&KEY_A, b"a"), (&KEY_B, b"b")], &claims);
+        assert_eq!(policy.verify(&token, 999).unwrap().seq, 7);
+        assert_eq!(policy.verify(&token, 1000).err().unwrap().kind(), ErrorKind::AccessDenied);
+        let err = policy.verify(&token, u64::MAX).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    }
+
+    #[test]
+    fn test_verify_rejects_repeated_and_unknown_signers() {
+        let policy = policy();
+        let claims = testing::claims(7, 1, None);
+        let a: (&_, &[u8]) = (&KEY_A, b"a");
+
+        // one signer counted once, however often it signs
+        let token = testing::cose_sign(&[a, a], &claims);
+        assert_eq!(policy.verify(&token, 0).err().unwrap().kind(), ErrorKind::SignatureInvalid);
+
+        // unknown key IDs are ignored, as is a trusted key ID with another key
+        let token = testing::cose_sign(&[a, (&KEY_D, b"d")], &claims);
+        assert_eq!(policy.verify(&token, 0).err().unwrap().kind(), ErrorKind::SignatureInvalid);
+        let token = testing::cose_sign(&[a, (&KEY_D, b"b")], &claims);
+        assert_eq!(policy.verify(&token, 0).err().unwrap().kind(), ErrorKind::SignatureInvalid);
+        let token = testing::cose_sign(&[(&KEY_D, b"d"), a, (&KEY_B, b"b")], &claims);
+        assert!(policy.verify(&token, 0).is_ok());
+    }
+
+    #[test]
+    fn test_verify_tampered_payload() {
+        let policy = policy();
+        let claims = testing::claims(7, 1, None);
+        let mut token = testing::cose_sign(&[(&KEY_A, b"a"), (&KEY_B, b"b")], &claims);
+        let start = token.windows(claims.len()).position(|w| w == claims).unwrap();
+        // {"seq": 7, ...} becomes {"seq": 8, ...}
+        assert_eq!(token[start + 5], 7);
+        token[start + 5] = 8;
+        assert_eq!(policy.verify(&token, 0).err().unwrap().kind(), ErrorKind::SignatureInvalid);
+    }
+}
@@ -0,0 +1,220 @@
+//! Client for the quota-service TA.
+//!
+//! OP-TEE private storage is isolated per TA, so counters kept with
+//! [`ExecutionCounter`](crate::ExecutionCounter) can only be managed by the TA
+//! that owns them. The quota-service TA (`projects/quota-service`) instead
+//! owns the counters of every TA it is configured to serve, and other TAs
+//! reach it through the TA-to-TA client API with [`QuotaClient`].
+//!
+//! Counters are namespaced by the calling TA's UUID, and the service applies
+//! the limit configured for that caller.
+//!
+//! ## Protocol
+//!
+//! Every command takes the counter name as a memref input in parameter 0:
+//!
+//! | Command   | Parameter 1                         | Parameter 2            |
+//! |-----------|-------------------------------------|------------------------|
+//! | `Check`   | value output: `a` = remaining       |                        |
+//! | `Reserve` | value output: `a` = reservation ID  |                        |
+//! | `Commit`  | value input: `a` = reservation ID   |                        |
+//! | `Query`   | value output: `a` = count, `b` = limit | value output: `a` = reserved |
+//! | `Reset`   | memref input: owner UUID (16 bytes) |                        |
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use n_time_model::quota::QuotaClient;
+//! use optee_utee::Uuid;
+//!
+//! # let service = Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap();
+//! let mut quota = QuotaClient::open(&service)?;
+//! let reservation = quota.reserve(b"inference")?;
+//! // perform the guarded operation...
+//! quota.commit(reservation)?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use optee_utee::ta_session::{ParamNone, ParamTmpRef, ParamValue};
+use optee_utee::{ParamType, Result, TaOperation, TaSession, Uuid};
+use optee_utee_sys as raw;
+
+/// Commands supported by the quota-service TA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Returns the number of executions still available.
+    Check,
+    /// Sets one execution aside until it is committed or the session closes.
+    Reserve,
+    /// Consumes a reserved execution.
+    Commit,
+    /// Returns the count, limit and reservations of a counter.
+    Query,
+    /// Deletes a counter of any TA; admin callers only.
+    Reset,
+    /// Fallback for unsupported commands.
+    Unknown,
+}
+
+impl From<u32> for Command {
+    fn from(value: u32) -> Command {
+        match value {
+            0 => Command::Check,
+            1 => Command::Reserve,
+            2 => Command::Commit,
+            3 => Command::Query,
+            4 => Command::Reset,
+            _ => Command::Unknown,
+        }
+    }
+}
+
+impl From<Command> for u32 {
+    fn from(command: Command) -> u32 {
+        match command {
+            Command::Check => 0,
+            Command::Reserve => 1,
+            Command::Commit => 2,
+            Command::Query => 3,
+            Command::Reset => 4,
+            Command::Unknown => u32::MAX,
+        }
+    }
+}
+
+/// Length in bytes of an encoded UUID.
+pub const UUID_LEN: usize = 16;
+
+/// Encodes a UUID in its big-endian (RFC 4122) byte order.
+pub fn uuid_bytes(uuid: &raw::TEE_UUID) -> [u8; UUID_LEN] {
+    let mut bytes = [0u8; UUID_LEN];
+    bytes[0..4].copy_from_slice(&uuid.timeLow.to_be_bytes());
+    bytes[4..6].copy_from_slice(&uuid.timeMid.to_be_bytes());
+    bytes[6..8].copy_from_slice(&uuid.timeHiAndVersion.to_be_bytes());
+    bytes[8..16].copy_from_slice(&uuid.clockSeqAndNode);
+    bytes
+}
+
+/// The state of a counter as reported by the quota service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaStatus {
+    /// Number of committed executions.
+    pub count: u32,
+    /// Limit applied to the counter.
+    pub limit: u32,
+    /// Executions reserved but not yet committed.
+    pub reserved: u32,
+}
+
+impl QuotaStatus {
+    /// Returns the number of executions that can still be reserved.
+    pub fn remaining(&self) -> u32 {
+        self.limit.saturating_sub(self.count).saturating_sub(self.reserved)
+    }
+}
+
+/// An execution set aside by [`QuotaClient::reserve`].
+///
+/// Reservations that are not committed are released when the session that
+/// made them is closed.
+#[must_use = "a reservation does nothing unless committed"]
+#[derive(Debug)]
+pub struct Reservation {
+    id: u32,
+}
+
+/// A session with the quota-service TA.
+pub struct QuotaClient {
+    session: TaSession,
+}
+
+impl QuotaClient {
+    /// Opens a session with the quota service identified by `service`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `ItemNotFound`: the service TA is not installed.
+    /// 2) `AccessDenied`: the service is not configured to serve this TA.
+    pub fn open(service: &Uuid) -> Result<Self> {
+        Ok(Self {
+            session: TaSession::open(service)?,
+        })
+    }
+
+    /// Returns the number of executions of `counter` still available.
+    pub fn check(&mut self, counter: &[u8]) -> Result<u32> {
+        let p0 = ParamTmpRef::new_input(counter);
+        let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+        let mut operation = TaOperation::new(&p0, &p1, &ParamNone, &ParamNone);
+        self.session.invoke_command(Command::Check.into(), &mut operation)?;
+        Ok(operation.parameters().1.a())
+    }
+
+    /// Sets aside one execution of `counter`.
+    ///
+    /// Returns `AccessDenied` if no execution is available.
+    pub fn reserve(&mut self, counter: &[u8]) -> Result<Reservation> {
+        let p0 = ParamTmpRef::new_input(counter);
+        let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+        let mut operation = TaOperation::new(&p0, &p1, &ParamNone, &ParamNone);
+        self.session.invoke_command(Command::Reserve.into(), &mut operation)?;
+        Ok(Reservation {
+            id: operation.parameters().1.a(),
+        })
+    }
+
+    /// Consumes a reserved execution.
+    ///
+    /// Returns `ItemNotFound` if the reservation was not made in this session.
+    pub fn commit(&mut self, reservation: Reservation) -> Result<()> {
+        let p0 = ParamTmpRef::new_input(&[]);
+        let p1 = ParamValue::new(reservation.id, 0, ParamType::ValueInput);
+        let mut operation = TaOperation::new(&p0, &p1, &ParamNone, &ParamNone);
+        self.session.invoke_command(Command::Commit.into(), &mut operation)
+    }
+
+    /// Returns the state of `counter`.
+    pub fn query(&mut self, counter: &[u8]) -> Result<QuotaStatus> {
+        let p0 = ParamTmpRef::new_input(counter);
+        let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+        let p2 = ParamValue::new(0, 0, ParamType::ValueOutput);
+        let mut operation = TaOperation::new(&p0, &p1, &p2, &ParamNone);
+        self.session.invoke_command(Command::Query.into(), &mut operation)?;
+        let (_, p1, p2, _) = operation.parameters();
+        Ok(QuotaStatus {
+            count: p1.a(),
+            limit: p1.b(),
+            reserved: p2.a(),
+        })
+    }
+
+    /// Deletes `counter` of the TA identified by `owner`.
+    ///
+    /// Only callers configured as admins of the service may do this; others
+    /// get `AccessDenied`.
+    pub fn reset(&mut self, owner: &Uuid, counter: &[u8]) -> Result<()> {
+        let owner = uuid_bytes(unsafe { &*owner.as_raw_ptr() });
+        let p0 = ParamTmpRef::new_input(counter);
+        let p1 = ParamTmpRef::new_input(&owner);
+        let mut operation = TaOperation::new(&p0, &p1, &ParamNone, &ParamNone);
+        self.session.invoke_command(Command::Reset.into(), &mut operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_bytes() {
+        let uuid = Uuid::parse_str("8a3c7b2e-137b-11f0-a12b-a3d91c3091bf").unwrap();
+        let bytes = uuid_bytes(unsafe { &*uuid.as_raw_ptr() });
+        assert_eq!(
+            bytes,
+            [
+                0x8a, 0x3c, 0x7b, 0x2e, 0x13, 0x7b, 0x11, 0xf0, 0xa1, 0x2b, 0xa3, 0xd9, 0x1c, 0x30,
+                0x91, 0xbf
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Issuer keys and token encoders shared by the unit tests.
+//!
+//! The keys are 512-bit RSA keys, far too short for real issuers but quick to
+//! use on the simulator.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use optee_utee::{
+    AlgorithmId, Asymmetric, AttributeId, AttributeMemref, OperationMode, TransientObject,
+    TransientObjectType,
+};
+
+use crate::cbor;
+use crate::cose::{CLAIM_EXP, CLAIM_LIMIT, CLAIM_SEQ, HEADER_ALG, HEADER_CHAIN, HEADER_KID};
+use crate::token::{sha256, IssuerKey};
+
+/// Public exponent of all test keys.
+pub const EXPONENT: [u8; 3] = [0x01, 0x00, 0x01];
+
+/// An RSA keypair able to sign tokens and certificates.
+pub struct TestKey {
+    pub modulus: [u8; 64],
+    pub private_exponent: [u8; 64],
+}
+
+pub const KEY_A: TestKey = TestKey {
+    modulus: [
+        0xf4, 0x54, 0x97, 0xec, 0x3b, 0x77, 0x3e, 0xa7, 0xec, 0xba, 0x2a, 0xc0, 0xfc, 0xaf, 0xa8,
+        0x33, 0xcb, 0x01, 0x1a, 0x46, 0x90, 0xdb, 0x3d, 0xf3, 0x24, 0xf7, 0x9e, 0xc3, 0xe8, 0x7e,
+        0x03, 0x9e, 0xae, 0x0c, 0x84, 0x84, 0x89, 0x64, 0x9f, 0xfb, 0xad, 0x32, 0xc7, 0x95, 0x27,
+        0x46, 0x9e, 0x28, 0x19, 0xae, 0x84, 0x0f, 0x7d, 0xde, 0xee, 0x3c, 0x2e, 0xeb, 0x92, 0xf7,
+        0x60, 0x1f, 0x55, 0xe5,
+    ],
+    private_exponent: [
+        0x93, 0x8b, 0x62, 0x12, 0xed, 0xf6, 0x1c, 0x88, 0x1b, 0x94, 0x70, 0xad, 0x09, 0xea, 0xa9,
+        0x90, 0x5d, 0x4e, 0xd5, 0x27, 0x37, 0xe2, 0xad, 0x8e, 0x5f, 0x0c, 0x89, 0x7b, 0x94, 0xb2,
+        0x44, 0x4c, 0x28, 0x8e, 0x67, 0xfc, 0x9e, 0x9b, 0xac, 0xcf, 0xff, 0x55, 0xd1, 0xa7, 0xbb,
+        0x2e, 0xde, 0x64, 0x5c, 0xd1, 0xe5, 0xae, 0x83, 0xf6, 0xc8, 0x22, 0x42, 0x11, 0x8e, 0x9f,
+        0x12, 0xa3, 0xd1, 0x21,
+    ],
+};
+
+pub const KEY_B: TestKey = TestKey {
+    modulus: [
+        0xd5, 0x9a, 0x92, 0xec, 0x46, 0xd9, 0x5b, 0x6d, 0xea, 0x03, 0xbb, 0x1a, 0xbf, 0xfe, 0x45,
+        0x53, 0x50, 0x31, 0x8a, 0x4c, 0xfb, 0x1f, 0xd3, 0x33, 0x20, 0x0f, 0x6b, 0x39, 0x44, 0x5b,
+        0x27, 0x64, 0xa7, 0x07, 0x61, 0x7c, 0x53, 0x7b, 0x84, 0x62, 0xac, 0xdf, 0xbc, 0x72, 0x14,
+        0xe1, 0x89, 0xf2, 0xfb, 0x3e, 0x6c, 0xd0, 0x1f, 0x94, 0xb1, 0xbd, 0x44, 0x1c, 0x94, 0x39,
+        0x30, 0x6c, 0x94, 0xc5,
+    ],
+    private_exponent: [
+        0x13, 0xc7, 0xd5, 0xb5, 0x45, 0xb6, 0x59, 0x2e, 0x2b, 0x8e, 0x9e, 0xe4, 0x12, 0x45, 0x84,
+        0xbb, 0xfb, 0x60, 0x2b, 0x13, 0x87, 0xe9, 0xe1, 0x90, 0x09, 0x77, 0x04, 0x11, 0xf0, 0x8f,
+        0x94, 0x6d, 0x25, 0x6c, 0xb2, 0x0f, 0x22, 0x7e, 0x1a, 0x41, 0xdd, 0x87, 0xb7, 0x13, 0x98,
+        0xb3, 0x0c, 0x2c, 0x30, 0x32, 0x67, 0x9f, 0x04, 0x3a, 0x59, 0x4d, 0xa8, 0xa0, 0x54, 0x6d,
+        0x82, 0xda, 0x92, 0xdd,
+    ],
+};
+
+pub const KEY_C: TestKey = TestKey {
+    modulus: [
+        0xb7, 0xf5, 0xbc, 0xa6, 0x4a, 0xb5, 0x05, 0x18, 0xd9, 0x64, 0x87, 0x69, 0xb5, 0x7f, 0x2a,
+        0xa3, 0x44, 0xb1, 0xc3, 0xd3, 0x5e, 0x88, 0xb8, 0x4e, 0x84, 0x45, 0xc3, 0x89, 0xfd, 0xbf,
+        0x83, 0xc1, 0xee, 0xea, 0x1b, 0xa7, 0x6b, 0xae, 0x3c, 0x27, 0x36, 0xc8, 0x15, 0xfc, 0x25,
+        0x39, 0x6a, 0x79, 0xd3, 0xb6, 0x91, 0x73, 0xe8, 0x57, 0x1d, 0xd6, 0x73, 0x0c, 0x67, 0xdf,
+        0xe1, 0x1d, 0x2d, 0xe1,
+    ],
+    private_exponent: [
+        0x0c, 0xe1, 0x93, 0x25, 0x1d, 0xc4, 0x11, 0x28, 0x4d, 0x48, 0xa1, 0xc6, 0xc0, 0x23, 0xb8,
+        0x6b, 0x98, 0x4a, 0xce, 0x48, 0x85, 0xad, 0x13, 0x22, 0xc4, 0x56, 0x52, 0x3b, 0xde, 0x5d,
+        0xc3, 0xae, 0xf9, 0x54, 0x8e, 0x4e, 0x5c, 0x32, 0x5e, 0x43, 0x73, 0x3b, 0x4c, 0xf3, 0xa9,
+        0x0b, 0x4b, 0xca, 0x0b, 0x0f, 0xaa, 0x0b, 0x92, 0xc5, 0xea, 0xe1, 0xc2, 0xb7, 0xde, 0x80,
+        0x4c, 0x4a, 0x00, 0x75,
+    ],
+};
+
+pub const KEY_D: TestKey = TestKey {
+    modulus: [
+        0xd9, 0x72, 0x6b, 0x31, 0x35, 0x13, 0xf0, 0x51, 0xaa, 0x98, 0x03, 0x1f, 0xae, 0x29, 0xb0,
+        0xc9, 0x14, 0xac, 0x39, 0x08, 0x37, 0xf1, 0x3c, 0xa0, 0xbc, 0x96, 0x5f, 0x92, 0x6f, 0x9b,
+        0x20, 0x36, 0x36, 0xd9, 0x74, 0x60, 0x09, 0x07, 0x52, 0xb8, 0xb0, 0x33, 0x6d, 0xa3, 0x79,
+        0x9e, 0x3a, 0xb5, 0x0b, 0x46, 0xba, 0xb9, 0x95, 0x43, 0xb8, 0x2d, 0x6b, 0x52, 0x74, 0x81,
+        0x4b, 0xe3, 0x64, 0x6d,
+    ],
+    private_exponent: [
+        0x5d, 0x6e, 0xcc, 0xa0, 0x58, 0x2e, 0x39, 0x14, 0xde, 0x38, 0x73, 0x1f, 0x29, 0xb6, 0xc0,
+        0x3f, 0xa6, 0xa2, 0x59, 0xdf, 0x2e, 0x2a, 0x79, 0xe2, 0x97, 0x26, 0x05, 0xea, 0xe6, 0x09,
+        0xc1, 0xcc, 0xbe, 0xcd, 0xbc, 0x2d, 0x88, 0x67, 0x3b, 0x03, 0x6e, 0xde, 0x21, 0x1b, 0x53,
+        0x83, 0x59, 0x5f, 0x1a, 0x1f, 0xbe, 0x8c, 0x85, 0x38, 0xe7, 0xfd, 0xb8, 0x3c, 0xd2, 0x47,
+        0x43, 0x09, 0x80, 0x01,
+    ],
+};
+
+impl TestKey {
+    /// Returns the public half as an issuer key registered under `kid`.
+    pub fn public(&self, kid: &[u8]) -> IssuerKey {
+        IssuerKey::rsa(kid, &self.modulus, &EXPONENT).unwrap()
+    }
+
+    /// Signs `message` with RSASSA-PKCS1-v1_5 SHA-256, the way issuers do.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let mut key = TransientObject::allocate(TransientObjectType::RsaKeypair, 512).unwrap();
+        key.populate(&[
+            AttributeMemref::from_ref(AttributeId::RsaModulus, &self.modulus).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPublicExponent, &EXPONENT).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPrivateExponent, &self.private_exponent)
+                .into(),
+        ])
+        .unwrap();
+        let op = Asymmetric::allocate(AlgorithmId::RsassaPkcs1V15Sha256, OperationMode::Sign, 512)
+            .unwrap();
+        op.set_key(&key).unwrap();
+        let mut signature = vec![0u8; 64];
+        op.sign_digest(&[], &sha256(message).unwrap(), &mut signature).unwrap();
+        signature
+    }
+}
+
+/// Encodes a claims map with the given sequence number, limit and expiry.
+pub fn claims(seq: u32, limit: u32, exp: Option<u64>) -> Vec<u8> {
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_MAP, 2 + exp.is_some() as u64);
+    cbor::write_text(&mut out, CLAIM_SEQ);
+    cbor::write_header(&mut out, cbor::MAJOR_UINT, seq as u64);
+    cbor::write_text(&mut out, CLAIM_LIMIT);
+    cbor::write_header(&mut out, cbor::MAJOR_UINT, limit as u64);
+    if let Some(exp) = exp {
+        cbor::write_text(&mut out, CLAIM_EXP);
+        cbor::write_header(&mut out, cbor::MAJOR_UINT, exp);
+    }
+    out
+}
+
+/// Encodes the protected header `{1: -257, 4: kid}`.
+pub fn protected(kid: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_MAP, 2);
+    cbor::write_header(&mut out, cbor::MAJOR_UINT, HEADER_ALG as u64);
+    cbor::write_header(&mut out, cbor::MAJOR_NINT, 256);
+    cbor::write_header(&mut out, cbor::MAJOR_UINT, HEADER_KID as u64);
+    cbor::write_bytes(&mut out, kid);
+    out
+}
+
+/// Encodes a COSE_Sign1 token over `payload` signed by `key`, carrying
+/// `chain` in its unprotected header if given.
+pub fn sign1(key: &TestKey, kid: &[u8], chain: Option<&[u8]>, payload: &[u8]) -> Vec<u8> {
+    let protected = protected(kid);
+    let mut tbs = vec![];
+    cbor::write_header(&mut tbs, cbor::MAJOR_ARRAY, 4);
+    cbor::write_text(&mut tbs, "Signature1");
+    cbor::write_bytes(&mut tbs, &protected);
+    cbor::write_bytes(&mut tbs, &[]);
+    cbor::write_bytes(&mut tbs, payload);
+
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 4);
+    cbor::write_bytes(&mut out, &protected);
+    match chain {
+        Some(chain) => {
+            cbor::write_header(&mut out, cbor::MAJOR_MAP, 1);
+            cbor::write_header(&mut out, cbor::MAJOR_NINT, (-1 - HEADER_CHAIN) as u64);
+            out.extend_from_slice(chain);
+        }
+        None => cbor::write_header(&mut out, cbor::MAJOR_MAP, 0),
+    }
+    cbor::write_bytes(&mut out, payload);
+    cbor::write_bytes(&mut out, &key.sign(&tbs));
+    out
+}
+
+/// Encodes a COSE_Sign token over `payload` with one signature per
+/// `(key, kid)` signer.
+pub fn cose_sign(signers: &[(&TestKey, &[u8])], payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 4);
+    cbor::write_bytes(&mut out, &[]);
+    cbor::write_header(&mut out, cbor::MAJOR_MAP, 0);
+    cbor::write_bytes(&mut out, payload);
+    cbor::write_header(&mut out, cbor::MAJOR_ARRAY, signers.len() as u64);
+    for (key, kid) in signers {
+        let protected = protected(kid);
+        let mut tbs = vec![];
+        cbor::write_header(&mut tbs, cbor::MAJOR_ARRAY, 5);
+        cbor::write_text(&mut tbs, "Signature");
+        cbor::write_bytes(&mut tbs, &[]);
+        cbor::write_bytes(&mut tbs, &protected);
+        cbor::write_bytes(&mut tbs, &[]);
+        cbor::write_bytes(&mut tbs, payload);
+
+        cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 3);
+        cbor::write_bytes(&mut out, &protected);
+        cbor::write_header(&mut out, cbor::MAJOR_MAP, 0);
+        cbor::write_bytes(&mut out, &key.sign(&tbs));
+    }
+    out
+}
+
+/// Encodes a certificate for `subject`, signed by `issuer` and valid from
+/// `not_before` until `not_after`.
+pub fn certificate(
+    issuer: &TestKey,
+    subject: &TestKey,
+    kid: &[u8],
+    (not_before, not_after): (u64, u64),
+    usage: u32,
+) -> Vec<u8> {
+    let mut body = vec![];
+    cbor::write_header(&mut body, cbor::MAJOR_MAP, 6);
+    cbor::write_text(&mut body, "kid");
+    cbor::write_bytes(&mut body, kid);
+    cbor::write_text(&mut body, "n");
+    cbor::write_bytes(&mut body, &subject.modulus);
+    cbor::write_text(&mut body, "e");
+    cbor::write_bytes(&mut body, &EXPONENT);
+    cbor::write_text(&mut body, "nbf");
+    cbor::write_header(&mut body, cbor::MAJOR_UINT, not_before);
+    cbor::write_text(&mut body, "exp");
+    cbor::write_header(&mut body, cbor::MAJOR_UINT, not_after);
+    cbor::write_text(&mut body, "usage");
+    cbor::write_header(&mut body, cbor::MAJOR_UINT, usage as u64);
+
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_ARRAY, 2);
+    cbor::write_bytes(&mut out, &body);
+    cbor::write_bytes(&mut out, &issuer.sign(&body));
+    out
+}
+
+/// Encodes a chain of certificates.
+pub fn chain(certificates: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = vec![];
+    cbor::write_header(&mut out, cbor::MAJOR_ARRAY, certificates.len() as u64);
+    for certificate in certificates {
+        out.extend_from_slice(certificate);
+    }
+    out
+}
@@ -0,0 +1,220 @@
+//! Issuer-signed execution tokens.
+//!
+//! A token carries a sequence number, a usage limit, an optional expiry and an
+//! opaque payload, signed by the issuer's RSA key (RSASSA-PKCS1-v1_5 with
+//! SHA-256). Two encodings are supported:
+//!
+//! * [`TokenFormat::Raw`]: the legacy `seq || limit || payload || signature`
+//!   layout produced by `token-gen --format raw`.
+//! * [`TokenFormat::Cose`]: a COSE_Sign1 message whose payload is a CBOR claims
+//!   map, see the [`cose`](crate::cose) module.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use n_time_model::token::{verify_token, IssuerKey, TokenFormat};
+//!
+//! # let (modulus, exponent, bytes) = (&[0u8; 256], &[1u8, 0, 1], &[0u8; 0]);
+//! # let now = 0;
+//! // `now` comes from a time source the TA trusts, e.g. the TA persistent time.
+//! let key = IssuerKey::rsa(b"issuer-1", modulus, exponent)?;
+//! let claims = verify_token(TokenFormat::Cose, bytes, &key, now)?;
+//! // use claims.seq, claims.limit and claims.payload...
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use alloc::vec::Vec;
+use optee_utee::key::RsaPublicKey;
+use optee_utee::{AlgorithmId, Asymmetric, Digest, Error, ErrorKind, OperationMode, Result};
+
+use crate::cose::CoseSign1;
+
+/// Size in bytes of a SHA-256 digest.
+pub const SHA256_LEN: usize = 32;
+
+/// Size of the fixed `seq || limit` header of a raw token.
+const RAW_HEADER_LEN: usize = 8;
+
+/// Wire format of a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenFormat {
+    /// Legacy `message || signature` layout without framing.
+    Raw,
+    /// COSE_Sign1 with a CBOR claims map.
+    Cose,
+}
+
+/// The claims carried by a verified token.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenClaims<'a> {
+    /// Sequence number chosen by the issuer.
+    pub seq: u32,
+    /// Maximum number of executions granted by this token.
+    pub limit: u32,
+    /// Expiry as seconds since the issuer's epoch, if any.
+    pub exp: Option<u64>,
+    /// Operation-specific payload.
+    pub payload: &'a [u8],
+}
+
+impl<'a> TokenClaims<'a> {
+    /// Returns `AccessDenied` if the token has an expiry and `now` is past it.
+    ///
+    /// `now` must come from a time source the TA trusts, e.g. the TA
+    /// persistent time.
+    pub fn check_expiry(&self, now: u64) -> Result<()> {
+        match self.exp {
+            Some(exp) if now >= exp => Err(Error::new(ErrorKind::AccessDenied)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An RSA public key trusted to sign tokens.
+pub struct IssuerKey {
+    kid: Vec<u8>,
+    key: RsaPublicKey,
+    key_size: usize,
+}
+
+impl IssuerKey {
+    /// Builds an issuer key from its big-endian RSA modulus and public exponent.
+    ///
+    /// `kid` identifies the key in COSE headers; pass an empty slice if tokens
+    /// are not expected to carry a key ID.
+    pub fn rsa(kid: &[u8], modulus: &[u8], exponent: &[u8]) -> Result<Self> {
+        let key = RsaPublicKey::from_components(modulus, exponent)?;
+        let key_size = key.key_size()?;
+        Ok(Self {
+            kid: kid.to_vec(),
+            key,
+            key_size,
+        })
+    }
+
+    /// Returns the key ID this key was registered with.
+    pub fn kid(&self) -> &[u8] {
+        &self.kid
+    }
+
+    /// Returns the big-endian RSA modulus of this key.
+    pub fn modulus(&self) -> Result<Vec<u8>> {
+        self.key.modulus()
+    }
+
+    /// Returns the length in bytes of a signature made with this key.
+    pub fn signature_len(&self) -> usize {
+        self.key_size.div_ceil(8)
+    }
+
+    /// Verifies an RSASSA-PKCS1-v1_5 SHA-256 signature over `message`.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+        self.verify_digest(&sha256(message)?, signature)
+    }
+
+    /// Verifies an RSASSA-PKCS1-v1_5 signature over a precomputed SHA-256 digest.
+    pub fn verify_digest(&self, digest: &[u8], signature: &[u8]) -> Result<()> {
+        if signature.len() != self.signature_len() {
+            return Err(Error::new(ErrorKind::SignatureInvalid));
+        }
+        let op = Asymmetric::allocate(
+            AlgorithmId::RsassaPkcs1V15Sha256,
+            OperationMode::Verify,
+            self.key_size,
+        )?;
+        op.set_key(&self.key)?;
+        op.verify_digest(&[], digest, signature)
+    }
+}
+
+/// Computes the SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> Result<[u8; SHA256_LEN]> {
+    let mut hash = [0u8; SHA256_LEN];
+    let op = Digest::allocate(AlgorithmId::Sha256)?;
+    op.do_final(data, &mut hash)?;
+    Ok(hash)
+}
+
+/// A token in the legacy raw layout, split but not yet verified.
+pub struct RawToken<'a> {
+    message: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> RawToken<'a> {
+    /// Splits `bytes` into the signed message and a trailing signature of
+    /// `signature_len` bytes.
+    pub fn parse(bytes: &'a [u8], signature_len: usize) -> Result<Self> {
+        if bytes.len() < RAW_HEADER_LEN + signature_len {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let (message, signature) = bytes.split_at(bytes.len() - signature_len);
+        Ok(Self { message, signature })
+    }
+
+    /// Verifies the signature and returns the claims of the token.
+    pub fn verify(&self, key: &IssuerKey) -> Result<TokenClaims<'a>> {
+        key.verify(self.message, self.signature)?;
+        let (header, payload) = self.message.split_at(RAW_HEADER_LEN);
+        Ok(TokenClaims {
+            seq: u32::from_le_bytes([header[0], header[1], header[2], header[3]]),
+            limit: u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+            exp: None,
+            payload,
+        })
+    }
+}
+
+/// Decodes a token in the given format, verifies it against `key` and checks
+/// its expiry against `now`.
+///
+/// Raw tokens carry no expiry, so `now` only matters for COSE tokens.
+pub fn verify_token<'a>(
+    format: TokenFormat,
+    bytes: &'a [u8],
+    key: &IssuerKey,
+    now: u64,
+) -> Result<TokenClaims<'a>> {
+    match format {
+        TokenFormat::Raw => RawToken::parse(bytes, key.signature_len())?.verify(key),
+        TokenFormat::Cose => CoseSign1::decode(bytes)?.verify(key, now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, KEY_A};
+
+    #[test]
+    fn test_verify_raw_token() {
+        let key = KEY_A.public(b"");
+        let mut token = Vec::new();
+        token.extend_from_slice(&7u32.to_le_bytes());
+        token.extend_from_slice(&3u32.to_le_bytes());
+        token.extend_from_slice(b"payload");
+        token.extend_from_slice(&KEY_A.sign(&token));
+
+        let claims = verify_token(TokenFormat::Raw, &token, &key, 0).unwrap();
+        assert_eq!((claims.seq, claims.limit, claims.payload), (7, 3, &b"payload"[..]));
+
+        token[0] = 8;
+        let err = verify_token(TokenFormat::Raw, &token, &key, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+        let err = verify_token(TokenFormat::Raw, &token[..70], &key, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadFormat);
+    }
+
+    #[test]
+    fn test_verify_cose_token_expiry() {
+        let key = KEY_A.public(b"k1");
+        let token = testing::sign1(&KEY_A, b"k1", None, &testing::claims(7, 3, Some(1000)));
+
+        let claims = verify_token(TokenFormat::Cose, &token, &key, 999).unwrap();
+        assert_eq!((claims.seq, claims.limit, claims.exp), (7, 3, Some(1000)));
+        for now in [1000, u64::MAX] {
+            let err = verify_token(TokenFormat::Cose, &token, &key, now).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        }
+    }
+}
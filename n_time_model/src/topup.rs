@@ -0,0 +1,164 @@
+//! Signed top-up tokens raising the limit of an existing counter.
+//!
+//! A top-up is a COSE_Sign1 message (see [`CoseSign1`]) whose payload names a
+//! counter and an increment:
+//!
+//! ```text
+//! TopUpClaims = { "seq" => uint, "counter" => bstr, "increment" => uint }
+//! ```
+//!
+//! The sequence number is recorded by the counter when the top-up is applied,
+//! so each top-up can only be used once and older ones are rejected.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use n_time_model::token::IssuerKey;
+//! use n_time_model::topup::TopUp;
+//! use n_time_model::CounterStorage;
+//!
+//! # let (modulus, exponent, bytes) = (&[0u8; 256], &[1u8, 0, 1], &[0u8; 0]);
+//! let key = IssuerKey::rsa(b"", modulus, exponent)?;
+//! let top_up = TopUp::verify(bytes, &key)?;
+//! top_up.apply(1, CounterStorage::RpmbOrRee)?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use optee_utee::{Error, ErrorKind, Result};
+
+use crate::cbor::{Decoder, Key};
+use crate::cose::{CoseSign1, CLAIM_SEQ};
+use crate::token::IssuerKey;
+use crate::{CounterStorage, ExecutionCounter};
+
+/// Claim name of the counter key.
+pub const CLAIM_COUNTER: &str = "counter";
+/// Claim name of the number of executions granted.
+pub const CLAIM_INCREMENT: &str = "increment";
+
+/// The claims of a verified top-up token.
+#[derive(Clone, Copy, Debug)]
+pub struct TopUp<'a> {
+    /// Sequence number of the top-up, increasing per counter.
+    pub seq: u32,
+    /// Storage key of the counter to raise.
+    pub counter: &'a [u8],
+    /// Number of executions granted.
+    pub increment: u32,
+}
+
+impl<'a> TopUp<'a> {
+    /// Verifies a top-up token signed by `key` and decodes its claims.
+    pub fn verify(bytes: &'a [u8], key: &IssuerKey) -> Result<Self> {
+        let payload = CoseSign1::decode(bytes)?.verify_payload(key)?;
+        Self::decode(payload)
+    }
+
+    /// Decodes a CBOR top-up claims map.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self> {
+        let mut d = Decoder::new(bytes);
+        let (mut seq, mut counter, mut increment) = (None, None, None);
+        for _ in 0..d.map()? {
+            let duplicate = match d.key()? {
+                Key::Text(CLAIM_SEQ) => seq.replace(d.u32()?).is_some(),
+                Key::Text(CLAIM_COUNTER) => counter.replace(d.bytes()?).is_some(),
+                Key::Text(CLAIM_INCREMENT) => increment.replace(d.u32()?).is_some(),
+                _ => d.skip().map(|_| false)?,
+            };
+            if duplicate {
+                return Err(Error::new(ErrorKind::BadFormat));
+            }
+        }
+        if !d.is_empty() {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        Ok(Self {
+            seq: seq.ok_or(Error::new(ErrorKind::BadFormat))?,
+            counter: counter.ok_or(Error::new(ErrorKind::BadFormat))?,
+            increment: increment.ok_or(Error::new(ErrorKind::BadFormat))?,
+        })
+    }
+
+    /// Applies the top-up to the named counter, whose base limit is `max`,
+    /// kept in `storage`.
+    ///
+    /// See [`ExecutionCounter::top_up`] for the possible errors.
+    pub fn apply(&self, max: u32, storage: CounterStorage) -> Result<()> {
+        ExecutionCounter::new(self.counter, max)
+            .with_storage(storage)
+            .top_up(self.seq, self.increment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor;
+    use crate::testing::{self, KEY_A, KEY_B};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn claims(seq: u32, counter: &[u8], increment: u32) -> Vec<u8> {
+        let mut claims = vec![];
+        cbor::write_header(&mut claims, cbor::MAJOR_MAP, 3);
+        cbor::write_text(&mut claims, "seq");
+        cbor::write_header(&mut claims, cbor::MAJOR_UINT, seq as u64);
+        cbor::write_text(&mut claims, CLAIM_COUNTER);
+        cbor::write_bytes(&mut claims, counter);
+        cbor::write_text(&mut claims, CLAIM_INCREMENT);
+        cbor::write_header(&mut claims, cbor::MAJOR_UINT, increment as u64);
+        claims
+    }
+
+    #[test]
+    fn test_decode_topup() {
+        let claims = claims(4, b"counter\0", 10);
+        let top_up = TopUp::decode(&claims).unwrap();
+        assert_eq!(top_up.seq, 4);
+        assert_eq!(top_up.counter, b"counter\0");
+        assert_eq!(top_up.increment, 10);
+
+        // an execution token's claims are not a top-up
+        let mut claims = vec![];
+        cbor::write_header(&mut claims, cbor::MAJOR_MAP, 2);
+        cbor::write_text(&mut claims, "seq");
+        cbor::write_header(&mut claims, cbor::MAJOR_UINT, 4);
+        cbor::write_text(&mut claims, "limit");
+        cbor::write_header(&mut claims, cbor::MAJOR_UINT, 1);
+        assert!(TopUp::decode(&claims).is_err());
+    }
+
+    #[test]
+    fn test_verify_and_apply() {
+        const KEY: &[u8] = b"topup-test";
+        let counter = ExecutionCounter::new(KEY, 1).with_storage(CounterStorage::Ree);
+        counter.check_and_increment().unwrap();
+        let key = KEY_A.public(b"issuer");
+        let token = |seq, increment| {
+            testing::sign1(&KEY_A, b"issuer", None, &claims(seq, KEY, increment))
+        };
+        let apply = |bytes: &[u8]| TopUp::verify(bytes, &key)?.apply(1, CounterStorage::Ree);
+
+        let token_4 = token(4, 2);
+        let top_up = TopUp::verify(&token_4, &key).unwrap();
+        assert_eq!((top_up.seq, top_up.counter, top_up.increment), (4, KEY, 2));
+        // the counter is only found in the storage it is kept in
+        let err = top_up.apply(1, CounterStorage::Rpmb).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+        top_up.apply(1, CounterStorage::Ree).unwrap();
+        assert_eq!(counter.status().unwrap().remaining, 2);
+
+        // replayed and older top-ups
+        for bytes in [&token_4, &token(3, 2)] {
+            assert_eq!(apply(bytes).unwrap_err().kind(), ErrorKind::AccessDenied);
+        }
+
+        assert_eq!(apply(&token(5, u32::MAX)).unwrap_err().kind(), ErrorKind::Overflow);
+        assert_eq!(counter.status().unwrap().remaining, 2);
+
+        // signed by another key
+        let token_6 = testing::sign1(&KEY_B, b"issuer", None, &claims(6, KEY, 1));
+        let err = TopUp::verify(&token_6, &key).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+    }
+}
@@ -0,0 +1,268 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parenthesized};
+
+/// Derives the client stub and the dispatcher of a protocol from an enum of
+/// commands.
+///
+/// Each variant is a command whose ID is the index of the variant. A variant
+/// with one unnamed field takes that type as its request, a unit variant
+/// takes no request. The response type is set with `#[response(Type)]` and
+/// defaults to `()`. The response buffer allocated by the client is set
+/// with `#[protocol(response_len = N)]` on the enum.
+///
+/// For `enum Command` it generates:
+///
+/// * `CommandClient<T: Transport>` with one method per command, named after
+///   the variant in snake case, taking the request by reference;
+/// * `trait CommandHandler` with one method per command taking the request
+///   by value, to be implemented by the TA;
+/// * `Command::dispatch(handler, command_id, request, response)`, which
+///   decodes the request, calls the handler and encodes the response.
+///
+/// # Examples
+///
+/// ``` no_run
+/// #[derive(Protocol)]
+/// #[protocol(response_len = 256)]
+/// pub enum Command {
+///     #[response(u32)]
+///     Increment(u32),
+///     Reset,
+/// }
+/// ```
+#[proc_macro_derive(Protocol, attributes(protocol, response))]
+pub fn derive_protocol(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct Command {
+    id: u32,
+    method: syn::Ident,
+    request: Option<syn::Type>,
+    response: syn::Type,
+}
+
+struct ParenType(syn::Type);
+
+impl Parse for ParenType {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        parenthesized!(content in input);
+        Ok(ParenType(content.parse()?))
+    }
+}
+
+fn expand(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let variants = match &input.data {
+        syn::Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "`#[derive(Protocol)]` can only be used on an enum",
+            ))
+        }
+    };
+    let response_len = response_len(&input.attrs)?;
+
+    let mut commands = Vec::new();
+    for (id, variant) in variants.iter().enumerate() {
+        let request = match &variant.fields {
+            syn::Fields::Unit => None,
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                Some(fields.unnamed[0].ty.clone())
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "a command must be a unit variant or have a single unnamed field",
+                ))
+            }
+        };
+        let mut response = syn::parse_quote!(());
+        for attr in &variant.attrs {
+            if attr.path.is_ident("response") {
+                response = syn::parse2::<ParenType>(attr.tts.clone())?.0;
+            }
+        }
+        commands.push(Command {
+            id: id as u32,
+            method: syn::Ident::new(&snake_case(&variant.ident.to_string()), variant.ident.span()),
+            request,
+            response,
+        });
+    }
+
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let client = syn::Ident::new(&format!("{}Client", ident), ident.span());
+    let handler = syn::Ident::new(&format!("{}Handler", ident), ident.span());
+
+    let handler_methods = commands.iter().map(|c| {
+        let method = &c.method;
+        let response = &c.response;
+        match &c.request {
+            Some(request) => quote! {
+                fn #method(&mut self, request: #request)
+                    -> ::core::result::Result<#response, Self::Error>;
+            },
+            None => quote! {
+                fn #method(&mut self) -> ::core::result::Result<#response, Self::Error>;
+            },
+        }
+    });
+
+    let client_methods = commands.iter().map(|c| {
+        let id = c.id;
+        let method = &c.method;
+        let response = &c.response;
+        match &c.request {
+            Some(request) => quote! {
+                pub fn #method(&mut self, request: &#request)
+                    -> ::core::result::Result<#response, T::Error> {
+                    self.transport.request(#id, request, #response_len)
+                }
+            },
+            None => quote! {
+                pub fn #method(&mut self) -> ::core::result::Result<#response, T::Error> {
+                    self.transport.request(#id, &(), #response_len)
+                }
+            },
+        }
+    });
+
+    let dispatch_arms = commands.iter().map(|c| {
+        let id = c.id;
+        let method = &c.method;
+        let call = match &c.request {
+            Some(request) => quote! {
+                handler.#method(::optee_protocol::decode::<#request>(request)?)?
+            },
+            None => quote! {{
+                ::optee_protocol::decode::<()>(request)?;
+                handler.#method()?
+            }},
+        };
+        quote! {
+            #id => {
+                let value = #call;
+                Ok(::optee_protocol::encode_into(&value, response)?)
+            }
+        }
+    });
+
+    Ok(quote! {
+        /// Client for the commands of the protocol.
+        #vis struct #client<T> {
+            transport: T,
+        }
+
+        impl<T: ::optee_protocol::Transport> #client<T> {
+            /// Creates a client sending its requests over `transport`.
+            pub fn new(transport: T) -> Self {
+                Self { transport }
+            }
+
+            /// Returns the underlying transport.
+            pub fn into_inner(self) -> T {
+                self.transport
+            }
+
+            #(#client_methods)*
+        }
+
+        /// Handler for the commands of the protocol, implemented by the TA.
+        #vis trait #handler {
+            type Error: ::core::convert::From<::optee_protocol::Error>;
+
+            #(#handler_methods)*
+        }
+
+        impl #ident {
+            /// Decodes `request` for `command_id`, passes it to `handler` and
+            /// encodes the response into `response`, returning its length.
+            pub fn dispatch<H: #handler>(
+                handler: &mut H,
+                command_id: u32,
+                request: &[u8],
+                response: &mut [u8],
+            ) -> ::core::result::Result<usize, H::Error> {
+                match command_id {
+                    #(#dispatch_arms)*
+                    id => Err(::optee_protocol::Error::UnknownCommand(id).into()),
+                }
+            }
+        }
+    })
+}
+
+/// Returns the `response_len` set with `#[protocol(..)]`.
+fn response_len(attrs: &[syn::Attribute]) -> syn::Result<proc_macro2::TokenStream> {
+    let mut response_len = quote!(::optee_protocol::DEFAULT_RESPONSE_LEN);
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("protocol")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new(meta.span(), "expected `protocol(..)`")),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    ident,
+                    lit: syn::Lit::Int(len),
+                    ..
+                })) if ident == "response_len" => {
+                    let len = len.value() as usize;
+                    response_len = quote!(#len);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        nested.span(),
+                        "expected `response_len = <integer>`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(response_len)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
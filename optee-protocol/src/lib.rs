@@ -0,0 +1,193 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+//! A typed request/response protocol between host applications and TAs.
+//!
+//! Requests and responses are serde types defined once in a project's `proto`
+//! crate and encoded into memref buffers with [postcard]. Deriving
+//! [`Protocol`] on an enum of commands generates a client stub for the host
+//! and a handler trait with a dispatcher for the TA.
+//!
+//! Each command is invoked with the encoded request in parameter 0
+//! (`MemrefInput`) and the encoded response in parameter 1 (`MemrefOutput`);
+//! the command ID is the index of the variant.
+//!
+//! # Examples
+//!
+//! In the `proto` crate:
+//!
+//! ``` no_run
+//! use optee_protocol::Protocol;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! pub struct SortRequest {
+//!     pub values: Vec<i32>,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! pub struct Status {
+//!     pub count: u32,
+//!     pub max: u32,
+//! }
+//!
+//! #[derive(Protocol)]
+//! pub enum Command {
+//!     #[response(Vec<i32>)]
+//!     Sort(SortRequest),
+//!     #[response(Status)]
+//!     Status,
+//! }
+//! ```
+//!
+//! The host calls the TA through the generated `CommandClient`, and the TA
+//! implements `CommandHandler` and passes its parameters to
+//! `utee::serve(params, |request, response| Command::dispatch(&mut handler,
+//! cmd_id, request, response))`.
+//!
+//! [postcard]: https://docs.rs/postcard
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub use optee_protocol_macros::Protocol;
+
+#[cfg(feature = "teec")]
+mod teec;
+#[cfg(feature = "utee")]
+pub mod utee;
+
+/// Size of the response buffer the generated clients allocate, unless the
+/// protocol sets another one with `#[protocol(response_len = N)]`.
+pub const DEFAULT_RESPONSE_LEN: usize = 1024;
+
+/// A specialized `Result` type for protocol operations.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// The error type for encoding, decoding and dispatching requests.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The TA does not know the command ID.
+    UnknownCommand(u32),
+    /// The value could not be encoded.
+    Encode,
+    /// The buffer does not hold a valid encoding of the expected type.
+    Decode,
+    /// The buffer is too short for the encoded value.
+    ShortBuffer,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownCommand(id) => write!(f, "unknown command {}", id),
+            Error::Encode => write!(f, "value cannot be encoded"),
+            Error::Decode => write!(f, "malformed encoding"),
+            Error::ShortBuffer => write!(f, "buffer too short"),
+        }
+    }
+}
+
+/// Encodes `value`.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    postcard::to_allocvec(value).map_err(|_| Error::Encode)
+}
+
+/// Encodes `value` into `buffer` and returns the encoded length.
+///
+/// # Errors
+///
+/// 1) `ShortBuffer`: the encoding does not fit in `buffer`.
+/// 2) `Encode`: the value cannot be encoded.
+pub fn encode_into<T: Serialize + ?Sized>(value: &T, buffer: &mut [u8]) -> Result<usize> {
+    match postcard::to_slice(value, buffer) {
+        Ok(encoded) => Ok(encoded.len()),
+        Err(postcard::Error::SerializeBufferFull) => Err(Error::ShortBuffer),
+        Err(_) => Err(Error::Encode),
+    }
+}
+
+/// Decodes a value of type `T` that takes up all of `bytes`.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    match postcard::take_from_bytes(bytes) {
+        Ok((value, [])) => Ok(value),
+        _ => Err(Error::Decode),
+    }
+}
+
+/// A channel to a TA that carries encoded requests and responses.
+pub trait Transport {
+    type Error: From<Error>;
+
+    /// Invokes `command_id` with the encoded `request`, writes the encoded
+    /// response to `response` and returns its length.
+    fn call(
+        &mut self,
+        command_id: u32,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> core::result::Result<usize, Self::Error>;
+
+    /// Encodes `request`, invokes `command_id` and decodes the response,
+    /// which must fit in `response_len` bytes.
+    fn request<Req, Resp>(
+        &mut self,
+        command_id: u32,
+        request: &Req,
+        response_len: usize,
+    ) -> core::result::Result<Resp, Self::Error>
+    where
+        Req: Serialize + ?Sized,
+        Resp: DeserializeOwned,
+    {
+        let request = encode(request)?;
+        let mut response = vec![0u8; response_len];
+        let len = self.call(command_id, &request, &mut response)?;
+        let response = response.get(..len).ok_or(Error::ShortBuffer)?;
+        Ok(decode(response)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec() {
+        let value = (7u32, vec![-1i32, 2, 3]);
+        let encoded = encode(&value).unwrap();
+        assert_eq!(decode::<(u32, Vec<i32>)>(&encoded).unwrap(), value);
+
+        let mut buffer = [0u8; 16];
+        let len = encode_into(&value, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &encoded[..]);
+        assert_eq!(encode_into(&value, &mut buffer[..2]), Err(Error::ShortBuffer));
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(decode::<(u32, Vec<i32>)>(&trailing), Err(Error::Decode));
+        assert_eq!(decode::<(u32, Vec<i32>)>(&encoded[..3]), Err(Error::Decode));
+    }
+}
@@ -0,0 +1,48 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+use crate::{Error, Transport};
+use optee_teec::{ErrorKind, Operation, ParamNone, ParamTmpRef, TeeSession};
+
+impl<S: TeeSession> Transport for S {
+    type Error = optee_teec::Error;
+
+    fn call(
+        &mut self,
+        command_id: u32,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> optee_teec::Result<usize> {
+        let p0 = ParamTmpRef::new_input(request);
+        let p1 = ParamTmpRef::new_output(response);
+        let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+        self.invoke_command(command_id, &mut operation)?;
+        Ok(operation.parameters().1.updated_size())
+    }
+}
+
+impl From<Error> for optee_teec::Error {
+    fn from(error: Error) -> Self {
+        let kind = match error {
+            Error::UnknownCommand(_) => ErrorKind::NotSupported,
+            Error::Encode | Error::Decode => ErrorKind::BadFormat,
+            Error::ShortBuffer => ErrorKind::ShortBuffer,
+        };
+        optee_teec::Error::new(kind)
+    }
+}
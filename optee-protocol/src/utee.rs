@@ -0,0 +1,78 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+//! Serving protocol requests in a TA.
+
+use crate::Error;
+use optee_utee::{ErrorKind, ParamType, ParamTypes, Parameters};
+
+/// Passes the request in parameter 0 to `handle` and reports the response
+/// it writes as the output of parameter 1.
+///
+/// `handle` is usually a call to the dispatcher generated by
+/// [`Protocol`](crate::Protocol).
+///
+/// # Examples
+///
+/// ``` no_run
+/// # use optee_utee::{Parameters, Result};
+/// # struct Command;
+/// # impl Command {
+/// #     fn dispatch(_: &mut (), _: u32, _: &[u8], _: &mut [u8]) -> Result<usize> { Ok(0) }
+/// # }
+/// fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
+///     let mut handler = ();
+///     optee_protocol::utee::serve(params, |request, response| {
+///         Command::dispatch(&mut handler, cmd_id, request, response)
+///     })
+/// }
+/// ```
+///
+/// # Errors
+///
+/// 1) `BadParameters`: the parameters are not a `MemrefInput` and a
+///    `MemrefOutput`.
+/// 2) `ShortBuffer`: the response does not fit in parameter 1.
+/// 3) Any error returned by `handle`.
+pub fn serve<F>(params: &mut Parameters, handle: F) -> optee_utee::Result<()>
+where
+    F: FnOnce(&[u8], &mut [u8]) -> optee_utee::Result<usize>,
+{
+    params.check_types(ParamTypes::new(
+        ParamType::MemrefInput,
+        ParamType::MemrefOutput,
+        ParamType::None,
+        ParamType::None,
+    ))?;
+    let mut p0 = unsafe { params.0.as_memref()? };
+    let mut p1 = unsafe { params.1.as_memref()? };
+    let len = handle(p0.as_slice::<u8>()?, p1.as_mut_slice::<u8>()?)?;
+    p1.set_updated_size(len);
+    Ok(())
+}
+
+impl From<Error> for optee_utee::Error {
+    fn from(error: Error) -> Self {
+        let kind = match error {
+            Error::UnknownCommand(_) => ErrorKind::NotSupported,
+            Error::Encode | Error::Decode => ErrorKind::BadFormat,
+            Error::ShortBuffer => ErrorKind::ShortBuffer,
+        };
+        optee_utee::Error::new(kind)
+    }
+}
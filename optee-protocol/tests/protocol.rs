@@ -0,0 +1,109 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+use optee_protocol::{Error, Protocol, Transport};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    count: u32,
+    max: u32,
+}
+
+#[derive(Protocol)]
+#[protocol(response_len = 64)]
+pub enum Command {
+    #[response(Vec<i32>)]
+    Sort(Vec<i32>),
+    #[response(Status)]
+    Status,
+    SetMax(u32),
+    #[response(Vec<u8>)]
+    Echo(Vec<u8>),
+}
+
+#[derive(Default)]
+struct Counter {
+    count: u32,
+    max: u32,
+}
+
+impl CommandHandler for Counter {
+    type Error = Error;
+
+    fn sort(&mut self, mut request: Vec<i32>) -> Result<Vec<i32>, Error> {
+        self.count += 1;
+        request.sort();
+        Ok(request)
+    }
+
+    fn status(&mut self) -> Result<Status, Error> {
+        Ok(Status {
+            count: self.count,
+            max: self.max,
+        })
+    }
+
+    fn set_max(&mut self, request: u32) -> Result<(), Error> {
+        self.max = request;
+        Ok(())
+    }
+
+    fn echo(&mut self, request: Vec<u8>) -> Result<Vec<u8>, Error> {
+        Ok(request)
+    }
+}
+
+/// Passes requests straight to the dispatcher.
+struct Loopback(Counter);
+
+impl Transport for Loopback {
+    type Error = Error;
+
+    fn call(&mut self, command_id: u32, request: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+        Command::dispatch(&mut self.0, command_id, request, response)
+    }
+}
+
+#[test]
+fn test_client_dispatch() {
+    let mut client = CommandClient::new(Loopback(Counter::default()));
+    assert_eq!(client.sort(&vec![3, -1, 2]).unwrap(), vec![-1, 2, 3]);
+    client.set_max(&5).unwrap();
+    assert_eq!(client.status().unwrap(), Status { count: 1, max: 5 });
+    assert_eq!(client.echo(&vec![0; 100]), Err(Error::ShortBuffer));
+    assert_eq!(client.into_inner().0.max, 5);
+}
+
+#[test]
+fn test_dispatch_errors() {
+    let mut counter = Counter::default();
+    let mut response = [0u8; 16];
+    assert_eq!(
+        Command::dispatch(&mut counter, 4, &[], &mut response),
+        Err(Error::UnknownCommand(4))
+    );
+    assert_eq!(
+        Command::dispatch(&mut counter, 1, &[0], &mut response),
+        Err(Error::Decode)
+    );
+    assert_eq!(
+        Command::dispatch(&mut counter, 2, &[], &mut response),
+        Err(Error::Decode)
+    );
+}
@@ -0,0 +1,136 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::raw;
+use std::sync::{Arc, Mutex};
+
+/// A handle to cancel an operation invoked with
+/// [`Session::invoke_command_cancellable`](crate::Session::invoke_command_cancellable)
+/// from another thread.
+///
+/// Cancelling is a request: the TA may ignore it or have masked
+/// cancellation, in which case the command completes as usual. A handle
+/// cancelled before the command starts makes it fail with `Cancel` without
+/// reaching the TA.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # use optee_teec::{CancelHandle, Context, Operation, ParamNone, Uuid};
+/// # fn main() -> optee_teec::Result<()> {
+/// # let mut ctx = Context::new()?;
+/// # let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+/// let mut session = ctx.open_session(uuid)?;
+/// let handle = CancelHandle::new();
+/// let canceller = handle.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(5));
+///     canceller.cancel();
+/// });
+/// let mut operation = Operation::new(0, ParamNone, ParamNone, ParamNone, ParamNone);
+/// session.invoke_command_cancellable(0, &mut operation, &handle)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CancelHandle {
+    state: Arc<Mutex<CancelState>>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    operation: Option<*mut raw::TEEC_Operation>,
+}
+
+// The operation pointer is only dereferenced by the TEE client library while
+// the invoking thread keeps the operation borrowed, see `register`.
+unsafe impl Send for CancelState {}
+
+impl CancelHandle {
+    /// Creates a handle which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of the operation using this handle, if any, and
+    /// of any operation that uses it later.
+    pub fn cancel(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.cancelled = true;
+        if let Some(operation) = state.operation {
+            unsafe { raw::TEEC_RequestCancellation(operation) };
+        }
+    }
+
+    /// Returns whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().unwrap().cancelled
+    }
+
+    /// Registers the operation being invoked, or returns `false` if the handle
+    /// is already cancelled.
+    ///
+    /// The operation must stay valid until [`unregister`](Self::unregister)
+    /// returns.
+    pub(crate) fn register(&self, operation: *mut raw::TEEC_Operation) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.cancelled {
+            return false;
+        }
+        state.operation = Some(operation);
+        true
+    }
+
+    /// Unregisters the operation once its invocation has returned.
+    ///
+    /// This waits for a concurrent [`cancel`](Self::cancel) to finish, so the
+    /// operation is not freed while cancellation is being requested.
+    pub(crate) fn unregister(&self) {
+        self.state.lock().unwrap().operation = None;
+    }
+}
+
+// the mocks below clash with the `runner` implementation
+#[cfg(all(test, not(feature = "runner")))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static REQUESTED: AtomicUsize = AtomicUsize::new(0);
+
+    #[no_mangle]
+    extern "C" fn TEEC_RequestCancellation(operation: *mut raw::TEEC_Operation) {
+        REQUESTED.store(operation as usize, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let handle = CancelHandle::new();
+        let mut operation: raw::TEEC_Operation = unsafe { std::mem::zeroed() };
+        let ptr: *mut raw::TEEC_Operation = &mut operation;
+
+        assert!(handle.register(ptr));
+        handle.clone().cancel();
+        assert_eq!(REQUESTED.load(Ordering::SeqCst), ptr as usize);
+        assert!(handle.is_cancelled());
+        handle.unregister();
+
+        // a cancelled handle refuses new operations
+        assert!(!handle.register(ptr));
+    }
+}
@@ -0,0 +1,108 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{ConnectionMethods, Context, Operation, Param, Result, Session, Uuid};
+
+/// The operations of a [`Context`], so that client applications can be
+/// written against this trait and tested with
+/// [`MockContext`](crate::mock::MockContext).
+///
+/// # Examples
+///
+/// ``` no_run
+/// use optee_teec::{Context, Operation, ParamNone, TeeContext, TeeSession, Uuid};
+///
+/// fn ping<C: TeeContext>(ctx: &mut C, uuid: Uuid) -> optee_teec::Result<()> {
+///     let mut session = ctx.open_session(uuid)?;
+///     let mut operation = Operation::new(0, ParamNone, ParamNone, ParamNone, ParamNone);
+///     session.invoke_command(0, &mut operation)
+/// }
+///
+/// fn main() -> optee_teec::Result<()> {
+///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+///     ping(&mut Context::new()?, uuid)
+/// }
+/// ```
+pub trait TeeContext {
+    /// The sessions opened by this context.
+    type Session: TeeSession;
+
+    /// See [`Context::open_session`].
+    fn open_session(&mut self, uuid: Uuid) -> Result<Self::Session>;
+
+    /// See [`Context::open_session_with_operation`].
+    fn open_session_with_operation<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<Self::Session>;
+
+    /// See [`Context::open_session_with_login`].
+    fn open_session_with_login<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        method: ConnectionMethods,
+        gid: Option<u32>,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<Self::Session>;
+}
+
+/// The operations of a [`Session`], see [`TeeContext`].
+pub trait TeeSession {
+    /// See [`Session::invoke_command`].
+    fn invoke_command<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<()>;
+}
+
+impl TeeContext for Context {
+    type Session = Session;
+
+    fn open_session(&mut self, uuid: Uuid) -> Result<Session> {
+        Context::open_session(self, uuid)
+    }
+
+    fn open_session_with_operation<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<Session> {
+        Context::open_session_with_operation(self, uuid, operation)
+    }
+
+    fn open_session_with_login<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        method: ConnectionMethods,
+        gid: Option<u32>,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<Session> {
+        Context::open_session_with_login(self, uuid, method, gid, operation)
+    }
+}
+
+impl TeeSession for Session {
+    fn invoke_command<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<()> {
+        Session::invoke_command(self, command_id, operation)
+    }
+}
@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{raw, Error, Operation, Param, ParamNone, Result, Session, Uuid};
+use crate::{raw, ConnectionMethods, Error, Operation, Param, ParamNone, Result, Session, Uuid};
 use std::{cell::RefCell, ptr, rc::Rc};
 
 pub struct InnerContext(pub raw::TEEC_Context);
@@ -126,6 +126,43 @@ impl Context {
     ) -> Result<Session> {
         Session::new(self, uuid, Some(operation))
     }
+
+    /// Opens a new session with the specified trusted application, logging
+    /// in with `method`.
+    ///
+    /// The TA can then read the resulting client identity. `gid` is required
+    /// for the group login methods and must be `None` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{ConnectionMethods, Context, ErrorKind, Operation, ParamNone, Uuid};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").map_err(|err| {
+    ///         println!("bad uuid: {:?}", err);
+    ///         ErrorKind::BadParameters
+    ///     })?;
+    ///     let mut operation = Operation::new(0, ParamNone, ParamNone, ParamNone, ParamNone);
+    ///     let session = ctx.open_session_with_login(
+    ///         uuid,
+    ///         ConnectionMethods::LoginGroup,
+    ///         Some(1000),
+    ///         &mut operation,
+    ///     )?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn open_session_with_login<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        method: ConnectionMethods,
+        gid: Option<u32>,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<Session> {
+        Session::new_with_login(self, uuid, method, gid, Some(operation))
+    }
 }
 
 // Internal usage only
@@ -42,6 +42,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub struct Error {
     kind: ErrorKind,
     origin: Option<ErrorOrigin>,
+    required_size: Option<usize>,
 }
 
 /// A list specifying general categories of TEE client error and its
@@ -127,7 +128,11 @@ impl ErrorKind {
 
 impl Error {
     pub fn new(kind: ErrorKind) -> Error {
-        Error { kind, origin: None }
+        Error {
+            kind,
+            origin: None,
+            required_size: None,
+        }
     }
     /// Creates a new instance of an `Error` from a particular TEE error code.
     ///
@@ -143,6 +148,7 @@ impl Error {
         Error {
             kind: ErrorKind::from(code),
             origin: None,
+            required_size: None,
         }
     }
 
@@ -151,6 +157,13 @@ impl Error {
         self
     }
 
+    /// Attaches the size of the output buffer needed by the TA, as reported
+    /// with a `ShortBuffer` error.
+    pub fn with_required_size(mut self, size: usize) -> Self {
+        self.required_size = Some(size);
+        self
+    }
+
     /// Returns the corresponding `ErrorKind` for this error.
     ///
     /// # Examples
@@ -169,6 +182,16 @@ impl Error {
         self.origin.clone()
     }
 
+    /// Returns the size of the output buffer needed by the TA, if it was
+    /// attached to a `ShortBuffer` error.
+    ///
+    /// Clients generated by `optee-utee-build` attach it for the commands
+    /// declaring `ShortBuffer`, so the caller can retry with a buffer of this
+    /// size.
+    pub fn required_size(&self) -> Option<usize> {
+        self.required_size
+    }
+
     /// Returns raw code of this error.
     pub fn raw_code(&self) -> u32 {
         self.kind.into()
@@ -207,7 +230,11 @@ impl std::error::Error for Error {
 impl From<ErrorKind> for Error {
     #[inline]
     fn from(kind: ErrorKind) -> Error {
-        Error { kind, origin: None }
+        Error {
+            kind,
+            origin: None,
+            required_size: None,
+        }
     }
 }
 
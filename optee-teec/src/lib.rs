@@ -15,22 +15,33 @@
 // specific language governing permissions and limitations
 // under the License.
 
+pub use self::cancel::CancelHandle;
+pub use self::client::{TeeContext, TeeSession};
 pub use self::context::Context;
 pub use self::error::{Error, ErrorKind, ErrorOrigin, Result};
 pub use self::extension::*;
 pub use self::operation::Operation;
-pub use self::parameter::{Param, ParamNone, ParamTmpRef, ParamType, ParamTypes, ParamValue};
+pub use self::parameter::{
+    Param, ParamMemref, ParamNone, ParamTmpRef, ParamType, ParamTypes, ParamValue,
+};
 pub use self::session::{ConnectionMethods, Session};
+pub use self::shared_memory::{SharedMemory, SharedMemoryFlags};
 pub use self::uuid::Uuid;
 pub use optee_teec_macros::{plugin_init, plugin_invoke};
 // Re-export optee_teec_sys so developers don't have to add it to their cargo
 // dependencies.
 pub use optee_teec_sys as raw;
 
+mod cancel;
+mod client;
 mod context;
 mod error;
 mod extension;
+pub mod mock;
 mod operation;
 mod parameter;
+#[cfg(feature = "runner")]
+pub mod runner;
 mod session;
+mod shared_memory;
 mod uuid;
@@ -0,0 +1,403 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A scriptable TEE for unit tests of client applications.
+//!
+//! [`MockContext`] implements [`TeeContext`] without a TEE: each command ID
+//! is answered by a handler given by the test, which reads the parameters
+//! passed by the client and sets the outputs, and every invocation is
+//! recorded so that the test can check the parameters afterwards.
+//!
+//! # Examples
+//!
+//! ```
+//! use optee_teec::mock::{MockContext, MockParam};
+//! use optee_teec::{ErrorKind, Operation, ParamNone, ParamType, ParamValue};
+//! use optee_teec::{TeeContext, TeeSession, Uuid};
+//!
+//! let mut ctx = MockContext::new();
+//! ctx.on_command(0, |params| {
+//!     params[0].set_value(3, 5);
+//!     Ok(())
+//! });
+//! ctx.fail_command(1, ErrorKind::AccessDenied);
+//!
+//! let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+//! let mut session = ctx.open_session(uuid).unwrap();
+//! let p0 = ParamValue::new(0, 0, ParamType::ValueOutput);
+//! let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+//! session.invoke_command(0, &mut operation).unwrap();
+//! assert_eq!(operation.parameters().0.a(), 3);
+//!
+//! let err = session.invoke_command(1, &mut operation).unwrap_err();
+//! assert_eq!(err.kind(), ErrorKind::AccessDenied);
+//! assert_eq!(ctx.invocations()[1].command_id, 1);
+//! ```
+
+use crate::{
+    raw, ConnectionMethods, Error, ErrorKind, ErrorOrigin, Operation, Param, ParamType, Result,
+    TeeContext, TeeSession, Uuid,
+};
+use std::{cell::RefCell, collections::HashMap, ptr, rc::Rc, slice};
+
+type Handler = Box<dyn FnMut(&mut [MockParam; 4]) -> Result<()>>;
+
+/// A parameter of a command, as seen by a [`MockContext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockParam {
+    /// The parameter is not used.
+    None,
+    /// A value parameter.
+    Value {
+        param_type: ParamType,
+        a: u32,
+        b: u32,
+    },
+    /// A temporary or registered memory reference and the bytes it refers to.
+    Memref { param_type: ParamType, data: Vec<u8> },
+}
+
+impl MockParam {
+    /// Returns the `(a, b)` of a value parameter.
+    pub fn value(&self) -> Option<(u32, u32)> {
+        match self {
+            MockParam::Value { a, b, .. } => Some((*a, *b)),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of a memory reference.
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            MockParam::Memref { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Sets the output of a value parameter.
+    ///
+    /// # Panics
+    ///
+    /// If the parameter is not a value.
+    pub fn set_value(&mut self, a: u32, b: u32) {
+        match self {
+            MockParam::Value { a: old_a, b: old_b, .. } => {
+                *old_a = a;
+                *old_b = b;
+            }
+            _ => panic!("parameter is not a value: {:?}", self),
+        }
+    }
+
+    /// Sets the output of a memory reference.
+    ///
+    /// As with a TA, `data` is only copied to the client if it fits in the
+    /// referenced memory, while the size seen by the client is always updated
+    /// to its length, e.g. to answer `ShortBuffer`.
+    ///
+    /// # Panics
+    ///
+    /// If the parameter is not a memory reference.
+    pub fn set_data(&mut self, data: &[u8]) {
+        match self {
+            MockParam::Memref { data: old, .. } => *old = data.to_vec(),
+            _ => panic!("parameter is not a memory reference: {:?}", self),
+        }
+    }
+}
+
+/// A command invoked on a [`MockSession`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invocation {
+    /// The UUID of the TA the session was opened to.
+    pub uuid: String,
+    pub command_id: u32,
+    /// The parameters as passed by the client.
+    pub params: [MockParam; 4],
+}
+
+#[derive(Default)]
+struct MockState {
+    handlers: HashMap<u32, Handler>,
+    open_session_error: Option<ErrorKind>,
+    invocations: Vec<Invocation>,
+}
+
+/// A [`TeeContext`] answering commands with handlers given by the test, see
+/// the [module documentation](self).
+///
+/// Commands without a handler fail with `NotImplemented`.
+#[derive(Clone, Default)]
+pub struct MockContext {
+    state: Rc<RefCell<MockState>>,
+}
+
+impl MockContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers `command_id` with `handler`, which gets the parameters passed
+    /// by the client, sets the outputs and returns the result of the command.
+    ///
+    /// Errors without an origin are returned with the origin `TA`.
+    pub fn on_command<F>(&mut self, command_id: u32, handler: F)
+    where
+        F: FnMut(&mut [MockParam; 4]) -> Result<()> + 'static,
+    {
+        self.state
+            .borrow_mut()
+            .handlers
+            .insert(command_id, Box::new(handler));
+    }
+
+    /// Fails `command_id` with `kind`.
+    pub fn fail_command(&mut self, command_id: u32, kind: ErrorKind) {
+        self.on_command(command_id, move |_| Err(Error::new(kind)));
+    }
+
+    /// Fails the sessions opened from now on with `kind`, or lets them open
+    /// again if `kind` is `None`.
+    pub fn fail_open_session(&mut self, kind: Option<ErrorKind>) {
+        self.state.borrow_mut().open_session_error = kind;
+    }
+
+    /// Returns the commands invoked so far, in order.
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.state.borrow().invocations.clone()
+    }
+
+    fn new_session(&self, uuid: Uuid) -> Result<MockSession> {
+        match self.state.borrow().open_session_error {
+            Some(kind) => Err(Error::new(kind).with_origin(ErrorOrigin::TA)),
+            None => Ok(MockSession {
+                uuid: uuid.to_string(),
+                state: self.state.clone(),
+            }),
+        }
+    }
+}
+
+impl TeeContext for MockContext {
+    type Session = MockSession;
+
+    fn open_session(&mut self, uuid: Uuid) -> Result<MockSession> {
+        self.new_session(uuid)
+    }
+
+    fn open_session_with_operation<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        _operation: &mut Operation<A, B, C, D>,
+    ) -> Result<MockSession> {
+        self.new_session(uuid)
+    }
+
+    fn open_session_with_login<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        uuid: Uuid,
+        method: ConnectionMethods,
+        gid: Option<u32>,
+        _operation: &mut Operation<A, B, C, D>,
+    ) -> Result<MockSession> {
+        if method.requires_group() != gid.is_some() {
+            return Err(Error::new(ErrorKind::BadParameters).with_origin(ErrorOrigin::API));
+        }
+        self.new_session(uuid)
+    }
+}
+
+/// A session opened by a [`MockContext`].
+pub struct MockSession {
+    uuid: String,
+    state: Rc<RefCell<MockState>>,
+}
+
+impl TeeSession for MockSession {
+    fn invoke_command<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        operation: &mut Operation<A, B, C, D>,
+    ) -> Result<()> {
+        let operation = unsafe { &mut *operation.as_mut_raw_ptr() };
+        let mut params = [MockParam::None, MockParam::None, MockParam::None, MockParam::None];
+        for (i, param) in params.iter_mut().enumerate() {
+            *param = unsafe { read_param(operation, i) }
+                .ok_or_else(|| Error::new(ErrorKind::BadParameters).with_origin(ErrorOrigin::API))?;
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.invocations.push(Invocation {
+            uuid: self.uuid.clone(),
+            command_id,
+            params: params.clone(),
+        });
+        let result = match state.handlers.get_mut(&command_id) {
+            Some(handler) => handler(&mut params),
+            None => Err(Error::new(ErrorKind::NotImplemented)),
+        };
+        for (i, param) in params.iter().enumerate() {
+            unsafe { write_param(operation, i, param) };
+        }
+        result.map_err(|err| match err.origin() {
+            Some(_) => err,
+            None => err.with_origin(ErrorOrigin::TA),
+        })
+    }
+}
+
+fn param_type(operation: &raw::TEEC_Operation, i: usize) -> u32 {
+    (operation.paramTypes >> (4 * i)) & 0xf
+}
+
+/// Returns the memory referenced by parameter `i` and whether it is written
+/// back, or `None` if it is not a valid memory reference.
+unsafe fn memref(operation: &raw::TEEC_Operation, i: usize) -> Option<(*mut u8, usize, bool)> {
+    let param = &operation.params[i];
+    match param_type(operation, i) {
+        t @ (raw::TEEC_MEMREF_TEMP_INPUT
+        | raw::TEEC_MEMREF_TEMP_OUTPUT
+        | raw::TEEC_MEMREF_TEMP_INOUT) => Some((
+            param.tmpref.buffer as *mut u8,
+            param.tmpref.size,
+            t != raw::TEEC_MEMREF_TEMP_INPUT,
+        )),
+        raw::TEEC_MEMREF_WHOLE => {
+            let shm = param.memref.parent.as_ref()?;
+            Some((shm.buffer as *mut u8, shm.size, shm.flags & raw::TEEC_MEM_OUTPUT != 0))
+        }
+        t @ (raw::TEEC_MEMREF_PARTIAL_INPUT
+        | raw::TEEC_MEMREF_PARTIAL_OUTPUT
+        | raw::TEEC_MEMREF_PARTIAL_INOUT) => {
+            let memref = param.memref;
+            let shm = memref.parent.as_ref()?;
+            if memref.offset.checked_add(memref.size)? > shm.size || shm.buffer.is_null() {
+                return None;
+            }
+            let buffer = (shm.buffer as *mut u8).add(memref.offset);
+            Some((buffer, memref.size, t != raw::TEEC_MEMREF_PARTIAL_INPUT))
+        }
+        _ => None,
+    }
+}
+
+unsafe fn read_param(operation: &raw::TEEC_Operation, i: usize) -> Option<MockParam> {
+    let param_type = param_type(operation, i);
+    Some(match param_type {
+        raw::TEEC_NONE => MockParam::None,
+        raw::TEEC_VALUE_INPUT | raw::TEEC_VALUE_OUTPUT | raw::TEEC_VALUE_INOUT => {
+            let value = operation.params[i].value;
+            MockParam::Value {
+                param_type: param_type.into(),
+                a: value.a,
+                b: value.b,
+            }
+        }
+        _ => {
+            let (buffer, size, _) = memref(operation, i)?;
+            let data = match buffer.is_null() {
+                true => vec![0; size],
+                false => slice::from_raw_parts(buffer, size).to_vec(),
+            };
+            MockParam::Memref {
+                param_type: param_type.into(),
+                data,
+            }
+        }
+    })
+}
+
+/// Copies the output of `param` back to parameter `i` of `operation`.
+unsafe fn write_param(operation: &mut raw::TEEC_Operation, i: usize, param: &MockParam) {
+    let param_type = param_type(operation, i);
+    match param {
+        MockParam::Value { a, b, .. } if param_type != raw::TEEC_VALUE_INPUT => {
+            operation.params[i].value = raw::TEEC_Value { a: *a, b: *b };
+        }
+        MockParam::Memref { data, .. } => {
+            let (buffer, size, output) = match memref(operation, i) {
+                Some(memref) => memref,
+                None => return,
+            };
+            if !output {
+                return;
+            }
+            if !buffer.is_null() && data.len() <= size {
+                ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+            }
+            match param_type {
+                raw::TEEC_MEMREF_TEMP_OUTPUT | raw::TEEC_MEMREF_TEMP_INOUT => {
+                    operation.params[i].tmpref.size = data.len();
+                }
+                _ => operation.params[i].memref.size = data.len(),
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParamNone, ParamTmpRef, ParamValue};
+
+    #[test]
+    fn test_memrefs() {
+        let mut ctx = MockContext::new();
+        ctx.on_command(7, |params| {
+            let mut data = params[0].data().unwrap().to_vec();
+            data.reverse();
+            params[0].set_data(&data);
+            params[1].set_data(b"too long");
+            Err(Error::new(ErrorKind::ShortBuffer))
+        });
+        let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+        let mut session = ctx.open_session(uuid).unwrap();
+
+        let mut inout = *b"abc";
+        let mut output = [0u8; 4];
+        let p0 = ParamTmpRef::new_inout(&mut inout);
+        let p1 = ParamTmpRef::new_output(&mut output);
+        let p2 = ParamValue::new(1, 2, ParamType::ValueInput);
+        let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
+        let err = session.invoke_command(7, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+        assert_eq!(operation.parameters().1.updated_size(), 8);
+        let err = session.invoke_command(8, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotImplemented);
+        assert_eq!((&inout, &output), (b"cba", &[0u8; 4]));
+
+        let invocations = ctx.invocations();
+        assert_eq!(invocations.len(), 2);
+        assert_eq!(invocations[0].uuid, "8abcf200-2450-11e4-abe2-0002a5d5c51b");
+        assert_eq!(invocations[0].params[0].data(), Some(&b"abc"[..]));
+        assert_eq!(
+            invocations[0].params[2],
+            MockParam::Value {
+                param_type: ParamType::ValueInput,
+                a: 1,
+                b: 2
+            }
+        );
+        assert_eq!(invocations[0].params[3], MockParam::None);
+
+        ctx.fail_open_session(Some(ErrorKind::Busy));
+        let err = ctx.open_session(Uuid::from_bytes([0; 16])).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Busy);
+    }
+}
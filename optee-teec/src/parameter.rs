@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::raw;
+use crate::{raw, SharedMemory};
 use std::{marker, mem};
 
 pub trait Param {
@@ -127,6 +127,20 @@ impl<'a> ParamTmpRef<'a> {
         }
     }
 
+    /// Creates a temporary memory reference which is both read and written
+    /// by the TA.
+    pub fn new_inout(buffer: &'a mut [u8]) -> Self {
+        let raw = raw::TEEC_TempMemoryReference {
+            buffer: buffer.as_ptr() as _,
+            size: buffer.len(),
+        };
+        Self {
+            raw,
+            param_type: ParamType::MemrefTempInout,
+            _marker: marker::PhantomData,
+        }
+    }
+
     pub fn updated_size(&self) -> usize {
         self.raw.size
     }
@@ -150,9 +164,87 @@ impl<'a> Param for ParamTmpRef<'a> {
     }
 }
 
+/// This type defines a registered memory reference, i.e. a region of a
+/// [`SharedMemory`] block. It is used as a `Operation` parameter when the
+/// corresponding parameter type is one of `MemrefWhole`,
+/// `MemrefPartialInput`, `MemrefPartialOutput` or `MemrefPartialInout`.
+///
+/// The TEE client library checks that the region lies within the block and
+/// matches its flags when the operation is invoked.
+pub struct ParamMemref<'a> {
+    raw: raw::TEEC_RegisteredMemoryReference,
+    param_type: ParamType,
+    _marker: marker::PhantomData<&'a mut SharedMemory<'a>>,
+}
+
+impl<'a> ParamMemref<'a> {
+    /// Creates a reference to the whole of `shm`, in the directions given by
+    /// its flags.
+    pub fn new_whole(shm: &'a mut SharedMemory) -> Self {
+        Self::new(shm.as_raw_ptr(), 0, shm.size(), ParamType::MemrefWhole)
+    }
+
+    /// Creates an input only reference to `size` bytes of `shm` at `offset`.
+    pub fn new_partial_input(shm: &'a SharedMemory, offset: usize, size: usize) -> Self {
+        Self::new(shm.as_raw_ptr(), offset, size, ParamType::MemrefPartialInput)
+    }
+
+    /// Creates an output reference to `size` bytes of `shm` at `offset`.
+    pub fn new_partial_output(shm: &'a mut SharedMemory, offset: usize, size: usize) -> Self {
+        Self::new(shm.as_raw_ptr(), offset, size, ParamType::MemrefPartialOutput)
+    }
+
+    /// Creates a reference to `size` bytes of `shm` at `offset` which is both
+    /// read and written by the TA.
+    pub fn new_partial_inout(shm: &'a mut SharedMemory, offset: usize, size: usize) -> Self {
+        Self::new(shm.as_raw_ptr(), offset, size, ParamType::MemrefPartialInout)
+    }
+
+    fn new(
+        parent: *mut raw::TEEC_SharedMemory,
+        offset: usize,
+        size: usize,
+        param_type: ParamType,
+    ) -> Self {
+        Self {
+            raw: raw::TEEC_RegisteredMemoryReference {
+                parent,
+                size,
+                offset,
+            },
+            param_type,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Returns the size written by the TA, or the size it needs if it
+    /// returned `ShortBuffer`.
+    pub fn updated_size(&self) -> usize {
+        self.raw.size
+    }
+}
+
+impl<'a> Param for ParamMemref<'a> {
+    fn into_raw(&mut self) -> raw::TEEC_Parameter {
+        raw::TEEC_Parameter { memref: self.raw }
+    }
+
+    fn param_type(&self) -> ParamType {
+        self.param_type
+    }
+
+    fn from_raw(raw: raw::TEEC_Parameter, param_type: ParamType) -> Self {
+        Self {
+            raw: unsafe { raw.memref },
+            param_type,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
 /// These are used to indicate the type of Parameter encoded inside the
 /// operation structure.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ParamType {
     /// The Parameter is not used.
     None = 0,
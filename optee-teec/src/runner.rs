@@ -0,0 +1,963 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! An in-process TEE to test client applications together with their TAs.
+//!
+//! With the `runner` feature, optee-teec provides the libteec functions it
+//! calls itself instead of linking libteec, and sends sessions to TAs loaded
+//! into the client process. A TA is built for the host as a shared library
+//! with its `sim` feature, so that optee-utee implements the TEE Internal Core
+//! API (see `optee_utee::sim`). The TA crate declares the library as an
+//! example:
+//!
+//! ```toml
+//! [features]
+//! sim = ["optee-utee/sim"]
+//!
+//! [[example]]
+//! name = "sim"
+//! path = "src/main.rs"
+//! crate-type = ["cdylib"]
+//! required-features = ["sim"]
+//! ```
+//!
+//! and a test of the client application builds and loads it, then uses the TA
+//! as usual:
+//!
+//! ```no_run
+//! use optee_teec::{runner, Context};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let uuid = runner::load(runner::build_ta("../ta")?)?;
+//! let mut ctx = Context::new()?;
+//! let mut session = ctx.open_session(uuid)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The entry points of a TA run on the calling thread, one call at a time. As
+//! with OP-TEE:
+//!
+//! - memory references are copied to buffers of the runner for the call, and
+//!   output data, updated sizes and output values are copied back;
+//! - a TA is created when its first session opens and destroyed when its last
+//!   session closes, unless it is a keep-alive single instance TA;
+//! - a single instance TA without the multi-session flag has one session at a
+//!   time, opening another one fails with `Busy`.
+//!
+//! The TA sees the login method of a session as the login of its client, with
+//! a nil UUID. A test opens sessions as another TA would with
+//! [`set_client_ta`].
+//!
+//! Each TA has one instance in the process, even if it is not a single
+//! instance TA. Cancellation requests have no effect and a TA that panics
+//! aborts the process.
+
+use crate::{raw, Error, ErrorKind, Result, Uuid};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{c_char, c_void, CString};
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::cell::Cell;
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+const TA_FLAG_SINGLE_INSTANCE: u32 = 1 << 2;
+const TA_FLAG_MULTI_SESSION: u32 = 1 << 3;
+const TA_FLAG_INSTANCE_KEEP_ALIVE: u32 = 1 << 4;
+const TEE_LOGIN_TRUSTED_APP: u32 = 0xF000_0000;
+
+std::thread_local! {
+    /// The TA opening sessions on this thread, see [`set_client_ta`].
+    static CLIENT_TA: Cell<Option<[u8; 16]>> = const { Cell::new(None) };
+}
+
+/// A `TEE_Param` of the TA.
+///
+/// The parameter types seen by the TA have the values of `TEEC_NONE`,
+/// `TEEC_VALUE_*` and `TEEC_MEMREF_TEMP_*`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+union TaParam {
+    memref: raw::TEEC_TempMemoryReference,
+    value: raw::TEEC_Value,
+}
+
+/// A `TEE_Identity` of the client of a session.
+#[repr(C)]
+struct TaIdentity {
+    login: u32,
+    uuid: raw::TEEC_UUID,
+}
+
+/// The `ta_head` written by optee-utee-build.
+#[repr(C)]
+struct TaHead {
+    uuid: raw::TEEC_UUID,
+    stack_size: u32,
+    flags: u32,
+    depr_entry: u64,
+}
+
+type CreateEntryPoint = unsafe extern "C" fn() -> raw::TEEC_Result;
+type OpenSessionEntryPoint =
+    unsafe extern "C" fn(u32, *mut [TaParam; 4], *mut *mut c_void) -> raw::TEEC_Result;
+type InvokeCommandEntryPoint =
+    unsafe extern "C" fn(*mut c_void, u32, u32, *mut [TaParam; 4]) -> raw::TEEC_Result;
+type CloseSessionEntryPoint = unsafe extern "C" fn(*mut c_void);
+type DestroyEntryPoint = unsafe extern "C" fn();
+type SetClientIdentity = unsafe extern "C" fn(*const TaIdentity);
+
+/// The entry points generated by the `ta_*` macros of optee-utee.
+struct EntryPoints {
+    create: CreateEntryPoint,
+    open_session: OpenSessionEntryPoint,
+    invoke_command: InvokeCommandEntryPoint,
+    close_session: CloseSessionEntryPoint,
+    destroy: DestroyEntryPoint,
+    /// Sets the client identity seen by the TA, if it is built with
+    /// `optee_utee::sim`.
+    set_client_identity: Option<SetClientIdentity>,
+}
+
+struct TrustedApp {
+    uuid: [u8; 16],
+    flags: u32,
+    entry: EntryPoints,
+    /// The handle returned by `dlopen`, as an address.
+    library: usize,
+    /// Locked while an entry point runs.
+    instance: Mutex<Instance>,
+}
+
+#[derive(Default)]
+struct Instance {
+    created: bool,
+    sessions: usize,
+}
+
+impl TrustedApp {
+    /// Makes `client`, a login and a UUID, the client of the next entry point
+    /// calls.
+    unsafe fn set_client(&self, (login, uuid): (u32, [u8; 16])) {
+        if let Some(set_client_identity) = self.entry.set_client_identity {
+            let identity = TaIdentity {
+                login,
+                uuid: raw_uuid(uuid),
+            };
+            set_client_identity(&identity);
+        }
+    }
+
+    /// Destroys the instance once it has no sessions, unless it is kept
+    /// alive.
+    unsafe fn release(&self, instance: &mut Instance) {
+        let keep_alive = TA_FLAG_SINGLE_INSTANCE | TA_FLAG_INSTANCE_KEEP_ALIVE;
+        if instance.sessions == 0 && self.flags & keep_alive != keep_alive {
+            (self.entry.destroy)();
+            instance.created = false;
+        }
+    }
+}
+
+struct OpenSession {
+    ta: Arc<TrustedApp>,
+    /// The session context set by the TA, as an address.
+    ctx: usize,
+    /// The login and UUID of the client.
+    client: (u32, [u8; 16]),
+}
+
+struct Registry {
+    tas: Vec<Arc<TrustedApp>>,
+    sessions: BTreeMap<u32, OpenSession>,
+    next_session_id: u32,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    tas: Vec::new(),
+    sessions: BTreeMap::new(),
+    next_session_id: 1,
+});
+
+/// Builds the TA crate in `ta_dir` for the host with its `sim` feature and
+/// returns the path of the shared library to [`load`].
+///
+/// The library is the `sim` example of the crate, see the
+/// [module documentation](self), and is built in `ta_dir/target`.
+pub fn build_ta<P: AsRef<Path>>(ta_dir: P) -> io::Result<PathBuf> {
+    let ta_dir = ta_dir.as_ref();
+    let target_dir = ta_dir.join("target");
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(["build", "--example", "sim", "--features", "sim", "--manifest-path"])
+        .arg(ta_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("failed to build the TA in {}", ta_dir.display()),
+        ));
+    }
+    let file_name = format!("{}sim{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX);
+    Ok(target_dir.join("debug/examples").join(file_name))
+}
+
+/// Loads the TA built as the shared library at `path` and returns its UUID.
+///
+/// Loading a library again returns the UUID of the TA already loaded from it.
+/// TAs stay loaded until the process exits.
+///
+/// # Errors
+///
+/// 1) `ItemNotFound`: the library cannot be loaded.
+/// 2) `BadFormat`: the library does not export the header and the entry
+///    points of a TA.
+/// 3) `AccessConflict`: a TA with the same UUID was loaded from another
+///    library.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Uuid> {
+    let path = CString::new(path.as_ref().as_os_str().as_bytes())
+        .map_err(|_| Error::new(ErrorKind::BadParameters))?;
+    let library = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if library.is_null() {
+        return Err(Error::new(ErrorKind::ItemNotFound));
+    }
+    let result = match unsafe { resolve(library) } {
+        Some(ta) => register(ta),
+        None => Err(Error::new(ErrorKind::BadFormat)),
+    };
+    if result.is_err() {
+        unsafe { libc::dlclose(library) };
+    }
+    result
+}
+
+/// Makes the sessions opened afterwards on the calling thread come from the
+/// TA `uuid`, as if it opened them with `TEE_OpenTASession`, or from the
+/// client application again for `None`.
+///
+/// The TA then sees a `TEE_LOGIN_TRUSTED_APP` client with this UUID, so that
+/// TAs serving other TAs can be tested from the host.
+pub fn set_client_ta(uuid: Option<&Uuid>) {
+    let uuid = uuid.map(|uuid| uuid_bytes(unsafe { &*uuid.as_raw_ptr() }));
+    CLIENT_TA.with(|client| client.set(uuid));
+}
+
+unsafe fn symbol(library: *mut c_void, name: &[u8]) -> Option<*mut c_void> {
+    let symbol = libc::dlsym(library, name.as_ptr() as *const c_char);
+    match symbol.is_null() {
+        true => None,
+        false => Some(symbol),
+    }
+}
+
+unsafe fn resolve(library: *mut c_void) -> Option<TrustedApp> {
+    let head = &*(symbol(library, b"ta_head\0")? as *const TaHead);
+    let entry = EntryPoints {
+        create: mem::transmute::<*mut c_void, CreateEntryPoint>(symbol(
+            library,
+            b"TA_CreateEntryPoint\0",
+        )?),
+        open_session: mem::transmute::<*mut c_void, OpenSessionEntryPoint>(symbol(
+            library,
+            b"TA_OpenSessionEntryPoint\0",
+        )?),
+        invoke_command: mem::transmute::<*mut c_void, InvokeCommandEntryPoint>(symbol(
+            library,
+            b"TA_InvokeCommandEntryPoint\0",
+        )?),
+        close_session: mem::transmute::<*mut c_void, CloseSessionEntryPoint>(symbol(
+            library,
+            b"TA_CloseSessionEntryPoint\0",
+        )?),
+        destroy: mem::transmute::<*mut c_void, DestroyEntryPoint>(symbol(
+            library,
+            b"TA_DestroyEntryPoint\0",
+        )?),
+        set_client_identity: symbol(library, b"optee_utee_sim_set_client_identity\0")
+            .map(|symbol| mem::transmute::<*mut c_void, SetClientIdentity>(symbol)),
+    };
+    Some(TrustedApp {
+        uuid: uuid_bytes(&head.uuid),
+        flags: head.flags,
+        entry,
+        library: library as usize,
+        instance: Mutex::default(),
+    })
+}
+
+fn register(ta: TrustedApp) -> Result<Uuid> {
+    let uuid = Uuid::from_bytes(ta.uuid);
+    let mut registry = REGISTRY.lock().unwrap();
+    match registry.tas.iter().find(|loaded| loaded.uuid == ta.uuid) {
+        Some(loaded) if loaded.library != ta.library => {
+            Err(Error::new(ErrorKind::AccessConflict))
+        }
+        Some(_) => Ok(uuid),
+        None => {
+            registry.tas.push(Arc::new(ta));
+            Ok(uuid)
+        }
+    }
+}
+
+fn uuid_bytes(uuid: &raw::TEEC_UUID) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    bytes[..4].copy_from_slice(&uuid.timeLow.to_be_bytes());
+    bytes[4..6].copy_from_slice(&uuid.timeMid.to_be_bytes());
+    bytes[6..8].copy_from_slice(&uuid.timeHiAndVersion.to_be_bytes());
+    bytes[8..].copy_from_slice(&uuid.clockSeqAndNode);
+    bytes
+}
+
+fn raw_uuid(bytes: [u8; 16]) -> raw::TEEC_UUID {
+    let mut clock_seq_and_node = [0u8; 8];
+    clock_seq_and_node.copy_from_slice(&bytes[8..]);
+    raw::TEEC_UUID {
+        timeLow: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        timeMid: u16::from_be_bytes([bytes[4], bytes[5]]),
+        timeHiAndVersion: u16::from_be_bytes([bytes[6], bytes[7]]),
+        clockSeqAndNode: clock_seq_and_node,
+    }
+}
+
+/// Returns the TA parameter type of a memory reference in the directions of
+/// the `TEEC_MEM_*` `flags`.
+fn memref_type(flags: u32) -> Option<u32> {
+    match flags & (raw::TEEC_MEM_INPUT | raw::TEEC_MEM_OUTPUT) {
+        0 => None,
+        raw::TEEC_MEM_INPUT => Some(raw::TEEC_MEMREF_TEMP_INPUT),
+        raw::TEEC_MEM_OUTPUT => Some(raw::TEEC_MEMREF_TEMP_OUTPUT),
+        _ => Some(raw::TEEC_MEMREF_TEMP_INOUT),
+    }
+}
+
+/// The parameters of an operation as passed to the TA.
+struct Params {
+    types: u32,
+    params: [TaParam; 4],
+    /// Copies of the client memory referenced by each parameter.
+    buffers: [Vec<u8>; 4],
+    /// The client memory referenced by each parameter, or null.
+    targets: [*mut u8; 4],
+}
+
+impl Params {
+    /// Copies the parameters of `operation`, which may be null.
+    unsafe fn from_operation(
+        operation: *const raw::TEEC_Operation,
+    ) -> std::result::Result<Self, raw::TEEC_Result> {
+        let mut params = Self {
+            types: 0,
+            params: [TaParam {
+                value: raw::TEEC_Value { a: 0, b: 0 },
+            }; 4],
+            buffers: Default::default(),
+            targets: [ptr::null_mut(); 4],
+        };
+        let operation = match operation.as_ref() {
+            Some(operation) => operation,
+            None => return Ok(params),
+        };
+        for i in 0..4 {
+            let param = &operation.params[i];
+            let ta_type = match (operation.paramTypes >> (4 * i)) & 0xf {
+                raw::TEEC_NONE => raw::TEEC_NONE,
+                raw::TEEC_VALUE_OUTPUT => raw::TEEC_VALUE_OUTPUT,
+                value_type @ (raw::TEEC_VALUE_INPUT | raw::TEEC_VALUE_INOUT) => {
+                    params.params[i].value = param.value;
+                    value_type
+                }
+                memref_type @ (raw::TEEC_MEMREF_TEMP_INPUT
+                | raw::TEEC_MEMREF_TEMP_OUTPUT
+                | raw::TEEC_MEMREF_TEMP_INOUT) => {
+                    let tmpref = param.tmpref;
+                    params.copy_in(i, tmpref.buffer as *mut u8, tmpref.size, memref_type);
+                    memref_type
+                }
+                raw::TEEC_MEMREF_WHOLE => {
+                    let shm = param.memref.parent.as_ref().ok_or(raw::TEEC_ERROR_BAD_PARAMETERS)?;
+                    let ta_type = memref_type(shm.flags).ok_or(raw::TEEC_ERROR_BAD_PARAMETERS)?;
+                    params.copy_in(i, shm.buffer as *mut u8, shm.size, ta_type);
+                    ta_type
+                }
+                partial_type @ (raw::TEEC_MEMREF_PARTIAL_INPUT
+                | raw::TEEC_MEMREF_PARTIAL_OUTPUT
+                | raw::TEEC_MEMREF_PARTIAL_INOUT) => {
+                    let memref = param.memref;
+                    let shm = memref.parent.as_ref().ok_or(raw::TEEC_ERROR_BAD_PARAMETERS)?;
+                    // the low bits of the partial types are the TEEC_MEM_* flags
+                    let direction = partial_type & (raw::TEEC_MEM_INPUT | raw::TEEC_MEM_OUTPUT);
+                    let end = memref.offset.checked_add(memref.size);
+                    if shm.flags & direction != direction || end.map_or(true, |end| end > shm.size)
+                    {
+                        return Err(raw::TEEC_ERROR_BAD_PARAMETERS);
+                    }
+                    let ta_type = memref_type(direction).unwrap();
+                    let buffer = match shm.buffer.is_null() {
+                        true => ptr::null_mut(),
+                        false => (shm.buffer as *mut u8).add(memref.offset),
+                    };
+                    params.copy_in(i, buffer, memref.size, ta_type);
+                    ta_type
+                }
+                _ => return Err(raw::TEEC_ERROR_BAD_PARAMETERS),
+            };
+            params.types |= ta_type << (4 * i);
+        }
+        Ok(params)
+    }
+
+    /// Passes the `size` bytes at `target` to the TA, copying them unless the
+    /// TA only writes them.
+    unsafe fn copy_in(&mut self, i: usize, target: *mut u8, size: usize, ta_type: u32) {
+        if target.is_null() {
+            self.params[i].memref = raw::TEEC_TempMemoryReference {
+                buffer: ptr::null_mut(),
+                size,
+            };
+            return;
+        }
+        let mut buffer = match ta_type {
+            raw::TEEC_MEMREF_TEMP_OUTPUT => vec![0u8; size],
+            _ => slice::from_raw_parts(target, size).to_vec(),
+        };
+        self.params[i].memref = raw::TEEC_TempMemoryReference {
+            buffer: buffer.as_mut_ptr() as *mut c_void,
+            size,
+        };
+        self.buffers[i] = buffer;
+        self.targets[i] = target;
+    }
+
+    /// Copies the output values, sizes and data written by the TA back to
+    /// `operation`, which may be null.
+    ///
+    /// Data is only copied if it fits in the client memory.
+    unsafe fn copy_out(&self, operation: *mut raw::TEEC_Operation) {
+        let operation = match operation.as_mut() {
+            Some(operation) => operation,
+            None => return,
+        };
+        for i in 0..4 {
+            let client_type = (operation.paramTypes >> (4 * i)) & 0xf;
+            let param = &mut operation.params[i];
+            match (self.types >> (4 * i)) & 0xf {
+                raw::TEEC_VALUE_OUTPUT | raw::TEEC_VALUE_INOUT => {
+                    param.value = self.params[i].value;
+                }
+                raw::TEEC_MEMREF_TEMP_OUTPUT | raw::TEEC_MEMREF_TEMP_INOUT => {
+                    let size = self.params[i].memref.size;
+                    if !self.targets[i].is_null() && size <= self.buffers[i].len() {
+                        ptr::copy_nonoverlapping(self.buffers[i].as_ptr(), self.targets[i], size);
+                    }
+                    match client_type {
+                        raw::TEEC_MEMREF_TEMP_OUTPUT | raw::TEEC_MEMREF_TEMP_INOUT => {
+                            param.tmpref.size = size;
+                        }
+                        _ => param.memref.size = size,
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+unsafe fn start(operation: *mut raw::TEEC_Operation) {
+    if let Some(operation) = operation.as_mut() {
+        operation.started = 1;
+    }
+}
+
+unsafe fn open_session(
+    context: *mut raw::TEEC_Context,
+    session: *mut raw::TEEC_Session,
+    destination: *const raw::TEEC_UUID,
+    connection_method: u32,
+    operation: *mut raw::TEEC_Operation,
+) -> (raw::TEEC_Result, u32) {
+    if context.is_null() || session.is_null() || destination.is_null() {
+        return (raw::TEEC_ERROR_BAD_PARAMETERS, raw::TEEC_ORIGIN_API);
+    }
+    let uuid = uuid_bytes(&*destination);
+    let ta = match REGISTRY.lock().unwrap().tas.iter().find(|ta| ta.uuid == uuid) {
+        Some(ta) => ta.clone(),
+        None => return (raw::TEEC_ERROR_ITEM_NOT_FOUND, raw::TEEC_ORIGIN_TEE),
+    };
+    let mut params = match Params::from_operation(operation) {
+        Ok(params) => params,
+        Err(code) => return (code, raw::TEEC_ORIGIN_API),
+    };
+    start(operation);
+
+    let client = match CLIENT_TA.with(Cell::get) {
+        Some(uuid) => (TEE_LOGIN_TRUSTED_APP, uuid),
+        None => (connection_method, [0; 16]),
+    };
+
+    let mut instance = ta.instance.lock().unwrap();
+    let single_session = TA_FLAG_SINGLE_INSTANCE | TA_FLAG_MULTI_SESSION;
+    if instance.sessions > 0 && ta.flags & single_session == TA_FLAG_SINGLE_INSTANCE {
+        return (raw::TEEC_ERROR_BUSY, raw::TEEC_ORIGIN_TEE);
+    }
+    ta.set_client(client);
+    if !instance.created {
+        let code = (ta.entry.create)();
+        if code != raw::TEEC_SUCCESS {
+            return (code, raw::TEEC_ORIGIN_TRUSTED_APP);
+        }
+        instance.created = true;
+    }
+    let mut ctx = ptr::null_mut();
+    let code = (ta.entry.open_session)(params.types, &mut params.params, &mut ctx);
+    params.copy_out(operation);
+    if code != raw::TEEC_SUCCESS {
+        ta.release(&mut instance);
+        return (code, raw::TEEC_ORIGIN_TRUSTED_APP);
+    }
+    instance.sessions += 1;
+    drop(instance);
+
+    let mut registry = REGISTRY.lock().unwrap();
+    let session_id = registry.next_session_id;
+    registry.next_session_id = session_id.wrapping_add(1);
+    registry.sessions.insert(
+        session_id,
+        OpenSession {
+            ta,
+            ctx: ctx as usize,
+            client,
+        },
+    );
+    (*session).ctx = context;
+    (*session).session_id = session_id;
+    (raw::TEEC_SUCCESS, raw::TEEC_ORIGIN_TRUSTED_APP)
+}
+
+unsafe fn invoke_command(
+    session: *mut raw::TEEC_Session,
+    command_id: u32,
+    operation: *mut raw::TEEC_Operation,
+) -> (raw::TEEC_Result, u32) {
+    let session_id = match session.as_ref() {
+        Some(session) => session.session_id,
+        None => return (raw::TEEC_ERROR_BAD_PARAMETERS, raw::TEEC_ORIGIN_API),
+    };
+    let (ta, ctx, client) = match REGISTRY.lock().unwrap().sessions.get(&session_id) {
+        Some(open) => (open.ta.clone(), open.ctx, open.client),
+        None => return (raw::TEEC_ERROR_BAD_PARAMETERS, raw::TEEC_ORIGIN_API),
+    };
+    let mut params = match Params::from_operation(operation) {
+        Ok(params) => params,
+        Err(code) => return (code, raw::TEEC_ORIGIN_API),
+    };
+    start(operation);
+
+    let _instance = ta.instance.lock().unwrap();
+    ta.set_client(client);
+    let code = (ta.entry.invoke_command)(
+        ctx as *mut c_void,
+        command_id,
+        params.types,
+        &mut params.params,
+    );
+    params.copy_out(operation);
+    (code, raw::TEEC_ORIGIN_TRUSTED_APP)
+}
+
+unsafe fn set_origin(return_origin: *mut u32, origin: u32) {
+    if !return_origin.is_null() {
+        *return_origin = origin;
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEEC_InitializeContext(
+    _name: *const c_char,
+    context: *mut raw::TEEC_Context,
+) -> raw::TEEC_Result {
+    match context.is_null() {
+        true => raw::TEEC_ERROR_BAD_PARAMETERS,
+        false => raw::TEEC_SUCCESS,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEEC_FinalizeContext(_context: *mut raw::TEEC_Context) {}
+
+#[no_mangle]
+extern "C" fn TEEC_OpenSession(
+    context: *mut raw::TEEC_Context,
+    session: *mut raw::TEEC_Session,
+    destination: *const raw::TEEC_UUID,
+    connection_method: u32,
+    _connection_data: *const c_void,
+    operation: *mut raw::TEEC_Operation,
+    return_origin: *mut u32,
+) -> raw::TEEC_Result {
+    unsafe {
+        let (code, origin) =
+            open_session(context, session, destination, connection_method, operation);
+        set_origin(return_origin, origin);
+        code
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEEC_CloseSession(session: *mut raw::TEEC_Session) {
+    let session_id = match unsafe { session.as_ref() } {
+        Some(session) => session.session_id,
+        None => return,
+    };
+    let open = match REGISTRY.lock().unwrap().sessions.remove(&session_id) {
+        Some(open) => open,
+        None => return,
+    };
+    let mut instance = open.ta.instance.lock().unwrap();
+    unsafe { open.ta.set_client(open.client) };
+    unsafe { (open.ta.entry.close_session)(open.ctx as *mut c_void) };
+    instance.sessions -= 1;
+    unsafe { open.ta.release(&mut instance) };
+}
+
+#[no_mangle]
+extern "C" fn TEEC_InvokeCommand(
+    session: *mut raw::TEEC_Session,
+    command_id: u32,
+    operation: *mut raw::TEEC_Operation,
+    return_origin: *mut u32,
+) -> raw::TEEC_Result {
+    unsafe {
+        let (code, origin) = invoke_command(session, command_id, operation);
+        set_origin(return_origin, origin);
+        code
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEEC_RegisterSharedMemory(
+    context: *mut raw::TEEC_Context,
+    shm: *mut raw::TEEC_SharedMemory,
+) -> raw::TEEC_Result {
+    let shm = match unsafe { shm.as_mut() } {
+        Some(shm) if !context.is_null() => shm,
+        _ => return raw::TEEC_ERROR_BAD_PARAMETERS,
+    };
+    if shm.buffer.is_null() && shm.size > 0 {
+        return raw::TEEC_ERROR_BAD_PARAMETERS;
+    }
+    shm.shadow_buffer = ptr::null_mut();
+    shm.alloced_size = shm.size;
+    raw::TEEC_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEEC_AllocateSharedMemory(
+    context: *mut raw::TEEC_Context,
+    shm: *mut raw::TEEC_SharedMemory,
+) -> raw::TEEC_Result {
+    let shm = match unsafe { shm.as_mut() } {
+        Some(shm) if !context.is_null() => shm,
+        _ => return raw::TEEC_ERROR_BAD_PARAMETERS,
+    };
+    let buffer = Box::into_raw(vec![0u8; shm.size].into_boxed_slice()) as *mut c_void;
+    // as libteec, keep memory owned by the library in `shadow_buffer`
+    shm.buffer = buffer;
+    shm.shadow_buffer = buffer;
+    shm.alloced_size = shm.size;
+    raw::TEEC_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEEC_ReleaseSharedMemory(shm: *mut raw::TEEC_SharedMemory) {
+    let shm = match unsafe { shm.as_mut() } {
+        Some(shm) => shm,
+        None => return,
+    };
+    if !shm.shadow_buffer.is_null() {
+        let buffer = ptr::slice_from_raw_parts_mut(shm.shadow_buffer as *mut u8, shm.alloced_size);
+        drop(unsafe { Box::from_raw(buffer) });
+    }
+    shm.buffer = ptr::null_mut();
+    shm.shadow_buffer = ptr::null_mut();
+    shm.size = 0;
+    shm.alloced_size = 0;
+}
+
+#[no_mangle]
+extern "C" fn TEEC_RequestCancellation(_operation: *mut raw::TEEC_Operation) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ConnectionMethods, Context, ErrorOrigin, Operation, ParamMemref, ParamNone, ParamTmpRef,
+        ParamType, ParamValue, Session, SharedMemory, SharedMemoryFlags,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ECHO_UUID: [u8; 16] = [1; 16];
+    const SINGLE_UUID: [u8; 16] = [2; 16];
+    const CLIENT_UUID: [u8; 16] = [3; 16];
+
+    const CMD_REVERSE: u32 = 0;
+    const CMD_FILL: u32 = 1;
+    const CMD_COUNT: u32 = 2;
+
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    std::thread_local! {
+        /// The client identities set on this thread.
+        static CLIENTS: std::cell::RefCell<Vec<(u32, [u8; 16])>> = const {
+            std::cell::RefCell::new(Vec::new())
+        };
+    }
+
+    fn param_type(param_types: u32, i: usize) -> u32 {
+        (param_types >> (4 * i)) & 0xf
+    }
+
+    extern "C" fn create() -> raw::TEEC_Result {
+        raw::TEEC_SUCCESS
+    }
+
+    extern "C" fn destroy() {}
+
+    extern "C" fn counted_create() -> raw::TEEC_Result {
+        CREATED.fetch_add(1, Ordering::SeqCst);
+        raw::TEEC_SUCCESS
+    }
+
+    extern "C" fn counted_destroy() {
+        DESTROYED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe extern "C" fn set_client_identity(identity: *const TaIdentity) {
+        let client = ((*identity).login, uuid_bytes(&(*identity).uuid));
+        CLIENTS.with(|clients| clients.borrow_mut().push(client));
+    }
+
+    /// Rejects sessions opened with a zero value input.
+    unsafe extern "C" fn open_session(
+        param_types: u32,
+        params: *mut [TaParam; 4],
+        sess_ctx: *mut *mut c_void,
+    ) -> raw::TEEC_Result {
+        if param_type(param_types, 0) == raw::TEEC_VALUE_INPUT && (*params)[0].value.a == 0 {
+            return raw::TEEC_ERROR_ACCESS_DENIED;
+        }
+        *sess_ctx = Box::into_raw(Box::new(0u32)) as *mut c_void;
+        raw::TEEC_SUCCESS
+    }
+
+    unsafe extern "C" fn close_session(sess_ctx: *mut c_void) {
+        drop(Box::from_raw(sess_ctx as *mut u32));
+    }
+
+    unsafe extern "C" fn invoke_command(
+        sess_ctx: *mut c_void,
+        cmd_id: u32,
+        param_types: u32,
+        params: *mut [TaParam; 4],
+    ) -> raw::TEEC_Result {
+        let params = &mut *params;
+        match (cmd_id, param_type(param_types, 0)) {
+            // reverses the bytes of p0 and increments p1.a
+            (CMD_REVERSE, raw::TEEC_MEMREF_TEMP_INOUT) => {
+                let memref = params[0].memref;
+                slice::from_raw_parts_mut(memref.buffer as *mut u8, memref.size).reverse();
+                if param_type(param_types, 1) == raw::TEEC_VALUE_INOUT {
+                    params[1].value.a += 1;
+                }
+                raw::TEEC_SUCCESS
+            }
+            // writes "abcd" to p0
+            (CMD_FILL, raw::TEEC_MEMREF_TEMP_OUTPUT) => {
+                let memref = &mut params[0].memref;
+                let capacity = memref.size;
+                memref.size = 4;
+                if capacity < 4 {
+                    return raw::TEEC_ERROR_SHORT_BUFFER;
+                }
+                slice::from_raw_parts_mut(memref.buffer as *mut u8, 4).copy_from_slice(b"abcd");
+                raw::TEEC_SUCCESS
+            }
+            // returns the number of calls in this session in p0.a
+            (CMD_COUNT, raw::TEEC_VALUE_OUTPUT) => {
+                let count = &mut *(sess_ctx as *mut u32);
+                *count += 1;
+                params[0].value.a = *count;
+                raw::TEEC_SUCCESS
+            }
+            _ => raw::TEEC_ERROR_BAD_PARAMETERS,
+        }
+    }
+
+    fn register_ta(uuid: [u8; 16], flags: u32, counted: bool) -> Uuid {
+        let (create, destroy): (CreateEntryPoint, DestroyEntryPoint) = match counted {
+            true => (counted_create, counted_destroy),
+            false => (create, destroy),
+        };
+        register(TrustedApp {
+            uuid,
+            flags,
+            entry: EntryPoints {
+                create,
+                open_session,
+                invoke_command,
+                close_session,
+                destroy,
+                set_client_identity: Some(set_client_identity),
+            },
+            library: 0,
+            instance: Mutex::default(),
+        })
+        .unwrap()
+    }
+
+    fn count(session: &mut Session) -> u32 {
+        let p0 = ParamValue::new(0, 0, ParamType::ValueOutput);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        session.invoke_command(CMD_COUNT, &mut operation).unwrap();
+        operation.parameters().0.a()
+    }
+
+    #[test]
+    fn test_temp_memrefs_and_values() {
+        let uuid = register_ta(ECHO_UUID, TA_FLAG_MULTI_SESSION, false);
+        let mut ctx = Context::new().unwrap();
+        let mut session = ctx.open_session(uuid.clone()).unwrap();
+
+        let mut buffer = *b"hello";
+        let p0 = ParamTmpRef::new_inout(&mut buffer);
+        let p1 = ParamValue::new(41, 0, ParamType::ValueInout);
+        let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+        session.invoke_command(CMD_REVERSE, &mut operation).unwrap();
+        assert_eq!(operation.parameters().1.a(), 42);
+        assert_eq!(&buffer, b"olleh");
+
+        let mut short = [0u8; 2];
+        let p0 = ParamTmpRef::new_output(&mut short);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = session.invoke_command(CMD_FILL, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+        assert_eq!(operation.parameters().0.updated_size(), 4);
+
+        let mut output = [b'-'; 6];
+        let p0 = ParamTmpRef::new_output(&mut output);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        session.invoke_command(CMD_FILL, &mut operation).unwrap();
+        assert_eq!(operation.parameters().0.updated_size(), 4);
+        assert_eq!(&output, b"abcd--");
+
+        let p0 = ParamValue::new(0, 0, ParamType::ValueInput);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = Session::new(&mut ctx, uuid, Some(&mut operation)).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+
+        let err = ctx.open_session(Uuid::from_bytes([0xff; 16])).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+    }
+
+    #[test]
+    fn test_registered_memrefs() {
+        let uuid = register_ta(ECHO_UUID, TA_FLAG_MULTI_SESSION, false);
+        let mut ctx = Context::new().unwrap();
+        let mut session = ctx.open_session(uuid).unwrap();
+
+        let mut shm = SharedMemory::allocate(&mut ctx, 8, SharedMemoryFlags::INOUT).unwrap();
+        shm.buffer_mut().copy_from_slice(b"01234567");
+        let p0 = ParamMemref::new_partial_inout(&mut shm, 2, 4);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        session.invoke_command(CMD_REVERSE, &mut operation).unwrap();
+        assert_eq!(shm.buffer(), b"01543267");
+
+        let p0 = ParamMemref::new_whole(&mut shm);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        session.invoke_command(CMD_REVERSE, &mut operation).unwrap();
+        assert_eq!(shm.buffer(), b"76234510");
+
+        let p0 = ParamMemref::new_partial_inout(&mut shm, 6, 4);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = session.invoke_command(CMD_REVERSE, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        assert_eq!(err.origin(), Some(ErrorOrigin::API));
+
+        let mut buffer = [0u8; 8];
+        let mut shm = SharedMemory::register(&mut ctx, &mut buffer, SharedMemoryFlags::INPUT)
+            .unwrap();
+        let p0 = ParamMemref::new_partial_output(&mut shm, 0, 4);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = session.invoke_command(CMD_FILL, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_instance_lifecycle() {
+        let uuid = register_ta(SINGLE_UUID, TA_FLAG_SINGLE_INSTANCE, true);
+        let mut ctx = Context::new().unwrap();
+
+        let mut session = ctx.open_session(uuid.clone()).unwrap();
+        assert_eq!(CREATED.load(Ordering::SeqCst), 1);
+        assert_eq!((count(&mut session), count(&mut session)), (1, 2));
+        let err = ctx.open_session(uuid.clone()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Busy);
+
+        drop(session);
+        assert_eq!(DESTROYED.load(Ordering::SeqCst), 1);
+        let mut session = ctx.open_session(uuid).unwrap();
+        assert_eq!(CREATED.load(Ordering::SeqCst), 2);
+        assert_eq!(count(&mut session), 1);
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let uuid = register_ta(CLIENT_UUID, TA_FLAG_MULTI_SESSION, false);
+        let client_ta = Uuid::from_bytes([4; 16]);
+        let mut ctx = Context::new().unwrap();
+
+        let mut operation = Operation::new(0, ParamNone, ParamNone, ParamNone, ParamNone);
+        let method = ConnectionMethods::LoginUser;
+        let mut session = ctx
+            .open_session_with_login(uuid.clone(), method, None, &mut operation)
+            .unwrap();
+        set_client_ta(Some(&client_ta));
+        let ta_session = ctx.open_session(uuid.clone()).unwrap();
+        set_client_ta(None);
+        count(&mut session);
+        drop(ta_session);
+        let clients = CLIENTS.with(|clients| clients.take());
+        let user = (raw::TEEC_LOGIN_USER, [0; 16]);
+        let ta = (TEE_LOGIN_TRUSTED_APP, [4; 16]);
+        assert_eq!(clients, [user, ta, user, ta]);
+
+        let _session = ctx.open_session(uuid).unwrap();
+        let clients = CLIENTS.with(|clients| clients.take());
+        assert_eq!(clients, [(raw::TEEC_LOGIN_PUBLIC, [0; 16])]);
+    }
+}
@@ -16,24 +16,41 @@
 // under the License.
 
 use super::context::InnerContext;
-use crate::{raw, Context, Error, Operation, Param, Result, Uuid};
+use crate::{
+    raw, CancelHandle, Context, Error, ErrorKind, ErrorOrigin, Operation, Param, Result, Uuid,
+};
 use std::{cell::RefCell, ptr, rc::Rc};
 
 /// Session login methods.
-#[derive(Copy, Clone)]
+///
+/// The TEE derives the client identity seen by the TA from the login method:
+/// for every method but `LoginPublic` the identity UUID is computed from the
+/// user, the group and/or the path of the client application.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
 pub enum ConnectionMethods {
     /// No login data is provided.
-    LoginPublic,
+    LoginPublic = raw::TEEC_LOGIN_PUBLIC,
     /// Login data about the user running the Client Application process is provided.
-    LoginUser,
+    LoginUser = raw::TEEC_LOGIN_USER,
     /// Login data about the group running the Client Application process is provided.
-    LoginGroup,
+    LoginGroup = raw::TEEC_LOGIN_GROUP,
     /// Login data about the running Client Application itself is provided.
-    LoginApplication,
+    LoginApplication = raw::TEEC_LOGIN_APPLICATION,
     /// Login data about the user and the running Client Application itself is provided.
-    LoginUserApplication,
+    LoginUserApplication = raw::TEEC_LOGIN_USER_APPLICATION,
     /// Login data about the group and the running Client Application itself is provided.
-    LoginGroupApplication,
+    LoginGroupApplication = raw::TEEC_LOGIN_GROUP_APPLICATION,
+}
+
+impl ConnectionMethods {
+    /// Returns whether this method needs a group ID as connection data.
+    pub fn requires_group(self) -> bool {
+        matches!(
+            self,
+            ConnectionMethods::LoginGroup | ConnectionMethods::LoginGroupApplication
+        )
+    }
 }
 
 /// Represents a connection between a client application and a trusted application.
@@ -58,6 +75,30 @@ impl Session {
         uuid: Uuid,
         operation: Option<&mut Operation<A, B, C, D>>,
     ) -> Result<Self> {
+        Self::new_with_login(context, uuid, ConnectionMethods::LoginPublic, None, operation)
+    }
+
+    /// Initializes a TEE session object with specified context and uuid,
+    /// logging in with `method`.
+    ///
+    /// `gid` is the group to log in as and must be given exactly when
+    /// `method` is `LoginGroup` or `LoginGroupApplication`; the calling
+    /// process must be a member of that group.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: `gid` is missing or given when it is not needed.
+    /// 2) `AccessDenied`: the TA rejected the login.
+    pub fn new_with_login<A: Param, B: Param, C: Param, D: Param>(
+        context: &mut Context,
+        uuid: Uuid,
+        method: ConnectionMethods,
+        gid: Option<u32>,
+        operation: Option<&mut Operation<A, B, C, D>>,
+    ) -> Result<Self> {
+        if method.requires_group() != gid.is_some() {
+            return Err(Error::new(ErrorKind::BadParameters).with_origin(ErrorOrigin::API));
+        }
         // define an empty TEEC_Session
         let mut raw_session = raw::TEEC_Session {
             ctx: ptr::null_mut(),
@@ -74,14 +115,18 @@ impl Session {
         let inner_ctx = context.inner_context();
         let raw_ctx = &mut inner_ctx.borrow_mut().0;
         let raw_uuid = uuid.as_raw_ptr();
+        let connection_data = match &gid {
+            Some(gid) => gid as *const u32 as *const _,
+            None => ptr::null(),
+        };
 
         match unsafe {
             raw::TEEC_OpenSession(
                 raw_ctx,
                 &mut raw_session,
                 raw_uuid,
-                ConnectionMethods::LoginPublic as u32,
-                ptr::null(),
+                method as u32,
+                connection_data,
                 raw_operation,
                 &mut err_origin,
             )
@@ -113,6 +158,26 @@ impl Session {
             code => Err(Error::from_raw_error(code).with_origin(err_origin.into())),
         }
     }
+
+    /// Invokes a command which can be cancelled from another thread through
+    /// `handle`.
+    ///
+    /// The operation must have been created with `started` set to 0. If the
+    /// command is cancelled, it returns `Cancel` unless the TA completed it
+    /// first.
+    pub fn invoke_command_cancellable<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        operation: &mut Operation<A, B, C, D>,
+        handle: &CancelHandle,
+    ) -> Result<()> {
+        if !handle.register(operation.as_mut_raw_ptr()) {
+            return Err(Error::new(ErrorKind::Cancel).with_origin(ErrorOrigin::API));
+        }
+        let result = self.invoke_command(command_id, operation);
+        handle.unregister();
+        result
+    }
 }
 
 impl Drop for Session {
@@ -122,3 +187,60 @@ impl Drop for Session {
         }
     }
 }
+
+// the mocks below clash with the `runner` implementation
+#[cfg(all(test, not(feature = "runner")))]
+mod tests {
+    use super::*;
+    use crate::ParamNone;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static LOGIN: AtomicU32 = AtomicU32::new(u32::MAX);
+    static GID: AtomicU32 = AtomicU32::new(0);
+
+    #[no_mangle]
+    extern "C" fn TEEC_OpenSession(
+        _context: *mut raw::TEEC_Context,
+        _session: *mut raw::TEEC_Session,
+        _destination: *const raw::TEEC_UUID,
+        connection_method: u32,
+        connection_data: *const std::ffi::c_void,
+        _operation: *mut raw::TEEC_Operation,
+        _return_origin: *mut u32,
+    ) -> raw::TEEC_Result {
+        LOGIN.store(connection_method, Ordering::SeqCst);
+        if !connection_data.is_null() {
+            GID.store(unsafe { *(connection_data as *const u32) }, Ordering::SeqCst);
+        }
+        raw::TEEC_SUCCESS
+    }
+
+    #[no_mangle]
+    extern "C" fn TEEC_CloseSession(_session: *mut raw::TEEC_Session) {}
+
+    #[test]
+    fn test_open_session_with_login() {
+        let mut ctx = Context::new().unwrap();
+        let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+        let mut operation = Operation::new(0, ParamNone, ParamNone, ParamNone, ParamNone);
+
+        let error = ctx
+            .open_session_with_login(uuid, ConnectionMethods::LoginGroup, None, &mut operation)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::BadParameters);
+        assert_eq!(error.origin(), Some(ErrorOrigin::API));
+        assert_eq!(LOGIN.load(Ordering::SeqCst), u32::MAX);
+
+        let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+        ctx.open_session_with_login(
+            uuid,
+            ConnectionMethods::LoginGroupApplication,
+            Some(1000),
+            &mut operation,
+        )
+        .unwrap();
+        assert_eq!(LOGIN.load(Ordering::SeqCst), raw::TEEC_LOGIN_GROUP_APPLICATION);
+        assert_eq!(GID.load(Ordering::SeqCst), 1000);
+    }
+}
@@ -0,0 +1,252 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::context::InnerContext;
+use crate::{raw, Context, Error, Result};
+use std::{cell::RefCell, marker, mem, ops, rc::Rc, slice};
+
+/// Direction of the data in a [`SharedMemory`] block, seen from the TA.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SharedMemoryFlags(u32);
+
+impl SharedMemoryFlags {
+    /// The memory can carry data from the client application to the TA.
+    pub const INPUT: Self = Self(raw::TEEC_MEM_INPUT);
+    /// The memory can carry data from the TA to the client application.
+    pub const OUTPUT: Self = Self(raw::TEEC_MEM_OUTPUT);
+    /// The memory can carry data in both directions.
+    pub const INOUT: Self = Self(raw::TEEC_MEM_INPUT | raw::TEEC_MEM_OUTPUT);
+
+    /// Returns the raw flags.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether all the flags in `other` are set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for SharedMemoryFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// A block of memory shared with TAs, either allocated by the TEE client
+/// library or registered from a buffer of the client application.
+///
+/// It is referenced in an [`Operation`](crate::Operation) through a
+/// [`ParamMemref`](crate::ParamMemref), which avoids copying the buffer on
+/// every invocation. The memory is released when dropped.
+///
+/// # Examples
+///
+/// ``` no_run
+/// use optee_teec::{Context, Operation, ParamMemref, ParamNone, SharedMemory, SharedMemoryFlags};
+///
+/// fn main() -> optee_teec::Result<()> {
+///     let mut ctx = Context::new()?;
+///     let mut shm = SharedMemory::allocate(&mut ctx, 4096, SharedMemoryFlags::INOUT)?;
+///     shm.buffer_mut()[..5].copy_from_slice(b"hello");
+///     let p0 = ParamMemref::new_partial_inout(&mut shm, 0, 5);
+///     let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+///     Ok(())
+/// }
+/// ```
+pub struct SharedMemory<'a> {
+    // Boxed so that the address given to the TEE client library, and stored
+    // by memref parameters, does not change when the block is moved.
+    raw: Box<raw::TEEC_SharedMemory>,
+    _marker: marker::PhantomData<&'a mut [u8]>,
+
+    // Just a holder to ensure InnerContext is not dropped before the memory
+    // is released, never use it.
+    _ctx: Rc<RefCell<InnerContext>>,
+}
+
+impl SharedMemory<'static> {
+    /// Allocates `size` bytes of memory shared with the TEE.
+    pub fn allocate(
+        context: &mut Context,
+        size: usize,
+        flags: SharedMemoryFlags,
+    ) -> Result<Self> {
+        let mut raw_shm = Self::new_raw(std::ptr::null_mut(), size, flags);
+        let inner_ctx = context.inner_context();
+        let code = unsafe {
+            raw::TEEC_AllocateSharedMemory(&mut inner_ctx.borrow_mut().0, &mut *raw_shm)
+        };
+        match code {
+            raw::TEEC_SUCCESS => Ok(Self {
+                raw: raw_shm,
+                _marker: marker::PhantomData,
+                _ctx: inner_ctx,
+            }),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+}
+
+impl<'a> SharedMemory<'a> {
+    /// Registers `buffer` as memory shared with the TEE.
+    ///
+    /// The buffer stays borrowed until the shared memory is dropped.
+    pub fn register(
+        context: &mut Context,
+        buffer: &'a mut [u8],
+        flags: SharedMemoryFlags,
+    ) -> Result<Self> {
+        let mut raw_shm = Self::new_raw(buffer.as_mut_ptr() as _, buffer.len(), flags);
+        let inner_ctx = context.inner_context();
+        let code = unsafe {
+            raw::TEEC_RegisterSharedMemory(&mut inner_ctx.borrow_mut().0, &mut *raw_shm)
+        };
+        match code {
+            raw::TEEC_SUCCESS => Ok(Self {
+                raw: raw_shm,
+                _marker: marker::PhantomData,
+                _ctx: inner_ctx,
+            }),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
+    fn new_raw(
+        buffer: *mut std::ffi::c_void,
+        size: usize,
+        flags: SharedMemoryFlags,
+    ) -> Box<raw::TEEC_SharedMemory> {
+        let mut raw_shm: Box<raw::TEEC_SharedMemory> = Box::new(unsafe { mem::zeroed() });
+        raw_shm.buffer = buffer;
+        raw_shm.size = size;
+        raw_shm.flags = flags.bits();
+        raw_shm
+    }
+
+    /// Returns the size of the memory in bytes.
+    pub fn size(&self) -> usize {
+        self.raw.size
+    }
+
+    /// Returns the direction flags the memory was created with.
+    pub fn flags(&self) -> SharedMemoryFlags {
+        SharedMemoryFlags(self.raw.flags)
+    }
+
+    /// Returns the content of the memory.
+    pub fn buffer(&self) -> &[u8] {
+        if self.raw.size == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.raw.buffer as *const u8, self.raw.size) }
+    }
+
+    /// Returns the content of the memory for writing.
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        if self.raw.size == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.raw.buffer as *mut u8, self.raw.size) }
+    }
+
+    pub(crate) fn as_raw_ptr(&self) -> *mut raw::TEEC_SharedMemory {
+        &*self.raw as *const _ as *mut _
+    }
+}
+
+impl<'a> Drop for SharedMemory<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            raw::TEEC_ReleaseSharedMemory(&mut *self.raw);
+        }
+    }
+}
+
+// the mocks below clash with the `runner` implementation
+#[cfg(all(test, not(feature = "runner")))]
+mod tests {
+    use super::*;
+    use crate::{Param, ParamMemref, ParamType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RELEASED: AtomicUsize = AtomicUsize::new(0);
+
+    #[no_mangle]
+    extern "C" fn TEEC_InitializeContext(
+        _name: *const std::ffi::c_char,
+        _context: *mut raw::TEEC_Context,
+    ) -> raw::TEEC_Result {
+        raw::TEEC_SUCCESS
+    }
+
+    #[no_mangle]
+    extern "C" fn TEEC_FinalizeContext(_context: *mut raw::TEEC_Context) {}
+
+    #[no_mangle]
+    extern "C" fn TEEC_AllocateSharedMemory(
+        _context: *mut raw::TEEC_Context,
+        shm: *mut raw::TEEC_SharedMemory,
+    ) -> raw::TEEC_Result {
+        unsafe {
+            let buffer = vec![0u8; (*shm).size].into_boxed_slice();
+            (*shm).buffer = Box::into_raw(buffer) as _;
+        }
+        raw::TEEC_SUCCESS
+    }
+
+    #[no_mangle]
+    extern "C" fn TEEC_RegisterSharedMemory(
+        _context: *mut raw::TEEC_Context,
+        _shm: *mut raw::TEEC_SharedMemory,
+    ) -> raw::TEEC_Result {
+        raw::TEEC_SUCCESS
+    }
+
+    #[no_mangle]
+    extern "C" fn TEEC_ReleaseSharedMemory(_shm: *mut raw::TEEC_SharedMemory) {
+        RELEASED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_shared_memory_params() {
+        let mut ctx = Context::new().unwrap();
+        let mut shm = SharedMemory::allocate(&mut ctx, 16, SharedMemoryFlags::INOUT).unwrap();
+        shm.buffer_mut()[..3].copy_from_slice(b"abc");
+        let parent = shm.as_raw_ptr();
+
+        let mut p0 = ParamMemref::new_partial_output(&mut shm, 4, 8);
+        assert_eq!(p0.param_type() as u32, ParamType::MemrefPartialOutput as u32);
+        let memref = unsafe { p0.into_raw().memref };
+        assert_eq!(memref.parent, parent);
+        assert_eq!((memref.offset, memref.size), (4, 8));
+        assert_eq!(&shm.buffer()[..3], b"abc");
+
+        let mut buffer = [0u8; 8];
+        let registered =
+            SharedMemory::register(&mut ctx, &mut buffer, SharedMemoryFlags::INPUT).unwrap();
+        assert_eq!(registered.size(), 8);
+        assert!(SharedMemoryFlags::INOUT.contains(registered.flags()));
+
+        drop(registered);
+        drop(shm);
+        assert_eq!(RELEASED.load(Ordering::SeqCst), 2);
+    }
+}
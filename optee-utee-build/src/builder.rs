@@ -103,7 +103,11 @@ impl Builder {
             None => PathBuf::from(std::env::var("OUT_DIR")?),
         };
         self.write_header_file(out_dir.clone())?;
-        self.link(out_dir)?;
+        // TAs built with their `sim` feature are loaded into a host process
+        // by the runner of optee-teec, so they are not linked as TA binaries.
+        if std::env::var_os("CARGO_FEATURE_SIM").is_none() {
+            self.link(out_dir)?;
+        }
         Ok(())
     }
 }
@@ -22,6 +22,8 @@ pub enum Error {
     Uuid(uuid::Error),
     PropertyNotFound(String),
     InvalidVersion(String),
+    InvalidInterface(String),
+    IncompatibleInterface(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -0,0 +1,563 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+use crate::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use syn::parse::{Parse, ParseStream};
+use syn::{braced, bracketed, parenthesized, Token};
+use uuid::Uuid;
+
+/// Command ID reserved for the version query answered by the generated
+/// dispatcher.
+pub const VERSION_COMMAND_ID: u32 = 0xFFFF_FFFF;
+
+/// Error codes a command may declare, named after `ErrorKind` variants.
+const ERROR_NAMES: &[&str] = &[
+    "Generic",
+    "AccessDenied",
+    "Cancel",
+    "AccessConflict",
+    "ExcessData",
+    "BadFormat",
+    "BadParameters",
+    "BadState",
+    "ItemNotFound",
+    "NotImplemented",
+    "NotSupported",
+    "NoData",
+    "OutOfMemory",
+    "Busy",
+    "Communication",
+    "Security",
+    "ShortBuffer",
+    "ExternalCancel",
+    "Overflow",
+    "TargetDead",
+    "StorageNoSpace",
+    "MacInvalid",
+    "SignatureInvalid",
+    "TimeNotSet",
+    "TimeNeedsReset",
+];
+
+/// The interface of a TA: its commands and their parameters.
+///
+/// An interface is written in a small definition language using Rust tokens,
+/// usually in an `interface.idl` file next to the project's `uuid.txt`:
+///
+/// ```text
+/// /// Sorts arrays of integers.
+/// interface Sorter {
+///     uuid = "26509cec-4a2b-4935-87ab-762d89fbf0b0";
+///     version = "1.1";
+///
+///     /// Sorts the i32 array in `values` in place.
+///     command Sort = 0 (inout memref values) errors [AccessDenied];
+///     /// Returns the number of sorts done and the limit.
+///     command Status = 1 (out value count_max);
+/// }
+/// ```
+///
+/// Each parameter is `in`, `out` or `inout`, followed by `value` or `memref`
+/// and a name; a command takes at most four. `errors` lists the error codes
+/// the command may return, by `ErrorKind` name. `uuid` may be left out when
+/// the interface is read with [`Interface::from_file`] and a `uuid.txt`
+/// exists in the same directory. A command declaring `ShortBuffer` must have
+/// exactly one `out` or `inout` memref: generated clients report the size the
+/// TA asks for in it with `Error::required_size`.
+///
+/// The version is `major.minor`. Adding commands needs a new minor version;
+/// removing or changing them needs a new major version, see
+/// [`Interface::check_compatible`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interface {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub uuid: Option<Uuid>,
+    pub version: InterfaceVersion,
+    pub commands: Vec<InterfaceCommand>,
+}
+
+/// The `major.minor` version of an interface.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct InterfaceVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+/// A command of an interface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceCommand {
+    pub name: String,
+    pub id: u32,
+    pub docs: Vec<String>,
+    pub params: Vec<InterfaceParam>,
+    pub errors: Vec<String>,
+}
+
+/// A parameter of a command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceParam {
+    pub name: String,
+    pub direction: Direction,
+    pub kind: ParamKind,
+}
+
+/// Which way the data of a parameter flows.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    In,
+    Out,
+    InOut,
+}
+
+/// Whether a parameter is a pair of values or a memory reference.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParamKind {
+    Value,
+    Memref,
+}
+
+impl Interface {
+    /// Reads the interface defined in the file at `path`.
+    ///
+    /// When the definition has no `uuid`, it is read from `uuid.txt` in the
+    /// same directory.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut interface = Self::from_str(&fs::read_to_string(path)?)?;
+        if interface.uuid.is_none() {
+            let uuid_path = path.with_file_name("uuid.txt");
+            if uuid_path.exists() {
+                interface.uuid = Some(Uuid::parse_str(fs::read_to_string(uuid_path)?.trim())?);
+            }
+        }
+        Ok(interface)
+    }
+
+    /// Returns the command with `id`.
+    pub fn command(&self, id: u32) -> Option<&InterfaceCommand> {
+        self.commands.iter().find(|command| command.id == id)
+    }
+
+    /// Checks that clients built against `previous` keep working with this
+    /// interface.
+    ///
+    /// Within a major version, every command of `previous` must keep its ID
+    /// and parameters, and added commands need a higher minor version. A
+    /// higher major version may change anything but the UUID.
+    ///
+    /// # Errors
+    ///
+    /// 1) `IncompatibleInterface`: the versions or commands break the rules
+    ///    above.
+    pub fn check_compatible(&self, previous: &Interface) -> Result<(), Error> {
+        let incompatible = |reason: String| Err(Error::IncompatibleInterface(reason));
+        if self.uuid != previous.uuid {
+            return incompatible("the UUID changed".to_string());
+        }
+        if self.version < previous.version {
+            return incompatible(format!(
+                "version {} is older than {}",
+                self.version, previous.version
+            ));
+        }
+        if self.version.major > previous.version.major {
+            return Ok(());
+        }
+        for old in &previous.commands {
+            match self.command(old.id) {
+                None => {
+                    return incompatible(format!(
+                        "command {} was removed without a new major version",
+                        old.name
+                    ))
+                }
+                Some(new) if !new.same_layout(old) => {
+                    return incompatible(format!(
+                        "parameters of command {} changed without a new major version",
+                        old.name
+                    ))
+                }
+                Some(_) => (),
+            }
+        }
+        let added = self.commands.len() > previous.commands.len();
+        if added && self.version.minor == previous.version.minor {
+            return incompatible(format!(
+                "commands were added without a new minor version than {}",
+                previous.version
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: String| Err(Error::InvalidInterface(reason));
+        for (i, command) in self.commands.iter().enumerate() {
+            if command.id == VERSION_COMMAND_ID {
+                return invalid(format!(
+                    "command {} uses the reserved ID {:#x}",
+                    command.name, VERSION_COMMAND_ID
+                ));
+            }
+            for other in &self.commands[..i] {
+                if other.id == command.id || other.name == command.name {
+                    return invalid(format!(
+                        "commands {} and {} share a name or ID",
+                        other.name, command.name
+                    ));
+                }
+            }
+            if command.params.len() > 4 {
+                return invalid(format!("command {} has more than 4 parameters", command.name));
+            }
+            if let Some(error) = command.errors.iter().find(|e| !ERROR_NAMES.contains(&e.as_str()))
+            {
+                return invalid(format!("command {} declares unknown error {}", command.name, error));
+            }
+            if command.errors.iter().any(|e| e == "ShortBuffer")
+                && command.output_memrefs().count() != 1
+            {
+                return invalid(format!(
+                    "command {} declares ShortBuffer without exactly one output memref",
+                    command.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Interface {
+    type Err = Error;
+
+    /// Parses an interface definition.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let tokens = proc_macro2::TokenStream::from_str(s)
+            .map_err(|e| Error::InvalidInterface(e.to_string()))?;
+        let interface: Interface =
+            syn::parse2(tokens).map_err(|e| Error::InvalidInterface(e.to_string()))?;
+        interface.validate()?;
+        Ok(interface)
+    }
+}
+
+impl InterfaceCommand {
+    /// Indexes of the `out` and `inout` memref parameters.
+    pub(crate) fn output_memrefs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.params.iter().enumerate().filter_map(|(i, param)| {
+            (param.kind == ParamKind::Memref && param.direction != Direction::In).then_some(i)
+        })
+    }
+
+    fn same_layout(&self, other: &InterfaceCommand) -> bool {
+        self.params.len() == other.params.len()
+            && self
+                .params
+                .iter()
+                .zip(&other.params)
+                .all(|(a, b)| a.direction == b.direction && a.kind == b.kind)
+    }
+}
+
+impl fmt::Display for InterfaceVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for InterfaceVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidVersion(s.to_string());
+        let (major, minor) = s.split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+mod kw {
+    syn::custom_keyword!(interface);
+    syn::custom_keyword!(uuid);
+    syn::custom_keyword!(version);
+    syn::custom_keyword!(command);
+    syn::custom_keyword!(errors);
+    syn::custom_keyword!(out);
+    syn::custom_keyword!(inout);
+    syn::custom_keyword!(value);
+    syn::custom_keyword!(memref);
+}
+
+fn parse_docs(input: ParseStream) -> syn::Result<Vec<String>> {
+    let mut docs = Vec::new();
+    for attr in input.call(syn::Attribute::parse_outer)? {
+        match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => docs.push(doc.value()),
+            _ => return Err(syn::Error::new_spanned(attr, "only doc comments are allowed")),
+        }
+    }
+    Ok(docs)
+}
+
+impl Parse for Interface {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let docs = parse_docs(input)?;
+        input.parse::<kw::interface>()?;
+        let name: syn::Ident = input.parse()?;
+        let content;
+        braced!(content in input);
+
+        let mut uuid = None;
+        let mut version = None;
+        let mut commands = Vec::new();
+        while !content.is_empty() {
+            let item_docs = parse_docs(&content)?;
+            let lookahead = content.lookahead1();
+            if lookahead.peek(kw::uuid) {
+                content.parse::<kw::uuid>()?;
+                content.parse::<Token![=]>()?;
+                let value: syn::LitStr = content.parse()?;
+                content.parse::<Token![;]>()?;
+                uuid = Some(
+                    Uuid::parse_str(&value.value())
+                        .map_err(|e| syn::Error::new(value.span(), e))?,
+                );
+            } else if lookahead.peek(kw::version) {
+                content.parse::<kw::version>()?;
+                content.parse::<Token![=]>()?;
+                let value: syn::LitStr = content.parse()?;
+                content.parse::<Token![;]>()?;
+                version = Some(
+                    InterfaceVersion::from_str(&value.value())
+                        .map_err(|_| syn::Error::new(value.span(), "expected `major.minor`"))?,
+                );
+            } else if lookahead.peek(kw::command) {
+                let mut command: InterfaceCommand = content.parse()?;
+                command.docs = item_docs;
+                commands.push(command);
+            } else {
+                return Err(lookahead.error());
+            }
+        }
+
+        Ok(Interface {
+            name: name.to_string(),
+            docs,
+            uuid,
+            version: version.ok_or_else(|| syn::Error::new(name.span(), "missing `version`"))?,
+            commands,
+        })
+    }
+}
+
+impl Parse for InterfaceCommand {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<kw::command>()?;
+        let name: syn::Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let id: syn::LitInt = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let params = content.parse_terminated(InterfaceParam::parse, Token![,])?;
+
+        let mut errors = Vec::new();
+        if input.peek(kw::errors) {
+            input.parse::<kw::errors>()?;
+            let content;
+            bracketed!(content in input);
+            for error in content.parse_terminated(syn::Ident::parse, Token![,])? {
+                errors.push(error.to_string());
+            }
+        }
+        input.parse::<Token![;]>()?;
+
+        Ok(InterfaceCommand {
+            name: name.to_string(),
+            id: id.base10_parse()?,
+            docs: Vec::new(),
+            params: params.into_iter().collect(),
+            errors,
+        })
+    }
+}
+
+impl Parse for InterfaceParam {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lookahead = input.lookahead1();
+        let direction = if lookahead.peek(Token![in]) {
+            input.parse::<Token![in]>()?;
+            Direction::In
+        } else if lookahead.peek(kw::out) {
+            input.parse::<kw::out>()?;
+            Direction::Out
+        } else if lookahead.peek(kw::inout) {
+            input.parse::<kw::inout>()?;
+            Direction::InOut
+        } else {
+            return Err(lookahead.error());
+        };
+        let lookahead = input.lookahead1();
+        let kind = if lookahead.peek(kw::value) {
+            input.parse::<kw::value>()?;
+            ParamKind::Value
+        } else if lookahead.peek(kw::memref) {
+            input.parse::<kw::memref>()?;
+            ParamKind::Memref
+        } else {
+            return Err(lookahead.error());
+        };
+        let name: syn::Ident = input.parse()?;
+        Ok(InterfaceParam {
+            name: name.to_string(),
+            direction,
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDL: &str = r#"
+        /// Sorts arrays of integers.
+        interface Sorter {
+            uuid = "26509cec-4a2b-4935-87ab-762d89fbf0b0";
+            version = "1.1";
+
+            /// Sorts the i32 array in `values` in place.
+            command Sort = 0 (inout memref values) errors [AccessDenied];
+            command Status = 2 (out value count_max, out value remaining);
+        }
+    "#;
+
+    fn parse_err(idl: &str) -> String {
+        match Interface::from_str(idl) {
+            Err(Error::InvalidInterface(reason)) => reason,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let interface = Interface::from_str(IDL).unwrap();
+        assert_eq!(interface.name, "Sorter");
+        assert_eq!(interface.docs, vec![" Sorts arrays of integers.".to_string()]);
+        assert_eq!(
+            interface.uuid.unwrap().to_string(),
+            "26509cec-4a2b-4935-87ab-762d89fbf0b0"
+        );
+        assert_eq!(interface.version, InterfaceVersion { major: 1, minor: 1 });
+        assert_eq!(interface.commands.len(), 2);
+
+        let sort = interface.command(0).unwrap();
+        assert_eq!(sort.name, "Sort");
+        assert_eq!(sort.errors, vec!["AccessDenied".to_string()]);
+        assert_eq!(
+            sort.params,
+            vec![InterfaceParam {
+                name: "values".to_string(),
+                direction: Direction::InOut,
+                kind: ParamKind::Memref,
+            }]
+        );
+        let status = interface.command(2).unwrap();
+        assert!(status.docs.is_empty());
+        assert_eq!(status.params[1].direction, Direction::Out);
+        assert_eq!(status.params[1].kind, ParamKind::Value);
+    }
+
+    #[test]
+    fn test_invalid() {
+        parse_err("interface A { version = \"1.0\"; command B = 0 (up value x); }");
+        parse_err("interface A { command B = 0 (); }");
+        let reason = parse_err(
+            "interface A { version = \"1.0\"; command B = 0 (); command C = 0 (); }",
+        );
+        assert!(reason.contains("share a name or ID"));
+        let reason = parse_err("interface A { version = \"1.0\"; command B = 4294967295 (); }");
+        assert!(reason.contains("reserved"));
+        let reason = parse_err(
+            "interface A { version = \"1.0\"; \
+             command B = 0 (in value a, in value b, in value c, in value d, in value e); }",
+        );
+        assert!(reason.contains("more than 4"));
+        let reason =
+            parse_err("interface A { version = \"1.0\"; command B = 0 () errors [Oops]; }");
+        assert!(reason.contains("unknown error Oops"));
+        let reason = parse_err(
+            "interface A { version = \"1.0\"; \
+             command B = 0 (in memref a, out value b) errors [ShortBuffer]; }",
+        );
+        assert!(reason.contains("ShortBuffer without exactly one output memref"));
+        parse_err(
+            "interface A { version = \"1.0\"; \
+             command B = 0 (out memref a, inout memref b) errors [ShortBuffer]; }",
+        );
+    }
+
+    #[test]
+    fn test_compatibility() {
+        let previous = Interface::from_str(IDL).unwrap();
+        let with = |version: &str, commands: &str| {
+            Interface::from_str(&format!(
+                "interface Sorter {{ uuid = \"26509cec-4a2b-4935-87ab-762d89fbf0b0\"; \
+                 version = \"{}\"; {} }}",
+                version, commands
+            ))
+            .unwrap()
+        };
+        let sort = "command Sort = 0 (inout memref values);";
+        let status = "command Status = 2 (out value count_max, out value remaining);";
+        let list = "command List = 3 (out memref keys);";
+
+        let same = with("1.1", &format!("{} {}", sort, status));
+        assert!(same.check_compatible(&previous).is_ok());
+        let added = with("1.2", &format!("{} {} {}", sort, status, list));
+        assert!(added.check_compatible(&previous).is_ok());
+
+        let incompatible = [
+            with("1.1", &format!("{} {} {}", sort, status, list)),
+            with("1.2", sort),
+            with("1.2", &format!("command Sort = 0 (in memref values); {}", status)),
+            with("1.0", &format!("{} {}", sort, status)),
+        ];
+        for interface in incompatible.iter() {
+            match interface.check_compatible(&previous) {
+                Err(Error::IncompatibleInterface(_)) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        assert!(with("2.0", list).check_compatible(&previous).is_ok());
+    }
+}
@@ -0,0 +1,566 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+use crate::interface::{Direction, Interface, InterfaceCommand, ParamKind, VERSION_COMMAND_ID};
+use crate::Error;
+use quote::{format_ident, quote};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Which side of an interface to generate code for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InterfaceTarget {
+    /// The `Command` enum, UUID and version, for the `proto` crate shared by
+    /// the host and the TA. Needs neither `optee-teec` nor `optee-utee`.
+    Proto,
+    /// A client with one method per command, for the host. Uses `optee-teec`.
+    Client,
+    /// A handler trait with a dispatcher checking the parameter types of each
+    /// command, for the TA. Uses `optee-utee`.
+    Dispatcher,
+}
+
+impl InterfaceTarget {
+    fn file_name(&self) -> &'static str {
+        match self {
+            InterfaceTarget::Proto => "interface_proto.rs",
+            InterfaceTarget::Client => "interface_client.rs",
+            InterfaceTarget::Dispatcher => "interface_dispatcher.rs",
+        }
+    }
+}
+
+/// Generator of the code of an interface, use it to generate a source file
+/// and then include it in user codes.
+///
+/// Use only if you want to place the file yourself, or you should use
+/// [`generate_interface`] instead.
+///
+/// Examples:
+/// ```rust
+/// use optee_utee_build::{Interface, InterfaceGenerator, InterfaceTarget};
+/// # use optee_utee_build::Error;
+/// # fn main() -> Result<(), Error> {
+/// let interface: Interface = r#"
+///     interface Example {
+///         uuid = "26509cec-4a2b-4935-87ab-762d89fbf0b0";
+///         version = "1.0";
+///         command Hello = 0 (in memref name, out value answer);
+///     }
+/// "#.parse()?;
+/// let codes = InterfaceGenerator::new(InterfaceTarget::Client).generate(&interface)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InterfaceGenerator {
+    target: InterfaceTarget,
+}
+
+impl InterfaceGenerator {
+    pub fn new(target: InterfaceTarget) -> Self {
+        Self { target }
+    }
+
+    pub fn generate(&self, interface: &Interface) -> Result<String, Error> {
+        let code = match self.target {
+            InterfaceTarget::Proto => proto_codes(interface),
+            InterfaceTarget::Client => client_codes(interface)?,
+            InterfaceTarget::Dispatcher => dispatcher_codes(interface),
+        };
+
+        const LICENSE_STR: &str = include_str!("./license_str.txt");
+        let f = syn::parse2(code).unwrap();
+        Ok(format!("{}\n{}", LICENSE_STR, prettyplease::unparse(&f)))
+    }
+}
+
+/// Generates the `target` code of the interface defined in `idl` into
+/// `OUT_DIR`, use it in `build.rs`.
+///
+/// The file is named `interface_proto.rs`, `interface_client.rs` or
+/// `interface_dispatcher.rs` after the target, so it can be included with:
+///
+/// ```ignore
+/// include!(concat!(env!("OUT_DIR"), "/interface_client.rs"));
+/// ```
+pub fn generate_interface<P: AsRef<Path>>(idl: P, target: InterfaceTarget) -> Result<(), Error> {
+    let idl = idl.as_ref();
+    println!("cargo:rerun-if-changed={}", idl.display());
+    println!("cargo:rerun-if-changed={}", idl.with_file_name("uuid.txt").display());
+    let interface = Interface::from_file(idl)?;
+    let code = InterfaceGenerator::new(target).generate(&interface)?;
+    let out = PathBuf::from(env::var("OUT_DIR")?);
+    fs::write(out.join(target.file_name()), code)?;
+    Ok(())
+}
+
+fn doc_codes(docs: &[String]) -> proc_macro2::TokenStream {
+    quote! { #(#[doc = #docs])* }
+}
+
+/// Docs of a command, followed by the errors it declares.
+fn command_doc_codes(command: &InterfaceCommand) -> proc_macro2::TokenStream {
+    let mut docs = command.docs.clone();
+    if !command.errors.is_empty() {
+        if !docs.is_empty() {
+            docs.push(String::new());
+        }
+        docs.push(format!(" Errors: `{}`.", command.errors.join("`, `")));
+    }
+    doc_codes(&docs)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn version_codes(interface: &Interface) -> proc_macro2::TokenStream {
+    let major = interface.version.major;
+    let minor = interface.version.minor;
+    quote! {
+        /// Major version of the interface; a client only talks to TAs with
+        /// the same major version.
+        pub const VERSION_MAJOR: u32 = #major;
+        /// Minor version of the interface; a client only talks to TAs with
+        /// at least this minor version.
+        pub const VERSION_MINOR: u32 = #minor;
+        /// ID of the command answering the version of the interface.
+        pub const VERSION_COMMAND_ID: u32 = #VERSION_COMMAND_ID;
+    }
+}
+
+fn proto_codes(interface: &Interface) -> proc_macro2::TokenStream {
+    let docs = doc_codes(&interface.docs);
+    let variants = interface.commands.iter().map(|command| {
+        let docs = command_doc_codes(command);
+        let name = format_ident!("{}", command.name);
+        let id = command.id;
+        quote! { #docs #name = #id, }
+    });
+    let arms = interface.commands.iter().map(|command| {
+        let name = format_ident!("{}", command.name);
+        let id = command.id;
+        quote! { #id => Command::#name, }
+    });
+    // Unknown takes the lowest ID left free, so `as u32` stays unambiguous.
+    let unknown_id = (0..)
+        .find(|id| interface.command(*id).is_none())
+        .unwrap_or(VERSION_COMMAND_ID - 1);
+    let uuid = interface.uuid.map(|uuid| {
+        let uuid = uuid.to_string();
+        quote! {
+            /// UUID of the TA implementing the interface.
+            pub const UUID: &str = #uuid;
+        }
+    });
+    let version = version_codes(interface);
+    quote! {
+        #docs
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        #[repr(u32)]
+        pub enum Command {
+            #(#variants)*
+            Unknown = #unknown_id,
+        }
+
+        impl From<u32> for Command {
+            #[inline]
+            fn from(value: u32) -> Command {
+                match value {
+                    #(#arms)*
+                    _ => Command::Unknown,
+                }
+            }
+        }
+
+        #uuid
+        #version
+    }
+}
+
+fn client_codes(interface: &Interface) -> Result<proc_macro2::TokenStream, Error> {
+    let uuid = interface.uuid.ok_or_else(|| {
+        Error::InvalidInterface(format!("interface {} has no uuid for its client", interface.name))
+    })?;
+    let uuid_bytes = uuid.as_bytes().iter();
+    let client = format_ident!("{}Client", interface.name);
+    let docs = doc_codes(&interface.docs);
+    let version = version_codes(interface);
+    let methods = interface.commands.iter().map(client_method_codes);
+    let client_doc = format!(" Client of the `{}` interface.", interface.name);
+    // Any minor version satisfies a client of minor version 0.
+    let version_check = if interface.version.minor > 0 {
+        quote! {
+            let (major, minor) = client.version()?;
+            if major != Self::VERSION_MAJOR || minor < Self::VERSION_MINOR
+        }
+    } else {
+        quote! {
+            let (major, _) = client.version()?;
+            if major != Self::VERSION_MAJOR
+        }
+    };
+
+    Ok(quote! {
+        #[doc = #client_doc]
+        ///
+        #docs
+        ///
+        /// The client is generic over the session so that it can be tested
+        /// with `optee_teec::mock::MockContext`.
+        pub struct #client<S = optee_teec::Session> {
+            session: S,
+        }
+
+        impl<S: optee_teec::TeeSession> #client<S> {
+            #version
+
+            /// Opens a session to the TA and checks that it implements a
+            /// compatible version of the interface.
+            ///
+            /// # Errors
+            ///
+            /// 1) `NotSupported`: the TA implements an incompatible version.
+            /// 2) Any error of opening the session.
+            pub fn open<C>(context: &mut C) -> optee_teec::Result<Self>
+            where
+                C: optee_teec::TeeContext<Session = S>,
+            {
+                let uuid = optee_teec::Uuid::from_bytes([#(#uuid_bytes),*]);
+                Self::from_session(optee_teec::TeeContext::open_session(context, uuid)?)
+            }
+
+            /// Wraps a session opened to the TA, checking that it implements a
+            /// compatible version of the interface.
+            ///
+            /// # Errors
+            ///
+            /// 1) `NotSupported`: the TA implements an incompatible version.
+            pub fn from_session(session: S) -> optee_teec::Result<Self> {
+                let mut client = Self { session };
+                #version_check {
+                    return Err(optee_teec::Error::new(optee_teec::ErrorKind::NotSupported)
+                        .with_origin(optee_teec::ErrorOrigin::API));
+                }
+                Ok(client)
+            }
+
+            /// Returns the `(major, minor)` version of the interface
+            /// implemented by the TA.
+            pub fn version(&mut self) -> optee_teec::Result<(u32, u32)> {
+                let p0 = optee_teec::ParamValue::new(0, 0, optee_teec::ParamType::ValueOutput);
+                let mut operation = optee_teec::Operation::new(
+                    0,
+                    p0,
+                    optee_teec::ParamNone,
+                    optee_teec::ParamNone,
+                    optee_teec::ParamNone,
+                );
+                optee_teec::TeeSession::invoke_command(
+                    &mut self.session,
+                    Self::VERSION_COMMAND_ID,
+                    &mut operation,
+                )?;
+                let (p0, _, _, _) = operation.parameters();
+                Ok((p0.a(), p0.b()))
+            }
+
+            /// Returns the session to the TA.
+            pub fn session(&mut self) -> &mut S {
+                &mut self.session
+            }
+
+            #(#methods)*
+        }
+    })
+}
+
+fn client_method_codes(command: &InterfaceCommand) -> proc_macro2::TokenStream {
+    let docs = command_doc_codes(command);
+    let method = format_ident!("{}", snake_case(&command.name));
+    let id = command.id;
+
+    let mut args = Vec::new();
+    let mut slots = Vec::new();
+    let mut bindings = Vec::new();
+    let mut updates = Vec::new();
+    let mut outputs = Vec::new();
+    let mut output_types = Vec::new();
+    for (i, param) in command.params.iter().enumerate() {
+        let name = format_ident!("{}", param.name);
+        let slot = format_ident!("p{}", i);
+        slots.push(match (param.kind, param.direction) {
+            (ParamKind::Value, Direction::In) => {
+                args.push(quote! { #name: (u32, u32) });
+                quote! {
+                    optee_teec::ParamValue::new(#name.0, #name.1, optee_teec::ParamType::ValueInput)
+                }
+            }
+            (ParamKind::Value, Direction::Out) => quote! {
+                optee_teec::ParamValue::new(0, 0, optee_teec::ParamType::ValueOutput)
+            },
+            (ParamKind::Value, Direction::InOut) => {
+                args.push(quote! { #name: &mut (u32, u32) });
+                quote! {
+                    optee_teec::ParamValue::new(#name.0, #name.1, optee_teec::ParamType::ValueInout)
+                }
+            }
+            (ParamKind::Memref, Direction::In) => {
+                args.push(quote! { #name: &[u8] });
+                quote! { optee_teec::ParamTmpRef::new_input(#name) }
+            }
+            (ParamKind::Memref, Direction::Out) => {
+                args.push(quote! { #name: &mut [u8] });
+                quote! { optee_teec::ParamTmpRef::new_output(#name) }
+            }
+            (ParamKind::Memref, Direction::InOut) => {
+                args.push(quote! { #name: &mut [u8] });
+                quote! { optee_teec::ParamTmpRef::new_inout(#name) }
+            }
+        });
+        match (param.kind, param.direction) {
+            (_, Direction::In) => bindings.push(quote! { _ }),
+            (ParamKind::Value, Direction::Out) => {
+                bindings.push(quote! { #slot });
+                outputs.push(quote! { (#slot.a(), #slot.b()) });
+                output_types.push(quote! { (u32, u32) });
+            }
+            (ParamKind::Value, Direction::InOut) => {
+                bindings.push(quote! { #slot });
+                updates.push(quote! { *#name = (#slot.a(), #slot.b()); });
+            }
+            (ParamKind::Memref, _) => {
+                bindings.push(quote! { #slot });
+                outputs.push(quote! { #slot.updated_size() });
+                output_types.push(quote! { usize });
+            }
+        }
+    }
+    while slots.len() < 4 {
+        slots.push(quote! { optee_teec::ParamNone });
+        bindings.push(quote! { _ });
+    }
+
+    let read_back = if updates.is_empty() && outputs.is_empty() {
+        quote! {}
+    } else {
+        quote! { let (#(#bindings),*) = operation.parameters(); }
+    };
+    let (output, output_type) = match outputs.len() {
+        1 => (outputs.remove(0), output_types.remove(0)),
+        _ => (quote! { (#(#outputs),*) }, quote! { (#(#output_types),*) }),
+    };
+    // The TA reports the size it needs in the output memref, which is lost
+    // unless read before returning the error.
+    let short_buffer = match command.output_memrefs().next() {
+        Some(index) if command.errors.iter().any(|e| e == "ShortBuffer") => {
+            let slot = format_ident!("p{}", index);
+            let size_bindings = (0..4).map(|i| match i == index {
+                true => quote! { #slot },
+                false => quote! { _ },
+            });
+            quote! {
+                .map_err(|err| match err.kind() {
+                    optee_teec::ErrorKind::ShortBuffer => {
+                        let (#(#size_bindings),*) = operation.parameters();
+                        err.with_required_size(#slot.updated_size())
+                    }
+                    _ => err,
+                })
+            }
+        }
+        _ => quote! {},
+    };
+
+    quote! {
+        #docs
+        pub fn #method(&mut self, #(#args),*) -> optee_teec::Result<#output_type> {
+            let mut operation = optee_teec::Operation::new(0, #(#slots),*);
+            optee_teec::TeeSession::invoke_command(&mut self.session, #id, &mut operation)
+                #short_buffer?;
+            #read_back
+            #(#updates)*
+            Ok(#output)
+        }
+    }
+}
+
+fn dispatcher_codes(interface: &Interface) -> proc_macro2::TokenStream {
+    let handler = format_ident!("{}Handler", interface.name);
+    let docs = doc_codes(&interface.docs);
+    let version = version_codes(interface);
+    let handler_doc = format!(" Handler of the commands of the `{}` interface.", interface.name);
+
+    let mut methods = Vec::new();
+    let mut arms = Vec::new();
+    for command in &interface.commands {
+        let docs = command_doc_codes(command);
+        let method = format_ident!("{}", snake_case(&command.name));
+        let id = command.id;
+        let mut args = Vec::new();
+        let mut names = Vec::new();
+        let mut conversions = Vec::new();
+        let mut types = Vec::new();
+        let fields = [quote!(0), quote!(1), quote!(2), quote!(3)];
+        for (param, field) in command.params.iter().zip(&fields) {
+            let name = format_ident!("{}", param.name);
+            let param_type = format_ident!(
+                "{}{}",
+                match param.kind {
+                    ParamKind::Value => "Value",
+                    ParamKind::Memref => "Memref",
+                },
+                match param.direction {
+                    Direction::In => "Input",
+                    Direction::Out => "Output",
+                    Direction::InOut => "Inout",
+                }
+            );
+            types.push(quote! { optee_utee::ParamType::#param_type });
+            match param.kind {
+                ParamKind::Value => {
+                    args.push(quote! { #name: optee_utee::ParamValue<'_> });
+                    conversions.push(quote! { let #name = unsafe { params.#field.as_value()? }; });
+                }
+                ParamKind::Memref => {
+                    args.push(quote! { #name: optee_utee::ParamMemref<'_> });
+                    conversions.push(quote! { let #name = unsafe { params.#field.as_memref()? }; });
+                }
+            }
+            names.push(name);
+        }
+        while types.len() < 4 {
+            types.push(quote! { optee_utee::ParamType::None });
+        }
+
+        methods.push(quote! {
+            #docs
+            fn #method(&mut self, #(#args),*) -> optee_utee::Result<()>;
+        });
+        arms.push(quote! {
+            #id => {
+                params.check_types(optee_utee::ParamTypes::new(#(#types),*))?;
+                #(#conversions)*
+                self.#method(#(#names),*)
+            }
+        });
+    }
+
+    quote! {
+        #version
+
+        #[doc = #handler_doc]
+        ///
+        #docs
+        pub trait #handler {
+            #(#methods)*
+
+            /// Checks the parameter types of command `cmd_id` and calls its
+            /// method, use it in `ta_invoke_command`. Also answers the version
+            /// command.
+            ///
+            /// # Errors
+            ///
+            /// 1) `BadParameters`: the parameter types do not match the command.
+            /// 2) `NotSupported`: the command is not part of the interface.
+            /// 3) Any error returned by the method.
+            fn dispatch(
+                &mut self,
+                cmd_id: u32,
+                params: &mut optee_utee::Parameters,
+            ) -> optee_utee::Result<()> {
+                match cmd_id {
+                    #(#arms)*
+                    VERSION_COMMAND_ID => {
+                        params.check_types(optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::ValueOutput,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ))?;
+                        let mut version = unsafe { params.0.as_value()? };
+                        version.set_a(VERSION_MAJOR);
+                        version.set_b(VERSION_MINOR);
+                        Ok(())
+                    }
+                    _ => Err(optee_utee::Error::new(optee_utee::ErrorKind::NotSupported)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDL: &str = r#"
+        /// Sorts arrays of integers.
+        interface Sorter {
+            uuid = "26509cec-4a2b-4935-87ab-762d89fbf0b0";
+            version = "1.1";
+
+            /// Sorts the i32 array in `values` in place.
+            command Sort = 0 (inout memref values) errors [AccessDenied];
+            command TopUp = 1 (in memref token, inout value limit);
+            command Status = 2 (out value count_max, out value remaining);
+            /// Lists the keys of the counters.
+            command List = 3 (out memref keys) errors [ShortBuffer];
+        }
+    "#;
+
+    fn generate(target: InterfaceTarget) -> String {
+        let interface: Interface = IDL.parse().unwrap();
+        InterfaceGenerator::new(target).generate(&interface).unwrap()
+    }
+
+    #[test]
+    fn test_proto() {
+        let exp_result = include_str!("../test_files/test_interface_proto_result.rs");
+        assert_eq!(generate(InterfaceTarget::Proto), exp_result);
+    }
+
+    #[test]
+    fn test_client() {
+        let exp_result = include_str!("../test_files/test_interface_client_result.rs");
+        assert_eq!(generate(InterfaceTarget::Client), exp_result);
+    }
+
+    #[test]
+    fn test_dispatcher() {
+        let exp_result = include_str!("../test_files/test_interface_dispatcher_result.rs");
+        assert_eq!(generate(InterfaceTarget::Dispatcher), exp_result);
+    }
+
+    #[test]
+    fn test_client_needs_uuid() {
+        let interface: Interface = "interface A { version = \"1.0\"; }".parse().unwrap();
+        match InterfaceGenerator::new(InterfaceTarget::Client).generate(&interface) {
+            Err(Error::InvalidInterface(_)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
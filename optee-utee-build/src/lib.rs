@@ -18,12 +18,16 @@
 mod builder;
 mod code_generator;
 mod error;
+mod interface;
+mod interface_generator;
 mod linker;
 mod ta_config;
 
 pub use builder::*;
 pub use code_generator::*;
 pub use error::Error;
+pub use interface::*;
+pub use interface_generator::*;
 pub use linker::*;
 pub use ta_config::*;
 pub use uuid::Uuid;
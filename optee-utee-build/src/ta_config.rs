@@ -17,6 +17,18 @@
 use crate::Error;
 use std::convert::TryInto;
 
+/// A single instance of the TA serves all sessions, instead of one instance
+/// per session.
+pub const TA_FLAG_SINGLE_INSTANCE: u32 = 1 << 2;
+/// A single instance TA accepts several sessions at a time.
+pub const TA_FLAG_MULTI_SESSION: u32 = 1 << 3;
+/// A single instance TA is kept alive when its last session is closed.
+pub const TA_FLAG_INSTANCE_KEEP_ALIVE: u32 = 1 << 4;
+/// The TA may access secure data path buffers.
+pub const TA_FLAG_SECURE_DATA_PATH: u32 = 1 << 5;
+/// The TA may perform cache maintenance on shared memory.
+pub const TA_FLAG_CACHE_MAINTENANCE: u32 = 1 << 7;
+
 /// Configuration options for TA
 ///
 /// Examples
@@ -111,6 +123,7 @@ impl TaConfig {
             ext_properties: Vec::new(),
         })
     }
+    /// Sets the TA flags, a combination of the `TA_FLAG_*` constants.
     pub fn ta_flags(mut self, flags: u32) -> Self {
         self.ta_flags = flags;
         self
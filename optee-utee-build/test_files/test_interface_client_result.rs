@@ -0,0 +1,163 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+/// Client of the `Sorter` interface.
+///
+/// Sorts arrays of integers.
+///
+/// The client is generic over the session so that it can be tested
+/// with `optee_teec::mock::MockContext`.
+pub struct SorterClient<S = optee_teec::Session> {
+    session: S,
+}
+impl<S: optee_teec::TeeSession> SorterClient<S> {
+    /// Major version of the interface; a client only talks to TAs with
+    /// the same major version.
+    pub const VERSION_MAJOR: u32 = 1u32;
+    /// Minor version of the interface; a client only talks to TAs with
+    /// at least this minor version.
+    pub const VERSION_MINOR: u32 = 1u32;
+    /// ID of the command answering the version of the interface.
+    pub const VERSION_COMMAND_ID: u32 = 4294967295u32;
+    /// Opens a session to the TA and checks that it implements a
+    /// compatible version of the interface.
+    ///
+    /// # Errors
+    ///
+    /// 1) `NotSupported`: the TA implements an incompatible version.
+    /// 2) Any error of opening the session.
+    pub fn open<C>(context: &mut C) -> optee_teec::Result<Self>
+    where
+        C: optee_teec::TeeContext<Session = S>,
+    {
+        let uuid = optee_teec::Uuid::from_bytes([
+            38u8, 80u8, 156u8, 236u8, 74u8, 43u8, 73u8, 53u8, 135u8, 171u8, 118u8, 45u8,
+            137u8, 251u8, 240u8, 176u8,
+        ]);
+        Self::from_session(optee_teec::TeeContext::open_session(context, uuid)?)
+    }
+    /// Wraps a session opened to the TA, checking that it implements a
+    /// compatible version of the interface.
+    ///
+    /// # Errors
+    ///
+    /// 1) `NotSupported`: the TA implements an incompatible version.
+    pub fn from_session(session: S) -> optee_teec::Result<Self> {
+        let mut client = Self { session };
+        let (major, minor) = client.version()?;
+        if major != Self::VERSION_MAJOR || minor < Self::VERSION_MINOR {
+            return Err(
+                optee_teec::Error::new(optee_teec::ErrorKind::NotSupported)
+                    .with_origin(optee_teec::ErrorOrigin::API),
+            );
+        }
+        Ok(client)
+    }
+    /// Returns the `(major, minor)` version of the interface
+    /// implemented by the TA.
+    pub fn version(&mut self) -> optee_teec::Result<(u32, u32)> {
+        let p0 = optee_teec::ParamValue::new(0, 0, optee_teec::ParamType::ValueOutput);
+        let mut operation = optee_teec::Operation::new(
+            0,
+            p0,
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+        );
+        optee_teec::TeeSession::invoke_command(
+            &mut self.session,
+            Self::VERSION_COMMAND_ID,
+            &mut operation,
+        )?;
+        let (p0, _, _, _) = operation.parameters();
+        Ok((p0.a(), p0.b()))
+    }
+    /// Returns the session to the TA.
+    pub fn session(&mut self) -> &mut S {
+        &mut self.session
+    }
+    /// Sorts the i32 array in `values` in place.
+    ///
+    /// Errors: `AccessDenied`.
+    pub fn sort(&mut self, values: &mut [u8]) -> optee_teec::Result<usize> {
+        let mut operation = optee_teec::Operation::new(
+            0,
+            optee_teec::ParamTmpRef::new_inout(values),
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+        );
+        optee_teec::TeeSession::invoke_command(&mut self.session, 0u32, &mut operation)?;
+        let (p0, _, _, _) = operation.parameters();
+        Ok(p0.updated_size())
+    }
+    pub fn top_up(
+        &mut self,
+        token: &[u8],
+        limit: &mut (u32, u32),
+    ) -> optee_teec::Result<()> {
+        let mut operation = optee_teec::Operation::new(
+            0,
+            optee_teec::ParamTmpRef::new_input(token),
+            optee_teec::ParamValue::new(
+                limit.0,
+                limit.1,
+                optee_teec::ParamType::ValueInout,
+            ),
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+        );
+        optee_teec::TeeSession::invoke_command(&mut self.session, 1u32, &mut operation)?;
+        let (_, p1, _, _) = operation.parameters();
+        *limit = (p1.a(), p1.b());
+        Ok(())
+    }
+    pub fn status(&mut self) -> optee_teec::Result<((u32, u32), (u32, u32))> {
+        let mut operation = optee_teec::Operation::new(
+            0,
+            optee_teec::ParamValue::new(0, 0, optee_teec::ParamType::ValueOutput),
+            optee_teec::ParamValue::new(0, 0, optee_teec::ParamType::ValueOutput),
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+        );
+        optee_teec::TeeSession::invoke_command(&mut self.session, 2u32, &mut operation)?;
+        let (p0, p1, _, _) = operation.parameters();
+        Ok(((p0.a(), p0.b()), (p1.a(), p1.b())))
+    }
+    /// Lists the keys of the counters.
+    ///
+    /// Errors: `ShortBuffer`.
+    pub fn list(&mut self, keys: &mut [u8]) -> optee_teec::Result<usize> {
+        let mut operation = optee_teec::Operation::new(
+            0,
+            optee_teec::ParamTmpRef::new_output(keys),
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+            optee_teec::ParamNone,
+        );
+        optee_teec::TeeSession::invoke_command(&mut self.session, 3u32, &mut operation)
+            .map_err(|err| match err.kind() {
+                optee_teec::ErrorKind::ShortBuffer => {
+                    let (p0, _, _, _) = operation.parameters();
+                    err.with_required_size(p0.updated_size())
+                }
+                _ => err,
+            })?;
+        let (p0, _, _, _) = operation.parameters();
+        Ok(p0.updated_size())
+    }
+}
@@ -0,0 +1,135 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+/// Major version of the interface; a client only talks to TAs with
+/// the same major version.
+pub const VERSION_MAJOR: u32 = 1u32;
+/// Minor version of the interface; a client only talks to TAs with
+/// at least this minor version.
+pub const VERSION_MINOR: u32 = 1u32;
+/// ID of the command answering the version of the interface.
+pub const VERSION_COMMAND_ID: u32 = 4294967295u32;
+/// Handler of the commands of the `Sorter` interface.
+///
+/// Sorts arrays of integers.
+pub trait SorterHandler {
+    /// Sorts the i32 array in `values` in place.
+    ///
+    /// Errors: `AccessDenied`.
+    fn sort(&mut self, values: optee_utee::ParamMemref<'_>) -> optee_utee::Result<()>;
+    fn top_up(
+        &mut self,
+        token: optee_utee::ParamMemref<'_>,
+        limit: optee_utee::ParamValue<'_>,
+    ) -> optee_utee::Result<()>;
+    fn status(
+        &mut self,
+        count_max: optee_utee::ParamValue<'_>,
+        remaining: optee_utee::ParamValue<'_>,
+    ) -> optee_utee::Result<()>;
+    /// Lists the keys of the counters.
+    ///
+    /// Errors: `ShortBuffer`.
+    fn list(&mut self, keys: optee_utee::ParamMemref<'_>) -> optee_utee::Result<()>;
+    /// Checks the parameter types of command `cmd_id` and calls its
+    /// method, use it in `ta_invoke_command`. Also answers the version
+    /// command.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the parameter types do not match the command.
+    /// 2) `NotSupported`: the command is not part of the interface.
+    /// 3) Any error returned by the method.
+    fn dispatch(
+        &mut self,
+        cmd_id: u32,
+        params: &mut optee_utee::Parameters,
+    ) -> optee_utee::Result<()> {
+        match cmd_id {
+            0u32 => {
+                params
+                    .check_types(
+                        optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::MemrefInout,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ),
+                    )?;
+                let values = unsafe { params.0.as_memref()? };
+                self.sort(values)
+            }
+            1u32 => {
+                params
+                    .check_types(
+                        optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::MemrefInput,
+                            optee_utee::ParamType::ValueInout,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ),
+                    )?;
+                let token = unsafe { params.0.as_memref()? };
+                let limit = unsafe { params.1.as_value()? };
+                self.top_up(token, limit)
+            }
+            2u32 => {
+                params
+                    .check_types(
+                        optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::ValueOutput,
+                            optee_utee::ParamType::ValueOutput,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ),
+                    )?;
+                let count_max = unsafe { params.0.as_value()? };
+                let remaining = unsafe { params.1.as_value()? };
+                self.status(count_max, remaining)
+            }
+            3u32 => {
+                params
+                    .check_types(
+                        optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::MemrefOutput,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ),
+                    )?;
+                let keys = unsafe { params.0.as_memref()? };
+                self.list(keys)
+            }
+            VERSION_COMMAND_ID => {
+                params
+                    .check_types(
+                        optee_utee::ParamTypes::new(
+                            optee_utee::ParamType::ValueOutput,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                            optee_utee::ParamType::None,
+                        ),
+                    )?;
+                let mut version = unsafe { params.0.as_value()? };
+                version.set_a(VERSION_MAJOR);
+                version.set_b(VERSION_MINOR);
+                Ok(())
+            }
+            _ => Err(optee_utee::Error::new(optee_utee::ErrorKind::NotSupported)),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+/// Sorts arrays of integers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Command {
+    /// Sorts the i32 array in `values` in place.
+    ///
+    /// Errors: `AccessDenied`.
+    Sort = 0u32,
+    TopUp = 1u32,
+    Status = 2u32,
+    /// Lists the keys of the counters.
+    ///
+    /// Errors: `ShortBuffer`.
+    List = 3u32,
+    Unknown = 4u32,
+}
+impl From<u32> for Command {
+    #[inline]
+    fn from(value: u32) -> Command {
+        match value {
+            0u32 => Command::Sort,
+            1u32 => Command::TopUp,
+            2u32 => Command::Status,
+            3u32 => Command::List,
+            _ => Command::Unknown,
+        }
+    }
+}
+/// UUID of the TA implementing the interface.
+pub const UUID: &str = "26509cec-4a2b-4935-87ab-762d89fbf0b0";
+/// Major version of the interface; a client only talks to TAs with
+/// the same major version.
+pub const VERSION_MAJOR: u32 = 1u32;
+/// Minor version of the interface; a client only talks to TAs with
+/// at least this minor version.
+pub const VERSION_MINOR: u32 = 1u32;
+/// ID of the command answering the version of the interface.
+pub const VERSION_COMMAND_ID: u32 = 4294967295u32;
@@ -23,6 +23,10 @@ fn main() {
     println!("cargo:rerun-if-env-changed={}", ENV_SYS_BUILD_TYPE);
 
     let build_type = env::var(ENV_SYS_BUILD_TYPE).unwrap_or(String::from("")).to_lowercase();
+    if env::var_os("CARGO_FEATURE_SIM").is_some() {
+        // optee-utee implements libutee in Rust
+        return unit_test_build();
+    }
     match build_type.as_str() {
         "unit_test" => unit_test_build(),
         _ => production_build(),
@@ -118,6 +118,9 @@ pub const TEE_MODE_ILLEGAL_VALUE: u32 = 0x7FFFFFFF;
 
 // Other constants
 pub const TEE_STORAGE_PRIVATE: u32 = 0x00000001;
+// OP-TEE extensions
+pub const TEE_STORAGE_PRIVATE_REE: u32 = 0x80000000;
+pub const TEE_STORAGE_PRIVATE_RPMB: u32 = 0x80000100;
 
 pub const TEE_DATA_FLAG_ACCESS_READ: u32 = 0x00000001;
 pub const TEE_DATA_FLAG_ACCESS_WRITE: u32 = 0x00000002;
@@ -217,6 +220,11 @@ pub const TEE_ALG_ECDSA_P224: u32 = 0x70002041;
 pub const TEE_ALG_ECDSA_P256: u32 = 0x70003041;
 pub const TEE_ALG_ECDSA_P384: u32 = 0x70004041;
 pub const TEE_ALG_ECDSA_P521: u32 = 0x70005041;
+pub const TEE_ALG_ECDSA_SHA1: u32 = 0x70001042;
+pub const TEE_ALG_ECDSA_SHA224: u32 = 0x70002042;
+pub const TEE_ALG_ECDSA_SHA256: u32 = 0x70003042;
+pub const TEE_ALG_ECDSA_SHA384: u32 = 0x70004042;
+pub const TEE_ALG_ECDSA_SHA512: u32 = 0x70005042;
 pub const TEE_ALG_ECDH_P192: u32 = 0x80001042;
 pub const TEE_ALG_ECDH_P224: u32 = 0x80002042;
 pub const TEE_ALG_ECDH_P256: u32 = 0x80003042;
@@ -247,6 +255,10 @@ pub const TEE_TYPE_ECDSA_PUBLIC_KEY: u32 = 0xA0000041;
 pub const TEE_TYPE_ECDSA_KEYPAIR: u32 = 0xA1000041;
 pub const TEE_TYPE_ECDH_PUBLIC_KEY: u32 = 0xA0000042;
 pub const TEE_TYPE_ECDH_KEYPAIR: u32 = 0xA1000042;
+pub const TEE_TYPE_ED25519_PUBLIC_KEY: u32 = 0xA0000043;
+pub const TEE_TYPE_ED25519_KEYPAIR: u32 = 0xA1000043;
+pub const TEE_TYPE_X25519_PUBLIC_KEY: u32 = 0xA0000044;
+pub const TEE_TYPE_X25519_KEYPAIR: u32 = 0xA1000044;
 pub const TEE_TYPE_SM2_DSA_PUBLIC_KEY: u32 = 0xA0000045;
 pub const TEE_TYPE_SM2_DSA_KEYPAIR: u32 = 0xA1000045;
 pub const TEE_TYPE_SM2_KEP_PUBLIC_KEY: u32 = 0xA0000046;
@@ -284,6 +296,10 @@ pub const TEE_ATTR_ECC_PUBLIC_VALUE_X: u32 = 0xD0000141;
 pub const TEE_ATTR_ECC_PUBLIC_VALUE_Y: u32 = 0xD0000241;
 pub const TEE_ATTR_ECC_PRIVATE_VALUE: u32 = 0xC0000341;
 pub const TEE_ATTR_ECC_CURVE: u32 = 0xF0000441;
+pub const TEE_ATTR_ED25519_PUBLIC_VALUE: u32 = 0xD0000743;
+pub const TEE_ATTR_ED25519_PRIVATE_VALUE: u32 = 0xC0000843;
+pub const TEE_ATTR_X25519_PUBLIC_VALUE: u32 = 0xD0000944;
+pub const TEE_ATTR_X25519_PRIVATE_VALUE: u32 = 0xC0000A44;
 pub const TEE_ATTR_SM2_ID_INITIATOR: u32 = 0xD0000446;
 pub const TEE_ATTR_SM2_ID_RESPONDER: u32 = 0xD0000546;
 pub const TEE_ATTR_SM2_KEP_USER: u32 = 0xF0000646;
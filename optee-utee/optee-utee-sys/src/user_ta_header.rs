@@ -17,8 +17,8 @@
 
 use core::ffi::*;
 use super::tee_api_types::*;
-use super::utee_syscalls::*;
-use super::utee_types::*;
+#[cfg(not(feature = "sim"))]
+use super::{utee_syscalls::*, utee_types::*};
 
 pub const TA_FLAG_SINGLE_INSTANCE: u32 = 1 << 2;
 pub const TA_FLAG_MULTI_SESSION: u32 = 1 << 3;
@@ -37,10 +37,14 @@ pub struct ta_head {
     pub depr_entry: u64,
 }
 
+// The entry point of TA binaries; TAs built with the `sim` feature are entered
+// through their `TA_*EntryPoint` functions instead.
+#[cfg(not(feature = "sim"))]
 extern "C" {
     pub fn __utee_entry(func: c_ulong, session_id: c_ulong, up: *mut utee_params, cmd_id: c_ulong) -> TEE_Result;
 }
 
+#[cfg(not(feature = "sim"))]
 #[no_mangle]
 pub fn __ta_entry(func: c_ulong, session_id: c_ulong, up: *mut utee_params, cmd_id: c_ulong) -> ! {
     let res: u32 = unsafe { __utee_entry(func, session_id, up, cmd_id) };
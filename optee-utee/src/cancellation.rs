@@ -0,0 +1,130 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use optee_utee_sys as raw;
+
+/// Cancellation requests from the client of the current session.
+///
+/// Cancellation is masked when an entry point is called, so a TA must unmask
+/// it before it can observe cancellation requests, either by polling
+/// [`is_requested`](Cancellation::is_requested) or through cancellable
+/// functions such as [`Time::wait`](crate::Time::wait), which then return
+/// `Cancel`.
+///
+/// # Example
+///
+/// ```no_run
+/// # use optee_utee::{Cancellation, Error, ErrorKind};
+/// let _unmasked = Cancellation::unmasked();
+/// for chunk in 0..1000 {
+///     if Cancellation::is_requested() {
+///         return Err(Error::new(ErrorKind::Cancel));
+///     }
+///     // process chunk...
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+pub struct Cancellation;
+
+impl Cancellation {
+    /// Returns whether the client has requested cancellation of the current
+    /// operation.
+    ///
+    /// Always returns `false` while cancellation is masked.
+    pub fn is_requested() -> bool {
+        unsafe { raw::TEE_GetCancellationFlag() }
+    }
+
+    /// Masks cancellation and returns whether it was masked before.
+    pub fn mask() -> bool {
+        unsafe { raw::TEE_MaskCancellation() }
+    }
+
+    /// Unmasks cancellation and returns whether it was masked before.
+    pub fn unmask() -> bool {
+        unsafe { raw::TEE_UnmaskCancellation() }
+    }
+
+    /// Unmasks cancellation until the returned guard is dropped, which
+    /// restores the previous mask.
+    pub fn unmasked() -> UnmaskGuard {
+        UnmaskGuard {
+            was_masked: Self::unmask(),
+        }
+    }
+}
+
+/// Keeps cancellation unmasked; returned by [`Cancellation::unmasked`].
+#[must_use = "cancellation is masked again when the guard is dropped"]
+pub struct UnmaskGuard {
+    was_masked: bool,
+}
+
+impl Drop for UnmaskGuard {
+    fn drop(&mut self) {
+        if self.was_masked {
+            Cancellation::mask();
+        }
+    }
+}
+
+// the mocks below clash with the `sim` implementation
+#[cfg(all(test, not(feature = "sim")))]
+mod tests {
+    use super::*;
+    use crate::{ErrorKind, Time};
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    static MASKED: AtomicBool = AtomicBool::new(true);
+    static REQUESTED: AtomicBool = AtomicBool::new(true);
+
+    #[no_mangle]
+    extern "C" fn TEE_GetCancellationFlag() -> bool {
+        !MASKED.load(Ordering::SeqCst) && REQUESTED.load(Ordering::SeqCst)
+    }
+
+    #[no_mangle]
+    extern "C" fn TEE_MaskCancellation() -> bool {
+        MASKED.swap(true, Ordering::SeqCst)
+    }
+
+    #[no_mangle]
+    extern "C" fn TEE_UnmaskCancellation() -> bool {
+        MASKED.swap(false, Ordering::SeqCst)
+    }
+
+    #[no_mangle]
+    extern "C" fn TEE_Wait(_timeout: u32) -> raw::TEE_Result {
+        match TEE_GetCancellationFlag() {
+            true => raw::TEE_ERROR_CANCEL,
+            false => raw::TEE_SUCCESS,
+        }
+    }
+
+    #[test]
+    fn test_unmasked() {
+        assert!(!Cancellation::is_requested());
+        assert!(Time::wait(10).is_ok());
+        {
+            let _unmasked = Cancellation::unmasked();
+            assert!(Cancellation::is_requested());
+            assert_eq!(Time::wait(10).unwrap_err().kind(), ErrorKind::Cancel);
+        }
+        // the guard restores the mask
+        assert!(!Cancellation::is_requested());
+    }
+}
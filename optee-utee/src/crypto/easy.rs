@@ -0,0 +1,343 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! One-shot cryptographic functions.
+//!
+//! Each function allocates the operation and key objects it needs, runs the
+//! whole computation and returns the output in a buffer of the right size,
+//! so common tasks do not need the `allocate`, `set_key`, `init`, `update`
+//! and `final` sequence of the [crypto_op](crate::crypto_op) types. Use those
+//! types for streaming input or for algorithms not covered here.
+//!
+//! # Examples
+//!
+//! ``` no_run
+//! use optee_utee::crypto::easy;
+//!
+//! let mac = easy::hmac_sha256(&key, b"message")?;
+//! let sealed = easy::aes_gcm_seal(&key, &nonce, b"header", b"secret")?;
+//! assert_eq!(easy::aes_gcm_open(&key, &nonce, b"header", &sealed)?, b"secret");
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use crate::key::{AesKey, HmacKey, RsaPublicKey};
+use crate::{
+    AlgorithmId, Asymmetric, Digest, Error, ErrorKind, Mac, OperationMode, Result,
+    TransientObject, TransientObjectType, AE,
+};
+#[cfg(not(target_os = "optee"))]
+use alloc::vec::Vec;
+
+/// Size in bytes of a SHA-256 digest and of an HMAC-SHA256 tag.
+pub const SHA256_LEN: usize = 32;
+/// Size in bytes of the tag [aes_gcm_seal](aes_gcm_seal) appends.
+pub const GCM_TAG_LEN: usize = 16;
+
+/// Block size of SHA-256, the longest HMAC-SHA256 key used without hashing.
+const SHA256_BLOCK_LEN: usize = 64;
+/// Shortest key accepted by [TransientObjectType::HmacSha256](TransientObjectType::HmacSha256).
+const HMAC_SHA256_MIN_KEY_LEN: usize = 24;
+
+/// Compute the SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> Result<Vec<u8>> {
+    digest(AlgorithmId::Sha256, SHA256_LEN, data)
+}
+
+fn digest(algorithm: AlgorithmId, len: usize, data: &[u8]) -> Result<Vec<u8>> {
+    let op = Digest::allocate(algorithm)?;
+    let mut hash = vec![0u8; len];
+    let len = op.do_final(data, &mut hash)?;
+    hash.truncate(len);
+    Ok(hash)
+}
+
+/// Compute the HMAC-SHA256 tag of `data` under `key`.
+///
+/// Keys of any length are accepted, as in RFC 2104: keys longer than the
+/// SHA-256 block are hashed first, and keys shorter than the minimum size of
+/// an HMAC-SHA256 key object are padded with zeros, which does not change the
+/// tag.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let mut key = if key.len() > SHA256_BLOCK_LEN {
+        sha256(key)?
+    } else {
+        key.to_vec()
+    };
+    if key.len() < HMAC_SHA256_MIN_KEY_LEN {
+        key.resize(HMAC_SHA256_MIN_KEY_LEN, 0);
+    }
+    let key = HmacKey::from_bytes(TransientObjectType::HmacSha256, &key)?;
+    let op = Mac::allocate(AlgorithmId::HmacSha256, key.key_size()?)?;
+    op.set_key(&key)?;
+    op.init(&[]);
+    let mut mac = vec![0u8; SHA256_LEN];
+    let len = op.compute_final(data, &mut mac)?;
+    mac.truncate(len);
+    Ok(mac)
+}
+
+/// Encrypt and authenticate `plaintext`, and authenticate `aad`, with
+/// AES-GCM under a 16, 24 or 32 byte `key`.
+///
+/// Returns the ciphertext followed by a [GCM_TAG_LEN](GCM_TAG_LEN) byte tag.
+/// A `nonce` must never be used twice with the same key; a random 12 byte
+/// nonce is the usual choice.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `key` is not 16, 24 or 32 bytes long.
+pub fn aes_gcm_seal(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let op = aes_gcm(key, OperationMode::Encrypt, nonce, aad)?;
+    let mut sealed = vec![0u8; plaintext.len() + GCM_TAG_LEN];
+    let (ciphertext, tag) = sealed.split_at_mut(plaintext.len());
+    let (len, tag_len) = op.encrypt_final(plaintext, ciphertext, tag)?;
+    if len != plaintext.len() {
+        return Err(Error::new(ErrorKind::Generic));
+    }
+    sealed.truncate(len + tag_len);
+    Ok(sealed)
+}
+
+/// Check and decrypt the output of [aes_gcm_seal](aes_gcm_seal), given the
+/// same `key`, `nonce` and `aad`.
+///
+/// # Errors
+///
+/// 1) `MacInvalid`: If `sealed` is shorter than a tag, or it or `aad` was
+///    modified.
+/// 2) `BadParameters`: If `key` is not 16, 24 or 32 bytes long.
+pub fn aes_gcm_open(key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < GCM_TAG_LEN {
+        return Err(Error::new(ErrorKind::MacInvalid));
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - GCM_TAG_LEN);
+    let op = aes_gcm(key, OperationMode::Decrypt, nonce, aad)?;
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let len = op.decrypt_final(ciphertext, &mut plaintext, tag)?;
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+fn aes_gcm(key: &[u8], mode: OperationMode, nonce: &[u8], aad: &[u8]) -> Result<AE> {
+    let key = AesKey::from_bytes(key)?;
+    let op = AE::allocate(AlgorithmId::AesGcm, mode, key.key_size()?)?;
+    op.set_key(&key)?;
+    op.init(nonce, GCM_TAG_LEN * 8, 0, 0)?;
+    if !aad.is_empty() {
+        op.update_aad(aad);
+    }
+    Ok(op)
+}
+
+/// Verify an RSASSA-PSS signature over `message`, with SHA-256 as the hash
+/// and for MGF1, and a salt as long as the digest.
+///
+/// # Errors
+///
+/// 1) `SignatureInvalid`: If the signature is not valid for `message`.
+pub fn rsa_pss_verify(key: &RsaPublicKey, message: &[u8], signature: &[u8]) -> Result<()> {
+    let key_size = key.key_size()?;
+    if signature.len() != key_size.div_ceil(8) {
+        return Err(Error::new(ErrorKind::SignatureInvalid));
+    }
+    let op = Asymmetric::allocate(
+        AlgorithmId::RsassaPkcs1PssMgf1Sha256,
+        OperationMode::Verify,
+        key_size,
+    )?;
+    op.set_key(key)?;
+    op.verify_digest(&[], &sha256(message)?, signature)
+}
+
+/// Sign `message` with an ECDSA key pair, an object of type
+/// [EcdsaKeypair](TransientObjectType::EcdsaKeypair).
+///
+/// The message is hashed with SHA-256 for curves of up to 256 bits, SHA-384
+/// for P-384 and SHA-512 for P-521. The signature is `r || s`, each padded to
+/// the size of a coordinate of the curve.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `key` is not an ECDSA key pair.
+pub fn ecdsa_sign(key: &TransientObject, message: &[u8]) -> Result<Vec<u8>> {
+    let info = key.info()?;
+    if info.object_type() != TransientObjectType::EcdsaKeypair as u32 {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let key_size = info.object_size();
+    let (algorithm, digest) = match key_size {
+        0..=256 => (AlgorithmId::EcDsaSha256, sha256(message)?),
+        257..=384 => (AlgorithmId::EcDsaSha384, digest(AlgorithmId::Sha384, 48, message)?),
+        _ => (AlgorithmId::EcDsaSha512, digest(AlgorithmId::Sha512, 64, message)?),
+    };
+    let op = Asymmetric::allocate(algorithm, OperationMode::Sign, key_size)?;
+    op.set_key(key)?;
+    let mut signature = vec![0u8; 2 * key_size.div_ceil(8)];
+    let len = op.sign_digest(&[], &digest, &mut signature)?;
+    signature.truncate(len);
+    Ok(signature)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::key::EcdsaPublicKey;
+    use crate::{AttributeId, AttributeMemref, AttributeValue, ElementId};
+
+    // A 1024 bit RSA key and a P-256 key, with signatures of "message" made
+    // with the Python cryptography package
+    const RSA_MODULUS: &str = "cac80d8f162c4a858363ab6ffd3edf713256cc176f6650707cfa3a4a1ff692c5\
+                               b94e3b49785aa1a151d069895f5f8848cc3ce225d9a006a2e146ad4e2506324b\
+                               a1b8b6f6194257af9c49d3b38cf884a309b687a5e9c02f8158a6c0b6d6ea926b\
+                               8a6b397e3286d6848208ca7d30b76658ccf5d848086c6ef45ca35393e0e93f9f";
+    const RSA_PRIVATE_EXPONENT: &str =
+        "134eb8fbc6c0b0d7c58ad7e7f1d4eab17c798735340629e28c03c749078212b7\
+         80cd5217ccbdd465e7c45048242c946d5a04f2036895340c8e268c99c42cb2dd\
+         15355b8b4c7b8967c49127985fc109f56cbc777bd0022fdc83bbf82324110176\
+         775641b5d88282c644dcaf577785e25f097d8163a54770e529f4042b5783d001";
+    const RSA_PSS_SIGNATURE: &str =
+        "5a4eec070b26f336607f74213079eb5fbe8d7f2e3db3088dc06d5b4a6e5d48a5\
+         90243545df667d43b1061f2e88a829aa534419201077674b90a2af50bbd97392\
+         9e2034e23303506ccee2637a9d96758212083ceca29ccb2aace054ce70882b85\
+         155440f01aa9a776a12181c19e402bd24b534275e500788092a61d782fa76023";
+    const P256_PRIVATE: &str = "8d829e088db1399923302c943ae289d99940001e223b8525a35015ecdb20346c";
+    const P256_X: &str = "b0c720092893d128f0a9b5fe6a4e14ff40cceca117dac80095d1244d8d7dfc43";
+    const P256_Y: &str = "cf40570653fe78d647c873e5fe1fe3ee132b593bab1e7460cb5dcffa2edbe677";
+    const P256_SIGNATURE: &str = "48a092c74579bfe0363f5da2e0fe98c7fa7541eb6d07ca87a7789fa3582f7d65\
+                                  c5f9a7afdecf8533f9fa420afac8c4ab35ca22277db392565f83d55555b2c9c1";
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            hex::encode(sha256(b"abc").unwrap()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test cases 1 and 6, with a short and a long key
+        let mac = hmac_sha256(&[0x0b; 20], b"Hi There").unwrap();
+        assert_eq!(
+            hex::encode(mac),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        let data = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        assert_eq!(
+            hex::encode(hmac_sha256(&[0xaa; 131], data).unwrap()),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_aes_gcm() {
+        let sealed = aes_gcm_seal(&[0; 16], &[0; 12], b"", &[0; 16]).unwrap();
+        assert_eq!(
+            hex::encode(&sealed),
+            "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf"
+        );
+        assert_eq!(aes_gcm_open(&[0; 16], &[0; 12], b"", &sealed).unwrap(), [0; 16]);
+
+        let (key, nonce) = ([7u8; 32], [1u8; 12]);
+        let sealed = aes_gcm_seal(&key, &nonce, b"header", b"secret").unwrap();
+        assert_eq!(sealed.len(), 6 + GCM_TAG_LEN);
+        assert_eq!(aes_gcm_open(&key, &nonce, b"header", &sealed).unwrap(), b"secret");
+        let err = |result: Result<Vec<u8>>| result.unwrap_err().kind();
+        assert_eq!(err(aes_gcm_open(&key, &nonce, b"other", &sealed)), ErrorKind::MacInvalid);
+        assert_eq!(err(aes_gcm_open(&key, &nonce, b"header", &sealed[1..])), ErrorKind::MacInvalid);
+        assert_eq!(err(aes_gcm_open(&key, &nonce, b"", &sealed[..8])), ErrorKind::MacInvalid);
+        assert_eq!(err(aes_gcm_seal(&key[..20], &nonce, b"", b"")), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_rsa_pss_verify() {
+        let modulus = hex::decode(RSA_MODULUS).unwrap();
+        let key = RsaPublicKey::from_components(&modulus, &[1, 0, 1]).unwrap();
+        let signature = hex::decode(RSA_PSS_SIGNATURE).unwrap();
+        rsa_pss_verify(&key, b"message", &signature).unwrap();
+        let err = |result: Result<()>| result.unwrap_err().kind();
+        assert_eq!(err(rsa_pss_verify(&key, b"massage", &signature)), ErrorKind::SignatureInvalid);
+        let short = &signature[1..];
+        assert_eq!(err(rsa_pss_verify(&key, b"message", short)), ErrorKind::SignatureInvalid);
+
+        let private_exponent = hex::decode(RSA_PRIVATE_EXPONENT).unwrap();
+        let mut key_pair =
+            TransientObject::allocate(TransientObjectType::RsaKeypair, 1024).unwrap();
+        key_pair
+            .populate(&[
+                AttributeMemref::from_ref(AttributeId::RsaModulus, &modulus).into(),
+                AttributeMemref::from_ref(AttributeId::RsaPublicExponent, &[1, 0, 1]).into(),
+                AttributeMemref::from_ref(AttributeId::RsaPrivateExponent, &private_exponent)
+                    .into(),
+            ])
+            .unwrap();
+        let op = Asymmetric::allocate(
+            AlgorithmId::RsassaPkcs1PssMgf1Sha256,
+            OperationMode::Sign,
+            1024,
+        )
+        .unwrap();
+        op.set_key(&key_pair).unwrap();
+        let mut signature = [0u8; 128];
+        let digest = sha256(b"message").unwrap();
+        assert_eq!(op.sign_digest(&[], &digest, &mut signature).unwrap(), 128);
+        rsa_pss_verify(&key, b"message", &signature).unwrap();
+        signature[127] ^= 1;
+        assert_eq!(err(rsa_pss_verify(&key, b"message", &signature)), ErrorKind::SignatureInvalid);
+    }
+
+    #[test]
+    fn test_ecdsa_sign() {
+        let (x, y) = (hex::decode(P256_X).unwrap(), hex::decode(P256_Y).unwrap());
+        let private = hex::decode(P256_PRIVATE).unwrap();
+        let curve = ElementId::EccCurveNistP256 as u32;
+        let mut key_pair =
+            TransientObject::allocate(TransientObjectType::EcdsaKeypair, 256).unwrap();
+        key_pair
+            .populate(&[
+                AttributeMemref::from_ref(AttributeId::EccPublicValueX, &x).into(),
+                AttributeMemref::from_ref(AttributeId::EccPublicValueY, &y).into(),
+                AttributeMemref::from_ref(AttributeId::EccPrivateValue, &private).into(),
+                AttributeValue::from_value(AttributeId::EccCurve, curve, 0).into(),
+            ])
+            .unwrap();
+        let signature = ecdsa_sign(&key_pair, b"message").unwrap();
+        assert_eq!(signature.len(), 64);
+
+        let point = [&[0x04][..], &x, &y].concat();
+        let key = EcdsaPublicKey::from_sec1(ElementId::EccCurveNistP256, &point).unwrap();
+        let verify = |message: &[u8], signature: &[u8]| {
+            let op = Asymmetric::allocate(AlgorithmId::EcDsaSha256, OperationMode::Verify, 256)?;
+            op.set_key(&key)?;
+            op.verify_digest(&[], &sha256(message)?, signature)
+        };
+        verify(b"message", &signature).unwrap();
+        verify(b"message", &hex::decode(P256_SIGNATURE).unwrap()).unwrap();
+        let err = verify(b"massage", &signature).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+        let mut tampered = signature.clone();
+        tampered[63] ^= 1;
+        assert_eq!(verify(b"message", &tampered).unwrap_err().kind(), ErrorKind::SignatureInvalid);
+        assert!(ecdsa_sign(&key_pair, b"message").unwrap() != signature);
+    }
+
+    #[test]
+    fn test_ecdsa_sign_needs_key_pair() {
+        let key = TransientObject::allocate(TransientObjectType::Aes, 128).unwrap();
+        assert_eq!(ecdsa_sign(&key, b"message").unwrap_err().kind(), ErrorKind::BadParameters);
+    }
+}
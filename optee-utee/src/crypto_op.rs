@@ -1782,6 +1782,7 @@ pub enum AlgorithmId {
 }
 
 /// This specification defines support for optional cryptographic elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ElementId {
     /// Where algId fully defines the required support,
@@ -36,6 +36,7 @@ pub type Result<T> = result::Result<T, Error>;
 
 pub struct Error {
     code: u32,
+    origin: Option<ErrorOrigin>,
 }
 
 /// A list specifying general categories of TEE error and its corresponding code
@@ -152,7 +153,10 @@ impl ErrorKind {
 
 impl Error {
     pub fn new(kind: ErrorKind) -> Error {
-        Error { code: kind as u32 }
+        Error {
+            code: kind as u32,
+            origin: None,
+        }
     }
 
     /// Creates a new instance of an `Error` from a particular TEE error code.
@@ -166,7 +170,14 @@ impl Error {
     /// assert_eq!(error.kind(), optee_utee::ErrorKind::Security);
     /// ```
     pub fn from_raw_error(code: u32) -> Error {
-        Error { code }
+        Error { code, origin: None }
+    }
+
+    /// Records where the error was raised, as reported by the TA-to-TA
+    /// client API.
+    pub fn with_origin(mut self, origin: ErrorOrigin) -> Self {
+        self.origin = Some(origin);
+        self
     }
 
     /// Returns the corresponding `ErrorKind` for this error.
@@ -213,6 +224,11 @@ impl Error {
         }
     }
 
+    /// Returns the origin of this error, if known.
+    pub fn origin(&self) -> Option<ErrorOrigin> {
+        self.origin
+    }
+
     pub fn raw_code(&self) -> u32 {
         self.code
     }
@@ -224,7 +240,16 @@ impl Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "{} (error code 0x{:x})", self.message(), self.code)
+        match self.origin {
+            Some(origin) => write!(
+                fmt,
+                "{} (error code 0x{:x}, origin 0x{:x})",
+                self.message(),
+                self.code,
+                origin as u32
+            ),
+            None => write!(fmt, "{} (error code 0x{:x})", self.message(), self.code),
+        }
     }
 }
 
@@ -243,6 +268,34 @@ impl error::Error for Error {
 impl From<ErrorKind> for Error {
     #[inline]
     fn from(kind: ErrorKind) -> Error {
-        Error { code: kind as u32 }
+        Error::new(kind)
+    }
+}
+
+/// Where an error returned by a TA-to-TA call was raised.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ErrorOrigin {
+    /// The Internal Core API of the calling TA.
+    API = raw::TEE_ORIGIN_API,
+    /// The communication stack between the two TAs.
+    COMMS = raw::TEE_ORIGIN_COMMS,
+    /// The Trusted OS, outside of the called TA.
+    TEE = raw::TEE_ORIGIN_TEE,
+    /// The called TA.
+    TA = raw::TEE_ORIGIN_TRUSTED_APP,
+    /// An origin not defined by the specification.
+    UNKNOWN = 0,
+}
+
+impl From<u32> for ErrorOrigin {
+    fn from(origin: u32) -> Self {
+        match origin {
+            raw::TEE_ORIGIN_API => ErrorOrigin::API,
+            raw::TEE_ORIGIN_COMMS => ErrorOrigin::COMMS,
+            raw::TEE_ORIGIN_TEE => ErrorOrigin::TEE,
+            raw::TEE_ORIGIN_TRUSTED_APP => ErrorOrigin::TA,
+            _ => ErrorOrigin::UNKNOWN,
+        }
     }
 }
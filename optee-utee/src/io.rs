@@ -0,0 +1,162 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! `Read`, `Write` and `Seek` for the data stream of a
+//! [PersistentObject](crate::PersistentObject).
+//!
+//! This module re-exports `std::io` in std TAs and
+//! [embedded-io](https://docs.rs/embedded-io/0.6) in no_std TAs. The traits
+//! have the same methods, but the embedded-io traits return the
+//! [Error](crate::Error) of the failed TEE call instead of a `std::io::Error`,
+//! and have no buffered readers or `read_to_end`:
+//!
+//! ```no_run
+//! use optee_utee::io::{Read, Seek, SeekFrom};
+//!
+//! fn magic(object: &mut PersistentObject) -> optee_utee::Result<[u8; 4]> {
+//!     let mut magic = [0u8; 4];
+//!     Seek::seek(object, SeekFrom::Start(0))?;
+//!     let len = Read::read(object, &mut magic)?;
+//!     if len < magic.len() {
+//!         return Err(Error::new(ErrorKind::BadFormat));
+//!     }
+//!     Ok(magic)
+//! }
+//! ```
+//!
+//! The inherent [read](crate::PersistentObject::read),
+//! [write](crate::PersistentObject::write) and
+//! [seek](crate::PersistentObject::seek) methods take precedence over the
+//! trait methods of the same name, so call those through the trait as above.
+
+use crate::{Error as TeeError, ErrorKind as TeeErrorKind, PersistentObject, Whence};
+use core::convert::TryFrom;
+
+#[cfg(target_os = "optee")]
+pub use std::io::*;
+#[cfg(not(target_os = "optee"))]
+pub use embedded_io::*;
+
+/// The error type of the trait methods: `std::io::Error` in std TAs, and the
+/// TEE error itself in no_std TAs.
+#[cfg(target_os = "optee")]
+type IoError = Error;
+#[cfg(not(target_os = "optee"))]
+type IoError = TeeError;
+
+fn kind(error: &TeeError) -> ErrorKind {
+    match error.kind() {
+        TeeErrorKind::ItemNotFound => ErrorKind::NotFound,
+        TeeErrorKind::AccessDenied | TeeErrorKind::AccessConflict => ErrorKind::PermissionDenied,
+        TeeErrorKind::BadParameters | TeeErrorKind::Overflow => ErrorKind::InvalidInput,
+        TeeErrorKind::CorruptObject | TeeErrorKind::CorruptObject2 | TeeErrorKind::BadFormat => {
+            ErrorKind::InvalidData
+        }
+        _ => ErrorKind::Other,
+    }
+}
+
+#[cfg(target_os = "optee")]
+impl From<TeeError> for Error {
+    fn from(error: TeeError) -> Error {
+        Error::new(kind(&error), error.kind().as_str())
+    }
+}
+
+#[cfg(not(target_os = "optee"))]
+impl Error for TeeError {
+    fn kind(&self) -> ErrorKind {
+        kind(self)
+    }
+}
+
+#[cfg(not(target_os = "optee"))]
+impl ErrorType for PersistentObject {
+    type Error = TeeError;
+}
+
+impl Read for PersistentObject {
+    fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, IoError> {
+        Ok(PersistentObject::read(self, buf)? as usize)
+    }
+}
+
+/// `TEE_WriteObjectData` writes the whole buffer or fails, so `write` never
+/// returns a short count, and there is nothing to flush.
+impl Write for PersistentObject {
+    fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, IoError> {
+        PersistentObject::write(self, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> core::result::Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// Offsets must fit in an `i32`, the offset type of `TEE_SeekObjectData`,
+/// or the seek fails with `BadParameters`. Unlike `std::fs::File`, seeking
+/// before the start of the data stream moves the position to 0 instead of
+/// failing.
+impl Seek for PersistentObject {
+    fn seek(&mut self, pos: SeekFrom) -> core::result::Result<u64, IoError> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (i32::try_from(offset).ok(), Whence::DataSeekSet),
+            SeekFrom::End(offset) => (i32::try_from(offset).ok(), Whence::DataSeekEnd),
+            SeekFrom::Current(offset) => (i32::try_from(offset).ok(), Whence::DataSeekCur),
+        };
+        let offset = offset.ok_or_else(|| TeeError::new(TeeErrorKind::BadParameters))?;
+        PersistentObject::seek(self, offset, whence)?;
+        Ok(self.info()?.data_position() as u64)
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{DataFlag, ObjectStorageConstants::Private};
+    #[cfg(not(target_os = "optee"))]
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_persistent_object_io() {
+        let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE;
+        let mut object = PersistentObject::create(Private, b"io", flags, None, b"").unwrap();
+        let record: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        Write::write_all(&mut object, &record).unwrap();
+        assert_eq!(Seek::seek(&mut object, SeekFrom::Current(-10)).unwrap(), 990);
+        assert_eq!(Seek::seek(&mut object, SeekFrom::End(-1)).unwrap(), 999);
+        let err = Seek::seek(&mut object, SeekFrom::Start(1 << 32)).unwrap_err();
+        assert_eq!(Error::kind(&err), ErrorKind::InvalidInput);
+
+        Seek::rewind(&mut object).unwrap();
+        let mut header = [0u8; 4];
+        Read::read_exact(&mut object, &mut header).unwrap();
+        assert_eq!(header, [0, 1, 2, 3]);
+        assert_eq!(Seek::stream_position(&mut object).unwrap(), 4);
+        let mut rest = vec![0u8; 996];
+        Read::read_exact(&mut object, &mut rest).unwrap();
+        assert_eq!(rest, &record[4..]);
+        let mut byte = [0u8; 1];
+        assert_eq!(Read::read(&mut object, &mut byte).unwrap(), 0);
+        let err = Read::read_exact(&mut object, &mut byte).unwrap_err();
+        assert!(matches!(err, ReadExactError::UnexpectedEof));
+
+        let err = PersistentObject::open(Private, b"none", flags).err().unwrap();
+        assert_eq!(Error::kind(&err), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,928 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Typed keys built from their standard encodings.
+//!
+//! Each type wraps a [TransientObject](crate::TransientObject) of the matching
+//! [TransientObjectType](crate::TransientObjectType) and size, populated and
+//! restricted to the usages that make sense for the key:
+//!
+//! | Key | Built from | Usage |
+//! |-----|------------|-------|
+//! | [RsaPublicKey](RsaPublicKey) | modulus/exponent, PKCS#1 or SPKI DER/PEM | `VERIFY`, `ENCRYPT` |
+//! | [EcdsaPublicKey](EcdsaPublicKey) | uncompressed SEC1 point, SPKI DER/PEM | `VERIFY` |
+//! | [Ed25519PublicKey](Ed25519PublicKey) | 32 raw bytes, SPKI DER/PEM | `VERIFY` |
+//! | [X25519PublicKey](X25519PublicKey) | 32 raw bytes, SPKI DER/PEM | `DERIVE` |
+//! | [AesKey](AesKey) | 16, 24 or 32 raw bytes | `ENCRYPT`, `DECRYPT`, `MAC` |
+//! | [HmacKey](HmacKey) | raw bytes | `MAC` |
+//!
+//! Keys implement [ObjHandle](crate::ObjHandle), so they can be passed to
+//! `set_key` directly, and `into_object` returns the underlying object.
+//! Public keys can be exported back to the encodings they are built from.
+//! Secret keys are not `EXTRACTABLE`, so their value cannot be read back.
+//!
+//! # Examples
+//!
+//! ``` no_run
+//! use optee_utee::key::RsaPublicKey;
+//! use optee_utee::{AlgorithmId, Asymmetric, OperationMode};
+//!
+//! let key = RsaPublicKey::from_pem(ISSUER_KEY_PEM)?;
+//! let op = Asymmetric::allocate(
+//!     AlgorithmId::RsassaPkcs1V15Sha256,
+//!     OperationMode::Verify,
+//!     key.key_size()?,
+//! )?;
+//! op.set_key(&key)?;
+//! op.verify_digest(&[], &digest, &signature)?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use crate::{
+    Attribute, AttributeId, AttributeMemref, AttributeValue, ElementId, Error, ErrorKind,
+    ObjHandle, Result, TransientObject, TransientObjectType, UsageFlag,
+};
+use optee_utee_sys as raw;
+#[cfg(not(target_os = "optee"))]
+use alloc::{string::String, vec::Vec};
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const NULL: u8 = 0x05;
+const OID: u8 = 0x06;
+
+// Contents of the DER encoded object identifiers of the key algorithms.
+const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+const X25519: &[u8] = &[0x2b, 0x65, 0x6e];
+
+const SPKI_LABEL: &str = "PUBLIC KEY";
+const PKCS1_LABEL: &str = "RSA PUBLIC KEY";
+
+/// Size in bytes of Ed25519 and X25519 public keys.
+pub const CURVE25519_KEY_LEN: usize = 32;
+
+struct Curve {
+    element: ElementId,
+    bits: usize,
+    oid: &'static [u8],
+}
+
+static CURVES: [Curve; 5] = [
+    Curve {
+        element: ElementId::EccCurveNistP192,
+        bits: 192,
+        oid: &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x01],
+    },
+    Curve {
+        element: ElementId::EccCurveNistP224,
+        bits: 224,
+        oid: &[0x2b, 0x81, 0x04, 0x00, 0x21],
+    },
+    Curve {
+        element: ElementId::EccCurveNistP256,
+        bits: 256,
+        oid: &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07],
+    },
+    Curve {
+        element: ElementId::EccCurveNistP384,
+        bits: 384,
+        oid: &[0x2b, 0x81, 0x04, 0x00, 0x22],
+    },
+    Curve {
+        element: ElementId::EccCurveNistP521,
+        bits: 521,
+        oid: &[0x2b, 0x81, 0x04, 0x00, 0x23],
+    },
+];
+
+impl Curve {
+    fn find(matches: impl Fn(&Curve) -> bool) -> Result<&'static Curve> {
+        CURVES
+            .iter()
+            .find(|curve| matches(curve))
+            .ok_or_else(|| Error::new(ErrorKind::NotSupported))
+    }
+
+    /// Size in bytes of one coordinate of a point on the curve.
+    fn coordinate_len(&self) -> usize {
+        self.bits.div_ceil(8)
+    }
+}
+
+macro_rules! key_type {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        pub struct $name(TransientObject);
+
+        impl $name {
+            /// Return the size of the key in bits, as needed to allocate an operation.
+            pub fn key_size(&self) -> Result<usize> {
+                Ok(self.0.info()?.object_size())
+            }
+
+            /// Return the underlying [TransientObject](crate::TransientObject).
+            pub fn into_object(self) -> TransientObject {
+                self.0
+            }
+        }
+
+        impl ObjHandle for $name {
+            fn handle(&self) -> raw::TEE_ObjectHandle {
+                self.0.handle()
+            }
+        }
+
+        impl From<$name> for TransientObject {
+            fn from(key: $name) -> Self {
+                key.0
+            }
+        }
+    };
+}
+
+key_type! {
+    /// An RSA public key, for verifying signatures and encrypting.
+    RsaPublicKey
+}
+
+key_type! {
+    /// An ECDSA public key on one of the NIST curves, for verifying signatures.
+    EcdsaPublicKey
+}
+
+key_type! {
+    /// An Ed25519 public key, for verifying signatures.
+    Ed25519PublicKey
+}
+
+key_type! {
+    /// An X25519 public key, the peer key of a key agreement.
+    X25519PublicKey
+}
+
+key_type! {
+    /// An AES key, for ciphers, authenticated encryption and CMAC.
+    AesKey
+}
+
+key_type! {
+    /// An HMAC key.
+    HmacKey
+}
+
+impl RsaPublicKey {
+    /// Build a key from its big-endian modulus and public exponent.
+    ///
+    /// Leading zero bytes are ignored, and the key size is the size of the
+    /// modulus rounded up to a whole number of bytes.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If the modulus or the exponent is zero.
+    /// 2) `NotSupported`: If the key size is not supported.
+    pub fn from_components(modulus: &[u8], exponent: &[u8]) -> Result<Self> {
+        let modulus = strip_zeros(modulus);
+        let exponent = strip_zeros(exponent);
+        if modulus.is_empty() || exponent.is_empty() {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let attrs = [
+            AttributeMemref::from_ref(AttributeId::RsaModulus, modulus).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPublicExponent, exponent).into(),
+        ];
+        let usage = UsageFlag::VERIFY | UsageFlag::ENCRYPT;
+        let object = new_key(TransientObjectType::RsaPublicKey, modulus.len() * 8, &attrs, usage)?;
+        Ok(Self(object))
+    }
+
+    /// Build a key from a DER encoded PKCS#1 `RSAPublicKey` or
+    /// `SubjectPublicKeyInfo`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not an RSA public key in either format.
+    /// 2) Any error of [from_components](Self::from_components).
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let pkcs1 = match DerReader(single(der, SEQUENCE)?).peek_tag() {
+            Some(SEQUENCE) => spki_key(der, RSA_ENCRYPTION)?,
+            _ => der,
+        };
+        let mut key = DerReader(single(pkcs1, SEQUENCE)?);
+        let modulus = key.read_unsigned()?;
+        let exponent = key.read_unsigned()?;
+        key.finish()?;
+        Self::from_components(modulus, exponent)
+    }
+
+    /// Build a key from a PEM encoded `RSA PUBLIC KEY` or `PUBLIC KEY`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `pem` is not an RSA public key in either format.
+    /// 2) Any error of [from_components](Self::from_components).
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let (label, der) = parse_pem(pem)?;
+        if label != SPKI_LABEL && label != PKCS1_LABEL {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        Self::from_der(&der)
+    }
+
+    /// Return the big-endian modulus, without leading zero bytes.
+    pub fn modulus(&self) -> Result<Vec<u8>> {
+        read_attribute(&self.0, AttributeId::RsaModulus, self.key_size()?.div_ceil(8))
+    }
+
+    /// Return the big-endian public exponent, without leading zero bytes.
+    pub fn public_exponent(&self) -> Result<Vec<u8>> {
+        read_attribute(&self.0, AttributeId::RsaPublicExponent, self.key_size()?.div_ceil(8))
+    }
+
+    /// Return the key as a DER encoded PKCS#1 `RSAPublicKey`.
+    pub fn to_pkcs1_der(&self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        write_unsigned(&self.modulus()?, &mut body);
+        write_unsigned(&self.public_exponent()?, &mut body);
+        let mut der = Vec::new();
+        write_tlv(SEQUENCE, &body, &mut der);
+        Ok(der)
+    }
+
+    /// Return the key as a DER encoded `SubjectPublicKeyInfo`.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        Ok(write_spki(RSA_ENCRYPTION, Some((NULL, &[])), &self.to_pkcs1_der()?))
+    }
+
+    /// Return the key as a PEM encoded `PUBLIC KEY`.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(write_pem(SPKI_LABEL, &self.to_der()?))
+    }
+}
+
+impl EcdsaPublicKey {
+    /// Build a key from a point on `curve` in the uncompressed SEC1 encoding,
+    /// `0x04 || X || Y`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `point` is not an uncompressed point of the
+    ///    size of `curve`.
+    /// 2) `NotSupported`: If `curve` is not a NIST curve, if `point` is
+    ///    compressed, or if the implementation does not support the curve.
+    pub fn from_sec1(curve: ElementId, point: &[u8]) -> Result<Self> {
+        Self::with_curve(Curve::find(|known| known.element == curve)?, point)
+    }
+
+    /// Build a key from a DER encoded `SubjectPublicKeyInfo` with named curve
+    /// parameters.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not an EC public key.
+    /// 2) Any error of [from_sec1](Self::from_sec1).
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (oid, params, point) = parse_spki(der)?;
+        match params {
+            Some((OID, curve)) if oid == EC_PUBLIC_KEY => {
+                Self::with_curve(Curve::find(|known| known.oid == curve)?, point)
+            }
+            _ => Err(Error::new(ErrorKind::BadFormat)),
+        }
+    }
+
+    /// Build a key from a PEM encoded `PUBLIC KEY`.
+    ///
+    /// # Errors
+    ///
+    /// Any error of [from_der](Self::from_der).
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&spki_pem(pem)?)
+    }
+
+    fn with_curve(curve: &Curve, point: &[u8]) -> Result<Self> {
+        let len = curve.coordinate_len();
+        let (x, y) = match point {
+            [0x04, xy @ ..] if xy.len() == 2 * len => xy.split_at(len),
+            [0x02, ..] | [0x03, ..] => return Err(Error::new(ErrorKind::NotSupported)),
+            _ => return Err(Error::new(ErrorKind::BadParameters)),
+        };
+        let attrs = [
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+            AttributeValue::from_value(AttributeId::EccCurve, curve.element as u32, 0).into(),
+        ];
+        let object =
+            new_key(TransientObjectType::EcdsaPublicKey, curve.bits, &attrs, UsageFlag::VERIFY)?;
+        Ok(Self(object))
+    }
+
+    fn curve_info(&self) -> Result<&'static Curve> {
+        let (id, _) = self.0.value_attribute(AttributeId::EccCurve as u32)?;
+        Curve::find(|known| known.element as u32 == id)
+    }
+
+    /// Return the curve of the key.
+    pub fn curve(&self) -> Result<ElementId> {
+        Ok(self.curve_info()?.element)
+    }
+
+    /// Return the key as an uncompressed SEC1 point, `0x04 || X || Y`.
+    pub fn to_sec1(&self) -> Result<Vec<u8>> {
+        let len = self.curve_info()?.coordinate_len();
+        let x = read_attribute(&self.0, AttributeId::EccPublicValueX, len)?;
+        let y = read_attribute(&self.0, AttributeId::EccPublicValueY, len)?;
+        let mut point = vec![0u8; 1 + 2 * len];
+        point[0] = 0x04;
+        point[1 + len - x.len()..1 + len].copy_from_slice(&x);
+        point[1 + 2 * len - y.len()..].copy_from_slice(&y);
+        Ok(point)
+    }
+
+    /// Return the key as a DER encoded `SubjectPublicKeyInfo`.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        let curve = self.curve_info()?;
+        Ok(write_spki(EC_PUBLIC_KEY, Some((OID, curve.oid)), &self.to_sec1()?))
+    }
+
+    /// Return the key as a PEM encoded `PUBLIC KEY`.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(write_pem(SPKI_LABEL, &self.to_der()?))
+    }
+}
+
+impl Ed25519PublicKey {
+    /// Build a key from its 32 byte encoding.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `bytes` is not 32 bytes long.
+    /// 2) `NotSupported`: If the implementation does not support Ed25519.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let object = curve25519_key(
+            TransientObjectType::Ed25519PublicKey,
+            AttributeId::Ed25519PublicValue,
+            bytes,
+            UsageFlag::VERIFY,
+        )?;
+        Ok(Self(object))
+    }
+
+    /// Build a key from a DER encoded `SubjectPublicKeyInfo`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not an Ed25519 public key.
+    /// 2) Any error of [from_bytes](Self::from_bytes).
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        Self::from_bytes(spki_key(der, ED25519)?)
+    }
+
+    /// Build a key from a PEM encoded `PUBLIC KEY`.
+    ///
+    /// # Errors
+    ///
+    /// Any error of [from_der](Self::from_der).
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&spki_pem(pem)?)
+    }
+
+    /// Return the 32 byte encoding of the key.
+    pub fn to_bytes(&self) -> Result<[u8; CURVE25519_KEY_LEN]> {
+        curve25519_bytes(&self.0, AttributeId::Ed25519PublicValue)
+    }
+
+    /// Return the key as a DER encoded `SubjectPublicKeyInfo`.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        Ok(write_spki(ED25519, None, &self.to_bytes()?))
+    }
+
+    /// Return the key as a PEM encoded `PUBLIC KEY`.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(write_pem(SPKI_LABEL, &self.to_der()?))
+    }
+}
+
+impl X25519PublicKey {
+    /// Build a key from its 32 byte encoding.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `bytes` is not 32 bytes long.
+    /// 2) `NotSupported`: If the implementation does not support X25519.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let object = curve25519_key(
+            TransientObjectType::X25519PublicKey,
+            AttributeId::X25519PublicValue,
+            bytes,
+            UsageFlag::DERIVE,
+        )?;
+        Ok(Self(object))
+    }
+
+    /// Build a key from a DER encoded `SubjectPublicKeyInfo`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not an X25519 public key.
+    /// 2) Any error of [from_bytes](Self::from_bytes).
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        Self::from_bytes(spki_key(der, X25519)?)
+    }
+
+    /// Build a key from a PEM encoded `PUBLIC KEY`.
+    ///
+    /// # Errors
+    ///
+    /// Any error of [from_der](Self::from_der).
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&spki_pem(pem)?)
+    }
+
+    /// Return the 32 byte encoding of the key.
+    pub fn to_bytes(&self) -> Result<[u8; CURVE25519_KEY_LEN]> {
+        curve25519_bytes(&self.0, AttributeId::X25519PublicValue)
+    }
+
+    /// Return the key as a DER encoded `SubjectPublicKeyInfo`.
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        Ok(write_spki(X25519, None, &self.to_bytes()?))
+    }
+
+    /// Return the key as a PEM encoded `PUBLIC KEY`.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(write_pem(SPKI_LABEL, &self.to_der()?))
+    }
+}
+
+impl AesKey {
+    /// Build a key from its raw value.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `key` is not 16, 24 or 32 bytes long.
+    pub fn from_bytes(key: &[u8]) -> Result<Self> {
+        if ![16, 24, 32].contains(&key.len()) {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let attrs = [AttributeMemref::from_ref(AttributeId::SecretValue, key).into()];
+        let usage = UsageFlag::ENCRYPT | UsageFlag::DECRYPT | UsageFlag::MAC;
+        let object = new_key(TransientObjectType::Aes, key.len() * 8, &attrs, usage)?;
+        Ok(Self(object))
+    }
+}
+
+impl HmacKey {
+    /// Build a key of `object_type`, one of the `Hmac*` types of
+    /// [TransientObjectType](crate::TransientObjectType), from its raw value.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `object_type` is not an HMAC type or `key` is
+    ///    empty.
+    /// 2) `NotSupported`: If the key size is not supported for `object_type`.
+    pub fn from_bytes(object_type: TransientObjectType, key: &[u8]) -> Result<Self> {
+        match object_type {
+            TransientObjectType::HmacMd5
+            | TransientObjectType::HmacSha1
+            | TransientObjectType::HmacSha224
+            | TransientObjectType::HmacSha256
+            | TransientObjectType::HmacSha384
+            | TransientObjectType::HmacSha512
+                if !key.is_empty() => {}
+            _ => return Err(Error::new(ErrorKind::BadParameters)),
+        }
+        let attrs = [AttributeMemref::from_ref(AttributeId::SecretValue, key).into()];
+        let object = new_key(object_type, key.len() * 8, &attrs, UsageFlag::MAC)?;
+        Ok(Self(object))
+    }
+}
+
+fn new_key(
+    object_type: TransientObjectType,
+    key_size: usize,
+    attrs: &[Attribute],
+    usage: UsageFlag,
+) -> Result<TransientObject> {
+    let mut object = TransientObject::allocate(object_type, key_size)?;
+    object.populate(attrs)?;
+    object.restrict_usage(usage)?;
+    Ok(object)
+}
+
+fn curve25519_key(
+    object_type: TransientObjectType,
+    id: AttributeId,
+    bytes: &[u8],
+    usage: UsageFlag,
+) -> Result<TransientObject> {
+    if bytes.len() != CURVE25519_KEY_LEN {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let attrs = [AttributeMemref::from_ref(id, bytes).into()];
+    new_key(object_type, CURVE25519_KEY_LEN * 8, &attrs, usage)
+}
+
+fn curve25519_bytes(
+    object: &TransientObject,
+    id: AttributeId,
+) -> Result<[u8; CURVE25519_KEY_LEN]> {
+    let mut bytes = [0u8; CURVE25519_KEY_LEN];
+    match object.ref_attribute(id, &mut bytes)? {
+        CURVE25519_KEY_LEN => Ok(bytes),
+        _ => Err(Error::new(ErrorKind::BadFormat)),
+    }
+}
+
+fn read_attribute(object: &TransientObject, id: AttributeId, max_len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; max_len];
+    let len = object.ref_attribute(id, &mut buffer)?;
+    buffer.truncate(len);
+    Ok(buffer)
+}
+
+fn strip_zeros(number: &[u8]) -> &[u8] {
+    let start = number.iter().position(|byte| *byte != 0).unwrap_or(number.len());
+    &number[start..]
+}
+
+fn bad_format() -> Error {
+    Error::new(ErrorKind::BadFormat)
+}
+
+/// Reads DER values one after the other.
+struct DerReader<'a>(&'a [u8]);
+
+impl<'a> DerReader<'a> {
+    fn peek_tag(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    /// Read the next value and return its tag and contents.
+    fn read_any(&mut self) -> Result<(u8, &'a [u8])> {
+        let (&tag, rest) = self.0.split_first().ok_or_else(bad_format)?;
+        let (&first, mut rest) = rest.split_first().ok_or_else(bad_format)?;
+        let len = match first {
+            0..=0x7f => first as usize,
+            0x81..=0x83 => {
+                let count = (first & 0x7f) as usize;
+                if rest.len() < count {
+                    return Err(bad_format());
+                }
+                let (bytes, tail) = rest.split_at(count);
+                rest = tail;
+                bytes.iter().fold(0, |len, byte| len << 8 | *byte as usize)
+            }
+            _ => return Err(bad_format()),
+        };
+        if rest.len() < len {
+            return Err(bad_format());
+        }
+        let (contents, tail) = rest.split_at(len);
+        self.0 = tail;
+        Ok((tag, contents))
+    }
+
+    fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        match self.read_any()? {
+            (found, contents) if found == tag => Ok(contents),
+            _ => Err(bad_format()),
+        }
+    }
+
+    /// Read a non-negative INTEGER and return it without leading zero bytes.
+    fn read_unsigned(&mut self) -> Result<&'a [u8]> {
+        match self.read(INTEGER)? {
+            contents @ [first, ..] if first & 0x80 == 0 => Ok(strip_zeros(contents)),
+            _ => Err(bad_format()),
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        if !self.0.is_empty() {
+            return Err(bad_format());
+        }
+        Ok(())
+    }
+}
+
+/// Return the contents of `der`, which must be a single value with `tag`.
+fn single(der: &[u8], tag: u8) -> Result<&[u8]> {
+    let mut reader = DerReader(der);
+    let contents = reader.read(tag)?;
+    reader.finish()?;
+    Ok(contents)
+}
+
+/// The algorithm identifier, the tag and contents of the algorithm parameters
+/// if any, and the public key of a `SubjectPublicKeyInfo`.
+type SpkiParts<'a> = (&'a [u8], Option<(u8, &'a [u8])>, &'a [u8]);
+
+/// Split a `SubjectPublicKeyInfo` into its parts.
+fn parse_spki(der: &[u8]) -> Result<SpkiParts<'_>> {
+    let mut spki = DerReader(single(der, SEQUENCE)?);
+    let mut algorithm = DerReader(spki.read(SEQUENCE)?);
+    let oid = algorithm.read(OID)?;
+    let params = match algorithm.peek_tag() {
+        Some(_) => Some(algorithm.read_any()?),
+        None => None,
+    };
+    algorithm.finish()?;
+    let key = match spki.read(BIT_STRING)? {
+        [0, key @ ..] => key,
+        _ => return Err(bad_format()),
+    };
+    spki.finish()?;
+    Ok((oid, params, key))
+}
+
+/// Return the public key of a `SubjectPublicKeyInfo` of algorithm `oid`.
+fn spki_key<'a>(der: &'a [u8], oid: &[u8]) -> Result<&'a [u8]> {
+    match parse_spki(der)? {
+        (found, _, key) if found == oid => Ok(key),
+        _ => Err(bad_format()),
+    }
+}
+
+fn spki_pem(pem: &str) -> Result<Vec<u8>> {
+    match parse_pem(pem)? {
+        (SPKI_LABEL, der) => Ok(der),
+        _ => Err(bad_format()),
+    }
+}
+
+fn write_tlv(tag: u8, contents: &[u8], out: &mut Vec<u8>) {
+    let len = contents.len();
+    out.push(tag);
+    match len {
+        0..=0x7f => out.push(len as u8),
+        0x80..=0xff => out.extend_from_slice(&[0x81, len as u8]),
+        0x100..=0xffff => out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+        _ => out.extend_from_slice(&[0x83, (len >> 16) as u8, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(contents);
+}
+
+/// Write a big-endian number as a non-negative INTEGER.
+fn write_unsigned(number: &[u8], out: &mut Vec<u8>) {
+    let number = strip_zeros(number);
+    let mut contents = Vec::with_capacity(number.len() + 1);
+    if !matches!(number.first(), Some(first) if first & 0x80 == 0) {
+        contents.push(0);
+    }
+    contents.extend_from_slice(number);
+    write_tlv(INTEGER, &contents, out);
+}
+
+fn write_spki(oid: &[u8], params: Option<(u8, &[u8])>, key: &[u8]) -> Vec<u8> {
+    let mut algorithm = Vec::new();
+    write_tlv(OID, oid, &mut algorithm);
+    if let Some((tag, contents)) = params {
+        write_tlv(tag, contents, &mut algorithm);
+    }
+    let mut bits = Vec::with_capacity(key.len() + 1);
+    bits.push(0);
+    bits.extend_from_slice(key);
+
+    let mut body = Vec::new();
+    write_tlv(SEQUENCE, &algorithm, &mut body);
+    write_tlv(BIT_STRING, &bits, &mut body);
+    let mut der = Vec::new();
+    write_tlv(SEQUENCE, &body, &mut der);
+    der
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Return the label and the decoded contents of a PEM document.
+fn parse_pem(pem: &str) -> Result<(&str, Vec<u8>)> {
+    let rest = pem.trim().strip_prefix("-----BEGIN ").ok_or_else(bad_format)?;
+    let (label, rest) = rest.split_once("-----").ok_or_else(bad_format)?;
+    let (body, end) = rest.rsplit_once("-----END ").ok_or_else(bad_format)?;
+    if end.strip_suffix("-----") != Some(label) {
+        return Err(bad_format());
+    }
+
+    let mut data = Vec::with_capacity(body.len() / 4 * 3);
+    let (mut acc, mut bits, mut count, mut padding) = (0u32, 0, 0, 0);
+    for c in body.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' if padding < 2 => {
+                padding += 1;
+                continue;
+            }
+            _ => return Err(bad_format()),
+        };
+        if padding > 0 {
+            return Err(bad_format());
+        }
+        count += 1;
+        acc = (acc << 6 | value as u32) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+        }
+    }
+    if (count + padding) % 4 != 0 {
+        return Err(bad_format());
+    }
+    Ok((label, data))
+}
+
+fn write_pem(label: &str, der: &[u8]) -> String {
+    let mut encoded = Vec::with_capacity(der.len().div_ceil(3) * 4);
+    for chunk in der.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i)) as usize & 0x3f]);
+            } else {
+                encoded.push(b'=');
+            }
+        }
+    }
+
+    let mut pem = String::new();
+    pem.push_str("-----BEGIN ");
+    pem.push_str(label);
+    pem.push_str("-----\n");
+    for line in encoded.chunks(64) {
+        pem.extend(line.iter().map(|c| *c as char));
+        pem.push('\n');
+    }
+    pem.push_str("-----END ");
+    pem.push_str(label);
+    pem.push_str("-----\n");
+    pem
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+
+    const RSA_SPKI_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MFwwDQYJKoZIhvcNAQEBBQADSwAwSAJBAOUNloNuMwB7++q7oStrcKp5jV0en1/3
+Ep87SBZRItEnJnHlZqJMwy4XM9m6NehyYnwDN2tDHs+YxVHpMShfxB8CAwEAAQ==
+-----END PUBLIC KEY-----
+";
+    const RSA_PKCS1_PEM: &str = "-----BEGIN RSA PUBLIC KEY-----
+MEgCQQDlDZaDbjMAe/vqu6Era3CqeY1dHp9f9xKfO0gWUSLRJyZx5WaiTMMuFzPZ
+ujXocmJ8AzdrQx7PmMVR6TEoX8QfAgMBAAE=
+-----END RSA PUBLIC KEY-----
+";
+    const RSA_MODULUS: &str = "e50d96836e33007bfbeabba12b6b70aa798d5d1e9f5ff7129f3b48165122d127\
+                               2671e566a24cc32e1733d9ba35e872627c03376b431ecf98c551e931285fc41f";
+    const P256_SPKI: &str = "3059301306072a8648ce3d020106082a8648ce3d03010703420004c5e77778366d\
+                             0910294542ab318441ef610e4ec0aaee95b455adfb4688e42b8e4806070908689b\
+                             c75bea51791347c0e2fd8ab95d5562b633454d382612156249";
+    const ED25519_SPKI: &str =
+        "302a300506032b657003210045dfb5f050068ce5337994014b8614d9abf9042d09d2445160291711961e1197";
+    const X25519_SPKI: &str =
+        "302a300506032b656e0321008520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a";
+    // secp256k1, which is not a NIST curve
+    const SECP256K1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x0a];
+
+    #[test]
+    fn test_rsa_public_key() {
+        let key = RsaPublicKey::from_pem(RSA_SPKI_PEM).unwrap();
+        assert_eq!(key.key_size().unwrap(), 512);
+        assert_eq!(key.modulus().unwrap(), hex::decode(RSA_MODULUS).unwrap());
+        assert_eq!(key.public_exponent().unwrap(), [1, 0, 1]);
+        assert_eq!(key.to_pem().unwrap(), RSA_SPKI_PEM);
+        assert_eq!(write_pem(PKCS1_LABEL, &key.to_pkcs1_der().unwrap()), RSA_PKCS1_PEM);
+
+        let pkcs1 = RsaPublicKey::from_pem(RSA_PKCS1_PEM).unwrap();
+        assert_eq!(pkcs1.to_der().unwrap(), key.to_der().unwrap());
+        let modulus = [&[0u8][..], &key.modulus().unwrap()[..]].concat();
+        let key = RsaPublicKey::from_components(&modulus, &[3]).unwrap();
+        assert_eq!(key.key_size().unwrap(), 512);
+        assert_eq!(key.public_exponent().unwrap(), [3]);
+        let info = key.into_object().info().unwrap();
+        assert_eq!(info.object_type(), TransientObjectType::RsaPublicKey as u32);
+        assert_eq!(info.object_usage(), UsageFlag::VERIFY | UsageFlag::ENCRYPT);
+
+        let err = |result: Result<RsaPublicKey>| result.err().unwrap().kind();
+        assert_eq!(err(RsaPublicKey::from_components(&[0, 0], &[3])), ErrorKind::BadParameters);
+        let ed25519 = hex::decode(ED25519_SPKI).unwrap();
+        assert_eq!(err(RsaPublicKey::from_der(&ed25519)), ErrorKind::BadFormat);
+        let truncated = &RSA_SPKI_PEM[..RSA_SPKI_PEM.len() - 10];
+        assert_eq!(err(RsaPublicKey::from_pem(truncated)), ErrorKind::BadFormat);
+    }
+
+    #[test]
+    fn test_ecdsa_public_key() {
+        let der = hex::decode(P256_SPKI).unwrap();
+        let key = EcdsaPublicKey::from_der(&der).unwrap();
+        assert_eq!(key.key_size().unwrap(), 256);
+        assert_eq!(key.curve().unwrap(), ElementId::EccCurveNistP256);
+        assert_eq!(key.to_der().unwrap(), der);
+        let pem = key.to_pem().unwrap();
+        assert_eq!(EcdsaPublicKey::from_pem(&pem).unwrap().to_der().unwrap(), der);
+        let point = key.to_sec1().unwrap();
+        assert_eq!(point, der[der.len() - 65..]);
+        let key = EcdsaPublicKey::from_sec1(ElementId::EccCurveNistP256, &point).unwrap();
+        assert_eq!(key.to_der().unwrap(), der);
+        let info = key.into_object().info().unwrap();
+        assert_eq!(info.object_type(), TransientObjectType::EcdsaPublicKey as u32);
+        assert_eq!(info.object_usage(), UsageFlag::VERIFY);
+
+        let err = |result: Result<EcdsaPublicKey>| result.err().unwrap().kind();
+        let compressed = [&[0x02 | (point[64] & 1)][..], &point[1..33]].concat();
+        let spki = write_spki(EC_PUBLIC_KEY, Some((OID, CURVES[2].oid)), &compressed);
+        assert_eq!(err(EcdsaPublicKey::from_der(&spki)), ErrorKind::NotSupported);
+        let spki = write_spki(EC_PUBLIC_KEY, Some((OID, SECP256K1)), &point);
+        assert_eq!(err(EcdsaPublicKey::from_der(&spki)), ErrorKind::NotSupported);
+        let spki = write_spki(EC_PUBLIC_KEY, Some((OID, CURVES[3].oid)), &point);
+        assert_eq!(err(EcdsaPublicKey::from_der(&spki)), ErrorKind::BadParameters);
+        let spki = write_spki(EC_PUBLIC_KEY, Some((NULL, &[])), &point);
+        assert_eq!(err(EcdsaPublicKey::from_der(&spki)), ErrorKind::BadFormat);
+        let ed25519 = hex::decode(ED25519_SPKI).unwrap();
+        assert_eq!(err(EcdsaPublicKey::from_der(&ed25519)), ErrorKind::BadFormat);
+    }
+
+    #[test]
+    fn test_curve25519_public_keys() {
+        let der = hex::decode(ED25519_SPKI).unwrap();
+        let key = Ed25519PublicKey::from_der(&der).unwrap();
+        assert_eq!(key.key_size().unwrap(), 256);
+        assert_eq!(key.to_bytes().unwrap(), der[12..]);
+        assert_eq!(key.to_der().unwrap(), der);
+        let pem = key.to_pem().unwrap();
+        assert_eq!(Ed25519PublicKey::from_pem(&pem).unwrap().to_der().unwrap(), der);
+        assert_eq!(key.into_object().info().unwrap().object_usage(), UsageFlag::VERIFY);
+        let x25519 = hex::decode(X25519_SPKI).unwrap();
+        assert_eq!(Ed25519PublicKey::from_der(&x25519).err().unwrap().kind(), ErrorKind::BadFormat);
+
+        let key = X25519PublicKey::from_der(&x25519).unwrap();
+        assert_eq!(key.key_size().unwrap(), 256);
+        assert_eq!(key.to_bytes().unwrap(), x25519[12..]);
+        assert_eq!(key.to_der().unwrap(), x25519);
+        let pem = key.to_pem().unwrap();
+        assert_eq!(X25519PublicKey::from_pem(&pem).unwrap().to_der().unwrap(), x25519);
+        assert_eq!(key.into_object().info().unwrap().object_usage(), UsageFlag::DERIVE);
+        assert_eq!(X25519PublicKey::from_der(&der).err().unwrap().kind(), ErrorKind::BadFormat);
+        let err = X25519PublicKey::from_bytes(&x25519[13..]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_aes_and_hmac_keys() {
+        let key = AesKey::from_bytes(&[7u8; 24]).unwrap();
+        assert_eq!(key.key_size().unwrap(), 192);
+        let mut value = [0u8; 24];
+        let err = key.into_object().ref_attribute(AttributeId::SecretValue, &mut value);
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::AccessDenied);
+        assert_eq!(AesKey::from_bytes(&[7u8; 20]).err().unwrap().kind(), ErrorKind::BadParameters);
+
+        let key = HmacKey::from_bytes(TransientObjectType::HmacSha256, &[1u8; 32]).unwrap();
+        assert_eq!(key.key_size().unwrap(), 256);
+        let err = HmacKey::from_bytes(TransientObjectType::Aes, &[1u8; 32]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_spki_encoding() {
+        let der = hex::decode(P256_SPKI).unwrap();
+        let (oid, params, point) = parse_spki(&der).unwrap();
+        assert_eq!(oid, EC_PUBLIC_KEY);
+        let (tag, curve) = params.unwrap();
+        assert_eq!((tag, Curve::find(|known| known.oid == curve).unwrap().bits), (OID, 256));
+        assert_eq!(point.len(), 65);
+        assert_eq!(write_spki(oid, params, point), der);
+
+        let der = hex::decode(ED25519_SPKI).unwrap();
+        assert_eq!(spki_key(&der, ED25519).unwrap().len(), CURVE25519_KEY_LEN);
+        assert_eq!(write_spki(ED25519, None, spki_key(&der, ED25519).unwrap()), der);
+        assert!(spki_key(&der, X25519).is_err());
+        assert!(parse_spki(&der[..der.len() - 1]).is_err());
+        assert!(parse_spki(&[&der[..], &[0u8][..]].concat()).is_err());
+
+        let err = EcdsaPublicKey::from_sec1(ElementId::EccCurve25519, &[4; 65]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotSupported);
+    }
+}
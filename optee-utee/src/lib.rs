@@ -29,26 +29,33 @@ use libc_alloc::LibcAlloc;
 #[global_allocator]
 static ALLOCATOR: LibcAlloc = LibcAlloc;
 
-#[cfg(not(target_os = "optee"))]
-use core::panic::PanicInfo;
-#[cfg(not(target_os = "optee"))]
-use optee_utee_sys as raw;
+// The simulator runs on the host, with `std`.
+#[cfg(feature = "sim")]
+extern crate std;
 
-#[cfg(all(not(target_os = "optee"), not(feature = "no_panic_handler")))]
+#[cfg(all(
+    not(target_os = "optee"),
+    not(feature = "no_panic_handler"),
+    not(feature = "sim")
+))]
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    unsafe { raw::TEE_Panic(0); }
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { optee_utee_sys::TEE_Panic(0); }
     loop {}
 }
 
-pub use self::error::{Error, ErrorKind, Result};
+pub use self::error::{Error, ErrorKind, ErrorOrigin, Result};
 pub use self::object::*;
 pub use self::crypto_op::*;
 pub use self::time::*;
+pub use self::cancellation::*;
 pub use self::arithmetical::*;
 pub use self::extension::*;
 pub use self::uuid::*;
-pub use self::parameter::{ParamType, ParamTypes, Parameter, Parameters};
+pub use self::ta_session::{TaOperation, TaSession};
+pub use self::parameter::{
+    MemrefElement, ParamMemref, ParamType, ParamTypes, ParamValue, Parameter, Parameters,
+};
 pub use optee_utee_macros::{
     ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session,
 };
@@ -59,9 +66,20 @@ mod macros;
 mod error;
 mod parameter;
 pub mod object;
+pub mod io;
+#[cfg(feature = "secure_kv")]
+pub mod secure_kv;
+pub mod seal;
+pub mod key;
 pub mod crypto_op;
+pub mod crypto;
 pub mod time;
+pub mod cancellation;
 pub mod arithmetical;
 pub mod extension;
 pub mod uuid;
+pub mod ta_session;
+pub mod property;
 pub mod net;
+#[cfg(feature = "sim")]
+pub mod sim;
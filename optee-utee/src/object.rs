@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{Error, Result};
+use crate::{Error, ErrorKind, Result};
 use bitflags::bitflags;
 use optee_utee_sys as raw;
 use core::{marker, mem, ptr};
@@ -188,10 +188,25 @@ impl ObjectInfo {
         self.raw.dataSize as usize
     }
 
+    /// Return the `objectType` field of the raw structure `TEE_ObjectInfo`.
+    pub fn object_type(&self) -> u32 {
+        self.raw.objectType
+    }
+
+    /// Return the `objectUsage` field of the raw structure `TEE_ObjectInfo`.
+    pub fn object_usage(&self) -> UsageFlag {
+        UsageFlag::from_bits_truncate(self.raw.objectUsage)
+    }
+
     /// Return the `objectSize` field of the raw structure `TEE_ObjectInfo`.
     pub fn object_size(&self) -> usize {
         self.raw.objectSize as usize
     }
+
+    /// Return the `dataPosition` field of the raw structure `TEE_ObjectInfo`.
+    pub fn data_position(&self) -> usize {
+        self.raw.dataPosition
+    }
 }
 
 /// Indicate the possible start offset when moving a data position in the data stream associated with a [PersistentObject](PersistentObject).
@@ -279,9 +294,20 @@ impl ObjectHandle {
     }
 }
 
+/// The trusted storages a [PersistentObject](PersistentObject) can be kept in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ObjectStorageConstants {
+    /// The default private storage of the TA, one of the storages below
+    /// depending on the OP-TEE configuration.
     Private = 0x00000001,
+    /// Storage in the REE file system, encrypted and authenticated by the TEE
+    /// but open to rollback by the REE. OP-TEE extension.
+    PrivateRee = 0x80000000,
+    /// Storage in the Replay Protected Memory Block of an eMMC device, which
+    /// resists rollback. Only available if OP-TEE is built with
+    /// `CFG_RPMB_FS`. OP-TEE extension.
+    PrivateRpmb = 0x80000100,
     IllegalValue = 0x7FFFFFFF,
 }
 
@@ -1374,6 +1400,30 @@ impl PersistentObject {
             code => Err(Error::from_raw_error(code)),
         }
     }
+
+    /// List the [PersistentObject](PersistentObject)s in a storage with their identifier and
+    /// [ObjectInfo](ObjectInfo).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// for object in PersistentObject::list(ObjectStorageConstants::Private).with_prefix(b"key:") {
+    ///     let (id, info) = object?;
+    ///     trace_println!("{:?}: {} bytes", id, info.data_size());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The iterator yields an error and ends if the enumerator cannot be allocated, or if the
+    /// storage is currently inaccessible.
+    pub fn list(storage_id: ObjectStorageConstants) -> ObjectList {
+        ObjectList {
+            storage_id,
+            prefix: Vec::new(),
+            state: ObjectListState::NotStarted,
+        }
+    }
 }
 
 impl ObjHandle for PersistentObject {
@@ -1434,8 +1484,10 @@ impl ObjectEnumHandle {
     /// Start the enumeration of all the [PersistentObject](PersistentObject)s in a given Trusted Storage.
     /// The object information can be retrieved by calling the function
     /// [ObjectEnumHandle::get_next](ObjectEnumHandle::get_next) repeatedly.
-    pub fn start(&mut self, storage_id: u32) -> Result<()> {
-        match unsafe { raw::TEE_StartPersistentObjectEnumerator(*self.raw, storage_id) } {
+    ///
+    /// Returns `ItemNotFound` if the storage does not exist or holds no object.
+    pub fn start(&mut self, storage_id: ObjectStorageConstants) -> Result<()> {
+        match unsafe { raw::TEE_StartPersistentObjectEnumerator(*self.raw, storage_id as u32) } {
             raw::TEE_SUCCESS => Ok(()),
             code => Err(Error::from_raw_error(code)),
         }
@@ -1460,6 +1512,17 @@ impl ObjectEnumHandle {
             code => Err(Error::from_raw_error(code)),
         }
     }
+
+    /// Get the next object in an enumeration, or `None` once every object has been returned.
+    pub fn next_object(&mut self) -> Result<Option<(ObjectId, ObjectInfo)>> {
+        let mut info = ObjectInfo::from_raw(unsafe { mem::zeroed() });
+        let mut id = [0u8; MiscellaneousConstants::TeeObjectIdMaxLen as usize];
+        match self.get_next::<()>(&mut info, &mut id) {
+            Ok(len) => Ok(Some((id[..len as usize].to_vec(), info))),
+            Err(e) if e.kind() == ErrorKind::ItemNotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 impl Drop for ObjectEnumHandle {
@@ -1476,3 +1539,128 @@ impl Drop for ObjectEnumHandle {
         }
     }
 }
+
+/// The identifier of a [PersistentObject](PersistentObject), at most
+/// [MiscellaneousConstants::TeeObjectIdMaxLen](MiscellaneousConstants::TeeObjectIdMaxLen) bytes.
+pub type ObjectId = Vec<u8>;
+
+/// An iterator over the [PersistentObject](PersistentObject)s in a storage, returned by
+/// [PersistentObject::list](PersistentObject::list).
+///
+/// The enumeration starts on the first call to `next`. An empty or missing storage yields no
+/// object, and the iterator ends after the first error.
+pub struct ObjectList {
+    storage_id: ObjectStorageConstants,
+    prefix: Vec<u8>,
+    state: ObjectListState,
+}
+
+enum ObjectListState {
+    NotStarted,
+    Started(ObjectEnumHandle),
+    Done,
+}
+
+impl ObjectList {
+    /// Only yield the objects whose identifier starts with `prefix`.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = prefix.to_vec();
+        self
+    }
+
+    fn start(&self) -> Result<Option<ObjectEnumHandle>> {
+        let mut handle = ObjectEnumHandle::allocate()?;
+        match handle.start(self.storage_id) {
+            Ok(()) => Ok(Some(handle)),
+            Err(e) if e.kind() == ErrorKind::ItemNotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Iterator for ObjectList {
+    type Item = Result<(ObjectId, ObjectInfo)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let handle = match &mut self.state {
+                ObjectListState::Started(handle) => handle,
+                ObjectListState::Done => return None,
+                ObjectListState::NotStarted => {
+                    self.state = match self.start() {
+                        Ok(Some(handle)) => ObjectListState::Started(handle),
+                        Ok(None) => ObjectListState::Done,
+                        Err(e) => {
+                            self.state = ObjectListState::Done;
+                            return Some(Err(e));
+                        }
+                    };
+                    continue;
+                }
+            };
+            match handle.next_object() {
+                Ok(Some((id, info))) if id.starts_with(&self.prefix) => {
+                    return Some(Ok((id, info)))
+                }
+                Ok(Some(_)) => (),
+                Ok(None) => {
+                    self.state = ObjectListState::Done;
+                    return None;
+                }
+                Err(e) => {
+                    self.state = ObjectListState::Done;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use ObjectStorageConstants::{Private, PrivateRee, PrivateRpmb};
+
+    #[test]
+    fn test_list() {
+        assert_eq!(PersistentObject::list(Private).count(), 0);
+        for (id, len) in [(&b"key:a"[..], 1), (&b"counter:a"[..], 2), (&b"key:b"[..], 3)] {
+            let data = vec![0u8; len];
+            PersistentObject::create(Private, id, DataFlag::ACCESS_READ, None, &data).unwrap();
+        }
+        let objects: Vec<_> = PersistentObject::list(Private)
+            .with_prefix(b"key:")
+            .map(|object| object.map(|(id, info)| (id, info.data_size())))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(objects, [(b"key:a".to_vec(), 1), (b"key:b".to_vec(), 3)]);
+        assert_eq!(PersistentObject::list(Private).count(), 3);
+        assert_eq!(PersistentObject::list(PrivateRpmb).count(), 0);
+
+        crate::sim::set_rpmb_available(false);
+        assert_eq!(PersistentObject::list(PrivateRpmb).count(), 0);
+    }
+
+    #[test]
+    fn test_storages() {
+        PersistentObject::create(PrivateRpmb, b"id", DataFlag::ACCESS_READ, None, b"rpmb").unwrap();
+        assert!(PersistentObject::open(Private, b"id", DataFlag::ACCESS_READ).is_err());
+        PersistentObject::create(Private, b"id", DataFlag::ACCESS_READ, None, b"ree").unwrap();
+        let object = PersistentObject::open(PrivateRee, b"id", DataFlag::ACCESS_READ).unwrap();
+        assert_eq!(object.info().unwrap().data_size(), 3);
+
+        crate::sim::set_rpmb_available(false);
+        let err = PersistentObject::open(PrivateRpmb, b"id", DataFlag::ACCESS_READ).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+        let err = PersistentObject::create(PrivateRpmb, b"new", DataFlag::ACCESS_READ, None, b"")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+        let mut enumerator = ObjectEnumHandle::allocate().unwrap();
+        assert!(enumerator.start(PrivateRpmb).is_err());
+
+        crate::sim::set_rpmb_available(true);
+        let object = PersistentObject::open(PrivateRpmb, b"id", DataFlag::ACCESS_READ).unwrap();
+        assert_eq!(object.info().unwrap().data_size(), 4);
+    }
+}
@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use core::{marker, slice};
+use core::{marker, mem, slice};
 use crate::{Error, ErrorKind, Result};
 use optee_utee_sys as raw;
 
@@ -31,8 +31,67 @@ impl Parameters {
 
         Parameters(p0, p1, p2, p3)
     }
+
+    /// Returns the types the parameters were passed with.
+    pub fn param_types(&self) -> ParamTypes {
+        ParamTypes::new(
+            self.0.param_type,
+            self.1.param_type,
+            self.2.param_type,
+            self.3.param_type,
+        )
+    }
+
+    /// Checks that the parameters were passed with the `expected` types.
+    ///
+    /// Commands should call this before reading any parameter.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_utee::{ParamType, ParamTypes, Parameters, Result};
+    ///
+    /// fn sum(params: &mut Parameters) -> Result<()> {
+    ///     params.check_types(ParamTypes::new(
+    ///         ParamType::MemrefInput,
+    ///         ParamType::ValueOutput,
+    ///         ParamType::None,
+    ///         ParamType::None,
+    ///     ))?;
+    ///     let mut p0 = unsafe { params.0.as_memref()? };
+    ///     let sum = p0.as_slice::<u32>()?.iter().sum();
+    ///     unsafe { params.1.as_value()? }.set_a(sum);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: a parameter has a different type.
+    pub fn check_types(&self, expected: ParamTypes) -> Result<()> {
+        if self.param_types() != expected {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        Ok(())
+    }
 }
 
+/// An element type that a memref buffer can be viewed as.
+///
+/// # Safety
+///
+/// Any bit pattern of the right size must be a valid value of the type.
+pub unsafe trait MemrefElement: Copy {}
+
+unsafe impl MemrefElement for u8 {}
+unsafe impl MemrefElement for i8 {}
+unsafe impl MemrefElement for u16 {}
+unsafe impl MemrefElement for i16 {}
+unsafe impl MemrefElement for u32 {}
+unsafe impl MemrefElement for i32 {}
+unsafe impl MemrefElement for u64 {}
+unsafe impl MemrefElement for i64 {}
+
 pub struct ParamValue<'parameter> {
     raw: *mut raw::Value,
     param_type: ParamType,
@@ -89,6 +148,64 @@ impl<'parameter> ParamMemref<'parameter> {
     pub fn set_updated_size(&mut self, size: usize) {
         unsafe { (*self.raw).size = size};
     }
+
+    /// Returns the buffer as a slice of `T`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the buffer is not aligned for `T`, or its size is
+    ///    not a multiple of the size of `T`.
+    pub fn as_slice<T: MemrefElement>(&mut self) -> Result<&[T]> {
+        self.as_mut_slice().map(|slice| &*slice)
+    }
+
+    /// Returns the buffer as a mutable slice of `T`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the buffer is not aligned for `T`, or its size is
+    ///    not a multiple of the size of `T`.
+    pub fn as_mut_slice<T: MemrefElement>(&mut self) -> Result<&mut [T]> {
+        let (buffer, size) = unsafe { ((*self.raw).buffer, (*self.raw).size) };
+        if size == 0 {
+            return Ok(&mut []);
+        }
+        if !(buffer as *const T).is_aligned() || size % mem::size_of::<T>() != 0 {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        Ok(unsafe { slice::from_raw_parts_mut(buffer as *mut T, size / mem::size_of::<T>()) })
+    }
+
+    /// Reports `len` bytes of output and returns them to be filled in.
+    ///
+    /// The size is reported even when the buffer is too short, so the client
+    /// can retry with a large enough buffer.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the parameter is not an output memref.
+    /// 2) `ShortBuffer`: the buffer is shorter than `len`.
+    pub fn output(&mut self, len: usize) -> Result<&mut [u8]> {
+        if self.param_type == ParamType::MemrefInput {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let size = unsafe { (*self.raw).size };
+        self.set_updated_size(len);
+        if size < len {
+            return Err(Error::new(ErrorKind::ShortBuffer));
+        }
+        Ok(self.buffer())
+    }
+
+    /// Writes `data` to the buffer and reports its length as the output size.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`ParamMemref::output`].
+    pub fn write_output(&mut self, data: &[u8]) -> Result<()> {
+        self.output(data.len())?.copy_from_slice(data);
+        Ok(())
+    }
 }
 
 pub struct Parameter {
@@ -135,9 +252,14 @@ impl Parameter {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct ParamTypes(u32);
 
 impl ParamTypes {
+    pub fn new(p0: ParamType, p1: ParamType, p2: ParamType, p3: ParamType) -> Self {
+        ParamTypes((p0 as u32) | (p1 as u32) << 4 | (p2 as u32) << 8 | (p3 as u32) << 12)
+    }
+
     pub fn into_flags(&self) -> (ParamType, ParamType, ParamType, ParamType) {
         (
             (0x000fu32 & self.0).into(),
@@ -154,7 +276,13 @@ impl From<u32> for ParamTypes {
     }
 }
 
-#[derive(Copy, Clone)]
+impl From<ParamTypes> for u32 {
+    fn from(param_types: ParamTypes) -> u32 {
+        param_types.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ParamType {
     None = 0,
     ValueInput = 1,
@@ -179,3 +307,90 @@ impl From<u32> for ParamType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_memref(buffer: &mut [u8]) -> raw::TEE_Param {
+        raw::TEE_Param {
+            memref: raw::Memref {
+                buffer: buffer.as_mut_ptr() as _,
+                size: buffer.len(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_check_types() {
+        let mut raw_params = [raw::TEE_Param {
+            value: raw::Value { a: 0, b: 0 },
+        }; 4];
+        let expected = ParamTypes::new(
+            ParamType::MemrefInout,
+            ParamType::ValueOutput,
+            ParamType::None,
+            ParamType::None,
+        );
+        let params = Parameters::from_raw(&mut raw_params, expected.into());
+        assert!(params.check_types(expected).is_ok());
+        let other = ParamTypes::new(
+            ParamType::MemrefInput,
+            ParamType::ValueOutput,
+            ParamType::None,
+            ParamType::None,
+        );
+        assert_eq!(
+            params.check_types(other).err().map(|e| e.kind()),
+            Some(ErrorKind::BadParameters)
+        );
+    }
+
+    #[test]
+    fn test_memref_views() {
+        let mut words = [0u32; 4];
+        let bytes = unsafe { slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, 16) };
+        bytes[..4].copy_from_slice(&7i32.to_ne_bytes());
+
+        let mut raw_param = raw_memref(bytes);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefInout);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        assert_eq!(memref.as_slice::<i32>().unwrap(), &[7, 0, 0, 0]);
+
+        let mut raw_param = raw_memref(&mut bytes[1..5]);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefInout);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        assert!(memref.as_slice::<i32>().is_err());
+
+        let mut raw_param = raw_memref(&mut bytes[..6]);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefInout);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        assert!(memref.as_slice::<i32>().is_err());
+        assert_eq!(memref.as_slice::<u16>().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut buffer = [0u8; 4];
+        let mut raw_param = raw_memref(&mut buffer);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefOutput);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        assert_eq!(
+            memref.write_output(b"hello").err().map(|e| e.kind()),
+            Some(ErrorKind::ShortBuffer)
+        );
+        assert_eq!(unsafe { raw_param.memref.size }, 5);
+
+        let mut raw_param = raw_memref(&mut buffer);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefOutput);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        memref.write_output(b"ok").unwrap();
+        assert_eq!(unsafe { raw_param.memref.size }, 2);
+        assert_eq!(&buffer[..2], b"ok");
+
+        let mut raw_param = raw_memref(&mut buffer);
+        let mut p = Parameter::from_raw(&mut raw_param, ParamType::MemrefInput);
+        let mut memref = unsafe { p.as_memref().unwrap() };
+        assert!(memref.output(1).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Access to the properties of the TEE, the current TA and its client.
+//!
+//! Properties are read from one of three [`PropertySet`]s with typed
+//! getters, or enumerated with [`PropertySet::properties`].
+//!
+//! # Examples
+//!
+//! ``` no_run
+//! use optee_utee::property::{Login, PropertySet};
+//!
+//! let identity = PropertySet::CurrentClient.get_identity("gpd.client.identity")?;
+//! if identity.login == Login::TrustedApp {
+//!     // the client is the TA identified by identity.uuid
+//! }
+//! let version = PropertySet::TeeImplementation.get_string("gpd.tee.apiversion")?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use crate::{Error, ErrorKind, Result, Uuid};
+use alloc::ffi::CString;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::c_char;
+use core::ptr;
+use optee_utee_sys as raw;
+
+/// Initial buffer size for string and binary properties.
+const INITIAL_BUFFER_LEN: usize = 64;
+
+/// A set of properties.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PropertySet {
+    /// Properties of the TEE implementation, such as `gpd.tee.deviceID`.
+    TeeImplementation,
+    /// Properties of the client of the current session, such as
+    /// `gpd.client.identity`.
+    CurrentClient,
+    /// Properties of the current TA, such as `gpd.ta.appID` and the extended
+    /// properties set at build time.
+    CurrentTa,
+}
+
+impl PropertySet {
+    fn as_raw(self) -> raw::TEE_PropSetHandle {
+        match self {
+            PropertySet::TeeImplementation => raw::TEE_PROPSET_TEE_IMPLEMENTATION,
+            PropertySet::CurrentClient => raw::TEE_PROPSET_CURRENT_CLIENT,
+            PropertySet::CurrentTa => raw::TEE_PROPSET_CURRENT_TA,
+        }
+    }
+
+    /// Returns the boolean property `name`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `ItemNotFound`: the property does not exist.
+    /// 2) `BadFormat`: the property cannot be read as this type.
+    /// 3) `BadParameters`: `name` contains a NUL byte.
+    pub fn get_bool(self, name: &str) -> Result<bool> {
+        get_bool(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the `u32` property `name`.
+    pub fn get_u32(self, name: &str) -> Result<u32> {
+        get_u32(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the `u64` property `name`.
+    pub fn get_u64(self, name: &str) -> Result<u64> {
+        get_u64(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the property `name` as a string.
+    ///
+    /// Any property can be read as a string.
+    pub fn get_string(self, name: &str) -> Result<String> {
+        get_string(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the binary block property `name`.
+    pub fn get_binary(self, name: &str) -> Result<Vec<u8>> {
+        get_binary(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the UUID property `name`.
+    pub fn get_uuid(self, name: &str) -> Result<Uuid> {
+        get_uuid(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns the identity property `name`.
+    pub fn get_identity(self, name: &str) -> Result<Identity> {
+        get_identity(self.as_raw(), c_name(name)?.as_ptr())
+    }
+
+    /// Returns an iterator over the names and string values of the
+    /// properties in this set.
+    pub fn properties(self) -> Result<Properties> {
+        let mut enumerator = PropertyEnumerator::allocate()?;
+        enumerator.start(self);
+        Ok(Properties {
+            enumerator,
+            started: false,
+        })
+    }
+}
+
+/// The login method of a client.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Login {
+    /// No login data is provided.
+    Public,
+    /// Login data about the user running the client application.
+    User,
+    /// Login data about the group running the client application.
+    Group,
+    /// Login data about the client application itself.
+    Application,
+    /// Login data about the user and the client application.
+    ApplicationUser,
+    /// Login data about the group and the client application.
+    ApplicationGroup,
+    /// The client is another TA.
+    TrustedApp,
+    /// A login method not defined by the specification.
+    Unknown(u32),
+}
+
+impl From<u32> for Login {
+    fn from(login: u32) -> Self {
+        match login {
+            raw::TEE_LOGIN_PUBLIC => Login::Public,
+            raw::TEE_LOGIN_USER => Login::User,
+            raw::TEE_LOGIN_GROUP => Login::Group,
+            raw::TEE_LOGIN_APPLICATION => Login::Application,
+            raw::TEE_LOGIN_APPLICATION_USER => Login::ApplicationUser,
+            raw::TEE_LOGIN_APPLICATION_GROUP => Login::ApplicationGroup,
+            raw::TEE_LOGIN_TRUSTED_APP => Login::TrustedApp,
+            login => Login::Unknown(login),
+        }
+    }
+}
+
+/// The identity of a client: its login method and UUID.
+#[derive(Clone, Copy)]
+pub struct Identity {
+    /// How the client logged in.
+    pub login: Login,
+    /// UUID of the client TA for `Login::TrustedApp`. For the user, group and
+    /// application logins it is derived by the TEE from the login data, so
+    /// it is stable for a given user, group or application; it is nil for
+    /// `Login::Public`.
+    pub uuid: Uuid,
+}
+
+impl Identity {
+    /// Returns the identity of the client of the current session, read from
+    /// the `gpd.client.identity` property.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_utee::property::{Identity, Login};
+    /// use optee_utee::{Error, ErrorKind};
+    ///
+    /// let client = Identity::current_client()?;
+    /// if client.login != Login::Group {
+    ///     return Err(Error::new(ErrorKind::AccessDenied));
+    /// }
+    /// # Ok::<(), optee_utee::Error>(())
+    /// ```
+    pub fn current_client() -> Result<Self> {
+        PropertySet::CurrentClient.get_identity("gpd.client.identity")
+    }
+}
+
+/// An enumerator over the properties of a [`PropertySet`].
+///
+/// The typed getters read the property the enumerator points at.
+pub struct PropertyEnumerator {
+    raw: raw::TEE_PropSetHandle,
+}
+
+impl PropertyEnumerator {
+    /// Allocates a property enumerator.
+    pub fn allocate() -> Result<Self> {
+        let mut handle = ptr::null_mut();
+        match unsafe { raw::TEE_AllocatePropertyEnumerator(&mut handle) } {
+            raw::TEE_SUCCESS => Ok(Self { raw: handle }),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
+    /// Starts enumerating `set`, pointing at its first property.
+    pub fn start(&mut self, set: PropertySet) {
+        unsafe { raw::TEE_StartPropertyEnumerator(self.raw, set.as_raw()) }
+    }
+
+    /// Resets the enumerator to its state after allocation.
+    pub fn reset(&mut self) {
+        unsafe { raw::TEE_ResetPropertyEnumerator(self.raw) }
+    }
+
+    /// Moves to the next property.
+    ///
+    /// Returns `ItemNotFound` when there are no more properties.
+    pub fn advance(&mut self) -> Result<()> {
+        match unsafe { raw::TEE_GetNextProperty(self.raw) } {
+            raw::TEE_SUCCESS => Ok(()),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
+    /// Returns the name of the current property.
+    ///
+    /// Returns `ItemNotFound` if the enumerator is not started or past the
+    /// last property.
+    pub fn name(&self) -> Result<String> {
+        read_buffer(|buf, len| unsafe {
+            raw::TEE_GetPropertyName(self.raw, buf as _, len)
+        })
+        .map(into_string)
+    }
+
+    /// Returns the current property as a boolean.
+    pub fn get_bool(&self) -> Result<bool> {
+        get_bool(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as a `u32`.
+    pub fn get_u32(&self) -> Result<u32> {
+        get_u32(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as a `u64`.
+    pub fn get_u64(&self) -> Result<u64> {
+        get_u64(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as a string.
+    pub fn get_string(&self) -> Result<String> {
+        get_string(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as a binary block.
+    pub fn get_binary(&self) -> Result<Vec<u8>> {
+        get_binary(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as a UUID.
+    pub fn get_uuid(&self) -> Result<Uuid> {
+        get_uuid(self.raw, ptr::null())
+    }
+
+    /// Returns the current property as an identity.
+    pub fn get_identity(&self) -> Result<Identity> {
+        get_identity(self.raw, ptr::null())
+    }
+}
+
+impl Drop for PropertyEnumerator {
+    fn drop(&mut self) {
+        unsafe { raw::TEE_FreePropertyEnumerator(self.raw) }
+    }
+}
+
+/// A property name and its value read as a string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: String,
+}
+
+/// Iterator over the properties of a set, returned by
+/// [`PropertySet::properties`].
+pub struct Properties {
+    enumerator: PropertyEnumerator,
+    started: bool,
+}
+
+impl Iterator for Properties {
+    type Item = Result<Property>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.started {
+            match self.enumerator.advance() {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::ItemNotFound => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.started = true;
+        let name = match self.enumerator.name() {
+            Ok(name) => name,
+            Err(e) if e.kind() == ErrorKind::ItemNotFound => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(
+            self.enumerator
+                .get_string()
+                .map(|value| Property { name, value }),
+        )
+    }
+}
+
+fn c_name(name: &str) -> Result<CString> {
+    CString::new(name).map_err(|_| Error::new(ErrorKind::BadParameters))
+}
+
+/// Calls `read` with a growing buffer until it is large enough.
+fn read_buffer(mut read: impl FnMut(*mut u8, *mut usize) -> u32) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; INITIAL_BUFFER_LEN];
+    loop {
+        let mut len = buf.len();
+        match read(buf.as_mut_ptr(), &mut len) {
+            raw::TEE_SUCCESS => {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            raw::TEE_ERROR_SHORT_BUFFER if len > buf.len() => buf.resize(len, 0),
+            code => return Err(Error::from_raw_error(code)),
+        }
+    }
+}
+
+/// Converts a NUL-terminated string returned by the TEE.
+fn into_string(mut bytes: Vec<u8>) -> String {
+    if let Some(end) = bytes.iter().position(|&b| b == 0) {
+        bytes.truncate(end);
+    }
+    String::from_utf8(bytes)
+        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+fn get_bool(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<bool> {
+    let mut value = false;
+    match unsafe { raw::TEE_GetPropertyAsBool(handle, name, &mut value) } {
+        raw::TEE_SUCCESS => Ok(value),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+fn get_u32(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<u32> {
+    let mut value = 0;
+    match unsafe { raw::TEE_GetPropertyAsU32(handle, name, &mut value) } {
+        raw::TEE_SUCCESS => Ok(value),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+fn get_u64(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<u64> {
+    let mut value = 0;
+    match unsafe { raw::TEE_GetPropertyAsU64(handle, name, &mut value) } {
+        raw::TEE_SUCCESS => Ok(value),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+fn get_string(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<String> {
+    read_buffer(|buf, len| unsafe { raw::TEE_GetPropertyAsString(handle, name, buf as _, len) })
+        .map(into_string)
+}
+
+fn get_binary(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<Vec<u8>> {
+    read_buffer(|buf, len| unsafe {
+        raw::TEE_GetPropertyAsBinaryBlock(handle, name, buf as _, len)
+    })
+}
+
+fn empty_uuid() -> raw::TEE_UUID {
+    raw::TEE_UUID {
+        timeLow: 0,
+        timeMid: 0,
+        timeHiAndVersion: 0,
+        clockSeqAndNode: [0; 8],
+    }
+}
+
+fn from_raw_uuid(uuid: raw::TEE_UUID) -> Uuid {
+    Uuid::new_raw(
+        uuid.timeLow,
+        uuid.timeMid,
+        uuid.timeHiAndVersion,
+        uuid.clockSeqAndNode,
+    )
+}
+
+fn get_uuid(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<Uuid> {
+    let mut value = empty_uuid();
+    match unsafe { raw::TEE_GetPropertyAsUUID(handle, name, &mut value) } {
+        raw::TEE_SUCCESS => Ok(from_raw_uuid(value)),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+fn get_identity(handle: raw::TEE_PropSetHandle, name: *const c_char) -> Result<Identity> {
+    let mut value = raw::TEE_Identity {
+        login: 0,
+        uuid: empty_uuid(),
+    };
+    match unsafe { raw::TEE_GetPropertyAsIdentity(handle, name, &mut value) } {
+        raw::TEE_SUCCESS => Ok(Identity {
+            login: value.login.into(),
+            uuid: from_raw_uuid(value.uuid),
+        }),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_buffer() {
+        let mut value = vec![b'a'; INITIAL_BUFFER_LEN + 10];
+        value.push(0);
+        let mut calls = 0;
+        let bytes = read_buffer(|buf, len| {
+            calls += 1;
+            let available = unsafe { *len };
+            unsafe { *len = value.len() };
+            if available < value.len() {
+                return raw::TEE_ERROR_SHORT_BUFFER;
+            }
+            unsafe { ptr::copy_nonoverlapping(value.as_ptr(), buf, value.len()) };
+            raw::TEE_SUCCESS
+        })
+        .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(into_string(bytes).as_bytes(), &value[..value.len() - 1]);
+
+        let err = read_buffer(|_, _| raw::TEE_ERROR_ITEM_NOT_FOUND).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+    }
+}
@@ -0,0 +1,403 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sealed blobs: TA data encrypted so that it can be stored by the normal
+//! world, in files of any size, and only read back by the same TA.
+//!
+//! Blobs are encrypted with AES-256-GCM under a key generated on first use
+//! and kept in the TA's private storage. The TA UUID, a version and a purpose
+//! label are authenticated with each blob, so a blob sealed for one purpose
+//! or version cannot be unsealed as another. Deleting the TA's storage loses
+//! the key and makes its blobs unreadable.
+//!
+//! Data is sealed in chunks, and [SealStream](SealStream) and
+//! [UnsealStream](UnsealStream) process it piece by piece without holding the
+//! whole blob in memory. Chunks are numbered and the last one is marked, so
+//! reordering, dropping or truncating chunks is detected.
+//!
+//! # Examples
+//!
+//! ``` no_run
+//! use optee_utee::seal::Sealer;
+//!
+//! let sealer = Sealer::new()?;
+//! let blob = sealer.seal(b"model", 1, &weights)?;
+//! // ... the host stores `blob` and passes it back later ...
+//! let weights = sealer.unseal(b"model", 1, &blob)?;
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use crate::property::PropertySet;
+use crate::{
+    AlgorithmId, AttributeId, AttributeMemref, DataFlag, Error, ErrorKind, ObjectStorageConstants,
+    OperationMode, PersistentObject, Random, Result, TransientObject, TransientObjectType, Uuid,
+    AE,
+};
+#[cfg(not(target_os = "optee"))]
+use alloc::vec::Vec;
+
+const MAGIC: &[u8; 4] = b"SEAL";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 4 + NONCE_PREFIX_LEN;
+const NONCE_PREFIX_LEN: usize = 8;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+/// The chunk size used by [Sealer::seal](Sealer::seal).
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// The largest chunk size accepted, which bounds the memory used to unseal a blob.
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+/// The identifier of the sealing key in the TA's private storage.
+pub const KEY_OBJECT_ID: &[u8] = b"optee_utee.seal.key";
+
+/// Seals and unseals blobs for one TA.
+pub struct Sealer {
+    key: TransientObject,
+    uuid: [u8; 16],
+}
+
+impl Sealer {
+    /// Load the sealing key of the current TA, generating it on first use.
+    ///
+    /// The TA UUID is read from the `gpd.ta.appID` property.
+    pub fn new() -> Result<Self> {
+        let uuid = PropertySet::CurrentTa.get_uuid("gpd.ta.appID")?;
+        Self::with_uuid(&uuid)
+    }
+
+    /// Load the sealing key of the current TA, authenticating blobs with
+    /// `uuid` instead of the `gpd.ta.appID` property.
+    pub fn with_uuid(uuid: &Uuid) -> Result<Self> {
+        let secret = load_or_generate_key()?;
+        let mut key = TransientObject::allocate(TransientObjectType::Aes, KEY_LEN * 8)?;
+        key.populate(&[AttributeMemref::from_ref(AttributeId::SecretValue, &secret).into()])?;
+        Ok(Self {
+            key,
+            uuid: uuid.to_bytes(),
+        })
+    }
+
+    /// Seal `data` for `purpose` and `version`.
+    pub fn seal(&self, purpose: &[u8], version: u32, data: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = self.seal_stream(purpose, version, DEFAULT_CHUNK_SIZE)?;
+        let mut blob = stream.header().to_vec();
+        blob.extend(stream.update(data)?);
+        blob.extend(stream.finish()?);
+        Ok(blob)
+    }
+
+    /// Unseal a blob sealed for `purpose` and `version`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `MacInvalid`: If the blob was not sealed by this TA for `purpose`
+    ///    and `version`, or it was modified.
+    /// 2) `BadFormat`: If the blob is truncated or not a sealed blob.
+    pub fn unseal(&self, purpose: &[u8], version: u32, blob: &[u8]) -> Result<Vec<u8>> {
+        if blob.len() < HEADER_LEN {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let (header, body) = blob.split_at(HEADER_LEN);
+        let mut stream = self.unseal_stream(purpose, version, header)?;
+        let mut data = stream.update(body)?;
+        data.extend(stream.finish()?);
+        Ok(data)
+    }
+
+    /// Start sealing data for `purpose` and `version` in chunks of
+    /// `chunk_size` bytes.
+    ///
+    /// The blob is the [header](SealStream::header) followed by the output of
+    /// every [update](SealStream::update) and of [finish](SealStream::finish).
+    pub fn seal_stream(
+        &self,
+        purpose: &[u8],
+        version: u32,
+        chunk_size: usize,
+    ) -> Result<SealStream> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let mut header = [0u8; HEADER_LEN];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = FORMAT_VERSION;
+        header[5..9].copy_from_slice(&(chunk_size as u32).to_be_bytes());
+        Random::generate(&mut header[9..]);
+        Ok(SealStream {
+            chunks: self.chunks(OperationMode::Encrypt, purpose, version, &header)?,
+            header,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Start unsealing a blob sealed for `purpose` and `version`, given its
+    /// first [HEADER_LEN](SealStream::HEADER_LEN) bytes.
+    pub fn unseal_stream(
+        &self,
+        purpose: &[u8],
+        version: u32,
+        header: &[u8],
+    ) -> Result<UnsealStream> {
+        if header.len() != HEADER_LEN || &header[..4] != MAGIC || header[4] != FORMAT_VERSION {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let chunk_size = chunk_size(header);
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        Ok(UnsealStream {
+            chunks: self.chunks(OperationMode::Decrypt, purpose, version, header)?,
+            pending: Vec::new(),
+        })
+    }
+
+    fn chunks(
+        &self,
+        mode: OperationMode,
+        purpose: &[u8],
+        version: u32,
+        header: &[u8],
+    ) -> Result<Chunks> {
+        let operation = AE::allocate(AlgorithmId::AesGcm, mode, KEY_LEN * 8)?;
+        operation.set_key(&self.key)?;
+        let mut aad = header.to_vec();
+        aad.extend_from_slice(&self.uuid);
+        aad.extend_from_slice(&version.to_be_bytes());
+        aad.extend_from_slice(&(purpose.len() as u32).to_be_bytes());
+        aad.extend_from_slice(purpose);
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&header[9..]);
+        Ok(Chunks {
+            operation,
+            aad,
+            nonce,
+            chunk_size: chunk_size(header),
+            index: 0,
+        })
+    }
+}
+
+/// The AES-GCM operation shared by the chunks of a blob.
+struct Chunks {
+    operation: AE,
+    /// The header, TA UUID, version and purpose, authenticated with every chunk.
+    aad: Vec<u8>,
+    nonce: [u8; 12],
+    chunk_size: usize,
+    index: u32,
+}
+
+impl Chunks {
+    /// Start the next chunk, authenticating its index and whether it is the last one.
+    fn init(&mut self, last: bool) -> Result<()> {
+        // the nonce is unique as long as a blob has fewer than 2^32 chunks
+        self.index = self.index.checked_add(1).ok_or(Error::new(ErrorKind::Overflow))?;
+        self.nonce[NONCE_PREFIX_LEN..].copy_from_slice(&self.index.to_be_bytes());
+        self.operation.init(&self.nonce, TAG_LEN * 8, 0, 0)?;
+        self.operation.update_aad(&self.aad);
+        self.operation.update_aad(&[last as u8]);
+        Ok(())
+    }
+
+    fn seal(&mut self, data: &[u8], last: bool, out: &mut Vec<u8>) -> Result<()> {
+        self.init(last)?;
+        let start = out.len();
+        out.resize(start + data.len() + TAG_LEN, 0);
+        let (sealed, tag) = out[start..].split_at_mut(data.len());
+        let (len, _) = self.operation.encrypt_final(data, sealed, tag)?;
+        debug_assert_eq!(len, data.len());
+        Ok(())
+    }
+
+    fn unseal(&mut self, chunk: &[u8], last: bool, out: &mut Vec<u8>) -> Result<()> {
+        self.init(last)?;
+        let (sealed, tag) = chunk.split_at(chunk.len() - TAG_LEN);
+        let start = out.len();
+        out.resize(start + sealed.len(), 0);
+        let len = self.operation.decrypt_final(sealed, &mut out[start..], tag)?;
+        out.truncate(start + len);
+        Ok(())
+    }
+}
+
+/// Seals data piece by piece, returned by [Sealer::seal_stream](Sealer::seal_stream).
+pub struct SealStream {
+    chunks: Chunks,
+    header: [u8; HEADER_LEN],
+    pending: Vec<u8>,
+}
+
+impl SealStream {
+    /// The length of the header starting every blob.
+    pub const HEADER_LEN: usize = HEADER_LEN;
+
+    /// Return the header of the blob.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Seal the next piece of data, returning the chunks completed so far.
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.pending.extend_from_slice(data);
+        let mut out = Vec::new();
+        let chunk_size = self.chunks.chunk_size;
+        // keep at least one byte back: the last chunk is only known in `finish`
+        let full = self.pending.len().saturating_sub(1) / chunk_size;
+        for chunk in self.pending[..full * chunk_size].chunks(chunk_size) {
+            self.chunks.seal(chunk, false, &mut out)?;
+        }
+        self.pending.drain(..full * chunk_size);
+        Ok(out)
+    }
+
+    /// Seal the remaining data as the last chunk.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.chunks.seal(&self.pending, true, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// Unseals a blob piece by piece, returned by
+/// [Sealer::unseal_stream](Sealer::unseal_stream).
+///
+/// Data returned by [update](UnsealStream::update) is authentic, but the blob
+/// may still turn out to be truncated: it is complete only once
+/// [finish](UnsealStream::finish) succeeds.
+pub struct UnsealStream {
+    chunks: Chunks,
+    pending: Vec<u8>,
+}
+
+impl UnsealStream {
+    /// Unseal the next piece of the blob, after its header, returning the data
+    /// of the chunks completed so far.
+    pub fn update(&mut self, sealed: &[u8]) -> Result<Vec<u8>> {
+        self.pending.extend_from_slice(sealed);
+        let mut out = Vec::new();
+        let chunk_len = self.chunks.chunk_size + TAG_LEN;
+        let full = self.pending.len().saturating_sub(1) / chunk_len;
+        for chunk in self.pending[..full * chunk_len].chunks(chunk_len) {
+            self.chunks.unseal(chunk, false, &mut out)?;
+        }
+        self.pending.drain(..full * chunk_len);
+        Ok(out)
+    }
+
+    /// Unseal the last chunk of the blob.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        if self.pending.len() < TAG_LEN {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let mut out = Vec::new();
+        self.chunks.unseal(&self.pending, true, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn chunk_size(header: &[u8]) -> usize {
+    u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize
+}
+
+fn load_or_generate_key() -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    let storage = ObjectStorageConstants::Private;
+    match PersistentObject::open(storage, KEY_OBJECT_ID, DataFlag::ACCESS_READ) {
+        Ok(object) => {
+            if object.read(&mut key)? as usize != KEY_LEN {
+                return Err(Error::new(ErrorKind::CorruptObject));
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::ItemNotFound => {
+            Random::generate(&mut key);
+            PersistentObject::create(storage, KEY_OBJECT_ID, DataFlag::ACCESS_READ, None, &key)?;
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(key)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+
+    fn sealer() -> Sealer {
+        Sealer::with_uuid(&Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_seal_unseal() {
+        let sealer = sealer();
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let blob = sealer.seal(b"model", 1, &data).unwrap();
+        assert_eq!(blob.len(), HEADER_LEN + data.len() + 4 * TAG_LEN);
+        assert_eq!(sealer.unseal(b"model", 1, &blob).unwrap(), data);
+
+        // the key is kept, and the nonce changes
+        let again = self::sealer();
+        assert_eq!(again.unseal(b"model", 1, &blob).unwrap(), data);
+        assert_ne!(again.seal(b"model", 1, &data).unwrap(), blob);
+
+        let empty = sealer.seal(b"model", 1, b"").unwrap();
+        assert_eq!(sealer.unseal(b"model", 1, &empty).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_metadata_is_bound() {
+        let sealer = sealer();
+        let blob = sealer.seal(b"model", 1, b"weights").unwrap();
+        let err = |result: Result<Vec<u8>>| result.unwrap_err().kind();
+        assert_eq!(err(sealer.unseal(b"dataset", 1, &blob)), ErrorKind::MacInvalid);
+        assert_eq!(err(sealer.unseal(b"model", 2, &blob)), ErrorKind::MacInvalid);
+        let other = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51c").unwrap();
+        let other = Sealer::with_uuid(&other).unwrap();
+        assert_eq!(err(other.unseal(b"model", 1, &blob)), ErrorKind::MacInvalid);
+
+        let mut modified = blob.clone();
+        modified[HEADER_LEN] ^= 1;
+        assert_eq!(err(sealer.unseal(b"model", 1, &modified)), ErrorKind::MacInvalid);
+        assert_eq!(err(sealer.unseal(b"model", 1, &blob[..10])), ErrorKind::BadFormat);
+    }
+
+    #[test]
+    fn test_streams() {
+        let sealer = sealer();
+        let mut stream = sealer.seal_stream(b"log", 3, 10).unwrap();
+        let mut chunks = vec![stream.header().to_vec()];
+        for piece in [&b"0123456"[..], b"789", b"abcdefghij", b"k"] {
+            chunks.push(stream.update(piece).unwrap());
+        }
+        chunks.push(stream.finish().unwrap());
+        assert_eq!(chunks[1..].iter().map(Vec::len).collect::<Vec<_>>(), [0, 0, 26, 26, 17]);
+
+        let blob = chunks.concat();
+        assert_eq!(sealer.unseal(b"log", 3, &blob).unwrap(), b"0123456789abcdefghijk");
+        let (header, body) = blob.split_at(SealStream::HEADER_LEN);
+        let mut stream = sealer.unseal_stream(b"log", 3, header).unwrap();
+        let mut data = Vec::new();
+        for piece in body.chunks(7) {
+            data.extend(stream.update(piece).unwrap());
+        }
+        data.extend(stream.finish().unwrap());
+        assert_eq!(data, b"0123456789abcdefghijk");
+
+        // dropping the last chunk makes the previous one the last
+        let truncated = &blob[..blob.len() - 17];
+        let err = sealer.unseal(b"log", 3, truncated).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MacInvalid);
+    }
+}
@@ -0,0 +1,379 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A key-value store on top of [PersistentObject](crate::PersistentObject)s,
+//! with atomic multi-key transactions.
+//!
+//! Each value is kept in its own persistent object, encoded with [postcard].
+//! The object identifier is the namespace of the store, `v:` and the key, so
+//! keys are limited to `64 - namespace.len() - 2` bytes.
+//!
+//! A [Transaction](Transaction) is first written to a write-ahead record,
+//! which is created under a temporary identifier and renamed into place so it
+//! is either complete or absent. The changes are then applied and the record
+//! deleted. If the TA stops in between, for instance on a power loss, the
+//! record is found by the next [Store::open](Store::open) and applied again.
+//!
+//! Requires the `secure_kv` feature.
+//!
+//! ```no_run
+//! use optee_utee::secure_kv::Store;
+//! use optee_utee::ObjectStorageConstants;
+//!
+//! let store = Store::open(ObjectStorageConstants::Private, b"cfg/")?;
+//! let mut tx = store.transaction();
+//! tx.put(b"limit", &10u32)?;
+//! tx.put(b"owner", "alice")?;
+//! tx.delete(b"legacy");
+//! tx.commit()?;
+//! assert_eq!(store.get::<u32>(b"limit")?, Some(10));
+//! ```
+//!
+//! [postcard]: https://docs.rs/postcard
+
+use crate::{
+    DataFlag, Error, ErrorKind, MiscellaneousConstants, ObjectStorageConstants, PersistentObject,
+    Result,
+};
+#[cfg(not(target_os = "optee"))]
+use alloc::vec::Vec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const VALUE_PREFIX: &[u8] = b"v:";
+const LOG_ID: &[u8] = b"log";
+const PENDING_LOG_ID: &[u8] = b"log~";
+const MAX_ID_LEN: usize = MiscellaneousConstants::TeeObjectIdMaxLen as usize;
+
+/// A change recorded in a transaction: the key, and the encoded value to
+/// store or `None` to delete the key.
+type Change = (Vec<u8>, Option<Vec<u8>>);
+
+/// A key-value store kept in one trusted storage under a namespace.
+pub struct Store {
+    storage_id: ObjectStorageConstants,
+    namespace: Vec<u8>,
+}
+
+impl Store {
+    /// Open the store kept in `storage_id` under `namespace`, completing the
+    /// transaction interrupted last time, if any.
+    ///
+    /// Several stores can share a storage as long as no namespace is a prefix
+    /// of another.
+    pub fn open(storage_id: ObjectStorageConstants, namespace: &[u8]) -> Result<Self> {
+        if namespace.len() + PENDING_LOG_ID.len() > MAX_ID_LEN {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let store = Self {
+            storage_id,
+            namespace: namespace.to_vec(),
+        };
+        store.recover()?;
+        Ok(store)
+    }
+
+    /// Return the value of `key`, or `None` if it is not set.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If the stored value cannot be decoded as a `V`.
+    /// 2) `BadParameters`: If the key is too long.
+    pub fn get<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>> {
+        match self.read(&self.value_id(key)?)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Set `key` to `value`.
+    ///
+    /// A single value is replaced atomically, without a write-ahead record.
+    pub fn put<V: Serialize + ?Sized>(&self, key: &[u8], value: &V) -> Result<()> {
+        self.write(&self.value_id(key)?, &encode(value)?)
+    }
+
+    /// Delete `key`, returning whether it was set.
+    pub fn delete(&self, key: &[u8]) -> Result<bool> {
+        self.remove(&self.value_id(key)?)
+    }
+
+    /// Return the keys starting with `prefix` and their values, in the order
+    /// of the storage enumeration.
+    pub fn scan<V: DeserializeOwned>(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, V)>> {
+        let mut entries = Vec::new();
+        for key in self.keys(prefix)? {
+            // a value deleted since the enumeration is skipped
+            if let Some(value) = self.get(&key)? {
+                entries.push((key, value));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Return the keys starting with `prefix`.
+    pub fn keys(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let start = self.namespace.len() + VALUE_PREFIX.len();
+        let objects = PersistentObject::list(self.storage_id).with_prefix(&self.value_id(prefix)?);
+        let mut keys = Vec::new();
+        for object in objects {
+            let (id, _) = object?;
+            keys.push(id[start..].to_vec());
+        }
+        Ok(keys)
+    }
+
+    /// Start a transaction. Its changes are applied together by
+    /// [Transaction::commit](Transaction::commit).
+    pub fn transaction(&self) -> Transaction<'_> {
+        Transaction {
+            store: self,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Apply the transaction left in the write-ahead record, if any.
+    ///
+    /// [open](Store::open) does this already; call it again after a
+    /// [commit](Transaction::commit) failed to finish the transaction without
+    /// reopening the store.
+    pub fn recover(&self) -> Result<()> {
+        // a pending record was never renamed, so its transaction never started
+        self.remove(&self.object_id(PENDING_LOG_ID))?;
+        let log_id = self.object_id(LOG_ID);
+        if let Some(bytes) = self.read(&log_id)? {
+            let changes: Vec<Change> = decode(&bytes)?;
+            self.apply(&changes)?;
+            self.remove(&log_id)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&self, changes: &[Change]) -> Result<()> {
+        // every change sets or removes a whole object, so applying a record
+        // again after an interruption gives the same result
+        for (key, value) in changes {
+            let id = self.value_id(key)?;
+            match value {
+                Some(value) => self.write(&id, value)?,
+                None => {
+                    self.remove(&id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn object_id(&self, name: &[u8]) -> Vec<u8> {
+        [&self.namespace[..], name].concat()
+    }
+
+    fn value_id(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let id = [&self.namespace[..], VALUE_PREFIX, key].concat();
+        if id.len() > MAX_ID_LEN {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        Ok(id)
+    }
+
+    fn read(&self, id: &[u8]) -> Result<Option<Vec<u8>>> {
+        let object = match PersistentObject::open(self.storage_id, id, DataFlag::ACCESS_READ) {
+            Ok(object) => object,
+            Err(e) if e.kind() == ErrorKind::ItemNotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut bytes = vec![0u8; object.info()?.data_size()];
+        let mut len = 0;
+        while len < bytes.len() {
+            match object.read(&mut bytes[len..])? {
+                0 => return Err(Error::new(ErrorKind::CorruptObject)),
+                n => len += n as usize,
+            }
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Replace the object `id` with one holding `data`, atomically.
+    fn write(&self, id: &[u8], data: &[u8]) -> Result<()> {
+        let flags = DataFlag::ACCESS_READ | DataFlag::OVERWRITE;
+        PersistentObject::create(self.storage_id, id, flags, None, data)?;
+        Ok(())
+    }
+
+    fn remove(&self, id: &[u8]) -> Result<bool> {
+        let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE_META;
+        let mut object = match PersistentObject::open(self.storage_id, id, flags) {
+            Ok(object) => object,
+            Err(e) if e.kind() == ErrorKind::ItemNotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        object.close_and_delete()?;
+        core::mem::forget(object);
+        Ok(true)
+    }
+}
+
+/// Changes to a [Store](Store) that are applied together.
+///
+/// Reads through the store do not see the changes until they are committed.
+/// Dropping the transaction discards them.
+pub struct Transaction<'a> {
+    store: &'a Store,
+    changes: Vec<Change>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Set `key` to `value` when the transaction is committed.
+    pub fn put<V: Serialize + ?Sized>(&mut self, key: &[u8], value: &V) -> Result<()> {
+        self.store.value_id(key)?;
+        self.changes.push((key.to_vec(), Some(encode(value)?)));
+        Ok(())
+    }
+
+    /// Delete `key` when the transaction is committed.
+    pub fn delete(&mut self, key: &[u8]) {
+        self.changes.push((key.to_vec(), None));
+    }
+
+    /// Apply the changes, in the order they were made.
+    ///
+    /// Once the write-ahead record is in place the transaction is complete
+    /// even if applying it fails: it is applied again by
+    /// [Store::recover](Store::recover) or the next [Store::open](Store::open).
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If a deleted key is too long. Nothing is changed.
+    /// 2) `StorageNoSpace`: If insufficient storage space is available.
+    pub fn commit(self) -> Result<()> {
+        for (key, _) in &self.changes {
+            self.store.value_id(key)?;
+        }
+        // finish an earlier transaction first, so the records are applied in order
+        self.store.recover()?;
+
+        let pending_id = self.store.object_id(PENDING_LOG_ID);
+        let flags = DataFlag::ACCESS_WRITE_META | DataFlag::OVERWRITE;
+        let mut log = PersistentObject::create(
+            self.store.storage_id,
+            &pending_id,
+            flags,
+            None,
+            &encode(&self.changes)?,
+        )?;
+        log.rename(&self.store.object_id(LOG_ID))?;
+        drop(log);
+
+        self.store.apply(&self.changes)?;
+        self.store.remove(&self.store.object_id(LOG_ID))?;
+        Ok(())
+    }
+}
+
+fn encode<V: Serialize + ?Sized>(value: &V) -> Result<Vec<u8>> {
+    postcard::to_allocvec(value).map_err(|_| Error::new(ErrorKind::BadFormat))
+}
+
+fn decode<V: DeserializeOwned>(bytes: &[u8]) -> Result<V> {
+    postcard::from_bytes(bytes).map_err(|_| Error::new(ErrorKind::BadFormat))
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::ObjectStorageConstants::Private;
+    use alloc::string::String;
+
+    #[test]
+    fn test_get_put_delete() {
+        let store = Store::open(Private, b"kv/").unwrap();
+        assert_eq!(store.get::<u32>(b"a").unwrap(), None);
+        store.put(b"a", &7u32).unwrap();
+        store.put(b"a", &8u32).unwrap();
+        assert_eq!(store.get::<u32>(b"a").unwrap(), Some(8));
+        assert_eq!(store.get::<String>(b"a").unwrap_err().kind(), ErrorKind::BadFormat);
+        assert!(store.delete(b"a").unwrap());
+        assert!(!store.delete(b"a").unwrap());
+
+        let long = [b'k'; 62];
+        assert_eq!(store.put(&long, &0u8).unwrap_err().kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_scan() {
+        let store = Store::open(Private, b"kv/").unwrap();
+        let other = Store::open(Private, b"other/").unwrap();
+        store.put(b"user:1", "alice").unwrap();
+        store.put(b"user:2", "bob").unwrap();
+        store.put(b"group:1", "admins").unwrap();
+        other.put(b"user:3", "eve").unwrap();
+
+        let users: Vec<(Vec<u8>, String)> = store.scan(b"user:").unwrap();
+        assert_eq!(
+            users,
+            [(b"user:1".to_vec(), String::from("alice")), (b"user:2".to_vec(), String::from("bob"))]
+        );
+        assert_eq!(store.keys(b"").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let store = Store::open(Private, b"kv/").unwrap();
+        store.put(b"old", &1u32).unwrap();
+        let mut tx = store.transaction();
+        tx.put(b"a", &1u32).unwrap();
+        tx.put(b"b", &2u32).unwrap();
+        tx.put(b"a", &3u32).unwrap();
+        tx.delete(b"old");
+        assert_eq!(store.get::<u32>(b"a").unwrap(), None);
+        tx.commit().unwrap();
+
+        assert_eq!(store.get::<u32>(b"a").unwrap(), Some(3));
+        assert_eq!(store.get::<u32>(b"b").unwrap(), Some(2));
+        assert_eq!(store.get::<u32>(b"old").unwrap(), None);
+        assert_eq!(store.keys(b"").unwrap().len(), 2);
+        assert_eq!(PersistentObject::list(Private).count(), 2);
+
+        let mut tx = store.transaction();
+        tx.put(b"a", &4u32).unwrap();
+        drop(tx);
+        assert_eq!(store.get::<u32>(b"a").unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let store = Store::open(Private, b"kv/").unwrap();
+        store.put(b"a", &1u32).unwrap();
+        store.put(b"b", &1u32).unwrap();
+
+        // a record that was renamed into place, then interrupted after
+        // applying the first change
+        let changes: Vec<Change> =
+            vec![(b"a".to_vec(), Some(encode(&2u32).unwrap())), (b"b".to_vec(), None)];
+        store.write(b"kv/log", &encode(&changes).unwrap()).unwrap();
+        store.put(b"a", &2u32).unwrap();
+        // and a record that was never renamed
+        let changes: Vec<Change> = vec![(b"c".to_vec(), Some(encode(&3u32).unwrap()))];
+        store.write(b"kv/log~", &encode(&changes).unwrap()).unwrap();
+
+        let store = Store::open(Private, b"kv/").unwrap();
+        assert_eq!(store.get::<u32>(b"a").unwrap(), Some(2));
+        assert_eq!(store.get::<u32>(b"b").unwrap(), None);
+        assert_eq!(store.get::<u32>(b"c").unwrap(), None);
+        assert_eq!(PersistentObject::list(Private).count(), 1);
+    }
+}
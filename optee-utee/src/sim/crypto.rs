@@ -0,0 +1,1283 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+//! Cryptographic operations.
+
+use super::object::{self, Key};
+use super::{slice, write_output};
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use num_bigint::BigUint;
+use optee_utee_sys as raw;
+use sha2::digest::DynDigest;
+use std::boxed::Box;
+use std::vec::Vec;
+
+const MODE_ENCRYPT: u32 = raw::TEE_OperationMode::TEE_MODE_ENCRYPT as u32;
+const MODE_DECRYPT: u32 = raw::TEE_OperationMode::TEE_MODE_DECRYPT as u32;
+const MODE_SIGN: u32 = raw::TEE_OperationMode::TEE_MODE_SIGN as u32;
+const MODE_VERIFY: u32 = raw::TEE_OperationMode::TEE_MODE_VERIFY as u32;
+const MODE_MAC: u32 = raw::TEE_OperationMode::TEE_MODE_MAC as u32;
+const MODE_DIGEST: u32 = raw::TEE_OperationMode::TEE_MODE_DIGEST as u32;
+
+const BLOCK_SIZE: usize = 16;
+
+type Block = [u8; BLOCK_SIZE];
+
+/// Returns a digest of the hash `id` used in the low bits of SHA, HMAC and
+/// RSA algorithm IDs.
+fn digest(id: u32) -> Option<Box<dyn DynDigest>> {
+    match id {
+        2 => Some(Box::new(sha1::Sha1::default())),
+        3 => Some(Box::new(sha2::Sha224::default())),
+        4 => Some(Box::new(sha2::Sha256::default())),
+        5 => Some(Box::new(sha2::Sha384::default())),
+        6 => Some(Box::new(sha2::Sha512::default())),
+        _ => None,
+    }
+}
+
+/// Returns the hash ID of a RSASSA algorithm.
+fn rsa_hash(algorithm: u32) -> u32 {
+    (algorithm >> 12) & 0xF
+}
+
+/// Returns whether a signature algorithm is RSASSA-PSS.
+fn is_pss(algorithm: u32) -> bool {
+    (algorithm >> 8) & 0xF == 0x9
+}
+
+/// Returns whether a signature algorithm is ECDSA.
+fn is_ecdsa(algorithm: u32) -> bool {
+    algorithm & 0xFF == 0x42
+}
+
+/// Returns the modes `algorithm` can be used in, or `None` if it is not
+/// supported.
+fn modes(algorithm: u32) -> Option<&'static [u32]> {
+    match algorithm {
+        raw::TEE_ALG_SHA1
+        | raw::TEE_ALG_SHA224
+        | raw::TEE_ALG_SHA256
+        | raw::TEE_ALG_SHA384
+        | raw::TEE_ALG_SHA512 => Some(&[MODE_DIGEST]),
+        raw::TEE_ALG_HMAC_SHA1
+        | raw::TEE_ALG_HMAC_SHA224
+        | raw::TEE_ALG_HMAC_SHA256
+        | raw::TEE_ALG_HMAC_SHA384
+        | raw::TEE_ALG_HMAC_SHA512
+        | raw::TEE_ALG_AES_CMAC
+        | raw::TEE_ALG_AES_CBC_MAC_NOPAD => Some(&[MODE_MAC]),
+        raw::TEE_ALG_AES_ECB_NOPAD
+        | raw::TEE_ALG_AES_CBC_NOPAD
+        | raw::TEE_ALG_AES_CTR
+        | raw::TEE_ALG_AES_GCM => Some(&[MODE_ENCRYPT, MODE_DECRYPT]),
+        raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA1
+        | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA224
+        | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA256
+        | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA384
+        | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA512
+        | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA1
+        | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA224
+        | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA256
+        | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA384
+        | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA512
+        | raw::TEE_ALG_ECDSA_SHA1
+        | raw::TEE_ALG_ECDSA_SHA224
+        | raw::TEE_ALG_ECDSA_SHA256
+        | raw::TEE_ALG_ECDSA_SHA384
+        | raw::TEE_ALG_ECDSA_SHA512 => Some(&[MODE_SIGN, MODE_VERIFY]),
+        _ => None,
+    }
+}
+
+/// Returns whether a key of `object_type` can be used with `algorithm`.
+fn key_type_matches(algorithm: u32, mode: u32, object_type: u32) -> bool {
+    match algorithm >> 28 {
+        3 if algorithm & 0xFFFF_FF00 == 0x3000_0000 => {
+            object_type == raw::TEE_TYPE_GENERIC_SECRET
+                || object_type == (raw::TEE_TYPE_HMAC_SHA1 & !0xFF) | (algorithm & 0xFF)
+        }
+        7 => {
+            let (key_pair, public_key) = match is_ecdsa(algorithm) {
+                true => (raw::TEE_TYPE_ECDSA_KEYPAIR, raw::TEE_TYPE_ECDSA_PUBLIC_KEY),
+                false => (raw::TEE_TYPE_RSA_KEYPAIR, raw::TEE_TYPE_RSA_PUBLIC_KEY),
+            };
+            object_type == key_pair || (mode == MODE_VERIFY && object_type == public_key)
+        }
+        _ => object_type == raw::TEE_TYPE_AES,
+    }
+}
+
+fn required_usage(mode: u32) -> u32 {
+    match mode {
+        MODE_ENCRYPT => raw::TEE_USAGE_ENCRYPT,
+        MODE_DECRYPT => raw::TEE_USAGE_DECRYPT,
+        MODE_SIGN => raw::TEE_USAGE_SIGN,
+        MODE_VERIFY => raw::TEE_USAGE_VERIFY,
+        MODE_MAC => raw::TEE_USAGE_MAC,
+        _ => 0,
+    }
+}
+
+#[derive(Clone)]
+enum Aes {
+    Aes128(aes::Aes128),
+    Aes192(aes::Aes192),
+    Aes256(aes::Aes256),
+}
+
+impl Aes {
+    fn new(key: &[u8]) -> Self {
+        match key.len() {
+            16 => Aes::Aes128(aes::Aes128::new_from_slice(key).unwrap()),
+            24 => Aes::Aes192(aes::Aes192::new_from_slice(key).unwrap()),
+            _ => Aes::Aes256(aes::Aes256::new_from_slice(key).unwrap()),
+        }
+    }
+
+    fn encrypt(&self, block: &mut Block) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Aes::Aes128(aes) => aes.encrypt_block(block),
+            Aes::Aes192(aes) => aes.encrypt_block(block),
+            Aes::Aes256(aes) => aes.encrypt_block(block),
+        }
+    }
+
+    fn decrypt(&self, block: &mut Block) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            Aes::Aes128(aes) => aes.decrypt_block(block),
+            Aes::Aes192(aes) => aes.decrypt_block(block),
+            Aes::Aes256(aes) => aes.decrypt_block(block),
+        }
+    }
+}
+
+fn xor(block: &mut [u8], other: &[u8]) {
+    block.iter_mut().zip(other).for_each(|(byte, other)| *byte ^= other);
+}
+
+/// Increments the last 32 bits of a counter block, as CTR mode and GCM do.
+fn increment(counter: &mut Block) {
+    let value = u32::from_be_bytes(counter[12..].try_into().unwrap()).wrapping_add(1);
+    counter[12..].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Keystream of CTR mode.
+#[derive(Clone)]
+struct Ctr {
+    counter: Block,
+    keystream: Block,
+    used: usize,
+}
+
+impl Ctr {
+    fn new(counter: Block) -> Self {
+        Self {
+            counter,
+            keystream: [0; BLOCK_SIZE],
+            used: BLOCK_SIZE,
+        }
+    }
+
+    fn apply(&mut self, aes: &Aes, data: &mut [u8]) {
+        for byte in data {
+            if self.used == BLOCK_SIZE {
+                self.keystream = self.counter;
+                aes.encrypt(&mut self.keystream);
+                increment(&mut self.counter);
+                self.used = 0;
+            }
+            *byte ^= self.keystream[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+#[derive(Clone)]
+enum CipherMode {
+    Ecb,
+    Cbc(Block),
+    Ctr(Ctr),
+}
+
+#[derive(Clone)]
+struct Cipher {
+    aes: Aes,
+    encrypt: bool,
+    mode: CipherMode,
+    /// Input not yet making up a whole block.
+    pending: Vec<u8>,
+}
+
+impl Cipher {
+    /// Returns the length of the output of `update` with `len` bytes.
+    fn output_len(&self, len: usize) -> usize {
+        match self.mode {
+            CipherMode::Ctr(_) => len,
+            _ => (self.pending.len() + len) / BLOCK_SIZE * BLOCK_SIZE,
+        }
+    }
+
+    fn update(&mut self, src: &[u8]) -> Vec<u8> {
+        if let CipherMode::Ctr(ctr) = &mut self.mode {
+            let mut output = src.to_vec();
+            ctr.apply(&self.aes, &mut output);
+            return output;
+        }
+        self.pending.extend_from_slice(src);
+        let len = self.pending.len() / BLOCK_SIZE * BLOCK_SIZE;
+        let mut output: Vec<u8> = self.pending.drain(..len).collect();
+        for chunk in output.chunks_exact_mut(BLOCK_SIZE) {
+            let block: &mut Block = chunk.try_into().unwrap();
+            match (&mut self.mode, self.encrypt) {
+                (CipherMode::Cbc(iv), true) => {
+                    xor(block, iv);
+                    self.aes.encrypt(block);
+                    *iv = *block;
+                }
+                (CipherMode::Cbc(iv), false) => {
+                    let input = *block;
+                    self.aes.decrypt(block);
+                    xor(block, iv);
+                    *iv = input;
+                }
+                (_, true) => self.aes.encrypt(block),
+                (_, false) => self.aes.decrypt(block),
+            }
+        }
+        output
+    }
+}
+
+#[derive(Clone)]
+struct Hmac {
+    inner: Box<dyn DynDigest>,
+    /// The key padded to the block size, XORed with the outer pad.
+    outer_key: Vec<u8>,
+    digest_id: u32,
+}
+
+impl Hmac {
+    fn new(digest_id: u32, key: &[u8]) -> Self {
+        let block_size = match digest_id {
+            5 | 6 => 128,
+            _ => 64,
+        };
+        let mut padded = match key.len() > block_size {
+            true => {
+                let mut hash = digest(digest_id).unwrap();
+                hash.update(key);
+                hash.finalize_reset().to_vec()
+            }
+            false => key.to_vec(),
+        };
+        padded.resize(block_size, 0);
+
+        let mut inner = digest(digest_id).unwrap();
+        inner.update(&padded.iter().map(|byte| byte ^ 0x36).collect::<Vec<_>>());
+        Self {
+            inner,
+            outer_key: padded.iter().map(|byte| byte ^ 0x5C).collect(),
+            digest_id,
+        }
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let inner = self.inner.finalize_reset();
+        let mut outer = digest(self.digest_id).unwrap();
+        outer.update(&self.outer_key);
+        outer.update(&inner);
+        outer.finalize_reset().to_vec()
+    }
+}
+
+/// AES-CMAC, or AES-CBC-MAC without padding.
+#[derive(Clone)]
+struct BlockMac {
+    aes: Aes,
+    cmac: bool,
+    state: Block,
+    /// Input not processed yet. CMAC keeps the last block here until the
+    /// final call, which processes it with a subkey.
+    pending: Vec<u8>,
+}
+
+impl BlockMac {
+    fn update(&mut self, data: &[u8]) {
+        self.pending.extend_from_slice(data);
+        let keep = match self.cmac {
+            true => 1,
+            false => 0,
+        };
+        while self.pending.len() >= BLOCK_SIZE + keep {
+            xor(&mut self.state, &self.pending[..BLOCK_SIZE]);
+            self.aes.encrypt(&mut self.state);
+            self.pending.drain(..BLOCK_SIZE);
+        }
+    }
+
+    fn finalize(&mut self) -> Result<Block, raw::TEE_Result> {
+        if !self.cmac {
+            if !self.pending.is_empty() {
+                return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+            }
+            return Ok(self.state);
+        }
+        let double = |block: Block| {
+            let value = u128::from_be_bytes(block);
+            let carry = match value >> 127 {
+                1 => 0x87,
+                _ => 0,
+            };
+            ((value << 1) ^ carry).to_be_bytes()
+        };
+        let mut k1 = [0; BLOCK_SIZE];
+        self.aes.encrypt(&mut k1);
+        let k1 = double(k1);
+        let mut last = [0; BLOCK_SIZE];
+        last[..self.pending.len()].copy_from_slice(&self.pending);
+        if self.pending.len() == BLOCK_SIZE {
+            xor(&mut last, &k1);
+        } else {
+            last[self.pending.len()] = 0x80;
+            xor(&mut last, &double(k1));
+        }
+        xor(&mut self.state, &last);
+        self.aes.encrypt(&mut self.state);
+        Ok(self.state)
+    }
+}
+
+/// Multiplies in GF(2^128) as defined for GHASH.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut product = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            product ^= v;
+        }
+        v = match v & 1 {
+            1 => (v >> 1) ^ (0xE1 << 120),
+            _ => v >> 1,
+        };
+    }
+    product
+}
+
+#[derive(Clone)]
+struct Ghash {
+    h: u128,
+    state: u128,
+    pending: Vec<u8>,
+}
+
+impl Ghash {
+    fn update(&mut self, data: &[u8]) {
+        self.pending.extend_from_slice(data);
+        while self.pending.len() >= BLOCK_SIZE {
+            let block: Block = self.pending[..BLOCK_SIZE].try_into().unwrap();
+            self.state = gf_mul(self.state ^ u128::from_be_bytes(block), self.h);
+            self.pending.drain(..BLOCK_SIZE);
+        }
+    }
+
+    /// Processes the pending input padded with zeros.
+    fn pad(&mut self) {
+        if !self.pending.is_empty() {
+            let padding = BLOCK_SIZE - self.pending.len();
+            self.update(&[0; BLOCK_SIZE][..padding]);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Gcm {
+    aes: Aes,
+    encrypt: bool,
+    j0: Block,
+    ctr: Ctr,
+    ghash: Ghash,
+    aad_len: u64,
+    payload_len: u64,
+    /// Tag length in bytes.
+    tag_len: usize,
+}
+
+impl Gcm {
+    fn new(aes: Aes, encrypt: bool, nonce: &[u8], tag_len: usize) -> Self {
+        let mut h = [0; BLOCK_SIZE];
+        aes.encrypt(&mut h);
+        let mut ghash = Ghash {
+            h: u128::from_be_bytes(h),
+            state: 0,
+            pending: Vec::new(),
+        };
+        let j0 = match nonce.len() {
+            12 => {
+                let mut j0 = [0; BLOCK_SIZE];
+                j0[..12].copy_from_slice(nonce);
+                j0[15] = 1;
+                j0
+            }
+            _ => {
+                ghash.update(nonce);
+                ghash.pad();
+                ghash.update(&(nonce.len() as u128 * 8).to_be_bytes());
+                let j0 = ghash.state.to_be_bytes();
+                ghash.state = 0;
+                j0
+            }
+        };
+        let mut counter = j0;
+        increment(&mut counter);
+        Self {
+            aes,
+            encrypt,
+            j0,
+            ctr: Ctr::new(counter),
+            ghash,
+            aad_len: 0,
+            payload_len: 0,
+            tag_len,
+        }
+    }
+
+    fn update_aad(&mut self, aad: &[u8]) {
+        assert!(self.payload_len == 0, "AAD after payload");
+        self.ghash.update(aad);
+        self.aad_len += aad.len() as u64;
+    }
+
+    fn update(&mut self, src: &[u8]) -> Vec<u8> {
+        if self.payload_len == 0 {
+            self.ghash.pad();
+        }
+        let mut output = src.to_vec();
+        if !self.encrypt {
+            self.ghash.update(src);
+        }
+        self.ctr.apply(&self.aes, &mut output);
+        if self.encrypt {
+            self.ghash.update(&output);
+        }
+        self.payload_len += src.len() as u64;
+        output
+    }
+
+    fn tag(&mut self) -> Vec<u8> {
+        self.ghash.pad();
+        let lengths = ((self.aad_len as u128 * 8) << 64) | (self.payload_len as u128 * 8);
+        self.ghash.update(&lengths.to_be_bytes());
+        let mut tag = self.j0;
+        self.aes.encrypt(&mut tag);
+        xor(&mut tag, &self.ghash.state.to_be_bytes());
+        tag[..self.tag_len].to_vec()
+    }
+}
+
+#[derive(Clone)]
+enum State {
+    /// Waiting for an init function, or the key.
+    Idle,
+    Digest(Box<dyn DynDigest>),
+    Cipher(Cipher),
+    Hmac(Hmac),
+    BlockMac(BlockMac),
+    Gcm(Gcm),
+}
+
+#[derive(Clone)]
+struct Operation {
+    algorithm: u32,
+    mode: u32,
+    max_key_size: u32,
+    key: Option<Key>,
+    state: State,
+}
+
+impl Operation {
+    fn class(&self) -> u32 {
+        self.algorithm >> 28
+    }
+
+    fn secret(&self) -> &[u8] {
+        let key = self.key.as_ref().expect("operation key not set");
+        key.buffer(raw::TEE_ATTR_SECRET_VALUE).unwrap()
+    }
+
+    /// Returns the state the operation is in before its init function.
+    fn initial_state(&self) -> State {
+        match self.class() {
+            raw::TEE_OPERATION_DIGEST => State::Digest(digest(self.algorithm & 0xFF).unwrap()),
+            _ => State::Idle,
+        }
+    }
+}
+
+unsafe fn operation<'a>(handle: raw::TEE_OperationHandle) -> &'a mut Operation {
+    assert!(!handle.is_null(), "invalid operation handle");
+    &mut *(handle as *mut Operation)
+}
+
+#[no_mangle]
+extern "C" fn TEE_AllocateOperation(
+    operation: *mut raw::TEE_OperationHandle,
+    algorithm: u32,
+    mode: u32,
+    max_key_size: u32,
+) -> raw::TEE_Result {
+    match modes(algorithm) {
+        Some(modes) if modes.contains(&mode) => (),
+        _ => return raw::TEE_ERROR_NOT_SUPPORTED,
+    }
+    let mut allocated = Operation {
+        algorithm,
+        mode,
+        max_key_size,
+        key: None,
+        state: State::Idle,
+    };
+    allocated.state = allocated.initial_state();
+    unsafe { *operation = Box::into_raw(Box::new(allocated)) as raw::TEE_OperationHandle };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_FreeOperation(operation: raw::TEE_OperationHandle) {
+    if !operation.is_null() {
+        drop(unsafe { Box::from_raw(operation as *mut Operation) });
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetOperationInfo(
+    operation: raw::TEE_OperationHandle,
+    info: *mut raw::TEE_OperationInfo,
+) {
+    let operation = unsafe { self::operation(operation) };
+    let digest_length = match operation.class() {
+        raw::TEE_OPERATION_DIGEST => digest(operation.algorithm & 0xFF).unwrap().output_size(),
+        raw::TEE_OPERATION_MAC => match digest(operation.algorithm & 0xFF) {
+            Some(digest) => digest.output_size(),
+            None => BLOCK_SIZE,
+        },
+        _ => 0,
+    };
+    let mut handle_state = 0;
+    if operation.key.is_some() {
+        handle_state |= raw::TEE_HANDLE_FLAG_KEY_SET;
+    }
+    if !matches!(operation.state, State::Idle) {
+        handle_state |= raw::TEE_HANDLE_FLAG_INITIALIZED;
+    }
+    unsafe {
+        *info = raw::TEE_OperationInfo {
+            algorithm: operation.algorithm,
+            operationClass: operation.class(),
+            mode: operation.mode,
+            digestLength: digest_length as u32,
+            maxKeySize: operation.max_key_size,
+            keySize: operation.key.as_ref().map_or(0, |key| key.object_size),
+            requiredKeyUsage: required_usage(operation.mode),
+            handleState: handle_state,
+        }
+    };
+}
+
+#[no_mangle]
+extern "C" fn TEE_ResetOperation(operation: raw::TEE_OperationHandle) {
+    let operation = unsafe { self::operation(operation) };
+    operation.state = operation.initial_state();
+}
+
+#[no_mangle]
+extern "C" fn TEE_SetOperationKey(
+    operation: raw::TEE_OperationHandle,
+    key: raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    let operation = unsafe { self::operation(operation) };
+    operation.state = operation.initial_state();
+    if key.is_null() {
+        operation.key = None;
+        return raw::TEE_SUCCESS;
+    }
+    let key = &unsafe { object::object(key) }.key;
+    let usage = required_usage(operation.mode);
+    if !key_type_matches(operation.algorithm, operation.mode, key.object_type)
+        || key.object_size > operation.max_key_size
+        || key.usage & usage != usage
+    {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    operation.key = Some(key.clone());
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_CopyOperation(dst: raw::TEE_OperationHandle, src: raw::TEE_OperationHandle) {
+    let src = unsafe { operation(src) }.clone();
+    let dst = unsafe { operation(dst) };
+    assert!(
+        src.algorithm == dst.algorithm && src.mode == dst.mode,
+        "operations do not match"
+    );
+    *dst = Operation {
+        max_key_size: dst.max_key_size,
+        ..src
+    };
+}
+
+#[no_mangle]
+extern "C" fn TEE_IsAlgorithmSupported(algorithm: u32, element: u32) -> raw::TEE_Result {
+    match (modes(algorithm), element) {
+        (Some(_), raw::TEE_CRYPTO_ELEMENT_NONE) => raw::TEE_SUCCESS,
+        _ => raw::TEE_ERROR_NOT_SUPPORTED,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_DigestUpdate(
+    operation: raw::TEE_OperationHandle,
+    chunk: *const c_void,
+    chunk_size: usize,
+) {
+    match &mut unsafe { self::operation(operation) }.state {
+        State::Digest(digest) => digest.update(unsafe { slice(chunk, chunk_size) }),
+        _ => panic!("not a digest operation"),
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_DigestDoFinal(
+    operation: raw::TEE_OperationHandle,
+    chunk: *const c_void,
+    chunk_len: usize,
+    hash: *mut c_void,
+    hash_len: *mut usize,
+) -> raw::TEE_Result {
+    let digest = match &mut unsafe { self::operation(operation) }.state {
+        State::Digest(digest) => digest,
+        _ => panic!("not a digest operation"),
+    };
+    if unsafe { *hash_len } < digest.output_size() {
+        unsafe { *hash_len = digest.output_size() };
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    digest.update(unsafe { slice(chunk, chunk_len) });
+    unsafe { write_output(&digest.finalize_reset(), hash, hash_len) }
+}
+
+#[no_mangle]
+extern "C" fn TEE_CipherInit(
+    operation: raw::TEE_OperationHandle,
+    iv: *const c_void,
+    iv_len: usize,
+) {
+    let operation = unsafe { self::operation(operation) };
+    let iv = unsafe { slice(iv, iv_len) };
+    let mode = match operation.algorithm {
+        raw::TEE_ALG_AES_ECB_NOPAD => CipherMode::Ecb,
+        raw::TEE_ALG_AES_CBC_NOPAD => CipherMode::Cbc(iv.try_into().expect("bad IV length")),
+        raw::TEE_ALG_AES_CTR => CipherMode::Ctr(Ctr::new(iv.try_into().expect("bad IV length"))),
+        _ => panic!("not a cipher operation"),
+    };
+    operation.state = State::Cipher(Cipher {
+        aes: Aes::new(operation.secret()),
+        encrypt: operation.mode == MODE_ENCRYPT,
+        mode,
+        pending: Vec::new(),
+    });
+}
+
+fn cipher<'a>(operation: raw::TEE_OperationHandle) -> &'a mut Cipher {
+    match &mut unsafe { self::operation(operation) }.state {
+        State::Cipher(cipher) => cipher,
+        _ => panic!("cipher operation not initialized"),
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_CipherUpdate(
+    operation: raw::TEE_OperationHandle,
+    src_data: *const c_void,
+    src_len: usize,
+    dest_data: *mut c_void,
+    dest_len: *mut usize,
+) -> raw::TEE_Result {
+    let cipher = cipher(operation);
+    let output_len = cipher.output_len(src_len);
+    if unsafe { *dest_len } < output_len {
+        unsafe { *dest_len = output_len };
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    let output = cipher.update(unsafe { slice(src_data, src_len) });
+    unsafe { write_output(&output, dest_data, dest_len) }
+}
+
+// `is_multiple_of` is newer than the pinned toolchain.
+#[allow(unknown_lints, clippy::manual_is_multiple_of)]
+#[no_mangle]
+extern "C" fn TEE_CipherDoFinal(
+    operation: raw::TEE_OperationHandle,
+    src_data: *const c_void,
+    src_len: usize,
+    dest_data: *mut c_void,
+    dest_len: *mut usize,
+) -> raw::TEE_Result {
+    let cipher = cipher(operation);
+    if !matches!(cipher.mode, CipherMode::Ctr(_))
+        && (cipher.pending.len() + src_len) % BLOCK_SIZE != 0
+    {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    match TEE_CipherUpdate(operation, src_data, src_len, dest_data, dest_len) {
+        raw::TEE_SUCCESS => (),
+        code => return code,
+    }
+    unsafe { self::operation(operation) }.state = State::Idle;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_MACInit(operation: raw::TEE_OperationHandle, iv: *const c_void, iv_len: usize) {
+    let operation = unsafe { self::operation(operation) };
+    let secret = operation.secret();
+    operation.state = match operation.algorithm {
+        raw::TEE_ALG_AES_CMAC | raw::TEE_ALG_AES_CBC_MAC_NOPAD => {
+            let mut state = [0; BLOCK_SIZE];
+            if iv_len == BLOCK_SIZE {
+                state.copy_from_slice(unsafe { slice(iv, iv_len) });
+            }
+            State::BlockMac(BlockMac {
+                aes: Aes::new(secret),
+                cmac: operation.algorithm == raw::TEE_ALG_AES_CMAC,
+                state,
+                pending: Vec::new(),
+            })
+        }
+        algorithm => State::Hmac(Hmac::new(algorithm & 0xFF, secret)),
+    };
+}
+
+#[no_mangle]
+extern "C" fn TEE_MACUpdate(
+    operation: raw::TEE_OperationHandle,
+    chunk: *const c_void,
+    chunk_size: usize,
+) {
+    let chunk = unsafe { slice(chunk, chunk_size) };
+    match &mut unsafe { self::operation(operation) }.state {
+        State::Hmac(hmac) => hmac.inner.update(chunk),
+        State::BlockMac(mac) => mac.update(chunk),
+        _ => panic!("MAC operation not initialized"),
+    }
+}
+
+/// Computes the MAC of the input so far and `message`, ending the operation.
+fn mac_final(
+    operation: raw::TEE_OperationHandle,
+    message: *const c_void,
+    message_len: usize,
+) -> Result<Vec<u8>, raw::TEE_Result> {
+    TEE_MACUpdate(operation, message, message_len);
+    let operation = unsafe { self::operation(operation) };
+    let mac = match &mut operation.state {
+        State::Hmac(hmac) => hmac.finalize(),
+        State::BlockMac(mac) => mac.finalize()?.to_vec(),
+        _ => unreachable!(),
+    };
+    operation.state = State::Idle;
+    Ok(mac)
+}
+
+#[no_mangle]
+extern "C" fn TEE_MACComputeFinal(
+    operation: raw::TEE_OperationHandle,
+    message: *const c_void,
+    message_len: usize,
+    mac: *mut c_void,
+    mac_len: *mut usize,
+) -> raw::TEE_Result {
+    let mut info = unsafe { core::mem::zeroed() };
+    TEE_GetOperationInfo(operation, &mut info);
+    if unsafe { *mac_len } < info.digestLength as usize {
+        unsafe { *mac_len = info.digestLength as usize };
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    match mac_final(operation, message, message_len) {
+        Ok(computed) => unsafe { write_output(&computed, mac, mac_len) },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_MACCompareFinal(
+    operation: raw::TEE_OperationHandle,
+    message: *const c_void,
+    message_len: usize,
+    mac: *const c_void,
+    mac_len: usize,
+) -> raw::TEE_Result {
+    let expected = unsafe { slice(mac, mac_len) };
+    match mac_final(operation, message, message_len) {
+        Ok(computed) if computed == expected => raw::TEE_SUCCESS,
+        Ok(_) => raw::TEE_ERROR_MAC_INVALID,
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AEInit(
+    operation: raw::TEE_OperationHandle,
+    nonce: *const c_void,
+    nonce_len: usize,
+    tag_len: u32,
+    _aad_len: usize,
+    _payload_len: usize,
+) -> raw::TEE_Result {
+    let operation = unsafe { self::operation(operation) };
+    assert!(operation.algorithm == raw::TEE_ALG_AES_GCM, "not an AE operation");
+    if !matches!(tag_len, 96 | 104 | 112 | 120 | 128) {
+        return raw::TEE_ERROR_NOT_SUPPORTED;
+    }
+    let nonce = unsafe { slice(nonce, nonce_len) };
+    let aes = Aes::new(operation.secret());
+    let encrypt = operation.mode == MODE_ENCRYPT;
+    operation.state = State::Gcm(Gcm::new(aes, encrypt, nonce, tag_len as usize / 8));
+    raw::TEE_SUCCESS
+}
+
+fn gcm<'a>(operation: raw::TEE_OperationHandle) -> &'a mut Gcm {
+    match &mut unsafe { self::operation(operation) }.state {
+        State::Gcm(gcm) => gcm,
+        _ => panic!("AE operation not initialized"),
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AEUpdateAAD(
+    operation: raw::TEE_OperationHandle,
+    aad_data: *const c_void,
+    aad_data_len: usize,
+) {
+    gcm(operation).update_aad(unsafe { slice(aad_data, aad_data_len) });
+}
+
+#[no_mangle]
+extern "C" fn TEE_AEUpdate(
+    operation: raw::TEE_OperationHandle,
+    src_data: *const c_void,
+    src_len: usize,
+    dest_data: *mut c_void,
+    dest_len: *mut usize,
+) -> raw::TEE_Result {
+    if unsafe { *dest_len } < src_len {
+        unsafe { *dest_len = src_len };
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    let output = gcm(operation).update(unsafe { slice(src_data, src_len) });
+    unsafe { write_output(&output, dest_data, dest_len) }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AEEncryptFinal(
+    operation: raw::TEE_OperationHandle,
+    src_data: *const c_void,
+    src_len: usize,
+    dest_data: *mut c_void,
+    dest_len: *mut usize,
+    tag: *mut c_void,
+    tag_len: *mut usize,
+) -> raw::TEE_Result {
+    let gcm = gcm(operation);
+    if unsafe { *tag_len } < gcm.tag_len {
+        unsafe { *tag_len = gcm.tag_len };
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    match TEE_AEUpdate(operation, src_data, src_len, dest_data, dest_len) {
+        raw::TEE_SUCCESS => (),
+        code => return code,
+    }
+    let computed = gcm.tag();
+    unsafe { self::operation(operation) }.state = State::Idle;
+    unsafe { write_output(&computed, tag, tag_len) }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AEDecryptFinal(
+    operation: raw::TEE_OperationHandle,
+    src_data: *const c_void,
+    src_len: usize,
+    dest_data: *mut c_void,
+    dest_len: *mut usize,
+    tag: *mut c_void,
+    tag_len: usize,
+) -> raw::TEE_Result {
+    match TEE_AEUpdate(operation, src_data, src_len, dest_data, dest_len) {
+        raw::TEE_SUCCESS => (),
+        code => return code,
+    }
+    let computed = gcm(operation).tag();
+    unsafe { self::operation(operation) }.state = State::Idle;
+    match computed == unsafe { slice(tag, tag_len) } {
+        true => raw::TEE_SUCCESS,
+        false => raw::TEE_ERROR_MAC_INVALID,
+    }
+}
+
+/// Returns the DER prefix of the DigestInfo of the hash `id`.
+fn digest_info_prefix(id: u32) -> &'static [u8] {
+    match id {
+        2 => &[
+            0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2B, 0x0E, 0x03, 0x02, 0x1A, 0x05, 0x00, 0x04,
+            0x14,
+        ],
+        3 => &[
+            0x30, 0x2D, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x04, 0x05, 0x00, 0x04, 0x1C,
+        ],
+        4 => &[
+            0x30, 0x31, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x01, 0x05, 0x00, 0x04, 0x20,
+        ],
+        5 => &[
+            0x30, 0x41, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x02, 0x05, 0x00, 0x04, 0x30,
+        ],
+        _ => &[
+            0x30, 0x51, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x03, 0x05, 0x00, 0x04, 0x40,
+        ],
+    }
+}
+
+/// Returns the RSASSA-PKCS1-v1_5 encoded message of `digest` for a modulus of
+/// `len` bytes.
+fn pkcs1_encode(algorithm: u32, digest: &[u8], len: usize) -> Option<Vec<u8>> {
+    let id = rsa_hash(algorithm);
+    if digest.len() != self::digest(id).unwrap().output_size() {
+        return None;
+    }
+    let prefix = digest_info_prefix(id);
+    let padding = len.checked_sub(prefix.len() + digest.len() + 3).filter(|len| *len >= 8)?;
+    let mut encoded = std::vec![0x00, 0x01];
+    encoded.resize(2 + padding, 0xFF);
+    encoded.push(0x00);
+    encoded.extend_from_slice(prefix);
+    encoded.extend_from_slice(digest);
+    Some(encoded)
+}
+
+/// Returns `number` as `len` big-endian bytes.
+fn to_bytes(number: &BigUint, len: usize) -> Vec<u8> {
+    let bytes = number.to_bytes_be();
+    let mut padded = std::vec![0; len - bytes.len()];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+/// Returns MGF1 of `seed` with the hash `id`, `len` bytes long.
+fn mgf1(id: u32, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len);
+    let mut counter = 0u32;
+    while mask.len() < len {
+        let mut hash = digest(id).unwrap();
+        hash.update(seed);
+        hash.update(&counter.to_be_bytes());
+        mask.extend_from_slice(&hash.finalize());
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+/// Returns the hash `id` of `0x00 * 8 || digest || salt`, as EMSA-PSS does.
+fn pss_hash(id: u32, digest: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut hash = self::digest(id).unwrap();
+    hash.update(&[0; 8]);
+    hash.update(digest);
+    hash.update(salt);
+    hash.finalize().to_vec()
+}
+
+/// Returns the EMSA-PSS encoded message of `digest` for a modulus of `bits`
+/// bits, with a random salt as long as the digest.
+fn pss_encode(algorithm: u32, digest: &[u8], bits: usize) -> Option<Vec<u8>> {
+    let id = rsa_hash(algorithm);
+    if digest.len() != self::digest(id).unwrap().output_size() {
+        return None;
+    }
+    let len = (bits - 1).div_ceil(8);
+    let db_len = len.checked_sub(digest.len() + 1).filter(|len| *len > digest.len())?;
+    let mut salt = std::vec![0u8; digest.len()];
+    getrandom::getrandom(&mut salt).expect("no random source");
+    let hash = pss_hash(id, digest, &salt);
+    let mut encoded = std::vec![0u8; db_len - salt.len() - 1];
+    encoded.push(0x01);
+    encoded.extend_from_slice(&salt);
+    xor(&mut encoded, &mgf1(id, &hash, db_len));
+    encoded[0] &= 0xFF >> (8 * len - (bits - 1));
+    encoded.extend_from_slice(&hash);
+    encoded.push(0xBC);
+    Some(encoded)
+}
+
+/// Returns whether `encoded` is an EMSA-PSS encoded message of `digest` for
+/// a modulus of `bits` bits, with a salt of any length.
+fn pss_verify(algorithm: u32, digest: &[u8], encoded: &[u8], bits: usize) -> bool {
+    let id = rsa_hash(algorithm);
+    let top_bits = 0xFF >> (8 * encoded.len() - (bits - 1));
+    let (masked, hash) = match encoded {
+        [rest @ .., 0xBC] if rest.len() > 2 * digest.len() => {
+            rest.split_at(rest.len() - digest.len())
+        }
+        _ => return false,
+    };
+    if masked[0] & !top_bits != 0 {
+        return false;
+    }
+    let mut db = masked.to_vec();
+    xor(&mut db, &mgf1(id, hash, masked.len()));
+    db[0] &= top_bits;
+    match db.iter().position(|byte| *byte != 0) {
+        Some(i) if db[i] == 0x01 => pss_hash(id, digest, &db[i + 1..]) == hash,
+        _ => false,
+    }
+}
+
+fn rsa_key(operation: &Operation, exponent: u32) -> (BigUint, BigUint, usize) {
+    let key = operation.key.as_ref().expect("operation key not set");
+    let modulus = key.buffer(raw::TEE_ATTR_RSA_MODULUS).unwrap();
+    let exponent = key.buffer(exponent).unwrap();
+    let len = (key.object_size as usize).div_ceil(8);
+    (BigUint::from_bytes_be(modulus), BigUint::from_bytes_be(exponent), len)
+}
+
+fn rsa_sign(operation: &Operation, digest: &[u8]) -> Result<Vec<u8>, raw::TEE_Result> {
+    let (modulus, exponent, len) = rsa_key(operation, raw::TEE_ATTR_RSA_PRIVATE_EXPONENT);
+    let encoded = match is_pss(operation.algorithm) {
+        true => pss_encode(operation.algorithm, digest, modulus.bits() as usize),
+        false => pkcs1_encode(operation.algorithm, digest, len),
+    };
+    let encoded = encoded.ok_or(raw::TEE_ERROR_BAD_PARAMETERS)?;
+    let computed = BigUint::from_bytes_be(&encoded).modpow(&exponent, &modulus);
+    Ok(to_bytes(&computed, len))
+}
+
+fn rsa_verify(operation: &Operation, digest: &[u8], signature: &[u8]) -> raw::TEE_Result {
+    let (modulus, exponent, len) = rsa_key(operation, raw::TEE_ATTR_RSA_PUBLIC_EXPONENT);
+    if digest.len() != self::digest(rsa_hash(operation.algorithm)).unwrap().output_size() {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    let signature_len = signature.len();
+    let signature = BigUint::from_bytes_be(signature);
+    if signature_len != len || signature >= modulus {
+        return raw::TEE_ERROR_SIGNATURE_INVALID;
+    }
+    let message = signature.modpow(&exponent, &modulus);
+    let valid = match is_pss(operation.algorithm) {
+        true => {
+            let bits = modulus.bits() as usize;
+            let encoded_len = (bits - 1).div_ceil(8);
+            message.bits() as usize <= 8 * encoded_len
+                && pss_verify(operation.algorithm, digest, &to_bytes(&message, encoded_len), bits)
+        }
+        false => Some(to_bytes(&message, len)) == pkcs1_encode(operation.algorithm, digest, len),
+    };
+    match valid {
+        true => raw::TEE_SUCCESS,
+        false => raw::TEE_ERROR_SIGNATURE_INVALID,
+    }
+}
+
+/// A point on an elliptic curve in affine coordinates, `None` being the point
+/// at infinity.
+type Point = Option<(BigUint, BigUint)>;
+
+/// A NIST curve `y^2 = x^3 - 3x + b` over the prime field of `p`, with a
+/// base point `g` of prime order `n`.
+struct Curve {
+    p: BigUint,
+    n: BigUint,
+    g: Point,
+}
+
+impl Curve {
+    /// Returns the curve of an ECDSA key, only P-256 being simulated.
+    fn of(key: &Key) -> Option<Curve> {
+        let (curve, _) = key.value(raw::TEE_ATTR_ECC_CURVE)?;
+        if curve != raw::TEE_ECC_CURVE_NIST_P256 {
+            return None;
+        }
+        let hex = |text: &str| BigUint::parse_bytes(text.as_bytes(), 16).unwrap();
+        Some(Curve {
+            p: hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff"),
+            n: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            g: Some((
+                hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+                hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+            )),
+        })
+    }
+
+    /// Size in bytes of a coordinate and of a scalar.
+    fn len(&self) -> usize {
+        (self.p.bits() as usize).div_ceil(8)
+    }
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        let p = &self.p;
+        let ((x1, y1), (x2, y2)) = match (a, b) {
+            (None, _) => return b.clone(),
+            (_, None) => return a.clone(),
+            (Some(a), Some(b)) => (a, b),
+        };
+        let slope = if x1 != x2 {
+            (y2 + p - y1) * inverse(&(x2 + p - x1), p) % p
+        } else if (y1 + y2) % p != BigUint::from(0u32) {
+            3u32 * (x1 * x1 + p - 1u32) * inverse(&(2u32 * y1), p) % p
+        } else {
+            return None;
+        };
+        let x3 = (&slope * &slope + 2u32 * p - x1 - x2) % p;
+        let y3 = (slope * (x1 + p - &x3) + p - y1) % p;
+        Some((x3, y3))
+    }
+
+    fn mul(&self, k: &BigUint, point: &Point) -> Point {
+        let mut result = None;
+        for i in (0..k.bits()).rev() {
+            result = self.add(&result, &result);
+            if k.bit(i) {
+                result = self.add(&result, point);
+            }
+        }
+        result
+    }
+
+    /// Returns the leftmost bits of `digest` as a number, as ECDSA does.
+    fn truncate(&self, digest: &[u8]) -> BigUint {
+        let number = BigUint::from_bytes_be(digest);
+        match digest.len() * 8 > self.n.bits() as usize {
+            true => number >> (digest.len() * 8 - self.n.bits() as usize),
+            false => number,
+        }
+    }
+}
+
+/// Returns the inverse of `x` modulo the prime `p`.
+fn inverse(x: &BigUint, p: &BigUint) -> BigUint {
+    x.modpow(&(p - 2u32), p)
+}
+
+fn ecdsa_sign(operation: &Operation, digest: &[u8]) -> Result<Vec<u8>, raw::TEE_Result> {
+    let key = operation.key.as_ref().expect("operation key not set");
+    let curve = Curve::of(key).ok_or(raw::TEE_ERROR_NOT_SUPPORTED)?;
+    let private = BigUint::from_bytes_be(key.buffer(raw::TEE_ATTR_ECC_PRIVATE_VALUE).unwrap());
+    let (z, n, len) = (curve.truncate(digest), &curve.n, curve.len());
+    loop {
+        let mut k = std::vec![0u8; len];
+        getrandom::getrandom(&mut k).expect("no random source");
+        let k = BigUint::from_bytes_be(&k) % n;
+        let (x, _) = match curve.mul(&k, &curve.g) {
+            Some(point) => point,
+            None => continue,
+        };
+        let r = x % n;
+        let s = inverse(&k, n) * ((&z + &r * &private) % n) % n;
+        if r.bits() != 0 && s.bits() != 0 {
+            return Ok([to_bytes(&r, len), to_bytes(&s, len)].concat());
+        }
+    }
+}
+
+fn ecdsa_verify(operation: &Operation, digest: &[u8], signature: &[u8]) -> raw::TEE_Result {
+    let key = operation.key.as_ref().expect("operation key not set");
+    let curve = match Curve::of(key) {
+        Some(curve) => curve,
+        None => return raw::TEE_ERROR_NOT_SUPPORTED,
+    };
+    let (z, n, len) = (curve.truncate(digest), &curve.n, curve.len());
+    if signature.len() != 2 * len {
+        return raw::TEE_ERROR_SIGNATURE_INVALID;
+    }
+    let r = BigUint::from_bytes_be(&signature[..len]);
+    let s = BigUint::from_bytes_be(&signature[len..]);
+    if r.bits() == 0 || s.bits() == 0 || &r >= n || &s >= n {
+        return raw::TEE_ERROR_SIGNATURE_INVALID;
+    }
+    let public = Some((
+        BigUint::from_bytes_be(key.buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_X).unwrap()),
+        BigUint::from_bytes_be(key.buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y).unwrap()),
+    ));
+    let w = inverse(&s, n);
+    let point =
+        curve.add(&curve.mul(&(z * &w % n), &curve.g), &curve.mul(&(&r * w % n), &public));
+    match point {
+        Some((x, _)) if &x % n == r => raw::TEE_SUCCESS,
+        _ => raw::TEE_ERROR_SIGNATURE_INVALID,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AsymmetricSignDigest(
+    operation: raw::TEE_OperationHandle,
+    _params: *const raw::TEE_Attribute,
+    _param_count: u32,
+    digest: *const c_void,
+    digest_len: usize,
+    signature: *mut c_void,
+    signature_len: *mut usize,
+) -> raw::TEE_Result {
+    let operation = unsafe { self::operation(operation) };
+    assert!(operation.mode == MODE_SIGN, "not a signing operation");
+    let digest = unsafe { slice(digest, digest_len) };
+    let computed = match is_ecdsa(operation.algorithm) {
+        true => ecdsa_sign(operation, digest),
+        false => rsa_sign(operation, digest),
+    };
+    match computed {
+        Ok(computed) => unsafe { write_output(&computed, signature, signature_len) },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AsymmetricVerifyDigest(
+    operation: raw::TEE_OperationHandle,
+    _params: *const raw::TEE_Attribute,
+    _param_count: u32,
+    digest: *const c_void,
+    digest_len: usize,
+    signature: *const c_void,
+    signature_len: usize,
+) -> raw::TEE_Result {
+    let operation = unsafe { self::operation(operation) };
+    assert!(operation.mode == MODE_VERIFY, "not a verifying operation");
+    let digest = unsafe { slice(digest, digest_len) };
+    let signature = unsafe { slice(signature, signature_len) };
+    match is_ecdsa(operation.algorithm) {
+        true => ecdsa_verify(operation, digest, signature),
+        false => rsa_verify(operation, digest, signature),
+    }
+}
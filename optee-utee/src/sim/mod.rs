@@ -0,0 +1,536 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+//! A host-native implementation of the TEE Internal Core API.
+//!
+//! With the `sim` feature, optee-utee provides the libutee functions it calls
+//! itself instead of linking libutee, so TA code runs under `cargo test` on
+//! the build machine:
+//!
+//! - persistent objects, kept in memory per thread in the REE FS and RPMB
+//!   storages, and transient objects;
+//! - digests (SHA-1, SHA-2), MACs (HMAC, AES-CMAC, AES-CBC-MAC), AES ciphers
+//!   (ECB, CBC, CTR), AES-GCM and RSASSA-PKCS1-v1_5 signatures;
+//! - random numbers, system, REE and TA persistent time, cancellation masking
+//!   and trace;
+//! - the `gpd.client.identity` property, see [`set_client_identity`].
+//!
+//! Each test runs on its own thread, so tests see separate storages and can
+//! use the same object IDs. Functions outside these groups are not provided
+//! and fail to link.
+//!
+//! ```toml
+//! [dev-dependencies]
+//! optee-utee = { path = "../optee-utee", features = ["sim"] }
+//! ```
+
+mod crypto;
+mod object;
+
+use crate::Uuid;
+use core::ffi::{c_char, c_int, c_void, CStr};
+use optee_utee_sys as raw;
+use std::cell::{Cell, RefCell};
+use std::string::String;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+std::thread_local! {
+    /// Starts at TRACE_INFO, as in TAs built with the default configuration.
+    static TRACE_LEVEL: Cell<c_int> = const { Cell::new(3) };
+    /// Offset of the TA persistent time from the REE time, once set.
+    static PERSISTENT_TIME_OFFSET: Cell<Option<i64>> = const { Cell::new(None) };
+    /// Cancellation is masked when an entry point is called.
+    static CANCELLATION_MASKED: Cell<bool> = const { Cell::new(true) };
+    /// Bytes of the current trace line.
+    static TRACE_LINE: RefCell<String> = const { RefCell::new(String::new()) };
+    /// Login and UUID of the client of the current session.
+    static CLIENT_IDENTITY: Cell<(u32, raw::TEE_UUID)> = const {
+        Cell::new((
+            raw::TEE_LOGIN_PUBLIC,
+            raw::TEE_UUID {
+                timeLow: 0,
+                timeMid: 0,
+                timeHiAndVersion: 0,
+                clockSeqAndNode: [0; 8],
+            },
+        ))
+    };
+}
+
+/// Clears the persistent objects and the TA persistent time of the calling
+/// thread, as if the TA was installed on a new device with RPMB storage.
+pub fn reset() {
+    object::reset_storage();
+    PERSISTENT_TIME_OFFSET.with(|offset| offset.set(None));
+}
+
+/// Sets whether the calling thread sees an RPMB storage.
+///
+/// Without it, `TEE_STORAGE_PRIVATE_RPMB` behaves as on an OP-TEE built
+/// without `CFG_RPMB_FS`: opening, creating and enumerating objects in it
+/// fail with `TEE_ERROR_ITEM_NOT_FOUND`. Objects already stored in RPMB are
+/// kept and reappear when it is made available again.
+pub fn set_rpmb_available(available: bool) {
+    object::set_rpmb_available(available);
+}
+
+/// Sets the identity of the client of the sessions run on the calling thread,
+/// as read from the `gpd.client.identity` property.
+///
+/// `login` is one of the `TEE_LOGIN_*` values. Until this is called, the
+/// client has a public login and a nil UUID. The runner of optee-teec sets
+/// the client of a session before calling its entry points.
+pub fn set_client_identity(login: u32, uuid: &Uuid) {
+    let uuid = unsafe { *uuid.as_raw_ptr() };
+    CLIENT_IDENTITY.with(|identity| identity.set((login, uuid)));
+}
+
+/// Returns a slice over `len` bytes at `ptr`, accepting a null `ptr` when
+/// `len` is 0.
+unsafe fn slice<'a>(ptr: *const c_void, len: usize) -> &'a [u8] {
+    match len {
+        0 => &[],
+        _ => core::slice::from_raw_parts(ptr as *const u8, len),
+    }
+}
+
+unsafe fn slice_mut<'a>(ptr: *mut c_void, len: usize) -> &'a mut [u8] {
+    match len {
+        0 => &mut [],
+        _ => core::slice::from_raw_parts_mut(ptr as *mut u8, len),
+    }
+}
+
+/// Copies `data` to the output buffer `buffer` of `*len` bytes, setting `*len`
+/// to the length of `data`.
+unsafe fn write_output(data: &[u8], buffer: *mut c_void, len: *mut usize) -> raw::TEE_Result {
+    let capacity = *len;
+    *len = data.len();
+    if capacity < data.len() {
+        return raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    slice_mut(buffer, data.len()).copy_from_slice(data);
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_Panic(code: raw::TEE_Result) {
+    panic!("TEE_Panic({:#010x})", code);
+}
+
+#[no_mangle]
+extern "C" fn _utee_log(buf: *const c_void, len: usize) {
+    let text = unsafe { slice(buf, len) };
+    TRACE_LINE.with(|line| {
+        let mut line = line.borrow_mut();
+        line.push_str(&String::from_utf8_lossy(text));
+        // print whole lines so the output of parallel tests does not mix
+        while let Some(end) = line.find('\n') {
+            std::eprintln!("{}", &line[..end]);
+            line.drain(..=end);
+        }
+    });
+}
+
+#[no_mangle]
+extern "C" fn trace_ext_puts(s: *const c_char) {
+    let s = unsafe { CStr::from_ptr(s) };
+    std::eprint!("{}", s.to_string_lossy());
+}
+
+#[no_mangle]
+extern "C" fn trace_ext_get_thread_id() -> c_int {
+    -1
+}
+
+#[no_mangle]
+extern "C" fn trace_set_level(level: c_int) {
+    TRACE_LEVEL.with(|trace_level| trace_level.set(level));
+}
+
+#[no_mangle]
+extern "C" fn trace_get_level() -> c_int {
+    TRACE_LEVEL.with(|trace_level| trace_level.get())
+}
+
+#[no_mangle]
+extern "C" fn TEE_GenerateRandom(buffer: *mut c_void, len: usize) {
+    let buffer = unsafe { slice_mut(buffer, len) };
+    getrandom::getrandom(buffer).expect("no random source");
+}
+
+fn to_tee_time(duration: Duration) -> raw::TEE_Time {
+    raw::TEE_Time {
+        seconds: duration.as_secs() as u32,
+        millis: duration.subsec_millis(),
+    }
+}
+
+fn ree_time() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetSystemTime(time: *mut raw::TEE_Time) {
+    static START: OnceLock<Instant> = OnceLock::new();
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    unsafe { *time = to_tee_time(elapsed) };
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetREETime(time: *mut raw::TEE_Time) {
+    unsafe { *time = to_tee_time(ree_time()) };
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetTAPersistentTime(time: *mut raw::TEE_Time) -> raw::TEE_Result {
+    let offset = match PERSISTENT_TIME_OFFSET.with(|offset| offset.get()) {
+        Some(offset) => offset,
+        None => return raw::TEE_ERROR_TIME_NOT_SET,
+    };
+    let millis = ree_time().as_millis() as i64 + offset;
+    if millis < 0 || millis / 1000 > u32::MAX as i64 {
+        return raw::TEE_ERROR_OVERFLOW;
+    }
+    unsafe { *time = to_tee_time(Duration::from_millis(millis as u64)) };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_SetTAPersistentTime(time: *const raw::TEE_Time) -> raw::TEE_Result {
+    let time = unsafe { &*time };
+    let millis = time.seconds as i64 * 1000 + time.millis as i64;
+    let offset = millis - ree_time().as_millis() as i64;
+    PERSISTENT_TIME_OFFSET.with(|persistent| persistent.set(Some(offset)));
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_Wait(timeout: u32) -> raw::TEE_Result {
+    std::thread::sleep(Duration::from_millis(timeout as u64));
+    raw::TEE_SUCCESS
+}
+
+/// There is no client in the simulator, so cancellation is never requested.
+#[no_mangle]
+extern "C" fn TEE_GetCancellationFlag() -> bool {
+    false
+}
+
+#[no_mangle]
+extern "C" fn TEE_MaskCancellation() -> bool {
+    CANCELLATION_MASKED.with(|masked| masked.replace(true))
+}
+
+#[no_mangle]
+extern "C" fn TEE_UnmaskCancellation() -> bool {
+    CANCELLATION_MASKED.with(|masked| masked.replace(false))
+}
+
+/// [`set_client_identity`] for TAs loaded as shared libraries, which have
+/// their own copy of the simulator.
+#[no_mangle]
+extern "C" fn optee_utee_sim_set_client_identity(identity: *const raw::TEE_Identity) {
+    let identity = unsafe { &*identity };
+    CLIENT_IDENTITY.with(|client| client.set((identity.login, identity.uuid)));
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetPropertyAsIdentity(
+    propset: raw::TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut raw::TEE_Identity,
+) -> raw::TEE_Result {
+    let name = unsafe { CStr::from_ptr(name) };
+    if propset != raw::TEE_PROPSET_CURRENT_CLIENT || name.to_bytes() != b"gpd.client.identity" {
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    let (login, uuid) = CLIENT_IDENTITY.with(Cell::get);
+    unsafe { *value = raw::TEE_Identity { login, uuid } };
+    raw::TEE_SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AlgorithmId, AttributeId, AttributeMemref, Cipher, DataFlag, Digest, ErrorKind, Mac,
+        ObjectEnumHandle, ObjectInfo, OperationMode, PersistentObject,
+        Time, TransientObject, TransientObjectType, Uuid, Whence, AE, Asymmetric,
+    };
+    use crate::property::{Identity, Login};
+    use crate::ObjectStorageConstants::Private;
+    use std::vec::Vec;
+
+    fn key(object_type: TransientObjectType, bits: usize, secret: &[u8]) -> TransientObject {
+        let mut key = TransientObject::allocate(object_type, bits).unwrap();
+        let attr = AttributeMemref::from_ref(AttributeId::SecretValue, secret);
+        key.populate(&[attr.into()]).unwrap();
+        key
+    }
+
+    fn unhex(text: &str) -> Vec<u8> {
+        hex::decode(text).unwrap()
+    }
+
+    #[test]
+    fn test_digest() {
+        let digest = Digest::allocate(AlgorithmId::Sha256).unwrap();
+        let mut hash = [0u8; 32];
+        digest.update(b"a");
+        assert_eq!(digest.do_final(b"bc", &mut hash).unwrap(), 32);
+        assert_eq!(
+            hash.to_vec(),
+            unhex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        let mut short = [0u8; 16];
+        assert_eq!(digest.do_final(b"", &mut short).unwrap_err().kind(), ErrorKind::ShortBuffer);
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231, test case 2
+        let key = key(TransientObjectType::HmacSha256, 32, b"Jefe");
+        let mac = Mac::allocate(AlgorithmId::HmacSha256, 256).unwrap();
+        mac.set_key(&key).unwrap();
+        mac.init(&[]);
+        mac.update(b"what do ya want ");
+        let mut out = [0u8; 32];
+        assert_eq!(mac.compute_final(b"for nothing?", &mut out).unwrap(), 32);
+        let expected = unhex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert_eq!(out.to_vec(), expected);
+
+        mac.init(&[]);
+        assert!(mac.compare_final(b"what do ya want for nothing?", &expected).is_ok());
+        mac.init(&[]);
+        let err = mac.compare_final(b"what do ya want for something?", &expected).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MacInvalid);
+    }
+
+    #[test]
+    fn test_cmac() {
+        // RFC 4493, examples 1, 2 and 3
+        let key = key(TransientObjectType::Aes, 128, &unhex("2b7e151628aed2a6abf7158809cf4f3c"));
+        let mac = Mac::allocate(AlgorithmId::AesCmac, 128).unwrap();
+        mac.set_key(&key).unwrap();
+        let message = unhex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411",
+        );
+        for (len, expected) in [
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+        ] {
+            let mut out = [0u8; 16];
+            mac.init(&[]);
+            mac.compute_final(&message[..len], &mut out).unwrap();
+            assert_eq!(out.to_vec(), unhex(expected));
+        }
+    }
+
+    #[test]
+    fn test_cipher() {
+        let key = key(TransientObjectType::Aes, 128, &[7u8; 16]);
+        let plain: Vec<u8> = (0u8..48).collect();
+        let iv = [1u8; 16];
+        let mut encrypted = [0u8; 48];
+        let mut decrypted = [0u8; 48];
+
+        let cipher = Cipher::allocate(AlgorithmId::AesCbcNopad, OperationMode::Encrypt, 128)
+            .unwrap();
+        cipher.set_key(&key).unwrap();
+        cipher.init(&iv);
+        // input is buffered up to a whole block
+        assert_eq!(cipher.update(&plain[..20], &mut encrypted).unwrap(), 16);
+        assert_eq!(cipher.do_final(&plain[20..], &mut encrypted[16..]).unwrap(), 32);
+
+        let cipher = Cipher::allocate(AlgorithmId::AesCbcNopad, OperationMode::Decrypt, 128)
+            .unwrap();
+        cipher.set_key(&key).unwrap();
+        cipher.init(&iv);
+        assert_eq!(cipher.do_final(&encrypted, &mut decrypted).unwrap(), 48);
+        assert_eq!(decrypted.to_vec(), plain);
+
+        cipher.init(&iv);
+        let err = cipher.do_final(&encrypted[..20], &mut decrypted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+
+    #[test]
+    fn test_gcm() {
+        // GCM specification, test case 2
+        let key = key(TransientObjectType::Aes, 128, &[0u8; 16]);
+        let nonce = [0u8; 12];
+        let ae = AE::allocate(AlgorithmId::AesGcm, OperationMode::Encrypt, 128).unwrap();
+        ae.set_key(&key).unwrap();
+        ae.init(&nonce, 128, 0, 16).unwrap();
+        let mut encrypted = [0u8; 16];
+        let mut tag = [0u8; 16];
+        assert_eq!(ae.encrypt_final(&[0u8; 16], &mut encrypted, &mut tag).unwrap(), (16, 16));
+        assert_eq!(encrypted.to_vec(), unhex("0388dace60b6a392f328c2b971b2fe78"));
+        assert_eq!(tag.to_vec(), unhex("ab6e47d42cec13bdf53a67b21257bddf"));
+
+        // round trip with AAD
+        ae.init(&nonce, 96, 4, 5).unwrap();
+        ae.update_aad(b"head");
+        let mut tag = [0u8; 12];
+        ae.encrypt_final(b"hello", &mut encrypted, &mut tag).unwrap();
+
+        let ae = AE::allocate(AlgorithmId::AesGcm, OperationMode::Decrypt, 128).unwrap();
+        ae.set_key(&key).unwrap();
+        let mut decrypted = [0u8; 5];
+        ae.init(&nonce, 96, 4, 5).unwrap();
+        ae.update_aad(b"head");
+        assert_eq!(ae.decrypt_final(&encrypted[..5], &mut decrypted, &tag).unwrap(), 5);
+        assert_eq!(&decrypted, b"hello");
+
+        ae.init(&nonce, 96, 4, 5).unwrap();
+        ae.update_aad(b"body");
+        let err = ae.decrypt_final(&encrypted[..5], &mut decrypted, &tag).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MacInvalid);
+    }
+
+    #[test]
+    fn test_rsa_signature() {
+        let modulus = unhex(
+            "f45497ec3b773ea7ecba2ac0fcafa833cb011a4690db3df324f79ec3e87e039e\
+             ae0c848489649ffbad32c79527469e2819ae840f7ddeee3c2eeb92f7601f55e5",
+        );
+        let exponent = unhex("010001");
+        let private_exponent = unhex(
+            "938b6212edf61c881b9470ad09eaa9905d4ed52737e2ad8e5f0c897b94b2444c\
+             288e67fc9e9baccfff55d1a7bb2ede645cd1e5ae83f6c82242118e9f12a3d121",
+        );
+        // signature of "abc" made with OpenSSL
+        let expected = unhex(
+            "11790990af0e3ebeacb4768d19e994815620a58b420f0f00cc4217702893fb97\
+             22bc0d33cfee9eba0299bab5a6dba69650fd97d5562cfe8397ac047311543227",
+        );
+        let mut key = TransientObject::allocate(TransientObjectType::RsaKeypair, 512).unwrap();
+        key.populate(&[
+            AttributeMemref::from_ref(AttributeId::RsaModulus, &modulus).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPublicExponent, &exponent).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPrivateExponent, &private_exponent).into(),
+        ])
+        .unwrap();
+        let mut hash = [0u8; 32];
+        Digest::allocate(AlgorithmId::Sha256).unwrap().do_final(b"abc", &mut hash).unwrap();
+
+        let sign = Asymmetric::allocate(AlgorithmId::RsassaPkcs1V15Sha256, OperationMode::Sign, 512)
+            .unwrap();
+        sign.set_key(&key).unwrap();
+        let mut signature = [0u8; 64];
+        assert_eq!(sign.sign_digest(&[], &hash, &mut signature).unwrap(), 64);
+        assert_eq!(signature.to_vec(), expected);
+
+        let verify =
+            Asymmetric::allocate(AlgorithmId::RsassaPkcs1V15Sha256, OperationMode::Verify, 512)
+                .unwrap();
+        verify.set_key(&key).unwrap();
+        assert!(verify.verify_digest(&[], &hash, &signature).is_ok());
+        signature[0] ^= 1;
+        let err = verify.verify_digest(&[], &hash, &signature).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::SignatureInvalid);
+    }
+
+    #[test]
+    fn test_persistent_object() {
+        let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE | DataFlag::ACCESS_WRITE_META;
+        let mut object = PersistentObject::create(Private, b"a", flags, None, b"hello").unwrap();
+        assert_eq!(
+            PersistentObject::create(Private, b"a", flags, None, b"").err().unwrap().kind(),
+            ErrorKind::AccessConflict
+        );
+        // the handle is exclusive without share flags
+        assert_eq!(
+            PersistentObject::open(Private, b"a", DataFlag::ACCESS_READ).err().unwrap().kind(),
+            ErrorKind::AccessConflict
+        );
+
+        object.seek(0, Whence::DataSeekEnd).unwrap();
+        object.write(b" world").unwrap();
+        object.seek(-5, Whence::DataSeekCur).unwrap();
+        let mut buffer = [0u8; 16];
+        assert_eq!(object.read(&mut buffer).unwrap(), 5);
+        assert_eq!(&buffer[..5], b"world");
+        object.rename(b"b").unwrap();
+        drop(object);
+
+        assert_eq!(
+            PersistentObject::open(Private, b"a", flags).err().unwrap().kind(),
+            ErrorKind::ItemNotFound
+        );
+        let mut object = PersistentObject::open(Private, b"b", DataFlag::ACCESS_READ).unwrap();
+        assert_eq!(object.info().unwrap().data_size(), 11);
+        assert_eq!(object.write(b"!").unwrap_err().kind(), ErrorKind::AccessConflict);
+        drop(object);
+
+        let mut enumerator = ObjectEnumHandle::allocate().unwrap();
+        enumerator.start(Private).unwrap();
+        let mut info = ObjectInfo::from_raw(unsafe { core::mem::zeroed() });
+        let mut id = [0u8; 64];
+        assert_eq!(enumerator.get_next::<()>(&mut info, &mut id).unwrap(), 1);
+        assert_eq!(id[0], b'b');
+        assert!(enumerator.get_next::<()>(&mut info, &mut id).is_err());
+        enumerator.start(Private).unwrap();
+        let (id, info) = enumerator.next_object().unwrap().unwrap();
+        assert_eq!((id.as_slice(), info.data_size()), (&b"b"[..], 11));
+        assert!(enumerator.next_object().unwrap().is_none());
+
+        let mut object = PersistentObject::open(Private, b"b", flags).unwrap();
+        object.close_and_delete().unwrap();
+        core::mem::forget(object);
+        assert!(enumerator.start(Private).is_err());
+    }
+
+    #[test]
+    fn test_storage_is_per_thread() {
+        PersistentObject::create(Private, b"id", DataFlag::ACCESS_READ, None, b"").unwrap();
+        std::thread::spawn(|| {
+            assert!(PersistentObject::open(Private, b"id", DataFlag::ACCESS_READ).is_err());
+        })
+        .join()
+        .unwrap();
+        super::reset();
+        assert!(PersistentObject::open(Private, b"id", DataFlag::ACCESS_READ).is_err());
+    }
+
+    #[test]
+    fn test_persistent_time() {
+        let mut time = Time::new();
+        assert_eq!(time.ta_time().unwrap_err().kind(), ErrorKind::TimeNotSet);
+        time.seconds = 1000;
+        time.set_ta_time().unwrap();
+        let mut now = Time::new();
+        now.ta_time().unwrap();
+        assert!(now.seconds >= 1000 && now.seconds < 1010);
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let identity = Identity::current_client().unwrap();
+        assert_eq!(identity.login, Login::Public);
+        assert_eq!(identity.uuid.to_bytes(), [0; 16]);
+
+        let uuid = Uuid::from_bytes([7; 16]);
+        super::set_client_identity(super::raw::TEE_LOGIN_TRUSTED_APP, &uuid);
+        let identity = Identity::current_client().unwrap();
+        assert_eq!(identity.login, Login::TrustedApp);
+        assert_eq!(identity.uuid.to_bytes(), [7; 16]);
+    }
+}
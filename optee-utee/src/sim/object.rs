@@ -0,0 +1,855 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+
+//! Transient and persistent objects, with the persistent objects of each
+//! thread kept in memory.
+
+use super::{slice, slice_mut, write_output};
+use core::ffi::c_void;
+use optee_utee_sys as raw;
+use std::boxed::Box;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::vec::Vec;
+
+
+const DATA_FLAG_ACCESS: u32 = raw::TEE_DATA_FLAG_ACCESS_READ
+    | raw::TEE_DATA_FLAG_ACCESS_WRITE
+    | raw::TEE_DATA_FLAG_ACCESS_WRITE_META
+    | raw::TEE_DATA_FLAG_SHARE_READ
+    | raw::TEE_DATA_FLAG_SHARE_WRITE;
+
+std::thread_local! {
+    static STORAGE: RefCell<Vec<Rc<RefCell<Entry>>>> = const { RefCell::new(Vec::new()) };
+    static RPMB_AVAILABLE: Cell<bool> = const { Cell::new(true) };
+}
+
+pub(super) fn reset_storage() {
+    STORAGE.with(|storage| storage.borrow_mut().clear());
+    RPMB_AVAILABLE.with(|available| available.set(true));
+}
+
+pub(super) fn set_rpmb_available(available: bool) {
+    RPMB_AVAILABLE.with(|rpmb| rpmb.set(available));
+}
+
+#[derive(Clone)]
+pub(super) enum AttributeValue {
+    Ref(Vec<u8>),
+    Value(u32, u32),
+}
+
+#[derive(Clone)]
+pub(super) struct Attribute {
+    pub id: u32,
+    pub value: AttributeValue,
+}
+
+/// The key material and usage of an object.
+#[derive(Clone)]
+pub(super) struct Key {
+    pub object_type: u32,
+    /// Key size in bits.
+    pub object_size: u32,
+    pub max_object_size: u32,
+    pub usage: u32,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Key {
+    fn empty(object_type: u32, max_object_size: u32) -> Self {
+        Self {
+            object_type,
+            object_size: 0,
+            max_object_size,
+            usage: 0xFFFF_FFFF,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn buffer(&self, id: u32) -> Option<&[u8]> {
+        self.attributes.iter().find(|attr| attr.id == id).and_then(|attr| match &attr.value {
+            AttributeValue::Ref(buffer) => Some(&buffer[..]),
+            AttributeValue::Value(..) => None,
+        })
+    }
+
+    pub fn value(&self, id: u32) -> Option<(u32, u32)> {
+        self.attributes.iter().find(|attr| attr.id == id).and_then(|attr| match attr.value {
+            AttributeValue::Value(a, b) => Some((a, b)),
+            AttributeValue::Ref(_) => None,
+        })
+    }
+}
+
+/// A persistent object in storage.
+struct Entry {
+    storage: u32,
+    id: Vec<u8>,
+    key: Key,
+    data: Vec<u8>,
+    /// Flags of the handles currently open.
+    handles: Vec<(usize, u32)>,
+}
+
+struct Persistent {
+    entry: Rc<RefCell<Entry>>,
+    flags: u32,
+    position: usize,
+}
+
+pub(super) struct Object {
+    pub key: Key,
+    initialized: bool,
+    persistent: Option<Persistent>,
+}
+
+impl Object {
+    fn into_handle(self) -> raw::TEE_ObjectHandle {
+        Box::into_raw(Box::new(self)) as raw::TEE_ObjectHandle
+    }
+
+    fn handle_flags(&self) -> u32 {
+        match &self.persistent {
+            Some(persistent) => {
+                raw::TEE_HANDLE_FLAG_PERSISTENT
+                    | raw::TEE_HANDLE_FLAG_INITIALIZED
+                    | persistent.flags
+            }
+            None if self.initialized => raw::TEE_HANDLE_FLAG_INITIALIZED,
+            None => 0,
+        }
+    }
+}
+
+/// Returns the object behind `handle`, which must come from this module.
+pub(super) unsafe fn object<'a>(handle: raw::TEE_ObjectHandle) -> &'a mut Object {
+    assert!(!handle.is_null(), "invalid object handle");
+    &mut *(handle as *mut Object)
+}
+
+fn object_info(object: &Object) -> raw::TEE_ObjectInfo {
+    let (data_size, data_position) = match &object.persistent {
+        Some(persistent) => (persistent.entry.borrow().data.len(), persistent.position),
+        None => (0, 0),
+    };
+    raw::TEE_ObjectInfo {
+        objectType: object.key.object_type,
+        objectSize: object.key.object_size,
+        maxObjectSize: object.key.max_object_size,
+        objectUsage: object.key.usage,
+        dataSize: data_size,
+        dataPosition: data_position,
+        handleFlags: object.handle_flags(),
+    }
+}
+
+/// Returns the largest key size in bits of `object_type`, or `None` if the
+/// type is not supported.
+fn max_key_size(object_type: u32) -> Option<u32> {
+    match object_type {
+        raw::TEE_TYPE_AES => Some(256),
+        raw::TEE_TYPE_HMAC_SHA1
+        | raw::TEE_TYPE_HMAC_SHA224
+        | raw::TEE_TYPE_HMAC_SHA256
+        | raw::TEE_TYPE_HMAC_SHA384
+        | raw::TEE_TYPE_HMAC_SHA512
+        | raw::TEE_TYPE_GENERIC_SECRET => Some(4096),
+        raw::TEE_TYPE_RSA_PUBLIC_KEY | raw::TEE_TYPE_RSA_KEYPAIR => Some(4096),
+        raw::TEE_TYPE_ECDSA_PUBLIC_KEY | raw::TEE_TYPE_ECDSA_KEYPAIR => Some(521),
+        raw::TEE_TYPE_ED25519_PUBLIC_KEY | raw::TEE_TYPE_X25519_PUBLIC_KEY => Some(256),
+        raw::TEE_TYPE_DATA => Some(0),
+        _ => None,
+    }
+}
+
+/// Returns the size in bits of a big-endian number.
+fn bit_len(number: &[u8]) -> u32 {
+    match number.iter().position(|byte| *byte != 0) {
+        Some(i) => ((number.len() - i) * 8) as u32 - number[i].leading_zeros(),
+        None => 0,
+    }
+}
+
+/// Returns the size in bits of the NIST curve `curve`.
+pub(super) fn curve_bits(curve: u32) -> Option<u32> {
+    match curve {
+        raw::TEE_ECC_CURVE_NIST_P192 => Some(192),
+        raw::TEE_ECC_CURVE_NIST_P224 => Some(224),
+        raw::TEE_ECC_CURVE_NIST_P256 => Some(256),
+        raw::TEE_ECC_CURVE_NIST_P384 => Some(384),
+        raw::TEE_ECC_CURVE_NIST_P521 => Some(521),
+        _ => None,
+    }
+}
+
+fn key_size(key: &Key) -> Option<u32> {
+    match key.object_type {
+        raw::TEE_TYPE_RSA_PUBLIC_KEY | raw::TEE_TYPE_RSA_KEYPAIR => {
+            key.buffer(raw::TEE_ATTR_RSA_MODULUS).map(bit_len)
+        }
+        raw::TEE_TYPE_ECDSA_PUBLIC_KEY | raw::TEE_TYPE_ECDSA_KEYPAIR => {
+            curve_bits(key.value(raw::TEE_ATTR_ECC_CURVE)?.0)
+        }
+        raw::TEE_TYPE_ED25519_PUBLIC_KEY | raw::TEE_TYPE_X25519_PUBLIC_KEY => {
+            let public_value = required_attributes(key.object_type)[0];
+            (key.buffer(public_value)?.len() == 32).then_some(256)
+        }
+        raw::TEE_TYPE_AES => match key.buffer(raw::TEE_ATTR_SECRET_VALUE)?.len() {
+            len @ (16 | 24 | 32) => Some(len as u32 * 8),
+            _ => None,
+        },
+        _ => key.buffer(raw::TEE_ATTR_SECRET_VALUE).map(|value| value.len() as u32 * 8),
+    }
+}
+
+/// Attributes an object of `object_type` must be populated with.
+fn required_attributes(object_type: u32) -> &'static [u32] {
+    match object_type {
+        raw::TEE_TYPE_RSA_PUBLIC_KEY => {
+            &[raw::TEE_ATTR_RSA_MODULUS, raw::TEE_ATTR_RSA_PUBLIC_EXPONENT]
+        }
+        raw::TEE_TYPE_RSA_KEYPAIR => &[
+            raw::TEE_ATTR_RSA_MODULUS,
+            raw::TEE_ATTR_RSA_PUBLIC_EXPONENT,
+            raw::TEE_ATTR_RSA_PRIVATE_EXPONENT,
+        ],
+        raw::TEE_TYPE_ECDSA_PUBLIC_KEY => &[
+            raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+            raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+            raw::TEE_ATTR_ECC_CURVE,
+        ],
+        raw::TEE_TYPE_ECDSA_KEYPAIR => &[
+            raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+            raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+            raw::TEE_ATTR_ECC_PRIVATE_VALUE,
+            raw::TEE_ATTR_ECC_CURVE,
+        ],
+        raw::TEE_TYPE_ED25519_PUBLIC_KEY => &[raw::TEE_ATTR_ED25519_PUBLIC_VALUE],
+        raw::TEE_TYPE_X25519_PUBLIC_KEY => &[raw::TEE_ATTR_X25519_PUBLIC_VALUE],
+        _ => &[raw::TEE_ATTR_SECRET_VALUE],
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_AllocateTransientObject(
+    object_type: raw::TEE_ObjectType,
+    max_object_size: u32,
+    object: *mut raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    match max_key_size(object_type) {
+        Some(max) if max_object_size <= max && object_type != raw::TEE_TYPE_DATA => (),
+        _ => return raw::TEE_ERROR_NOT_SUPPORTED,
+    }
+    let transient = Object {
+        key: Key::empty(object_type, max_object_size),
+        initialized: false,
+        persistent: None,
+    };
+    unsafe { *object = transient.into_handle() };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_FreeTransientObject(object: raw::TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    assert!(unsafe { self::object(object) }.persistent.is_none(), "not a transient object");
+    drop(unsafe { Box::from_raw(object as *mut Object) });
+}
+
+#[no_mangle]
+extern "C" fn TEE_ResetTransientObject(object: raw::TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    let object = unsafe { self::object(object) };
+    object.key = Key::empty(object.key.object_type, object.key.max_object_size);
+    object.initialized = false;
+}
+
+#[no_mangle]
+extern "C" fn TEE_PopulateTransientObject(
+    object: raw::TEE_ObjectHandle,
+    attrs: *const raw::TEE_Attribute,
+    attr_count: u32,
+) -> raw::TEE_Result {
+    let object = unsafe { self::object(object) };
+    assert!(!object.initialized, "object already populated");
+    let attrs = match attr_count {
+        0 => &[],
+        _ => unsafe { core::slice::from_raw_parts(attrs, attr_count as usize) },
+    };
+
+    let mut key = Key::empty(object.key.object_type, object.key.max_object_size);
+    for attr in attrs {
+        let value = if attr.attributeID & raw::TEE_ATTR_FLAG_VALUE != 0 {
+            let value = unsafe { attr.content.value };
+            AttributeValue::Value(value.a, value.b)
+        } else {
+            let memref = unsafe { attr.content.memref };
+            AttributeValue::Ref(unsafe { slice(memref.buffer, memref.size) }.to_vec())
+        };
+        key.attributes.push(Attribute {
+            id: attr.attributeID,
+            value,
+        });
+    }
+    let required = required_attributes(key.object_type);
+    if required.iter().any(|id| key.attributes.iter().all(|attr| attr.id != *id)) {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    key.object_size = match key_size(&key) {
+        Some(size) if size <= key.max_object_size => size,
+        _ => return raw::TEE_ERROR_BAD_PARAMETERS,
+    };
+    object.key = key;
+    object.initialized = true;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn TEE_InitRefAttribute(
+    attr: *mut raw::TEE_Attribute,
+    attribute_id: u32,
+    buffer: *const c_void,
+    length: usize,
+) {
+    (*attr).attributeID = attribute_id;
+    (*attr).content.memref = raw::Memref {
+        buffer: buffer as *mut c_void,
+        size: length,
+    };
+}
+
+#[no_mangle]
+unsafe extern "C" fn TEE_InitValueAttribute(
+    attr: *mut raw::TEE_Attribute,
+    attribute_id: u32,
+    a: u32,
+    b: u32,
+) {
+    (*attr).attributeID = attribute_id;
+    (*attr).content.value = raw::Value { a, b };
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetObjectInfo1(
+    object: raw::TEE_ObjectHandle,
+    info: *mut raw::TEE_ObjectInfo,
+) -> raw::TEE_Result {
+    unsafe { *info = object_info(self::object(object)) };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetObjectInfo(object: raw::TEE_ObjectHandle, info: *mut raw::TEE_ObjectInfo) {
+    TEE_GetObjectInfo1(object, info);
+}
+
+#[no_mangle]
+extern "C" fn TEE_RestrictObjectUsage1(
+    object: raw::TEE_ObjectHandle,
+    usage: u32,
+) -> raw::TEE_Result {
+    let object = unsafe { self::object(object) };
+    object.key.usage &= usage;
+    if let Some(persistent) = &object.persistent {
+        persistent.entry.borrow_mut().key.usage &= usage;
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_RestrictObjectUsage(object: raw::TEE_ObjectHandle, usage: u32) {
+    TEE_RestrictObjectUsage1(object, usage);
+}
+
+fn find_attribute(key: &Key, id: u32) -> Result<&AttributeValue, raw::TEE_Result> {
+    if id & raw::TEE_ATTR_FLAG_PUBLIC == 0 && key.usage & raw::TEE_USAGE_EXTRACTABLE == 0 {
+        return Err(raw::TEE_ERROR_ACCESS_DENIED);
+    }
+    key.attributes
+        .iter()
+        .find(|attr| attr.id == id)
+        .map(|attr| &attr.value)
+        .ok_or(raw::TEE_ERROR_ITEM_NOT_FOUND)
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetObjectBufferAttribute(
+    object: raw::TEE_ObjectHandle,
+    attribute_id: u32,
+    buffer: *mut c_void,
+    size: *mut usize,
+) -> raw::TEE_Result {
+    let object = unsafe { self::object(object) };
+    match find_attribute(&object.key, attribute_id) {
+        Ok(AttributeValue::Ref(value)) => unsafe { write_output(value, buffer, size) },
+        Ok(AttributeValue::Value(..)) => raw::TEE_ERROR_BAD_PARAMETERS,
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetObjectValueAttribute(
+    object: raw::TEE_ObjectHandle,
+    attribute_id: u32,
+    a: *mut u32,
+    b: *mut u32,
+) -> raw::TEE_Result {
+    let object = unsafe { self::object(object) };
+    match find_attribute(&object.key, attribute_id) {
+        Ok(AttributeValue::Value(value_a, value_b)) => {
+            unsafe {
+                if !a.is_null() {
+                    *a = *value_a;
+                }
+                if !b.is_null() {
+                    *b = *value_b;
+                }
+            }
+            raw::TEE_SUCCESS
+        }
+        Ok(AttributeValue::Ref(_)) => raw::TEE_ERROR_BAD_PARAMETERS,
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_CopyObjectAttributes1(
+    dest: raw::TEE_ObjectHandle,
+    src: raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    let src = unsafe { object(src) }.key.clone();
+    let dest = unsafe { object(dest) };
+    assert!(!dest.initialized, "object already populated");
+    let compatible = src.object_type == dest.key.object_type
+        || (src.object_type == raw::TEE_TYPE_RSA_KEYPAIR
+            && dest.key.object_type == raw::TEE_TYPE_RSA_PUBLIC_KEY)
+        || (src.object_type == raw::TEE_TYPE_ECDSA_KEYPAIR
+            && dest.key.object_type == raw::TEE_TYPE_ECDSA_PUBLIC_KEY);
+    if !compatible || src.object_size > dest.key.max_object_size {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    let required = required_attributes(dest.key.object_type);
+    dest.key.attributes = src
+        .attributes
+        .into_iter()
+        .filter(|attr| required.contains(&attr.id))
+        .collect();
+    dest.key.object_size = src.object_size;
+    dest.key.usage = src.usage;
+    dest.initialized = true;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_CopyObjectAttributes(dest: raw::TEE_ObjectHandle, src: raw::TEE_ObjectHandle) {
+    TEE_CopyObjectAttributes1(dest, src);
+}
+
+// `is_multiple_of` is newer than the pinned toolchain.
+#[allow(unknown_lints, clippy::manual_is_multiple_of)]
+#[no_mangle]
+extern "C" fn TEE_GenerateKey(
+    object: raw::TEE_ObjectHandle,
+    key_size: u32,
+    _params: *const raw::TEE_Attribute,
+    _param_count: u32,
+) -> raw::TEE_Result {
+    let object = unsafe { self::object(object) };
+    assert!(!object.initialized, "object already populated");
+    match object.key.object_type {
+        raw::TEE_TYPE_AES if ![128, 192, 256].contains(&key_size) => {
+            return raw::TEE_ERROR_NOT_SUPPORTED
+        }
+        // RSA and ECDSA key generation is not simulated
+        raw::TEE_TYPE_RSA_KEYPAIR | raw::TEE_TYPE_ECDSA_KEYPAIR => {
+            return raw::TEE_ERROR_NOT_SUPPORTED
+        }
+        _ if key_size % 8 != 0 || key_size > object.key.max_object_size => {
+            return raw::TEE_ERROR_NOT_SUPPORTED
+        }
+        _ => (),
+    }
+    let mut value = std::vec![0u8; key_size as usize / 8];
+    getrandom::getrandom(&mut value).expect("no random source");
+    object.key.attributes = std::vec![Attribute {
+        id: raw::TEE_ATTR_SECRET_VALUE,
+        value: AttributeValue::Ref(value),
+    }];
+    object.key.object_size = key_size;
+    object.initialized = true;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_CloseObject(object: raw::TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    let object = unsafe { Box::from_raw(object as *mut Object) };
+    if let Some(persistent) = &object.persistent {
+        let handle = &*object as *const Object as usize;
+        persistent.entry.borrow_mut().handles.retain(|(open, _)| *open != handle);
+    }
+}
+
+/// Maps `storage_id` to the storage it refers to, or `None` if there is no
+/// such storage.
+///
+/// `TEE_STORAGE_PRIVATE` refers to the REE FS storage, as with OP-TEE's
+/// default configuration.
+fn storage(storage_id: u32) -> Option<u32> {
+    match storage_id {
+        raw::TEE_STORAGE_PRIVATE => Some(raw::TEE_STORAGE_PRIVATE_REE),
+        raw::TEE_STORAGE_PRIVATE_REE => Some(storage_id),
+        raw::TEE_STORAGE_PRIVATE_RPMB if RPMB_AVAILABLE.with(Cell::get) => Some(storage_id),
+        _ => None,
+    }
+}
+
+fn find_entry(storage: u32, id: &[u8]) -> Option<Rc<RefCell<Entry>>> {
+    STORAGE.with(|entries| {
+        entries
+            .borrow()
+            .iter()
+            .find(|entry| {
+                let entry = entry.borrow();
+                entry.storage == storage && entry.id == id
+            })
+            .cloned()
+    })
+}
+
+fn remove_entry(entry: &Rc<RefCell<Entry>>) {
+    STORAGE.with(|entries| entries.borrow_mut().retain(|other| !Rc::ptr_eq(other, entry)));
+}
+
+/// Opens a handle to `entry`, returning `ACCESS_CONFLICT` if the sharing
+/// flags of the handles already open do not allow it.
+fn open_handle(entry: Rc<RefCell<Entry>>, flags: u32) -> Result<Box<Object>, raw::TEE_Result> {
+    let share = raw::TEE_DATA_FLAG_SHARE_READ | raw::TEE_DATA_FLAG_SHARE_WRITE;
+    let conflict = entry.borrow().handles.iter().any(|(_, open)| {
+        (open & share) != (flags & share)
+            || (flags & raw::TEE_DATA_FLAG_ACCESS_READ != 0
+                && open & raw::TEE_DATA_FLAG_SHARE_READ == 0)
+            || (flags & raw::TEE_DATA_FLAG_ACCESS_WRITE != 0
+                && open & raw::TEE_DATA_FLAG_SHARE_WRITE == 0)
+    });
+    if conflict {
+        return Err(raw::TEE_ERROR_ACCESS_CONFLICT);
+    }
+    let key = entry.borrow().key.clone();
+    let object = Box::new(Object {
+        key,
+        initialized: true,
+        persistent: Some(Persistent {
+            entry: entry.clone(),
+            flags: flags & DATA_FLAG_ACCESS,
+            position: 0,
+        }),
+    });
+    let handle = &*object as *const Object as usize;
+    entry.borrow_mut().handles.push((handle, flags));
+    Ok(object)
+}
+
+fn persistent<'a>(object: raw::TEE_ObjectHandle) -> &'a mut Persistent {
+    unsafe { self::object(object) }.persistent.as_mut().expect("not a persistent object")
+}
+
+#[no_mangle]
+extern "C" fn TEE_OpenPersistentObject(
+    storage_id: u32,
+    object_id: *const c_void,
+    object_id_len: usize,
+    flags: u32,
+    object: *mut raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    if object_id_len > raw::TEE_OBJECT_ID_MAX_LEN as usize {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    let id = unsafe { slice(object_id, object_id_len) };
+    let entry = match storage(storage_id).and_then(|storage| find_entry(storage, id)) {
+        Some(entry) => entry,
+        None => return raw::TEE_ERROR_ITEM_NOT_FOUND,
+    };
+    match open_handle(entry, flags) {
+        Ok(handle) => {
+            unsafe { *object = Box::into_raw(handle) as raw::TEE_ObjectHandle };
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_CreatePersistentObject(
+    storage_id: u32,
+    object_id: *const c_void,
+    object_id_len: usize,
+    flags: u32,
+    attributes: raw::TEE_ObjectHandle,
+    initial_data: *const c_void,
+    initial_data_len: usize,
+    object: *mut raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    if object_id_len > raw::TEE_OBJECT_ID_MAX_LEN as usize {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    let storage = match storage(storage_id) {
+        Some(storage) => storage,
+        None => return raw::TEE_ERROR_ITEM_NOT_FOUND,
+    };
+    let id = unsafe { slice(object_id, object_id_len) };
+    if let Some(existing) = find_entry(storage, id) {
+        if flags & raw::TEE_DATA_FLAG_OVERWRITE == 0 || !existing.borrow().handles.is_empty() {
+            return raw::TEE_ERROR_ACCESS_CONFLICT;
+        }
+        remove_entry(&existing);
+    }
+
+    let key = match attributes.is_null() {
+        true => Key::empty(raw::TEE_TYPE_DATA, 0),
+        false => unsafe { self::object(attributes) }.key.clone(),
+    };
+    let entry = Rc::new(RefCell::new(Entry {
+        storage,
+        id: id.to_vec(),
+        key,
+        data: unsafe { slice(initial_data, initial_data_len) }.to_vec(),
+        handles: Vec::new(),
+    }));
+    STORAGE.with(|entries| entries.borrow_mut().push(entry.clone()));
+
+    if !object.is_null() {
+        let handle = open_handle(entry, flags).expect("new object has no open handles");
+        unsafe { *object = Box::into_raw(handle) as raw::TEE_ObjectHandle };
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_CloseAndDeletePersistentObject1(
+    object: raw::TEE_ObjectHandle,
+) -> raw::TEE_Result {
+    if object.is_null() {
+        return raw::TEE_SUCCESS;
+    }
+    let persistent = persistent(object);
+    if persistent.flags & raw::TEE_DATA_FLAG_ACCESS_WRITE_META == 0 {
+        return raw::TEE_ERROR_ACCESS_CONFLICT;
+    }
+    remove_entry(&persistent.entry);
+    TEE_CloseObject(object);
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_CloseAndDeletePersistentObject(object: raw::TEE_ObjectHandle) {
+    TEE_CloseAndDeletePersistentObject1(object);
+}
+
+#[no_mangle]
+extern "C" fn TEE_RenamePersistentObject(
+    object: raw::TEE_ObjectHandle,
+    new_object_id: *const c_void,
+    new_object_id_len: usize,
+) -> raw::TEE_Result {
+    let persistent = persistent(object);
+    if persistent.flags & raw::TEE_DATA_FLAG_ACCESS_WRITE_META == 0 {
+        return raw::TEE_ERROR_ACCESS_CONFLICT;
+    }
+    if new_object_id_len > raw::TEE_OBJECT_ID_MAX_LEN as usize {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    let new_id = unsafe { slice(new_object_id, new_object_id_len) };
+    let storage = persistent.entry.borrow().storage;
+    match find_entry(storage, new_id) {
+        Some(other) if !Rc::ptr_eq(&other, &persistent.entry) => raw::TEE_ERROR_ACCESS_CONFLICT,
+        _ => {
+            persistent.entry.borrow_mut().id = new_id.to_vec();
+            raw::TEE_SUCCESS
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_ReadObjectData(
+    object: raw::TEE_ObjectHandle,
+    buffer: *mut c_void,
+    size: usize,
+    count: *mut usize,
+) -> raw::TEE_Result {
+    let persistent = persistent(object);
+    if persistent.flags & raw::TEE_DATA_FLAG_ACCESS_READ == 0 {
+        return raw::TEE_ERROR_ACCESS_CONFLICT;
+    }
+    let entry = persistent.entry.borrow();
+    let start = persistent.position.min(entry.data.len());
+    let read = size.min(entry.data.len() - start);
+    unsafe {
+        slice_mut(buffer, read).copy_from_slice(&entry.data[start..start + read]);
+        *count = read;
+    }
+    persistent.position += read;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_WriteObjectData(
+    object: raw::TEE_ObjectHandle,
+    buffer: *const c_void,
+    size: usize,
+) -> raw::TEE_Result {
+    let persistent = persistent(object);
+    if persistent.flags & raw::TEE_DATA_FLAG_ACCESS_WRITE == 0 {
+        return raw::TEE_ERROR_ACCESS_CONFLICT;
+    }
+    let end = persistent.position + size;
+    if end > raw::TEE_DATA_MAX_POSITION as usize {
+        return raw::TEE_ERROR_OVERFLOW;
+    }
+    let mut entry = persistent.entry.borrow_mut();
+    if entry.data.len() < end {
+        entry.data.resize(end, 0);
+    }
+    entry.data[persistent.position..end].copy_from_slice(unsafe { slice(buffer, size) });
+    persistent.position = end;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_TruncateObjectData(
+    object: raw::TEE_ObjectHandle,
+    size: usize,
+) -> raw::TEE_Result {
+    let persistent = persistent(object);
+    if persistent.flags & raw::TEE_DATA_FLAG_ACCESS_WRITE == 0 {
+        return raw::TEE_ERROR_ACCESS_CONFLICT;
+    }
+    persistent.entry.borrow_mut().data.resize(size, 0);
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_SeekObjectData(
+    object: raw::TEE_ObjectHandle,
+    offset: i32,
+    whence: raw::TEE_Whence,
+) -> raw::TEE_Result {
+    let persistent = persistent(object);
+    let base = match whence {
+        raw::TEE_Whence::TEE_DATA_SEEK_SET => 0,
+        raw::TEE_Whence::TEE_DATA_SEEK_CUR => persistent.position as i64,
+        raw::TEE_Whence::TEE_DATA_SEEK_END => persistent.entry.borrow().data.len() as i64,
+    };
+    let position = (base + offset as i64).max(0);
+    if position > raw::TEE_DATA_MAX_POSITION as i64 {
+        return raw::TEE_ERROR_OVERFLOW;
+    }
+    persistent.position = position as usize;
+    raw::TEE_SUCCESS
+}
+
+struct Enumerator {
+    /// Objects left to enumerate, once started.
+    entries: Option<Vec<Rc<RefCell<Entry>>>>,
+}
+
+unsafe fn enumerator<'a>(handle: raw::TEE_ObjectEnumHandle) -> &'a mut Enumerator {
+    assert!(!handle.is_null(), "invalid enumerator handle");
+    &mut *(handle as *mut Enumerator)
+}
+
+#[no_mangle]
+extern "C" fn TEE_AllocatePersistentObjectEnumerator(
+    enumerator: *mut raw::TEE_ObjectEnumHandle,
+) -> raw::TEE_Result {
+    let handle = Box::into_raw(Box::new(Enumerator { entries: None }));
+    unsafe { *enumerator = handle as raw::TEE_ObjectEnumHandle };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_FreePersistentObjectEnumerator(enumerator: raw::TEE_ObjectEnumHandle) {
+    if !enumerator.is_null() {
+        drop(unsafe { Box::from_raw(enumerator as *mut Enumerator) });
+    }
+}
+
+#[no_mangle]
+extern "C" fn TEE_ResetPersistentObjectEnumerator(enumerator: raw::TEE_ObjectEnumHandle) {
+    unsafe { self::enumerator(enumerator) }.entries = None;
+}
+
+#[no_mangle]
+extern "C" fn TEE_StartPersistentObjectEnumerator(
+    enumerator: raw::TEE_ObjectEnumHandle,
+    storage_id: u32,
+) -> raw::TEE_Result {
+    let storage = match storage(storage_id) {
+        Some(storage) => storage,
+        None => return raw::TEE_ERROR_ITEM_NOT_FOUND,
+    };
+    let mut entries: Vec<_> = STORAGE.with(|entries| {
+        entries.borrow().iter().filter(|entry| entry.borrow().storage == storage).cloned().collect()
+    });
+    if entries.is_empty() {
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    // objects are returned from the end
+    entries.reverse();
+    unsafe { self::enumerator(enumerator) }.entries = Some(entries);
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn TEE_GetNextPersistentObject(
+    enumerator: raw::TEE_ObjectEnumHandle,
+    info: *mut raw::TEE_ObjectInfo,
+    object_id: *mut c_void,
+    object_id_len: *mut usize,
+) -> raw::TEE_Result {
+    let enumerator = unsafe { self::enumerator(enumerator) };
+    let entry = match enumerator.entries.as_mut().and_then(|entries| entries.pop()) {
+        Some(entry) => entry,
+        None => return raw::TEE_ERROR_ITEM_NOT_FOUND,
+    };
+    let entry = entry.borrow();
+    unsafe {
+        if !info.is_null() {
+            *info = raw::TEE_ObjectInfo {
+                objectType: entry.key.object_type,
+                objectSize: entry.key.object_size,
+                maxObjectSize: entry.key.max_object_size,
+                objectUsage: entry.key.usage,
+                dataSize: entry.data.len(),
+                dataPosition: 0,
+                handleFlags: raw::TEE_HANDLE_FLAG_PERSISTENT | raw::TEE_HANDLE_FLAG_INITIALIZED,
+            };
+        }
+        // the buffer holds TEE_OBJECT_ID_MAX_LEN bytes
+        slice_mut(object_id, entry.id.len()).copy_from_slice(&entry.id);
+        *object_id_len = entry.id.len();
+    }
+    raw::TEE_SUCCESS
+}
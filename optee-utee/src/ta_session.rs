@@ -0,0 +1,401 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sessions with other TAs, through the TA-to-TA client API.
+//!
+//! [`TaSession`] and [`TaOperation`] mirror `optee_teec::Session` and
+//! `optee_teec::Operation` on the host side: an operation carries up to four
+//! typed parameters, and the session is closed when it is dropped.
+//!
+//! # Examples
+//!
+//! ``` no_run
+//! use optee_utee::ta_session::{ParamNone, ParamTmpRef, ParamValue};
+//! use optee_utee::{ParamType, TaOperation, TaSession, Uuid};
+//!
+//! let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+//! let mut session = TaSession::open(&uuid)?;
+//! let name = b"counter";
+//! let p0 = ParamTmpRef::new_input(name);
+//! let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+//! let mut operation = TaOperation::new(&p0, &p1, &ParamNone, &ParamNone);
+//! session.invoke_command(0, &mut operation)?;
+//! let (_, p1, _, _) = operation.parameters();
+//! let remaining = p1.a();
+//! # Ok::<(), optee_utee::Error>(())
+//! ```
+
+use crate::{Error, ParamType, ParamTypes, Result, Uuid};
+use core::{marker::PhantomData, ptr};
+use optee_utee_sys as raw;
+
+/// Timeout value for calls that must not time out.
+pub const TIMEOUT_INFINITE: u32 = raw::TEE_TIMEOUT_INFINITE;
+
+/// A parameter of a [`TaOperation`].
+pub trait Param {
+    fn to_raw(&self) -> raw::TEE_Param;
+    fn param_type(&self) -> ParamType;
+    fn from_raw(raw: raw::TEE_Param, param_type: ParamType) -> Self;
+}
+
+/// A parameter carrying two `u32` values. It is used when the parameter type
+/// is one of `ValueInput`, `ValueOutput` or `ValueInout`.
+pub struct ParamValue {
+    raw: raw::Value,
+    param_type: ParamType,
+}
+
+impl ParamValue {
+    /// Creates a value parameter with two `u32` integers and its type.
+    pub fn new(a: u32, b: u32, param_type: ParamType) -> Self {
+        Self {
+            raw: raw::Value { a, b },
+            param_type,
+        }
+    }
+
+    /// Returns the first value in the value parameter.
+    pub fn a(&self) -> u32 {
+        self.raw.a
+    }
+
+    /// Returns the second value in the value parameter.
+    pub fn b(&self) -> u32 {
+        self.raw.b
+    }
+}
+
+impl Param for ParamValue {
+    fn to_raw(&self) -> raw::TEE_Param {
+        raw::TEE_Param { value: self.raw }
+    }
+
+    fn param_type(&self) -> ParamType {
+        self.param_type
+    }
+
+    fn from_raw(raw: raw::TEE_Param, param_type: ParamType) -> Self {
+        Self {
+            raw: unsafe { raw.value },
+            param_type,
+        }
+    }
+}
+
+/// A parameter which carries no information.
+pub struct ParamNone;
+
+impl Param for ParamNone {
+    fn to_raw(&self) -> raw::TEE_Param {
+        raw::TEE_Param {
+            value: raw::Value { a: 0, b: 0 },
+        }
+    }
+
+    fn param_type(&self) -> ParamType {
+        ParamType::None
+    }
+
+    fn from_raw(_raw: raw::TEE_Param, _param_type: ParamType) -> Self {
+        Self
+    }
+}
+
+/// A reference to a buffer of the calling TA, shared with the called TA for
+/// the duration of the operation. It is used when the parameter type is one
+/// of `MemrefInput`, `MemrefOutput` or `MemrefInout`.
+pub struct ParamTmpRef<'a> {
+    raw: raw::Memref,
+    param_type: ParamType,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> ParamTmpRef<'a> {
+    /// Creates an input only memory reference to `buffer`.
+    pub fn new_input(buffer: &'a [u8]) -> Self {
+        Self {
+            raw: raw::Memref {
+                buffer: buffer.as_ptr() as _,
+                size: buffer.len(),
+            },
+            param_type: ParamType::MemrefInput,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates an output memory reference to `buffer`.
+    pub fn new_output(buffer: &'a mut [u8]) -> Self {
+        Self {
+            raw: raw::Memref {
+                buffer: buffer.as_mut_ptr() as _,
+                size: buffer.len(),
+            },
+            param_type: ParamType::MemrefOutput,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a memory reference to `buffer` which is both read and written
+    /// by the called TA.
+    pub fn new_inout(buffer: &'a mut [u8]) -> Self {
+        Self {
+            param_type: ParamType::MemrefInout,
+            ..Self::new_output(buffer)
+        }
+    }
+
+    /// Returns the size written by the called TA, or the size it needs if it
+    /// returned `ShortBuffer`.
+    pub fn updated_size(&self) -> usize {
+        self.raw.size
+    }
+}
+
+impl<'a> Param for ParamTmpRef<'a> {
+    fn to_raw(&self) -> raw::TEE_Param {
+        raw::TEE_Param { memref: self.raw }
+    }
+
+    fn param_type(&self) -> ParamType {
+        self.param_type
+    }
+
+    fn from_raw(raw: raw::TEE_Param, param_type: ParamType) -> Self {
+        Self {
+            raw: unsafe { raw.memref },
+            param_type,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The payload of an open session or invoke command operation on another TA.
+pub struct TaOperation<A, B, C, D> {
+    params: [raw::TEE_Param; 4],
+    param_types: u32,
+    phantom: PhantomData<(A, B, C, D)>,
+}
+
+impl<A: Param, B: Param, C: Param, D: Param> TaOperation<A, B, C, D> {
+    /// Builds an operation from its four parameters, which are only read:
+    /// the values updated by the called TA are returned by
+    /// [`parameters`](Self::parameters).
+    pub fn new(p0: &A, p1: &B, p2: &C, p3: &D) -> Self {
+        Self {
+            param_types: ParamTypes::new(
+                p0.param_type(),
+                p1.param_type(),
+                p2.param_type(),
+                p3.param_type(),
+            )
+            .into(),
+            params: [p0.to_raw(), p1.to_raw(), p2.to_raw(), p3.to_raw()],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the parameters as updated by the called TA.
+    pub fn parameters(&self) -> (A, B, C, D) {
+        let (f0, f1, f2, f3) = ParamTypes::from(self.param_types).into_flags();
+        (
+            A::from_raw(self.params[0], f0),
+            B::from_raw(self.params[1], f1),
+            C::from_raw(self.params[2], f2),
+            D::from_raw(self.params[3], f3),
+        )
+    }
+}
+
+/// A session with another TA, closed when dropped.
+pub struct TaSession {
+    raw: raw::TEE_TASessionHandle,
+}
+
+impl TaSession {
+    /// Opens a session with the TA identified by `uuid`, without parameters
+    /// or timeout.
+    ///
+    /// # Errors
+    ///
+    /// 1) `ItemNotFound`: the TA is not installed.
+    /// 2) Any error returned by the TA when opening the session; see
+    ///    [`Error::origin`].
+    pub fn open(uuid: &Uuid) -> Result<Self> {
+        let mut operation = TaOperation::new(&ParamNone, &ParamNone, &ParamNone, &ParamNone);
+        Self::open_with_operation(uuid, TIMEOUT_INFINITE, &mut operation)
+    }
+
+    /// Opens a session with the TA identified by `uuid`, passing it the
+    /// parameters of `operation`.
+    ///
+    /// `timeout` is in milliseconds, or [`TIMEOUT_INFINITE`]; once it expires
+    /// the call is cancelled.
+    pub fn open_with_operation<A: Param, B: Param, C: Param, D: Param>(
+        uuid: &Uuid,
+        timeout: u32,
+        operation: &mut TaOperation<A, B, C, D>,
+    ) -> Result<Self> {
+        let mut session = ptr::null_mut();
+        let mut origin = 0;
+        match unsafe {
+            raw::TEE_OpenTASession(
+                uuid.as_raw_ptr(),
+                timeout,
+                operation.param_types,
+                operation.params.as_mut_ptr(),
+                &mut session,
+                &mut origin,
+            )
+        } {
+            raw::TEE_SUCCESS => Ok(Self { raw: session }),
+            code => Err(Error::from_raw_error(code).with_origin(origin.into())),
+        }
+    }
+
+    /// Invokes a command of the TA, without timeout.
+    pub fn invoke_command<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        operation: &mut TaOperation<A, B, C, D>,
+    ) -> Result<()> {
+        self.invoke_command_with_timeout(command_id, TIMEOUT_INFINITE, operation)
+    }
+
+    /// Invokes a command of the TA, cancelling it after `timeout`
+    /// milliseconds.
+    pub fn invoke_command_with_timeout<A: Param, B: Param, C: Param, D: Param>(
+        &mut self,
+        command_id: u32,
+        timeout: u32,
+        operation: &mut TaOperation<A, B, C, D>,
+    ) -> Result<()> {
+        let mut origin = 0;
+        match unsafe {
+            raw::TEE_InvokeTACommand(
+                self.raw,
+                timeout,
+                command_id,
+                operation.param_types,
+                operation.params.as_mut_ptr(),
+                &mut origin,
+            )
+        } {
+            raw::TEE_SUCCESS => Ok(()),
+            code => Err(Error::from_raw_error(code).with_origin(origin.into())),
+        }
+    }
+}
+
+impl Drop for TaSession {
+    fn drop(&mut self) {
+        unsafe { raw::TEE_CloseTASession(self.raw) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{ErrorKind, ErrorOrigin};
+    use core::ffi::c_void;
+    use optee_utee_sys::{TEE_Param, TEE_Result, TEE_TASessionHandle, TEE_UUID};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[no_mangle]
+    extern "C" fn TEE_OpenTASession(
+        _destination: *const TEE_UUID,
+        _timeout: u32,
+        _param_types: u32,
+        _params: *mut TEE_Param,
+        session: *mut TEE_TASessionHandle,
+        _origin: *mut u32,
+    ) -> TEE_Result {
+        unsafe { *session = 0x1000 as *mut c_void as _ };
+        raw::TEE_SUCCESS
+    }
+
+    /// Command 0 adds one to the value in p1 and copies p0 into p2; other
+    /// commands fail in the called TA.
+    #[no_mangle]
+    extern "C" fn TEE_InvokeTACommand(
+        _session: TEE_TASessionHandle,
+        _timeout: u32,
+        command_id: u32,
+        param_types: u32,
+        params: *mut TEE_Param,
+        origin: *mut u32,
+    ) -> TEE_Result {
+        if command_id != 0 {
+            unsafe { *origin = raw::TEE_ORIGIN_TRUSTED_APP };
+            return raw::TEE_ERROR_ACCESS_DENIED;
+        }
+        assert_eq!(
+            param_types,
+            raw::TEE_PARAM_TYPES(
+                raw::TEE_PARAM_TYPE_MEMREF_INPUT,
+                raw::TEE_PARAM_TYPE_VALUE_INOUT,
+                raw::TEE_PARAM_TYPE_MEMREF_OUTPUT,
+                raw::TEE_PARAM_TYPE_NONE
+            )
+        );
+        unsafe {
+            let params = core::slice::from_raw_parts_mut(params, 4);
+            params[1].value.a += 1;
+            let (input, output) = (params[0].memref, &mut params[2].memref);
+            let input_buffer = input.buffer as *const u8;
+            core::ptr::copy_nonoverlapping(input_buffer, output.buffer as _, input.size);
+            output.size = input.size;
+        }
+        raw::TEE_SUCCESS
+    }
+
+    #[no_mangle]
+    extern "C" fn TEE_CloseTASession(_session: TEE_TASessionHandle) {
+        CLOSED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_invoke() {
+        let uuid = Uuid::parse_str("7dd54ee6-a705-4e4d-8b6b-aa5024dfcd10").unwrap();
+        let mut session = TaSession::open(&uuid).unwrap();
+
+        let input = [1u8, 2, 3];
+        let mut output = [0u8; 8];
+        let mut operation = TaOperation::new(
+            &ParamTmpRef::new_input(&input),
+            &ParamValue::new(41, 0, ParamType::ValueInout),
+            &ParamTmpRef::new_output(&mut output),
+            &ParamNone,
+        );
+        session.invoke_command(0, &mut operation).unwrap();
+        let (_, p1, p2, _) = operation.parameters();
+        assert_eq!(p1.a(), 42);
+        assert_eq!(p2.updated_size(), input.len());
+        assert_eq!(&output[..input.len()], &input);
+
+        let mut operation = TaOperation::new(&ParamNone, &ParamNone, &ParamNone, &ParamNone);
+        let err = session.invoke_command(1, &mut operation).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+
+        drop(session);
+        assert_eq!(CLOSED.load(Ordering::SeqCst), 1);
+    }
+}
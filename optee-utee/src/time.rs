@@ -73,12 +73,13 @@ impl Time {
     ///
     /// # Errors
     ///
-    /// 1) `Cancel`: If the wait has been cancelled.
+    /// 1) `Cancel`: If the wait has been cancelled. Cancellation is masked
+    ///    when an entry point is called, so it must first be unmasked, see
+    ///    [Cancellation](crate::Cancellation).
     ///
     /// # Panics
     ///
     /// 1) If the Implementation detects any error.
-
     pub fn wait(timeout: u32) -> Result<()> {
         match unsafe { raw::TEE_Wait(timeout) } {
             raw::TEE_SUCCESS => Ok(()),
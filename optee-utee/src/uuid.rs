@@ -95,6 +95,17 @@ impl Uuid {
         Self { raw: raw_uuid }
     }
 
+    /// Returns the big-endian bytes of the uuid, as taken by
+    /// [from_bytes](Uuid::from_bytes).
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..4].copy_from_slice(&self.raw.timeLow.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.raw.timeMid.to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.raw.timeHiAndVersion.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.raw.clockSeqAndNode);
+        bytes
+    }
+
     /// Converts a uuid to a const raw `TEE_UUID` pointer.
     pub fn as_raw_ptr(&self) -> *const raw::TEE_UUID {
         &self.raw
@@ -135,4 +146,14 @@ mod tests {
             assert_eq!(origin, &formatted);
         }
     }
+
+    #[test]
+    fn test_to_bytes() {
+        let bytes = [
+            70, 235, 208, 238, 14, 109, 67, 201, 185, 13, 204, 195, 90, 145, 63, 62,
+        ];
+        assert_eq!(Uuid::from_bytes(bytes).to_bytes(), bytes);
+        let uuid = Uuid::parse_str("00173366-2aca-49bc-beb7-10c975e6131e").unwrap();
+        assert_eq!(hex::encode(uuid.to_bytes()), "001733662aca49bcbeb710c975e6131e");
+    }
 }
@@ -1,14 +1,99 @@
-use optee_teec::{Context, Operation, ParamNone, ParamTmpRef, Uuid};
-use proto::{Command, UUID};
+use optee_teec::{Context, ErrorKind, TeeContext};
 use std::env;
+use std::fs;
 use std::mem;
 
+fn top_up(token_path: &str) -> optee_teec::Result<()> {
+    let token = match fs::read(token_path) {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", token_path, e);
+            return Ok(());
+        }
+    };
+
+    let mut ctx = Context::new()?;
+    let mut client = OneTimeSortClient::open(&mut ctx)?;
+
+    match client.top_up(&token) {
+        Ok(_) => println!("Top-up applied successfully!"),
+        Err(e) => println!("Error from TA: {:?}", e),
+    }
+    Ok(())
+}
+
+fn status() -> optee_teec::Result<()> {
+    let mut ctx = Context::new()?;
+    let mut client = OneTimeSortClient::open(&mut ctx)?;
+
+    let ((count, max), (remaining, _)) = client.status()?;
+    println!("count:     {}", count);
+    println!("max:       {}", max);
+    println!("remaining: {}", remaining);
+    Ok(())
+}
+
+fn list() -> optee_teec::Result<()> {
+    let mut ctx = Context::new()?;
+    for key in counter_keys(&mut ctx)? {
+        // Print NUL bytes as "\0", the escape accepted by token-gen.
+        println!("{}", String::from_utf8_lossy(&key).replace('\0', "\\0"));
+    }
+    Ok(())
+}
+
+/// Returns the keys of the counters stored by the TA.
+fn counter_keys<C: TeeContext>(ctx: &mut C) -> optee_teec::Result<Vec<Vec<u8>>> {
+    let mut client = OneTimeSortClient::open(ctx)?;
+
+    // Retry with the size the TA reports with `ShortBuffer`, as long as it
+    // grows.
+    let mut buf = vec![0u8; 256];
+    let len = loop {
+        match client.list(&mut buf) {
+            Ok(len) => break len,
+            Err(e) if e.kind() == ErrorKind::ShortBuffer => match e.required_size() {
+                Some(len) if len > buf.len() => buf.resize(len, 0),
+                _ => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    };
+
+    // Each key is preceded by its length in one byte.
+    let mut keys = Vec::new();
+    let mut rest = &buf[..len];
+    while let Some((&key_len, tail)) = rest.split_first() {
+        let (key, tail) = tail.split_at(usize::from(key_len).min(tail.len()));
+        keys.push(key.to_vec());
+        rest = tail;
+    }
+    Ok(keys)
+}
+
 fn main() -> optee_teec::Result<()> {
     let args: Vec<String> = env::args().skip(1).collect();
     if args.is_empty() {
         eprintln!("Usage: host <int1> <int2> ...");
+        eprintln!("       host topup <token_file>");
+        eprintln!("       host status");
+        eprintln!("       host list");
         return Ok(());
     }
+    match args[0].as_str() {
+        "status" => return status(),
+        "list" => return list(),
+        _ => (),
+    }
+    if args[0] == "topup" {
+        return match args.get(1) {
+            Some(path) => top_up(path),
+            None => {
+                eprintln!("Usage: host topup <token_file>");
+                Ok(())
+            }
+        };
+    }
 
     let mut numbers: Vec<i32> = match args.iter().map(|s| s.parse()).collect() {
         Ok(v) => v,
@@ -29,14 +114,10 @@ fn main() -> optee_teec::Result<()> {
     };
 
     let mut ctx = Context::new()?;
-    let uuid = Uuid::parse_str(UUID).unwrap();
-    let mut session = ctx.open_session(uuid)?;
-
-    let p0 = ParamTmpRef::new_output(byte_slice);
-    let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+    let mut client = OneTimeSortClient::open(&mut ctx)?;
 
     println!("\nAttempting TA sort...");
-    match session.invoke_command(Command::Sort as u32, &mut operation) {
+    match client.sort(byte_slice) {
         Ok(_) => {
             println!("Sort operation completed successfully!");
             println!("Sorted array: {:?}", numbers);
@@ -50,3 +131,41 @@ fn main() -> optee_teec::Result<()> {
     println!("\nDone.");
     Ok(())
 }
+
+include!(concat!(env!("OUT_DIR"), "/interface_client.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use optee_teec::mock::MockContext;
+
+    #[test]
+    fn test_counter_keys_uses_reported_size() {
+        let mut listing = vec![200];
+        listing.extend_from_slice(&[b'a'; 200]);
+        listing.push(98);
+        listing.extend_from_slice(&[b'b'; 98]);
+
+        let mut ctx = MockContext::new();
+        ctx.on_command(<OneTimeSortClient>::VERSION_COMMAND_ID, |params| {
+            params[0].set_value(1, 0);
+            Ok(())
+        });
+        ctx.on_command(3, move |params| {
+            let capacity = params[0].data().unwrap().len();
+            params[0].set_data(&listing);
+            match capacity < listing.len() {
+                true => Err(optee_teec::Error::new(ErrorKind::ShortBuffer)),
+                false => Ok(()),
+            }
+        });
+
+        let keys = counter_keys(&mut ctx).unwrap();
+        assert_eq!(keys, [vec![b'a'; 200], vec![b'b'; 98]]);
+        let sizes: Vec<_> = ctx.invocations()[1..]
+            .iter()
+            .map(|invocation| invocation.params[0].data().unwrap().len())
+            .collect();
+        assert_eq!(sizes, [256, 300]);
+    }
+}
@@ -0,0 +1,49 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Runs the client against the TA, built for the host and loaded in-process
+//! by the runner of optee-teec.
+
+use optee_teec::{runner, Context, ErrorKind, ErrorOrigin};
+
+include!(concat!(env!("OUT_DIR"), "/interface_client.rs"));
+
+#[test]
+fn test_sort_once() {
+    let ta = runner::build_ta(concat!(env!("CARGO_MANIFEST_DIR"), "/../ta")).unwrap();
+    runner::load(ta).unwrap();
+    let mut ctx = Context::new().unwrap();
+    let mut client = OneTimeSortClient::open(&mut ctx).unwrap();
+    assert_eq!(client.status().unwrap(), ((0, 1), (1, 0)));
+
+    let mut values: Vec<u8> = [9i32, -3, 7, 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(client.sort(&mut values).unwrap(), 16);
+    let sorted: Vec<i32> = values
+        .chunks(4)
+        .map(|v| i32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+        .collect();
+    assert_eq!(sorted, [-3, 1, 7, 9]);
+
+    let err = client.sort(&mut values).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+    assert_eq!(client.status().unwrap(), ((1, 1), (0, 0)));
+
+    let mut keys = [0u8; 64];
+    let len = client.list(&mut keys).unwrap();
+    assert_eq!(&keys[..len], b"\x16one_time_sort_counter\0");
+}
@@ -3,19 +3,5 @@
 
 #![no_std]
 
-pub enum Command {
-    Sort,
-    Unknown,
-}
-
-impl From<u32> for Command {
-    #[inline]
-    fn from(value: u32) -> Command {
-        match value {
-            0 => Command::Sort,
-            _ => Command::Unknown,
-        }
-    }
-}
-
-pub const UUID: &str = &include_str!(concat!(env!("OUT_DIR"), "/uuid.txt"));
\ No newline at end of file
+// The `Command` enum, `UUID` and version of the interface in `interface.idl`.
+include!(concat!(env!("OUT_DIR"), "/interface_proto.rs"));
@@ -15,10 +15,52 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use optee_utee_build::{Error, RustEdition, TaConfig};
+use optee_utee_build::{Error, InterfaceTarget, RustEdition, TaConfig};
 use proto;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const ISSUER_MODULUS: &str = "ISSUER_MODULUS";
+
+/// Writes the issuer modulus given in `ISSUER_MODULUS` to `issuer_modulus.rs`
+/// in `OUT_DIR`, see `src/issuer.rs`.
+fn issuer_modulus() {
+    println!("cargo:rerun-if-env-changed={}", ISSUER_MODULUS);
+    let hex = env::var(ISSUER_MODULUS).ok();
+    if env::var_os("CARGO_FEATURE_DEV_KEY").is_some() {
+        if hex.is_some() {
+            panic!("{} is set, but the dev-key feature trusts the development key", ISSUER_MODULUS);
+        }
+        return;
+    }
+    let hex = hex.unwrap_or_else(|| {
+        panic!("{} is not set; enable the dev-key feature for development builds", ISSUER_MODULUS)
+    });
+    let hex = hex.trim();
+    let modulus: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<_>>()
+        .filter(|modulus: &Vec<u8>| modulus.len() >= 256)
+        .unwrap_or_else(|| {
+            panic!("{} is not the hex modulus of an RSA-2048 or larger key", ISSUER_MODULUS)
+        });
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let code = format!(
+        "/// Big-endian RSA modulus of the issuer key, from `{}`.\n\
+         pub const ISSUER_MODULUS: [u8; {}] = {:?};\n",
+        ISSUER_MODULUS,
+        modulus.len(),
+        modulus
+    );
+    fs::write(out.join("issuer_modulus.rs"), code).unwrap();
+}
 
 fn main() -> Result<(), Error> {
+    issuer_modulus();
+    optee_utee_build::generate_interface("../interface.idl", InterfaceTarget::Dispatcher)?;
     let config = TaConfig::new_default_with_cargo_env(proto::UUID)?;
     optee_utee_build::build(RustEdition::Before2024, config)
 }
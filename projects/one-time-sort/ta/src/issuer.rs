@@ -0,0 +1,46 @@
+//! Public key of the token issuer trusted by this TA.
+//!
+//! The modulus of the issuer key is given at build time, in hex, in the
+//! `ISSUER_MODULUS` environment variable:
+//!
+//! ```sh
+//! export ISSUER_MODULUS=$(openssl rsa -pubin -in issuer_pub.pem -modulus -noout | cut -d= -f2)
+//! ```
+//!
+//! The build fails if it is not set, unless the `dev-key` feature is enabled.
+//! The TA then trusts the development key pair in `keys/issuer_dev.pem`,
+//! whose private key is in the repository, so such builds must not be
+//! deployed. The `sim` feature enables `dev-key`.
+
+#[cfg(not(feature = "dev-key"))]
+include!(concat!(env!("OUT_DIR"), "/issuer_modulus.rs"));
+
+/// Big-endian RSA-2048 modulus of the development issuer key.
+#[cfg(feature = "dev-key")]
+pub const ISSUER_MODULUS: [u8; 256] = [
+    0xc1, 0xd4, 0x65, 0xcb, 0x5e, 0x24, 0x83, 0x5c, 0xb8, 0xbd, 0xf2, 0xe7,
+    0x40, 0xb1, 0x8a, 0x4c, 0x20, 0x7e, 0xb1, 0xdc, 0x8f, 0x5f, 0x11, 0x0a,
+    0xfa, 0xba, 0x3a, 0xc8, 0xc6, 0x7b, 0x4a, 0x72, 0x32, 0x6f, 0x04, 0x3d,
+    0xfc, 0x95, 0xcc, 0xd0, 0xbb, 0x09, 0xe8, 0xdd, 0x58, 0xd8, 0xde, 0x8d,
+    0x27, 0xc0, 0x77, 0x21, 0x2a, 0x22, 0x99, 0x00, 0x31, 0x1a, 0x3a, 0x9b,
+    0x8d, 0xcb, 0xa9, 0x1e, 0x81, 0x75, 0xb2, 0x47, 0x82, 0x99, 0xba, 0xb8,
+    0x95, 0x94, 0xd5, 0x0f, 0x7b, 0x88, 0xbe, 0xc8, 0x9b, 0xbe, 0xdc, 0x00,
+    0x58, 0xa5, 0x61, 0x5f, 0x87, 0x8b, 0x56, 0x33, 0xa2, 0x50, 0x8a, 0x90,
+    0x63, 0x24, 0x51, 0x50, 0x2b, 0xdc, 0xc8, 0x58, 0xe4, 0x7b, 0x9c, 0xba,
+    0xc7, 0x3e, 0x73, 0x34, 0x6d, 0x10, 0xc4, 0x0a, 0x25, 0xd7, 0x33, 0xf6,
+    0xb3, 0xfd, 0x9b, 0x36, 0x69, 0xcd, 0x62, 0xa1, 0x64, 0xd4, 0xdd, 0xca,
+    0xcd, 0xb1, 0x5e, 0x1f, 0x7e, 0xd1, 0x99, 0x4f, 0x31, 0x80, 0xcc, 0xcc,
+    0x5a, 0xbe, 0xb7, 0x00, 0x93, 0x0d, 0x44, 0x76, 0xd7, 0xa3, 0xc3, 0xe8,
+    0x09, 0x24, 0xfc, 0xd5, 0x00, 0x93, 0x73, 0x1c, 0xcc, 0xdc, 0x11, 0xe4,
+    0x7e, 0x94, 0x81, 0x2e, 0x49, 0xab, 0x07, 0x45, 0xbd, 0x02, 0xba, 0xa2,
+    0x56, 0xec, 0x93, 0xae, 0xb0, 0x53, 0x6a, 0xac, 0xfe, 0xcf, 0x2b, 0xe8,
+    0xfe, 0x0c, 0xf0, 0x86, 0xe5, 0x7c, 0xc4, 0x06, 0x85, 0x2e, 0xaa, 0xea,
+    0x0f, 0xc1, 0x87, 0x9c, 0xd9, 0x46, 0x2f, 0x90, 0x49, 0xc0, 0x36, 0x02,
+    0xf7, 0xdb, 0xf3, 0xb7, 0x8f, 0xdf, 0x32, 0xf4, 0x63, 0x66, 0x32, 0xb2,
+    0x4f, 0x46, 0xbd, 0x0c, 0x9c, 0x85, 0xf0, 0xa4, 0x14, 0x03, 0xc7, 0xcf,
+    0x72, 0xf4, 0x54, 0x1e, 0x1c, 0x56, 0x14, 0x6c, 0xcd, 0xac, 0x06, 0xc4,
+    0x67, 0xa3, 0x77, 0xbf,
+];
+
+/// Public exponent of the issuer key (65537).
+pub const ISSUER_EXPONENT: [u8; 3] = [0x01, 0x00, 0x01];
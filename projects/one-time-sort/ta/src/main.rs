@@ -3,15 +3,21 @@
 
 extern crate alloc;
 
-use n_time_model::ExecutionCounter;
+mod issuer;
+
+use n_time_model::token::IssuerKey;
+use n_time_model::topup::TopUp;
+use n_time_model::{CounterStorage, ExecutionCounter};
 use optee_utee::{
     ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session, trace_println,
 };
-use optee_utee::{Error, ErrorKind, Parameters, Result};
-use proto::Command;
+use optee_utee::{Error, ErrorKind, ParamMemref, ParamValue, Parameters, Result};
 
 const MAX_EXECUTIONS: u32 = 1;
 const EXECUTION_KEY: &[u8] = b"one_time_sort_counter\0";
+// Kept in RPMB where available, so the REE cannot roll the counter back.
+const COUNTER: ExecutionCounter =
+    ExecutionCounter::new(EXECUTION_KEY, MAX_EXECUTIONS).with_storage(CounterStorage::RpmbOrRee);
 
 #[ta_create]
 fn create() -> Result<()> {
@@ -38,25 +44,53 @@ fn destroy() {
 #[ta_invoke_command]
 fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
     trace_println!("[+] One-Time Sort TA invoke command");
-    match Command::from(cmd_id) {
-        Command::Sort => one_time_sort(params),
-        _ => Err(Error::new(ErrorKind::NotSupported)),
-    }
+    OneTimeSort.dispatch(cmd_id, params)
 }
 
-fn one_time_sort(params: &mut Parameters) -> Result<()> {
-    let counter = ExecutionCounter::new(EXECUTION_KEY, MAX_EXECUTIONS);
-    counter.check_and_increment()?;
+struct OneTimeSort;
 
-    let mut p0 = unsafe { params.0.as_memref().unwrap() };
-    let array_ptr = p0.buffer().as_ptr() as *mut i32;
-    let array_len = p0.buffer().len() / core::mem::size_of::<i32>();
+impl OneTimeSortHandler for OneTimeSort {
+    fn sort(&mut self, mut values: ParamMemref) -> Result<()> {
+        let array = values.as_mut_slice::<i32>()?;
 
-    let array = unsafe { core::slice::from_raw_parts_mut(array_ptr, array_len) };
-    trace_println!("[+] Sorting array of {} elements", array_len);
-    sort_array(array);
-    trace_println!("[+] Sort operation completed successfully");
-    Ok(())
+        COUNTER.check_and_increment()?;
+
+        trace_println!("[+] Sorting array of {} elements", array.len());
+        sort_array(array);
+        trace_println!("[+] Sort operation completed successfully");
+        Ok(())
+    }
+
+    fn top_up(&mut self, mut token: ParamMemref) -> Result<()> {
+        let key = IssuerKey::rsa(b"", &issuer::ISSUER_MODULUS, &issuer::ISSUER_EXPONENT)?;
+        let top_up = TopUp::verify(token.buffer(), &key)?;
+        if top_up.counter != EXECUTION_KEY {
+            trace_println!("[!] Top-up names a counter not owned by this TA");
+            return Err(Error::new(ErrorKind::AccessDenied));
+        }
+        COUNTER.top_up(top_up.seq, top_up.increment)
+    }
+
+    fn status(&mut self, mut count_max: ParamValue, mut remaining: ParamValue) -> Result<()> {
+        let status = COUNTER.status()?;
+        count_max.set_a(status.count);
+        count_max.set_b(status.max);
+        remaining.set_a(status.remaining);
+        Ok(())
+    }
+
+    fn list(&mut self, mut keys: ParamMemref) -> Result<()> {
+        let counters = ExecutionCounter::list_in(CounterStorage::RpmbOrRee)?;
+        let len = counters.iter().map(|key| key.len() + 1).sum();
+        let buffer = keys.output(len)?;
+        let mut offset = 0;
+        for key in &counters {
+            buffer[offset] = key.len() as u8;
+            buffer[offset + 1..offset + 1 + key.len()].copy_from_slice(key);
+            offset += key.len() + 1;
+        }
+        Ok(())
+    }
 }
 
 fn sort_array(array: &mut [i32]) {
@@ -72,4 +106,5 @@ fn sort_array(array: &mut [i32]) {
     }
 }
 
+include!(concat!(env!("OUT_DIR"), "/interface_dispatcher.rs"));
 include!(concat!(env!("OUT_DIR"), "/user_ta_header.rs"));
@@ -0,0 +1,7 @@
+#![no_std]
+
+//! The quota service's commands and parameter layout are shared with its
+//! clients through `n_time_model::quota`.
+
+/// UUID for this TA — expected to be generated at build time and written to `uuid.txt`
+pub const UUID: &str = &include_str!(concat!(env!("OUT_DIR"), "/uuid.txt"));
@@ -0,0 +1,42 @@
+//! Callers allowed to use the quota service.
+//!
+//! Clients are identified by the UUID in their `gpd.client.identity`, which
+//! OP-TEE fills in for TA-to-TA sessions. Only TAs listed here may open a
+//! session.
+
+use n_time_model::quota::{uuid_bytes, UUID_LEN};
+use optee_utee::Uuid;
+
+/// The rights of one client TA.
+pub struct Grant {
+    /// UUID of the client TA.
+    pub client: &'static str,
+    /// Limit applied to each of the client's counters.
+    pub max: u32,
+    /// Whether the client may reset the counters of other TAs.
+    pub admin: bool,
+}
+
+/// The configured clients.
+pub const GRANTS: &[Grant] = &[
+    // projects/one-time-sort
+    Grant {
+        client: "8a3c7b2e-137b-11f0-a12b-a3d91c3091bf",
+        max: 1,
+        admin: false,
+    },
+    // projects/reset
+    Grant {
+        client: "7eccb5f2-1389-11f0-a9e5-d32b88282c49",
+        max: 0,
+        admin: true,
+    },
+];
+
+/// Returns the grant of the client with the given UUID, if any.
+pub fn lookup(client: &[u8; UUID_LEN]) -> Option<&'static Grant> {
+    GRANTS.iter().find(|grant| match Uuid::parse_str(grant.client) {
+        Ok(uuid) => uuid_bytes(unsafe { &*uuid.as_raw_ptr() }) == *client,
+        Err(_) => false,
+    })
+}
@@ -0,0 +1,179 @@
+#![no_std]
+#![no_main]
+// The session context entry points generated by the ta_* macros take raw
+// pointers from the framework.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+extern crate alloc;
+
+mod acl;
+mod reservations;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use n_time_model::quota::{uuid_bytes, Command, UUID_LEN};
+use n_time_model::{ExecutionCounter, MAX_KEY_LEN};
+use optee_utee::{
+    ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session, trace_println,
+};
+use optee_utee::property::{Identity, Login};
+use optee_utee::{Error, ErrorKind, Parameters, Result};
+
+/// Longest counter name accepted; the counter key is the client UUID followed
+/// by the name.
+const MAX_NAME_LEN: usize = MAX_KEY_LEN - UUID_LEN;
+
+#[derive(Default)]
+struct Session {
+    /// UUID of the client TA.
+    client: [u8; UUID_LEN],
+    /// Limit applied to the client's counters.
+    max: u32,
+    /// Whether the client may reset the counters of other TAs.
+    admin: bool,
+    /// Reservations made in this session, with the storage ID of the counter.
+    reservations: Vec<(u32, Vec<u8>)>,
+    /// ID of the last reservation made in this session.
+    last_reservation: u32,
+}
+
+#[ta_create]
+fn create() -> Result<()> {
+    trace_println!("[+] Quota service TA create");
+    Ok(())
+}
+
+#[ta_open_session]
+fn open_session(_params: &mut Parameters, sess: &mut Session) -> Result<()> {
+    let identity = Identity::current_client()?;
+    if identity.login != Login::TrustedApp {
+        trace_println!("[!] Rejecting session from a non-TA client");
+        return Err(Error::new(ErrorKind::AccessDenied));
+    }
+
+    let client = uuid_bytes(unsafe { &*identity.uuid.as_raw_ptr() });
+    let grant = acl::lookup(&client).ok_or_else(|| {
+        trace_println!("[!] Rejecting session from unknown TA");
+        Error::new(ErrorKind::AccessDenied)
+    })?;
+    sess.client = client;
+    sess.max = grant.max;
+    sess.admin = grant.admin;
+    trace_println!("[+] Quota service session opened by {}", grant.client);
+    Ok(())
+}
+
+#[ta_close_session]
+fn close_session(sess: &mut Session) {
+    for (_, id) in sess.reservations.drain(..) {
+        reservations::release(&id);
+    }
+    trace_println!("[+] Quota service session closed");
+}
+
+#[ta_destroy]
+fn destroy() {
+    trace_println!("[+] Quota service TA destroy");
+}
+
+#[ta_invoke_command]
+fn invoke_command(sess: &mut Session, cmd_id: u32, params: &mut Parameters) -> Result<()> {
+    match Command::from(cmd_id) {
+        Command::Check => check(sess, params),
+        Command::Reserve => reserve(sess, params),
+        Command::Commit => commit(sess, params),
+        Command::Query => query(sess, params),
+        Command::Reset => reset(sess, params),
+        Command::Unknown => {
+            trace_println!("[!] Unknown command ID: {}", cmd_id);
+            Err(Error::new(ErrorKind::NotSupported))
+        }
+    }
+}
+
+/// Builds the storage ID of the counter `name` owned by `owner`.
+fn storage_id(owner: &[u8], name: &[u8]) -> Result<Vec<u8>> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let mut id = Vec::with_capacity(owner.len() + name.len());
+    id.extend_from_slice(owner);
+    id.extend_from_slice(name);
+    Ok(id)
+}
+
+/// Returns the storage ID of the caller's counter named in parameter 0.
+fn counter_id(sess: &Session, params: &mut Parameters) -> Result<Vec<u8>> {
+    let mut p0 = unsafe { params.0.as_memref()? };
+    storage_id(&sess.client, p0.buffer())
+}
+
+/// Returns the number of executions that can still be reserved.
+fn remaining(sess: &Session, id: &[u8]) -> Result<u32> {
+    let counter = ExecutionCounter::new(id, sess.max);
+    Ok(counter
+        .limit()?
+        .saturating_sub(counter.count()?)
+        .saturating_sub(reservations::reserved(id)))
+}
+
+fn check(sess: &mut Session, params: &mut Parameters) -> Result<()> {
+    let id = counter_id(sess, params)?;
+    let mut p1 = unsafe { params.1.as_value()? };
+    p1.set_a(remaining(sess, &id)?);
+    Ok(())
+}
+
+fn reserve(sess: &mut Session, params: &mut Parameters) -> Result<()> {
+    let id = counter_id(sess, params)?;
+    let mut p1 = unsafe { params.1.as_value()? };
+    if remaining(sess, &id)? == 0 {
+        trace_println!("[+] No executions left to reserve");
+        return Err(Error::new(ErrorKind::AccessDenied));
+    }
+    reservations::add(&id);
+    sess.last_reservation = sess.last_reservation.wrapping_add(1);
+    sess.reservations.push((sess.last_reservation, id));
+    p1.set_a(sess.last_reservation);
+    Ok(())
+}
+
+fn commit(sess: &mut Session, params: &mut Parameters) -> Result<()> {
+    let p1 = unsafe { params.1.as_value()? };
+    let index = sess
+        .reservations
+        .iter()
+        .position(|(reservation, _)| *reservation == p1.a())
+        .ok_or(Error::new(ErrorKind::ItemNotFound))?;
+    let (_, id) = sess.reservations.swap_remove(index);
+    reservations::release(&id);
+    ExecutionCounter::new(&id, sess.max).check_and_increment()
+}
+
+fn query(sess: &mut Session, params: &mut Parameters) -> Result<()> {
+    let id = counter_id(sess, params)?;
+    let counter = ExecutionCounter::new(&id, sess.max);
+    let mut p1 = unsafe { params.1.as_value()? };
+    let mut p2 = unsafe { params.2.as_value()? };
+    p1.set_a(counter.count()?);
+    p1.set_b(counter.limit()?);
+    p2.set_a(reservations::reserved(&id));
+    Ok(())
+}
+
+fn reset(sess: &mut Session, params: &mut Parameters) -> Result<()> {
+    if !sess.admin {
+        trace_println!("[!] Reset requested by a non-admin client");
+        return Err(Error::new(ErrorKind::AccessDenied));
+    }
+    let mut p0 = unsafe { params.0.as_memref()? };
+    let mut p1 = unsafe { params.1.as_memref()? };
+    if p1.buffer().len() != UUID_LEN {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let id = storage_id(p1.buffer(), p0.buffer())?;
+    trace_println!("[+] Resetting counter on behalf of admin client");
+    ExecutionCounter::new(&id, 0).delete()
+}
+
+include!(concat!(env!("OUT_DIR"), "/user_ta_header.rs"));
@@ -0,0 +1,50 @@
+//! Executions reserved but not yet committed, shared by all sessions.
+//!
+//! The TA runs as a single instance (see `build.rs`), so every session sees
+//! the same table, and reservations vanish with the instance.
+
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+struct Table(UnsafeCell<Vec<(Vec<u8>, u32)>>);
+
+// SAFETY: OP-TEE serialises the entry points of a single-instance TA, so the
+// table is never accessed concurrently.
+unsafe impl Sync for Table {}
+
+static TABLE: Table = Table(UnsafeCell::new(Vec::new()));
+
+fn with<R>(f: impl FnOnce(&mut Vec<(Vec<u8>, u32)>) -> R) -> R {
+    // SAFETY: see `Table`; no reference to the table outlives this call.
+    f(unsafe { &mut *TABLE.0.get() })
+}
+
+/// Returns the number of executions reserved for the counter `id`.
+pub fn reserved(id: &[u8]) -> u32 {
+    with(|table| {
+        table
+            .iter()
+            .find(|(key, _)| key.as_slice() == id)
+            .map_or(0, |(_, count)| *count)
+    })
+}
+
+/// Reserves one execution of the counter `id`.
+pub fn add(id: &[u8]) {
+    with(|table| match table.iter_mut().find(|(key, _)| key.as_slice() == id) {
+        Some((_, count)) => *count += 1,
+        None => table.push((id.to_vec(), 1)),
+    })
+}
+
+/// Releases one reserved execution of the counter `id`.
+pub fn release(id: &[u8]) {
+    with(|table| {
+        if let Some(index) = table.iter().position(|(key, _)| key.as_slice() == id) {
+            table[index].1 -= 1;
+            if table[index].1 == 0 {
+                table.swap_remove(index);
+            }
+        }
+    })
+}
@@ -0,0 +1,142 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Runs the quota service, built for the host and loaded in-process by the
+//! runner of optee-teec, with sessions opened as the TAs listed in its ACL.
+
+use optee_teec::{
+    runner, Context, ErrorKind, Operation, ParamNone, ParamTmpRef, ParamType, ParamValue,
+    Result, Session, Uuid,
+};
+
+const CMD_CHECK: u32 = 0;
+const CMD_RESERVE: u32 = 1;
+const CMD_COMMIT: u32 = 2;
+const CMD_QUERY: u32 = 3;
+const CMD_RESET: u32 = 4;
+
+/// projects/one-time-sort, allowed one execution per counter.
+const ONE_TIME_SORT: &str = "8a3c7b2e-137b-11f0-a12b-a3d91c3091bf";
+/// projects/reset, an admin.
+const RESET: &str = "7eccb5f2-1389-11f0-a9e5-d32b88282c49";
+
+fn uuid(text: &str) -> Uuid {
+    Uuid::parse_str(text).unwrap()
+}
+
+/// Returns the bytes of a UUID, as the quota service takes the owner of a
+/// counter to reset.
+fn uuid_bytes(text: &str) -> [u8; 16] {
+    let hex = text.replace('-', "");
+    let mut bytes = [0u8; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes
+}
+
+/// Opens a session to the quota service as the TA `client`, or as a client
+/// application for `None`.
+fn open(ctx: &mut Context, service: &Uuid, client: Option<&str>) -> Result<Session> {
+    runner::set_client_ta(client.map(uuid).as_ref());
+    let session = ctx.open_session(service.clone());
+    runner::set_client_ta(None);
+    session
+}
+
+fn check(session: &mut Session, counter: &[u8]) -> Result<u32> {
+    let p0 = ParamTmpRef::new_input(counter);
+    let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+    session.invoke_command(CMD_CHECK, &mut operation)?;
+    Ok(operation.parameters().1.a())
+}
+
+fn reserve(session: &mut Session, counter: &[u8]) -> Result<u32> {
+    let p0 = ParamTmpRef::new_input(counter);
+    let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+    session.invoke_command(CMD_RESERVE, &mut operation)?;
+    Ok(operation.parameters().1.a())
+}
+
+fn commit(session: &mut Session, reservation: u32) -> Result<()> {
+    let p0 = ParamTmpRef::new_input(&[]);
+    let p1 = ParamValue::new(reservation, 0, ParamType::ValueInput);
+    let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+    session.invoke_command(CMD_COMMIT, &mut operation)
+}
+
+/// Returns the count, the limit and the reserved executions of a counter.
+fn query(session: &mut Session, counter: &[u8]) -> Result<(u32, u32, u32)> {
+    let p0 = ParamTmpRef::new_input(counter);
+    let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let p2 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
+    session.invoke_command(CMD_QUERY, &mut operation)?;
+    let (_, p1, p2, _) = operation.parameters();
+    Ok((p1.a(), p1.b(), p2.a()))
+}
+
+fn reset(session: &mut Session, owner: &str, counter: &[u8]) -> Result<()> {
+    let owner = uuid_bytes(owner);
+    let p0 = ParamTmpRef::new_input(counter);
+    let p1 = ParamTmpRef::new_input(&owner);
+    let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+    session.invoke_command(CMD_RESET, &mut operation)
+}
+
+#[test]
+fn test_quota_service() {
+    let ta = runner::build_ta(concat!(env!("CARGO_MANIFEST_DIR"), "/../ta")).unwrap();
+    let service = runner::load(ta).unwrap();
+    let mut ctx = Context::new().unwrap();
+
+    // only the TAs of the ACL are served
+    let unknown = "00000000-0000-0000-0000-000000000001";
+    let err = open(&mut ctx, &service, None).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    let err = open(&mut ctx, &service, Some(unknown)).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::AccessDenied);
+
+    // a reservation holds the only execution until it is committed
+    let mut sort = open(&mut ctx, &service, Some(ONE_TIME_SORT)).unwrap();
+    assert_eq!(check(&mut sort, b"sort").unwrap(), 1);
+    let reservation = reserve(&mut sort, b"sort").unwrap();
+    let err = reserve(&mut sort, b"sort").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AccessDenied);
+    assert_eq!(query(&mut sort, b"sort").unwrap(), (0, 1, 1));
+    commit(&mut sort, reservation).unwrap();
+    let err = commit(&mut sort, reservation).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+    assert_eq!(query(&mut sort, b"sort").unwrap(), (1, 1, 0));
+    assert_eq!(check(&mut sort, b"sort").unwrap(), 0);
+    let err = reset(&mut sort, ONE_TIME_SORT, b"sort").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AccessDenied);
+
+    // uncommitted reservations are released when their session closes
+    let mut other = open(&mut ctx, &service, Some(ONE_TIME_SORT)).unwrap();
+    reserve(&mut other, b"merge").unwrap();
+    assert_eq!(check(&mut sort, b"merge").unwrap(), 0);
+    drop(other);
+    assert_eq!(query(&mut sort, b"merge").unwrap(), (0, 1, 0));
+
+    // an admin resets the counters of other TAs
+    let mut admin = open(&mut ctx, &service, Some(RESET)).unwrap();
+    reset(&mut admin, ONE_TIME_SORT, b"sort").unwrap();
+    assert_eq!(check(&mut sort, b"sort").unwrap(), 1);
+}
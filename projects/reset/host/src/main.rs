@@ -1,32 +1,150 @@
-use optee_teec::{Context, Operation, ParamNone, ParamTmpRef, Uuid};
+use optee_teec::{
+    Context, ErrorKind, Operation, ParamNone, ParamTmpRef, ParamType, ParamValue, Session, Uuid,
+};
 use proto::{Command, UUID};
 use std::env;
+use std::fs;
 
-fn main() -> optee_teec::Result<()> {
-    let args: Vec<String> = env::args().collect();
+const USAGE: &str = "Usage: reset nonce
+       reset reset <admin_token>
+       reset set-count <admin_token>
+       reset set-max <admin_token>
+       reset log
+       reset status <counter>
+       reset list";
+
+/// Size of the nonce issued by the TA.
+const NONCE_LEN: usize = 16;
 
-    if args.len() != 2 {
-        eprintln!("Usage: {} <counter_key>", args[0]);
-        std::process::exit(1);
+fn get_nonce(session: &mut Session) -> optee_teec::Result<()> {
+    let mut nonce = [0u8; NONCE_LEN];
+    let p0 = ParamTmpRef::new_output(&mut nonce);
+    let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+    session.invoke_command(Command::GetNonce as u32, &mut operation)?;
+
+    for b in &nonce {
+        print!("{:02x}", b);
     }
+    println!();
+    Ok(())
+}
 
-    let key = args[1].as_bytes();
+/// Invokes `command_id` with an output memref in p0 and returns the output.
+///
+/// The buffer starts at `capacity` bytes and is grown to the size the TA
+/// reports with `ShortBuffer`, as long as that size is larger.
+fn read_output(
+    session: &mut Session,
+    command_id: u32,
+    capacity: usize,
+) -> optee_teec::Result<Vec<u8>> {
+    let mut buf = vec![0u8; capacity];
+    loop {
+        let p0 = ParamTmpRef::new_output(&mut buf);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        match session.invoke_command(command_id, &mut operation) {
+            Ok(()) => {
+                let len = operation.parameters().0.updated_size();
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            Err(e) if e.kind() == ErrorKind::ShortBuffer => {
+                let len = operation.parameters().0.updated_size();
+                if len <= buf.len() {
+                    return Err(e);
+                }
+                buf.resize(len, 0);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-    // Set up OP-TEE session
-    let mut ctx = Context::new()?;
-    let uuid = Uuid::parse_str(UUID).expect("Invalid UUID in proto");
-    let mut session = ctx.open_session(uuid)?;
+fn get_log(session: &mut Session) -> optee_teec::Result<()> {
+    let log = read_output(session, Command::GetLog as u32, 4096)?;
+    print!("{}", String::from_utf8_lossy(&log));
+    Ok(())
+}
 
-    // Send the key as a temp memref input
-    let p0 = ParamTmpRef::new_input(key);
-    let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+fn status(session: &mut Session, counter: &str) -> optee_teec::Result<()> {
+    // Counter keys are storage IDs such as `one_time_sort_counter\0`, so
+    // "\0" is unescaped as token-gen does.
+    let counter = counter.replace("\\0", "\0");
+    let p0 = ParamTmpRef::new_input(counter.as_bytes());
+    let p1 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let p2 = ParamValue::new(0, 0, ParamType::ValueOutput);
+    let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
+    session.invoke_command(Command::Status as u32, &mut operation)?;
 
-    println!("Resetting counter with key: '{}'", args[1]);
+    let (_, p1, p2, _) = operation.parameters();
+    println!("count:     {}", p1.a());
+    println!("max:       {}", p1.b());
+    println!("remaining: {}", p2.a());
+    Ok(())
+}
 
-    match session.invoke_command(Command::Reset as u32, &mut operation) {
-        Ok(()) => println!("✅ Counter deleted successfully."),
-        Err(e) => println!("❌ Failed to delete counter: {:?}", e),
+fn list(session: &mut Session) -> optee_teec::Result<()> {
+    let keys = read_output(session, Command::List as u32, 256)?;
+
+    // Each key is preceded by its length in one byte.
+    let mut rest = &keys[..];
+    while let Some((&key_len, tail)) = rest.split_first() {
+        let (key, tail) = tail.split_at(usize::from(key_len).min(tail.len()));
+        // Print NUL bytes as "\0", the escape accepted by `status`.
+        println!("{}", String::from_utf8_lossy(key).replace('\0', "\\0"));
+        rest = tail;
     }
+    Ok(())
+}
+
+fn admin_command(
+    session: &mut Session,
+    command: Command,
+    token_path: &str,
+) -> optee_teec::Result<()> {
+    let token = match fs::read(token_path) {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", token_path, e);
+            std::process::exit(1);
+        }
+    };
+    let p0 = ParamTmpRef::new_input(&token);
+    let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
 
+    match session.invoke_command(command as u32, &mut operation) {
+        Ok(()) => println!("✅ Admin command applied."),
+        Err(e) => println!("❌ Admin command rejected: {:?}", e),
+    }
     Ok(())
 }
+
+fn main() -> optee_teec::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match (args.first().map(String::as_str), args.len()) {
+        (Some("nonce"), 1) => Command::GetNonce,
+        (Some("log"), 1) => Command::GetLog,
+        (Some("status"), 2) => Command::Status,
+        (Some("list"), 1) => Command::List,
+        (Some("reset"), 2) => Command::Reset,
+        (Some("set-count"), 2) => Command::SetCount,
+        (Some("set-max"), 2) => Command::SetMax,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    // Set up OP-TEE session
+    let mut ctx = Context::new()?;
+    let uuid = Uuid::parse_str(UUID).expect("Invalid UUID in proto");
+    let mut session = ctx.open_session(uuid)?;
+
+    match command {
+        Command::GetNonce => get_nonce(&mut session),
+        Command::GetLog => get_log(&mut session),
+        Command::Status => status(&mut session, &args[1]),
+        Command::List => list(&mut session),
+        command => admin_command(&mut session, command, &args[1]),
+    }
+}
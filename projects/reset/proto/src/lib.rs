@@ -1,9 +1,27 @@
 #![no_std]
 
 /// Commands supported by this TA.
+///
+/// `Reset`, `SetCount` and `SetMax` take an admin token (memref input) whose
+/// action must match the command and whose nonce must come from `GetNonce`.
 pub enum Command {
-    /// Deletes the counter stored under a given key.
+    /// Deletes the counter named by the admin token.
     Reset,
+    /// Sets the execution count of the counter named by the admin token.
+    SetCount,
+    /// Sets the limit of the counter named by the admin token.
+    SetMax,
+    /// Issues a nonce for an admin token (memref output, 16 bytes). Up to
+    /// eight nonces are pending at a time, the oldest being dropped first.
+    GetNonce,
+    /// Reads the admin action log (memref output).
+    GetLog,
+    /// Reads the state of a counter: key (memref input), count and limit
+    /// (value output), remaining executions (value output).
+    Status,
+    /// Lists the counter keys (memref output), each preceded by its length
+    /// in one byte.
+    List,
     /// Fallback for unsupported commands.
     Unknown,
 }
@@ -12,6 +30,12 @@ impl From<u32> for Command {
     fn from(value: u32) -> Command {
         match value {
             0 => Command::Reset,
+            1 => Command::SetCount,
+            2 => Command::SetMax,
+            3 => Command::GetNonce,
+            4 => Command::GetLog,
+            5 => Command::Status,
+            6 => Command::List,
             _ => Command::Unknown,
         }
     }
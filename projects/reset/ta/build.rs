@@ -17,8 +17,49 @@
 
 use proto;
 use optee_utee_build::{TaConfig, RustEdition, Error};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const ADMIN_MODULUS: &str = "ADMIN_MODULUS";
+
+/// Writes the admin modulus given in `ADMIN_MODULUS` to `admin_modulus.rs`
+/// in `OUT_DIR`, see `src/admin_key.rs`.
+fn admin_modulus() {
+    println!("cargo:rerun-if-env-changed={}", ADMIN_MODULUS);
+    let hex = env::var(ADMIN_MODULUS).ok();
+    if env::var_os("CARGO_FEATURE_DEV_KEY").is_some() {
+        if hex.is_some() {
+            panic!("{} is set, but the dev-key feature trusts the development key", ADMIN_MODULUS);
+        }
+        return;
+    }
+    let hex = hex.unwrap_or_else(|| {
+        panic!("{} is not set; enable the dev-key feature for development builds", ADMIN_MODULUS)
+    });
+    let hex = hex.trim();
+    let modulus: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<_>>()
+        .filter(|modulus: &Vec<u8>| modulus.len() >= 256)
+        .unwrap_or_else(|| {
+            panic!("{} is not the hex modulus of an RSA-2048 or larger key", ADMIN_MODULUS)
+        });
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let code = format!(
+        "/// Big-endian RSA modulus of the admin key, from `{}`.\n\
+         pub const ADMIN_MODULUS: [u8; {}] = {:?};\n",
+        ADMIN_MODULUS,
+        modulus.len(),
+        modulus
+    );
+    fs::write(out.join("admin_modulus.rs"), code).unwrap();
+}
 
 fn main() -> Result<(), Error> {
+    admin_modulus();
     let config = TaConfig::new_default_with_cargo_env(proto::UUID)?;
     optee_utee_build::build(RustEdition::Before2024, config)
 
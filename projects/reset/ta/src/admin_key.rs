@@ -0,0 +1,45 @@
+//! Public key of the administrator allowed to modify counters.
+//!
+//! The modulus of the admin key is given at build time, in hex, in the
+//! `ADMIN_MODULUS` environment variable:
+//!
+//! ```sh
+//! export ADMIN_MODULUS=$(openssl rsa -pubin -in admin_pub.pem -modulus -noout | cut -d= -f2)
+//! ```
+//!
+//! The build fails if it is not set, unless the `dev-key` feature is enabled.
+//! The TA then trusts the development key pair in `keys/admin_dev.pem`, whose
+//! private key is in the repository, so such builds must not be deployed.
+
+#[cfg(not(feature = "dev-key"))]
+include!(concat!(env!("OUT_DIR"), "/admin_modulus.rs"));
+
+/// Big-endian RSA-2048 modulus of the development admin key.
+#[cfg(feature = "dev-key")]
+pub const ADMIN_MODULUS: [u8; 256] = [
+    0xa5, 0x50, 0x4d, 0xfc, 0x27, 0x30, 0x57, 0x67, 0x8c, 0xb1, 0x7a, 0xf9,
+    0x05, 0xc9, 0x92, 0x7b, 0xc9, 0x26, 0x4d, 0x16, 0x1c, 0x67, 0xf4, 0x55,
+    0x9a, 0x1d, 0x5d, 0xc0, 0xc2, 0x5e, 0x45, 0x4b, 0xd9, 0x6d, 0x7a, 0x01,
+    0x19, 0x7e, 0x25, 0xa2, 0x5c, 0x48, 0x92, 0xc3, 0x2e, 0x4c, 0x55, 0xb0,
+    0x31, 0x33, 0x2c, 0x29, 0xb3, 0x0f, 0x69, 0x66, 0x2d, 0x70, 0x7e, 0x71,
+    0x14, 0x58, 0xad, 0x73, 0xc4, 0x43, 0x98, 0x3e, 0x05, 0x28, 0x3c, 0x1e,
+    0xd0, 0x49, 0x31, 0x2f, 0xd5, 0xfb, 0x97, 0x4b, 0x53, 0x1a, 0xc9, 0x66,
+    0xba, 0x8a, 0xc8, 0x86, 0x1c, 0x4a, 0x6d, 0x81, 0xe5, 0xc5, 0xc0, 0xc6,
+    0x30, 0xf7, 0x85, 0x70, 0x7c, 0x6a, 0xbd, 0x22, 0xbe, 0xc5, 0xe3, 0xa8,
+    0x3e, 0xce, 0x3a, 0x0d, 0xd3, 0x0d, 0xbf, 0xd3, 0xb3, 0x41, 0x16, 0x3d,
+    0x62, 0x0c, 0xab, 0x70, 0x94, 0xfa, 0xb9, 0xee, 0xe6, 0x54, 0x70, 0x10,
+    0x16, 0xb4, 0xdc, 0xb1, 0x77, 0x22, 0x8e, 0xba, 0x79, 0x6a, 0x35, 0xb5,
+    0x91, 0xf7, 0xf6, 0x91, 0x36, 0x22, 0xce, 0x07, 0x89, 0xdd, 0x4e, 0x8a,
+    0x55, 0x8f, 0xd2, 0xec, 0x9d, 0x97, 0x31, 0xa6, 0xc9, 0x71, 0xa3, 0xd5,
+    0xe7, 0x4f, 0xda, 0x24, 0x3e, 0x6b, 0x3f, 0xf8, 0xa9, 0x11, 0x34, 0xf4,
+    0x3d, 0xc0, 0x74, 0x2a, 0x75, 0x2b, 0xe4, 0x85, 0x5f, 0x68, 0xa7, 0xc5,
+    0xd1, 0xe9, 0x72, 0x25, 0xf4, 0xd0, 0x66, 0x33, 0x6b, 0x4f, 0xa5, 0x6d,
+    0x23, 0x4c, 0x26, 0x4a, 0x51, 0x86, 0xb5, 0x0e, 0xd1, 0x49, 0xed, 0x5d,
+    0x85, 0xd7, 0xd3, 0x57, 0xd1, 0x62, 0x52, 0x60, 0x64, 0x82, 0x4f, 0xad,
+    0xcd, 0x7d, 0x50, 0xc5, 0x64, 0x13, 0x9b, 0x60, 0xe9, 0xe4, 0x01, 0xab,
+    0x25, 0x31, 0xa1, 0x85, 0x9f, 0x7e, 0xc4, 0x12, 0xe9, 0x9d, 0x2c, 0x9e,
+    0x09, 0x9b, 0xb2, 0xd7,
+];
+
+/// Public exponent of the admin key (65537).
+pub const ADMIN_EXPONENT: [u8; 3] = [0x01, 0x00, 0x01];
@@ -0,0 +1,85 @@
+//! Persistent log of admin actions.
+//!
+//! Every admin command appends one line to a log object in secure storage and
+//! to the TA trace. Once the log grows past [`LOG_CAPACITY`] it is started
+//! over.
+
+use alloc::format;
+use optee_utee::{
+    trace_println, DataFlag, ErrorKind, ObjectStorageConstants, PersistentObject, Result, Whence,
+};
+
+/// Storage key of the audit log.
+pub const LOG_KEY: &[u8] = b"reset_admin_log\0";
+
+/// Size in bytes after which the log is started over.
+pub const LOG_CAPACITY: usize = 16 * 1024;
+
+/// Records the outcome of an admin action on `counter`.
+///
+/// Failing to write the log is traced but does not fail the action, which
+/// has already been applied when this is called.
+pub fn record(action: &str, counter: &[u8], outcome: &Result<()>) {
+    let outcome = match outcome {
+        Ok(()) => "ok".into(),
+        Err(e) => format!("{:?}", e.kind()),
+    };
+    let counter = core::str::from_utf8(counter).unwrap_or("<binary>");
+    let line = format!("{} counter={:?} result={}\n", action, counter, outcome);
+    trace_println!("[audit] {}", line.trim_end());
+    if let Err(e) = append(line.as_bytes()) {
+        trace_println!("[!] Failed to write audit log: {:?}", e);
+    }
+}
+
+fn append(line: &[u8]) -> Result<()> {
+    let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE | DataFlag::ACCESS_WRITE_META;
+    let mut object = match PersistentObject::open(ObjectStorageConstants::Private, LOG_KEY, flags)
+    {
+        Ok(object) if object.info()?.data_size() + line.len() <= LOG_CAPACITY => object,
+        Ok(_) => {
+            trace_println!("[+] Audit log full, starting over");
+            return create(line);
+        }
+        Err(e) if e.kind() == ErrorKind::ItemNotFound => return create(line),
+        Err(e) => return Err(e),
+    };
+    object.seek(0, Whence::DataSeekEnd)?;
+    object.write(line)
+}
+
+fn create(line: &[u8]) -> Result<()> {
+    PersistentObject::create(
+        ObjectStorageConstants::Private,
+        LOG_KEY,
+        DataFlag::ACCESS_READ
+            | DataFlag::ACCESS_WRITE
+            | DataFlag::ACCESS_WRITE_META
+            | DataFlag::OVERWRITE,
+        None,
+        line,
+    )
+    .map(|_| ())
+}
+
+/// Copies the log into `buf` and returns its full length.
+///
+/// If `buf` is too small nothing is copied, so callers should compare the
+/// returned length against it.
+pub fn read(buf: &mut [u8]) -> Result<usize> {
+    let object = match PersistentObject::open(
+        ObjectStorageConstants::Private,
+        LOG_KEY,
+        DataFlag::ACCESS_READ,
+    ) {
+        Ok(object) => object,
+        Err(e) if e.kind() == ErrorKind::ItemNotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let len = object.info()?.data_size();
+    if len > buf.len() {
+        return Ok(len);
+    }
+    object.read(&mut buf[..len])?;
+    Ok(len)
+}
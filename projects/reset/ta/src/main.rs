@@ -3,12 +3,21 @@
 
 extern crate alloc;
 
+mod admin_key;
+mod audit;
+
+use n_time_model::admin::{self, AdminToken, NONCE_LEN};
+use n_time_model::token::IssuerKey;
+use n_time_model::{CounterStorage, ExecutionCounter};
 use optee_utee::{
     trace_println, ta_create, ta_destroy, ta_open_session, ta_close_session, ta_invoke_command,
-    Error, ErrorKind, Parameters, Result, ObjectStorageConstants, DataFlag, PersistentObject,
+    Error, ErrorKind, Parameters, Result,
 };
 use proto::Command;
 
+/// Storage key of the pending admin nonces.
+const NONCE_KEY: &[u8] = b"reset_admin_nonce\0";
+
 #[ta_create]
 fn create() -> Result<()> {
     trace_println!("[+] Reset TA created");
@@ -34,7 +43,13 @@ fn destroy() {
 #[ta_invoke_command]
 fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
     match Command::from(cmd_id) {
-        Command::Reset => reset_counter(params),
+        Command::Reset => admin_command(params, "reset"),
+        Command::SetCount => admin_command(params, "set_count"),
+        Command::SetMax => admin_command(params, "set_max"),
+        Command::GetNonce => get_nonce(params),
+        Command::GetLog => get_log(params),
+        Command::Status => status(params),
+        Command::List => list(params),
         Command::Unknown => {
             trace_println!("[!] Unknown command ID: {}", cmd_id);
             Err(Error::new(ErrorKind::NotSupported))
@@ -42,28 +57,88 @@ fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
     }
 }
 
-fn reset_counter(params: &mut Parameters) -> Result<()> {
+fn get_nonce(params: &mut Parameters) -> Result<()> {
     let mut p0 = unsafe { params.0.as_memref()? };
-    let key = core::str::from_utf8(p0.buffer())
-        .map_err(|_| Error::new(ErrorKind::BadParameters))?;
-
-    trace_println!("[+] Resetting secure counter with key: {}", key);
-
-    match PersistentObject::open(
-        ObjectStorageConstants::Private,
-        key.as_bytes(),
-        DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE | DataFlag::ACCESS_WRITE_META,
-    ) {
-        Ok(mut obj) => {
-            obj.close_and_delete()?;
-            trace_println!("[+] Object deleted");
-            Ok(())
-        }
+    let buffer = p0.buffer();
+    if buffer.len() < NONCE_LEN {
+        p0.set_updated_size(NONCE_LEN);
+        return Err(Error::new(ErrorKind::ShortBuffer));
+    }
+    let nonce = admin::issue_nonce(NONCE_KEY)?;
+    buffer[..NONCE_LEN].copy_from_slice(&nonce);
+    p0.set_updated_size(NONCE_LEN);
+    trace_println!("[+] Issued admin nonce");
+    Ok(())
+}
+
+fn get_log(params: &mut Parameters) -> Result<()> {
+    let mut p0 = unsafe { params.0.as_memref()? };
+    let capacity = p0.buffer().len();
+    let len = audit::read(p0.buffer())?;
+    p0.set_updated_size(len);
+    if len > capacity {
+        return Err(Error::new(ErrorKind::ShortBuffer));
+    }
+    Ok(())
+}
+
+fn status(params: &mut Parameters) -> Result<()> {
+    let mut p0 = unsafe { params.0.as_memref()? };
+    // As for admin actions, the base limit is only known to the TA using the
+    // counter, so the reported limit only includes top-ups and overrides.
+    let status = ExecutionCounter::new(p0.buffer(), 0).status()?;
+    let mut p1 = unsafe { params.1.as_value()? };
+    let mut p2 = unsafe { params.2.as_value()? };
+    p1.set_a(status.count);
+    p1.set_b(status.max);
+    p2.set_a(status.remaining);
+    Ok(())
+}
+
+/// Writes the counter keys to p0, each preceded by its length in one byte.
+fn list(params: &mut Parameters) -> Result<()> {
+    let mut p0 = unsafe { params.0.as_memref()? };
+    let keys = ExecutionCounter::list()?;
+    let len = keys.iter().map(|key| key.len() + 1).sum();
+    p0.set_updated_size(len);
+    let buffer = p0.buffer();
+    if buffer.len() < len {
+        return Err(Error::new(ErrorKind::ShortBuffer));
+    }
+    let mut offset = 0;
+    for key in &keys {
+        buffer[offset] = key.len() as u8;
+        buffer[offset + 1..offset + 1 + key.len()].copy_from_slice(key);
+        offset += key.len() + 1;
+    }
+    Ok(())
+}
+
+/// Verifies an admin token for the command `name` and applies it.
+fn admin_command(params: &mut Parameters, name: &str) -> Result<()> {
+    let mut p0 = unsafe { params.0.as_memref()? };
+    let key = IssuerKey::rsa(b"", &admin_key::ADMIN_MODULUS, &admin_key::ADMIN_EXPONENT)?;
+
+    let token = match AdminToken::verify(p0.buffer(), &key) {
+        Ok(token) => token,
         Err(e) => {
-            trace_println!("[!] Failed to open/delete object: {:?}", e);
-            Err(e)
+            let result = Err(e);
+            audit::record(name, b"", &result);
+            return result;
         }
-    }
+    };
+    // The nonce is only consumed once the whole token is valid, so that a
+    // token sent with the wrong command does not use it up.
+    let action = token.action.name();
+    let result = if action != name {
+        trace_println!("[!] Admin token is for {}, not {}", action, name);
+        Err(Error::new(ErrorKind::AccessDenied))
+    } else {
+        admin::consume_nonce(NONCE_KEY, token.nonce)
+            .and_then(|_| token.apply(CounterStorage::Private))
+    };
+    audit::record(name, token.counter, &result);
+    result
 }
 
 include!(concat!(env!("OUT_DIR"), "/user_ta_header.rs"));
@@ -0,0 +1,377 @@
+//! COSE encoding of execution tokens.
+//!
+//! Mirrors the decoder in `n_time_model::cose`: a tagged COSE_Sign1 message
+//! with `{1: RS256, 4: kid}` as protected header and a CBOR claims map as
+//! payload, or a tagged COSE_Sign message with one such header per signer.
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NINT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+const COSE_SIGN1_TAG: u64 = 18;
+const COSE_SIGN_TAG: u64 = 98;
+const HEADER_ALG: u64 = 1;
+const HEADER_KID: u64 = 4;
+/// RS256 is -257, encoded as a negative integer with argument 256.
+const ALG_RS256_ARG: u64 = 256;
+/// Private-use chain header -65537, encoded with argument 65536.
+const HEADER_CHAIN_ARG: u64 = 65536;
+
+pub const USAGE_SIGN_CERTS: u32 = 1 << 0;
+pub const USAGE_SIGN_TOKENS: u32 = 1 << 1;
+
+pub struct CertBody<'a> {
+    pub kid: &'a [u8],
+    pub modulus: &'a [u8],
+    pub exponent: &'a [u8],
+    pub not_before: u64,
+    pub not_after: u64,
+    pub usage: u32,
+}
+
+pub struct Claims<'a> {
+    pub seq: u32,
+    pub limit: u32,
+    pub exp: Option<u64>,
+    pub payload: &'a [u8],
+}
+
+fn header(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend(&arg.to_be_bytes());
+    }
+}
+
+fn bytes(out: &mut Vec<u8>, data: &[u8]) {
+    header(out, MAJOR_BYTES, data.len() as u64);
+    out.extend(data);
+}
+
+fn text(out: &mut Vec<u8>, data: &str) {
+    header(out, MAJOR_TEXT, data.len() as u64);
+    out.extend(data.as_bytes());
+}
+
+/// Encodes the claims map carried as the COSE payload.
+pub fn encode_claims(claims: &Claims) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_MAP, if claims.exp.is_some() { 4 } else { 3 });
+    text(&mut out, "seq");
+    header(&mut out, MAJOR_UINT, claims.seq as u64);
+    text(&mut out, "limit");
+    header(&mut out, MAJOR_UINT, claims.limit as u64);
+    if let Some(exp) = claims.exp {
+        text(&mut out, "exp");
+        header(&mut out, MAJOR_UINT, exp);
+    }
+    text(&mut out, "payload");
+    bytes(&mut out, claims.payload);
+    out
+}
+
+/// Encodes the claims of a top-up token.
+pub fn encode_topup(seq: u32, counter: &[u8], increment: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_MAP, 3);
+    text(&mut out, "seq");
+    header(&mut out, MAJOR_UINT, seq as u64);
+    text(&mut out, "counter");
+    bytes(&mut out, counter);
+    text(&mut out, "increment");
+    header(&mut out, MAJOR_UINT, increment as u64);
+    out
+}
+
+/// Encodes the claims of an admin token. `value` is required for
+/// `set_count` and `set_max` and must be absent for `reset`.
+pub fn encode_admin(nonce: &[u8], action: &str, counter: &[u8], value: Option<u32>) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_MAP, if value.is_some() { 4 } else { 3 });
+    text(&mut out, "nonce");
+    bytes(&mut out, nonce);
+    text(&mut out, "action");
+    text(&mut out, action);
+    text(&mut out, "counter");
+    bytes(&mut out, counter);
+    if let Some(value) = value {
+        text(&mut out, "value");
+        header(&mut out, MAJOR_UINT, value as u64);
+    }
+    out
+}
+
+/// Encodes the signed body of an issuer certificate.
+pub fn encode_cert_body(cert: &CertBody) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_MAP, 6);
+    text(&mut out, "kid");
+    bytes(&mut out, cert.kid);
+    text(&mut out, "n");
+    bytes(&mut out, cert.modulus);
+    text(&mut out, "e");
+    bytes(&mut out, cert.exponent);
+    text(&mut out, "nbf");
+    header(&mut out, MAJOR_UINT, cert.not_before);
+    text(&mut out, "exp");
+    header(&mut out, MAJOR_UINT, cert.not_after);
+    text(&mut out, "usage");
+    header(&mut out, MAJOR_UINT, cert.usage as u64);
+    out
+}
+
+/// Assembles a certificate from its body and the issuer's signature over it.
+pub fn encode_cert(body: &[u8], signature: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_ARRAY, 2);
+    bytes(&mut out, body);
+    bytes(&mut out, signature);
+    out
+}
+
+/// Encodes the protected header `{1: RS256, 4: kid}`.
+pub fn encode_protected(kid: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_MAP, 2);
+    header(&mut out, MAJOR_UINT, HEADER_ALG);
+    header(&mut out, MAJOR_NINT, ALG_RS256_ARG);
+    header(&mut out, MAJOR_UINT, HEADER_KID);
+    bytes(&mut out, kid);
+    out
+}
+
+/// Builds the `Sig_structure` that is signed for COSE_Sign1.
+pub fn to_be_signed(protected: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_ARRAY, 4);
+    text(&mut out, "Signature1");
+    bytes(&mut out, protected);
+    bytes(&mut out, &[]);
+    bytes(&mut out, payload);
+    out
+}
+
+/// Assembles the tagged COSE_Sign1 message, carrying the encoded
+/// certificates `chain` (ordered from the root) in the unprotected header.
+pub fn encode_sign1(
+    protected: &[u8],
+    chain: &[Vec<u8>],
+    payload: &[u8],
+    signature: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_TAG, COSE_SIGN1_TAG);
+    header(&mut out, MAJOR_ARRAY, 4);
+    bytes(&mut out, protected);
+    if chain.is_empty() {
+        header(&mut out, MAJOR_MAP, 0);
+    } else {
+        header(&mut out, MAJOR_MAP, 1);
+        header(&mut out, MAJOR_NINT, HEADER_CHAIN_ARG);
+        header(&mut out, MAJOR_ARRAY, chain.len() as u64);
+        for cert in chain {
+            out.extend(cert);
+        }
+    }
+    bytes(&mut out, payload);
+    bytes(&mut out, signature);
+    out
+}
+
+/// Builds the `Sig_structure` that each COSE_Sign signer signs. The body
+/// protected header is always empty.
+pub fn to_be_signed_multi(sign_protected: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_ARRAY, 5);
+    text(&mut out, "Signature");
+    bytes(&mut out, &[]);
+    bytes(&mut out, sign_protected);
+    bytes(&mut out, &[]);
+    bytes(&mut out, payload);
+    out
+}
+
+/// Encodes one COSE_Signature entry.
+pub fn encode_signature(sign_protected: &[u8], signature: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_ARRAY, 3);
+    bytes(&mut out, sign_protected);
+    header(&mut out, MAJOR_MAP, 0);
+    bytes(&mut out, signature);
+    out
+}
+
+/// Assembles a tagged COSE_Sign message from encoded signature entries.
+pub fn encode_sign(payload: &[u8], signatures: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, MAJOR_TAG, COSE_SIGN_TAG);
+    header(&mut out, MAJOR_ARRAY, 4);
+    bytes(&mut out, &[]);
+    header(&mut out, MAJOR_MAP, 0);
+    bytes(&mut out, payload);
+    header(&mut out, MAJOR_ARRAY, signatures.len() as u64);
+    for signature in signatures {
+        out.extend(signature);
+    }
+    out
+}
+
+/// A minimal CBOR reader, enough to take existing tokens apart.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.buf.len());
+        let end = end.ok_or("Truncated CBOR")?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn header(&mut self) -> Result<(u8, u64), String> {
+        let initial = self.take(1)?[0];
+        let len = match initial & 0x1f {
+            info @ 0..=23 => return Ok((initial >> 5, info as u64)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err("Unsupported CBOR encoding".into()),
+        };
+        let arg = self.take(len)?.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        Ok((initial >> 5, arg))
+    }
+
+    fn expect(&mut self, major: u8) -> Result<u64, String> {
+        match self.header()? {
+            (m, arg) if m == major => Ok(arg),
+            _ => Err("Unexpected CBOR item".into()),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.expect(MAJOR_BYTES)?;
+        self.take(len as usize)
+    }
+
+    fn skip(&mut self) -> Result<(), String> {
+        match self.header()? {
+            (MAJOR_BYTES, len) | (MAJOR_TEXT, len) => self.take(len as usize).map(|_| ()),
+            (MAJOR_ARRAY, len) => (0..len).try_for_each(|_| self.skip()),
+            (MAJOR_MAP, len) => (0..len * 2).try_for_each(|_| self.skip()),
+            (MAJOR_TAG, _) => self.skip(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Skips the next item and returns its encoding.
+    fn raw(&mut self) -> Result<&'a [u8], String> {
+        let start = self.pos;
+        self.skip()?;
+        Ok(&self.buf[start..self.pos])
+    }
+}
+
+/// Returns the key ID of a protected header map, if any.
+fn protected_kid(protected: &[u8]) -> Result<Option<&[u8]>, String> {
+    let mut r = Reader { buf: protected, pos: 0 };
+    let mut kid = None;
+    for _ in 0..r.expect(MAJOR_MAP)? {
+        if r.header()? == (MAJOR_UINT, HEADER_KID) {
+            kid = Some(r.bytes()?);
+        } else {
+            r.skip()?;
+        }
+    }
+    Ok(kid)
+}
+
+/// An existing COSE_Sign token, split into its payload and signer entries.
+pub struct SignedToken<'a> {
+    pub payload: &'a [u8],
+    pub signatures: Vec<Vec<u8>>,
+    pub kids: Vec<Vec<u8>>,
+}
+
+/// Parses a COSE_Sign token produced by [`encode_sign`].
+pub fn decode_sign(token: &[u8]) -> Result<SignedToken, String> {
+    let mut r = Reader { buf: token, pos: 0 };
+    if r.header()? != (MAJOR_TAG, COSE_SIGN_TAG) || r.expect(MAJOR_ARRAY)? != 4 {
+        return Err("Not a COSE_Sign token".into());
+    }
+    if !r.bytes()?.is_empty() {
+        return Err("Unsupported body protected header".into());
+    }
+    r.skip()?;
+    let payload = r.bytes()?;
+    let mut signatures = Vec::new();
+    let mut kids = Vec::new();
+    for _ in 0..r.expect(MAJOR_ARRAY)? {
+        let entry = r.raw()?;
+        let mut e = Reader { buf: entry, pos: 0 };
+        e.expect(MAJOR_ARRAY)?;
+        let kid = protected_kid(e.bytes()?)?.unwrap_or(&[]);
+        kids.push(kid.to_vec());
+        signatures.push(entry.to_vec());
+    }
+    if r.pos != token.len() {
+        return Err("Trailing data after token".into());
+    }
+    Ok(SignedToken {
+        payload,
+        signatures,
+        kids,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_protected() {
+        assert_eq!(
+            encode_protected(b"k1"),
+            [0xa2, 0x01, 0x39, 0x01, 0x00, 0x04, 0x42, 0x6b, 0x31]
+        );
+    }
+
+    #[test]
+    fn test_decode_sign() {
+        let payload = encode_claims(&Claims {
+            seq: 1,
+            limit: 1,
+            exp: None,
+            payload: &[],
+        });
+        let signatures = vec![
+            encode_signature(&encode_protected(b"a"), &[1; 256]),
+            encode_signature(&encode_protected(b"b"), &[2; 256]),
+        ];
+        let token = encode_sign(&payload, &signatures);
+        let decoded = decode_sign(&token).unwrap();
+        assert_eq!(decoded.payload, payload.as_slice());
+        assert_eq!(decoded.signatures, signatures);
+        assert_eq!(decoded.kids, vec![b"a".to_vec(), b"b".to_vec()]);
+        assert!(decode_sign(&token[..token.len() - 1]).is_err());
+    }
+}
@@ -1,22 +1,315 @@
+mod cose;
+
 use openssl::hash::MessageDigest;
-use openssl::pkey::PKey;
+use openssl::pkey::{PKey, Private};
 use openssl::rsa::Rsa;
 use openssl::sign::Signer;
 use std::env;
 use std::fs;
 use std::io::Write;
 
+const USAGE: &str = "Usage: token-gen [--format raw|cose|cose-multi] [--kid <id>] [--seq <n>] [--limit <n>] \
+                     [--exp <secs>] [--chain <cert_path>]... \
+                     <private_key_path> <output_path> <int1> <int2> ...
+       token-gen cert [--kid <id>] [--nbf <secs>] [--exp <secs>] [--usage tokens|certs|both] \
+                     <issuer_key_path> <subject_key_path> <output_path>
+       token-gen cosign --kid <id> <private_key_path> <token_path> [<output_path>]
+       token-gen topup --counter <key> --increment <n> [--seq <n>] [--kid <id>] \
+                     <private_key_path> <output_path>
+       token-gen admin --action reset|set-count|set-max --counter <key> [--value <n>] \
+                     --nonce <hex> [--kid <id>] <admin_key_path> <output_path>";
+
+#[derive(PartialEq)]
+enum Format {
+    Raw,
+    Cose,
+    CoseMulti,
+}
+
+struct Options {
+    format: Format,
+    kid: String,
+    seq: u32,
+    limit: u32,
+    exp: Option<u64>,
+    chain: Vec<String>,
+    positional: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| {
+            eprintln!("Invalid or missing value for {}", flag);
+            usage()
+        })
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    let mut opts = Options {
+        format: Format::Raw,
+        kid: String::new(),
+        seq: 1,
+        limit: 1,
+        exp: None,
+        chain: Vec::new(),
+        positional: Vec::new(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                opts.format = match args.next().as_deref() {
+                    Some("raw") => Format::Raw,
+                    Some("cose") => Format::Cose,
+                    Some("cose-multi") => Format::CoseMulti,
+                    _ => usage(),
+                }
+            }
+            "--kid" => opts.kid = args.next().unwrap_or_else(|| usage()),
+            "--seq" => opts.seq = parse_value("--seq", args.next()),
+            "--limit" => opts.limit = parse_value("--limit", args.next()),
+            "--exp" => opts.exp = Some(parse_value("--exp", args.next())),
+            "--chain" => opts.chain.push(args.next().unwrap_or_else(|| usage())),
+            _ => opts.positional.push(arg),
+        }
+    }
+    if opts.positional.len() < 3 {
+        usage();
+    }
+    if opts.format == Format::Raw
+        && (opts.exp.is_some() || !opts.kid.is_empty() || !opts.chain.is_empty())
+    {
+        eprintln!("--exp, --kid and --chain are only supported with --format cose");
+        usage();
+    }
+    if opts.format == Format::CoseMulti && (opts.kid.is_empty() || !opts.chain.is_empty()) {
+        eprintln!("--format cose-multi requires --kid and does not support --chain");
+        usage();
+    }
+    opts
+}
+
+fn read_private_key(path: &str) -> PKey<Private> {
+    let pem = fs::read(path).unwrap_or_else(|_| panic!("Failed to read: {}", path));
+    let rsa = Rsa::private_key_from_pem(&pem).expect("Invalid private key PEM");
+    PKey::from_rsa(rsa).expect("Failed to wrap RSA in PKey")
+}
+
+fn sign(key: &PKey<Private>, message: &[u8]) -> Vec<u8> {
+    let mut signer = Signer::new(MessageDigest::sha256(), key).expect("Failed to create signer");
+    signer.update(message).expect("Failed to hash message");
+    signer.sign_to_vec().expect("Signing failed")
+}
+
+/// Issues a certificate for the subject's public key, signed by the issuer.
+fn issue_cert(args: Vec<String>) {
+    let mut kid = String::new();
+    let mut not_before = 0;
+    let mut not_after = u64::MAX;
+    let mut usage_flags = cose::USAGE_SIGN_TOKENS;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--kid" => kid = args.next().unwrap_or_else(|| usage()),
+            "--nbf" => not_before = parse_value("--nbf", args.next()),
+            "--exp" => not_after = parse_value("--exp", args.next()),
+            "--usage" => {
+                usage_flags = match args.next().as_deref() {
+                    Some("tokens") => cose::USAGE_SIGN_TOKENS,
+                    Some("certs") => cose::USAGE_SIGN_CERTS,
+                    Some("both") => cose::USAGE_SIGN_TOKENS | cose::USAGE_SIGN_CERTS,
+                    _ => usage(),
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 3 {
+        usage();
+    }
+
+    let issuer = read_private_key(&positional[0]);
+    // The subject may be given as a public or a private key.
+    let subject_pem = fs::read(&positional[1])
+        .unwrap_or_else(|_| panic!("Failed to read: {}", positional[1]));
+    let (modulus, exponent) = match Rsa::public_key_from_pem(&subject_pem) {
+        Ok(rsa) => (rsa.n().to_vec(), rsa.e().to_vec()),
+        Err(_) => {
+            let rsa = Rsa::private_key_from_pem(&subject_pem).expect("Invalid subject key PEM");
+            (rsa.n().to_vec(), rsa.e().to_vec())
+        }
+    };
+    let body = cose::encode_cert_body(&cose::CertBody {
+        kid: kid.as_bytes(),
+        modulus: &modulus,
+        exponent: &exponent,
+        not_before,
+        not_after,
+        usage: usage_flags,
+    });
+    let cert = cose::encode_cert(&body, &sign(&issuer, &body));
+    fs::write(&positional[2], cert).expect("Failed to write certificate");
+
+    println!("[+] {} written:", positional[2]);
+    println!("    Kid: {:?}", kid);
+    println!("    Validity: {}..{}", not_before, not_after);
+}
+
+/// Adds a signature to an existing multi-signature token.
+fn cosign(args: Vec<String>) {
+    let mut kid = String::new();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--kid" => kid = args.next().unwrap_or_else(|| usage()),
+            _ => positional.push(arg),
+        }
+    }
+    if kid.is_empty() || !(2..=3).contains(&positional.len()) {
+        usage();
+    }
+    let token_path = &positional[1];
+    let output_path = positional.get(2).unwrap_or(token_path);
+
+    let key = read_private_key(&positional[0]);
+    let token = fs::read(token_path).unwrap_or_else(|_| panic!("Failed to read: {}", token_path));
+    let mut token = cose::decode_sign(&token).unwrap_or_else(|e| {
+        eprintln!("{}: {}", token_path, e);
+        std::process::exit(1);
+    });
+    if token.kids.iter().any(|k| k == kid.as_bytes()) {
+        eprintln!("{} is already signed by {:?}", token_path, kid);
+        std::process::exit(1);
+    }
+
+    let protected = cose::encode_protected(kid.as_bytes());
+    let signature = sign(&key, &cose::to_be_signed_multi(&protected, token.payload));
+    token.signatures.push(cose::encode_signature(&protected, &signature));
+    fs::write(output_path, cose::encode_sign(token.payload, &token.signatures))
+        .expect("Failed to write token");
+
+    println!("[+] {} written:", output_path);
+    println!("    Signers: {}", token.signatures.len());
+}
+
+/// Issues a top-up token raising the limit of an existing counter.
+fn issue_topup(args: Vec<String>) {
+    let mut counter = None;
+    let mut increment = None;
+    let mut seq = 1;
+    let mut kid = String::new();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--counter" => counter = Some(args.next().unwrap_or_else(|| usage())),
+            "--increment" => increment = Some(parse_value("--increment", args.next())),
+            "--seq" => seq = parse_value("--seq", args.next()),
+            "--kid" => kid = args.next().unwrap_or_else(|| usage()),
+            _ => positional.push(arg),
+        }
+    }
+    let (counter, increment) = match (counter, increment) {
+        (Some(counter), Some(increment)) if positional.len() == 2 => (counter, increment),
+        _ => usage(),
+    };
+    // Counter keys are used verbatim as storage IDs, so "\0" is unescaped to
+    // match keys such as `b"one_time_sort_counter\0"`.
+    let counter = counter.replace("\\0", "\0");
+
+    let key = read_private_key(&positional[0]);
+    let claims = cose::encode_topup(seq, counter.as_bytes(), increment);
+    let protected = cose::encode_protected(kid.as_bytes());
+    let signature = sign(&key, &cose::to_be_signed(&protected, &claims));
+    fs::write(&positional[1], cose::encode_sign1(&protected, &[], &claims, &signature))
+        .expect("Failed to write token");
+
+    println!("[+] {} written:", positional[1]);
+    println!("    Counter: {:?}", counter);
+    println!("    Increment: {} (top-up {})", increment, seq);
+}
+
+/// Issues an admin token for the reset TA, bound to a nonce it issued.
+fn issue_admin(args: Vec<String>) {
+    let mut action = None;
+    let mut counter = None;
+    let mut value = None;
+    let mut nonce = None;
+    let mut kid = String::new();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--action" => {
+                action = match args.next().as_deref() {
+                    Some("reset") => Some("reset"),
+                    Some("set-count") => Some("set_count"),
+                    Some("set-max") => Some("set_max"),
+                    _ => usage(),
+                }
+            }
+            "--counter" => counter = Some(args.next().unwrap_or_else(|| usage())),
+            "--value" => value = Some(parse_value("--value", args.next())),
+            "--nonce" => nonce = Some(args.next().unwrap_or_else(|| usage())),
+            "--kid" => kid = args.next().unwrap_or_else(|| usage()),
+            _ => positional.push(arg),
+        }
+    }
+    let (action, counter, nonce) = match (action, counter, nonce) {
+        (Some(action), Some(counter), Some(nonce)) if positional.len() == 2 => {
+            (action, counter, nonce)
+        }
+        _ => usage(),
+    };
+    if (action == "reset") != value.is_none() {
+        eprintln!("--value is required for set-count and set-max, and not allowed for reset");
+        usage();
+    }
+    let nonce: Vec<u8> = (0..nonce.len())
+        .step_by(2)
+        .map(|i| nonce.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+        .collect::<Option<_>>()
+        .unwrap_or_else(|| {
+            eprintln!("--nonce must be a hex string");
+            usage()
+        });
+    // See `issue_topup` for why "\0" is unescaped.
+    let counter = counter.replace("\\0", "\0");
+
+    let key = read_private_key(&positional[0]);
+    let claims = cose::encode_admin(&nonce, action, counter.as_bytes(), value);
+    let protected = cose::encode_protected(kid.as_bytes());
+    let signature = sign(&key, &cose::to_be_signed(&protected, &claims));
+    fs::write(&positional[1], cose::encode_sign1(&protected, &[], &claims, &signature))
+        .expect("Failed to write token");
+
+    println!("[+] {} written:", positional[1]);
+    println!("    Action: {} {:?}", action, counter);
+}
+
 fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
-    if args.len() < 3 {
-        eprintln!("Usage: token-gen <private_key_path> <output_path> <int1> <int2> ...");
-        std::process::exit(1);
+    match args.first().map(String::as_str) {
+        Some("cert") => return issue_cert(args[1..].to_vec()),
+        Some("cosign") => return cosign(args[1..].to_vec()),
+        Some("topup") => return issue_topup(args[1..].to_vec()),
+        Some("admin") => return issue_admin(args[1..].to_vec()),
+        _ => {}
     }
+    let opts = parse_args(args);
 
-    let private_key_path = &args[0];
-    let output_path = &args[1];
+    let private_key_path = &opts.positional[0];
+    let output_path = &opts.positional[1];
 
-    let numbers: Vec<i32> = args[2..]
+    let numbers: Vec<i32> = opts.positional[2..]
         .iter()
         .map(|s| s.parse())
         .collect::<Result<_, _>>()
@@ -25,32 +318,68 @@ fn main() {
             std::process::exit(1);
         });
 
-    let sequence_number: u32 = 1;
-    let usage_limit: u32 = 1;
-
-    // Serialize message: [seq, limit, payload...]
-    let mut message = Vec::new();
-    message.extend(&sequence_number.to_le_bytes());
-    message.extend(&usage_limit.to_le_bytes());
+    let mut payload = Vec::new();
     for &num in &numbers {
-        message.extend(&num.to_le_bytes());
+        payload.extend(&num.to_le_bytes());
     }
 
-    // Sign the message
-    let private_key_pem =
-        fs::read(private_key_path).unwrap_or_else(|_| panic!("Failed to read: {}", private_key_path));
-    let rsa = Rsa::private_key_from_pem(&private_key_pem).expect("Invalid private key PEM");
-    let key = PKey::from_rsa(rsa).expect("Failed to wrap RSA in PKey");
-    let mut signer = Signer::new(MessageDigest::sha256(), &key).expect("Failed to create signer");
-    signer.update(&message).expect("Failed to hash message");
-    let signature = signer.sign_to_vec().expect("Signing failed");
+    let key = read_private_key(private_key_path);
+
+    let claims = cose::Claims {
+        seq: opts.seq,
+        limit: opts.limit,
+        exp: opts.exp,
+        payload: &payload,
+    };
+    let (token, signature_len) = match opts.format {
+        Format::Raw => {
+            // Serialize message: [seq, limit, payload...]
+            let mut message = Vec::new();
+            message.extend(&opts.seq.to_le_bytes());
+            message.extend(&opts.limit.to_le_bytes());
+            message.extend(&payload);
+            let signature = sign(&key, &message);
+            let len = signature.len();
+            // [message || signature]
+            message.extend(signature);
+            (message, len)
+        }
+        Format::Cose => {
+            let claims = cose::encode_claims(&claims);
+            let protected = cose::encode_protected(opts.kid.as_bytes());
+            let signature = sign(&key, &cose::to_be_signed(&protected, &claims));
+            let chain: Vec<Vec<u8>> = opts
+                .chain
+                .iter()
+                .map(|path| fs::read(path).unwrap_or_else(|_| panic!("Failed to read: {}", path)))
+                .collect();
+            (
+                cose::encode_sign1(&protected, &chain, &claims, &signature),
+                signature.len(),
+            )
+        }
+        Format::CoseMulti => {
+            // Further signers are added with `token-gen cosign`.
+            let claims = cose::encode_claims(&claims);
+            let protected = cose::encode_protected(opts.kid.as_bytes());
+            let signature = sign(&key, &cose::to_be_signed_multi(&protected, &claims));
+            (
+                cose::encode_sign(&claims, &[cose::encode_signature(&protected, &signature)]),
+                signature.len(),
+            )
+        }
+    };
 
-    // Write [message || signature]
     let mut file = fs::File::create(output_path).expect("Failed to create output file");
-    file.write_all(&message).unwrap();
-    file.write_all(&signature).unwrap();
+    file.write_all(&token).unwrap();
 
     println!("[+] {} written:", output_path);
+    let format = match opts.format {
+        Format::Raw => "raw",
+        Format::Cose => "cose",
+        Format::CoseMulti => "cose-multi",
+    };
+    println!("    Format: {}", format);
     println!("    Payload: {:?} ({} integers)", numbers, numbers.len());
-    println!("    Signature: {} bytes", signature.len());
+    println!("    Signature: {} bytes", signature_len);
 }